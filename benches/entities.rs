@@ -0,0 +1,64 @@
+//! How much compression shrinks big entities, like the ones content packs
+//! are full of, and how long it takes to pack and unpack them.
+//!
+//! Run with `cargo bench --bench entities`. It's timed by hand, since what
+//! matters most is the sizes it prints.
+
+use std::{hint::black_box, time::Instant};
+
+use rlsh::game::{Component, Entity, compress};
+
+const ROUNDS: usize = 1_000;
+
+/// Entities from a content pack, from a lone goblin to a dragon's hoard.
+fn pack() -> Vec<(&'static str, Vec<u8>)> {
+    let loot = ["gold coin", "ruby", "sword", "potion", "bone", "scroll"];
+    let hoard = |size: usize| {
+        let items = loot.iter().cycle().take(size).map(|item| item.to_string());
+        Vec::from(Entity {
+            components: vec![
+                Component::Enemy,
+                Component::TakesDamage(40),
+                Component::HasInventory(items.collect()),
+            ],
+        })
+    };
+    let merchant = Vec::from(Entity {
+        components: vec![Component::Merchant {
+            stock: (0..200).map(|i| (format!("wares #{i}"), i)).collect(),
+            leaves_at: 0,
+        }],
+    });
+    vec![
+        ("goblin", hoard(2)),
+        ("chest", hoard(50)),
+        ("merchant", merchant),
+        ("hoard", hoard(5_000)),
+    ]
+}
+
+fn main() {
+    for (name, payload) in pack() {
+        let start = Instant::now();
+        let mut packed = Vec::new();
+        for _ in 0..ROUNDS {
+            packed = compress::pack(black_box(payload.clone()));
+        }
+        let packing = start.elapsed();
+
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            black_box(compress::unpack(black_box(&packed)).unwrap());
+        }
+        let unpacking = start.elapsed();
+
+        println!(
+            "{name}: {} bytes to {} ({:.0}%), packed in {:.2?}, unpacked in {:.2?}",
+            payload.len(),
+            packed.len(),
+            100.0 * packed.len() as f64 / payload.len() as f64,
+            packing / ROUNDS as u32,
+            unpacking / ROUNDS as u32,
+        );
+    }
+}
@@ -0,0 +1,74 @@
+//! How quickly command lines are split into words, and how many allocations
+//! that takes, over a long script like the ones rlsh is run with headless.
+//!
+//! Run with `cargo bench --bench parse`. The allocations are counted once, up
+//! front, and printed before criterion times the lines.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+
+/// The system allocator, counting how often it's asked for memory.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+// SAFETY: everything is passed straight on to the system allocator
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Lines a script might have, from plain to heavily quoted.
+const LINES: &[&str] = &[
+    "ls -l dungeon/crypt",
+    "cat notes | sort -r | head -2 > top",
+    "make && ./run || echo failed; jobs",
+    r#"echo "the $monster hits you for ${damage}hp" 'no $expansion here'"#,
+    r"touch a\ file\ with\ spaces ~/projects/x",
+    "export LEVEL=3 MONSTER=goblin",
+];
+
+/// Splits every line of the script once, returning how many words it had.
+fn script() -> usize {
+    LINES
+        .iter()
+        .map(|line| rlsh::words_in(black_box(line)).unwrap())
+        .sum()
+}
+
+fn words(c: &mut Criterion) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    script();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    println!(
+        "{:.1} allocations a line",
+        allocations as f64 / LINES.len() as f64
+    );
+
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Elements(LINES.len() as u64));
+    group.bench_function("script", |b| b.iter(script));
+    group.finish();
+}
+
+criterion_group!(benches, words);
+criterion_main!(benches);
@@ -0,0 +1,226 @@
+//! User settings, kept in a plain text file in the data directory so they can
+//! be edited by hand as well as with the `config` builtin.
+//!
+//! The file has one `name = value` setting per line. Lines starting with `#`
+//! are comments. Settings that aren't in the file have their default value.
+
+use std::{fmt::Write, fs, io, path::PathBuf};
+
+use crate::{
+    game::{locale, player},
+    style::Palette,
+    theme,
+};
+
+/// A setting the user can change.
+pub struct Setting {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub default: &'static str,
+}
+
+/// Every setting there is.
+pub const SETTINGS: &[Setting] = &[
+    Setting {
+        name: "accessible",
+        description: "on for plain text instead of icons and colours, for screen readers",
+        default: "off",
+    },
+    Setting {
+        name: "audit",
+        description: "off to stop logging commands for `audit query`",
+        default: "on",
+    },
+    Setting {
+        name: "language",
+        description: "which language game text is in, see `game strings`",
+        default: locale::DEFAULT_LANGUAGE,
+    },
+    Setting {
+        name: "clipboard",
+        description: "osc52 to share cut and pasted text with the system clipboard",
+        default: "off",
+    },
+    Setting {
+        name: "helphints",
+        description: "on to run `<command> --help` to describe flags when completing them",
+        default: "off",
+    },
+    Setting {
+        name: "keymap",
+        description: "keys for editing commands: emacs or vi",
+        default: "emacs",
+    },
+    Setting {
+        name: "palette",
+        description: "colours to use: default, deuteranopia, protanopia or tritanopia",
+        default: "default",
+    },
+    Setting {
+        name: "theme",
+        description: "how the prompt, ls and battles look, see `theme`",
+        default: "default",
+    },
+];
+
+fn find(name: &str) -> Option<&'static Setting> {
+    SETTINGS.iter().find(|setting| setting.name == name)
+}
+
+fn get_config_path() -> PathBuf {
+    let mut path = player::get_data_dir();
+    path.push("config");
+    path
+}
+
+#[derive(Debug, Default)]
+pub struct Config {
+    /// The settings that have been changed from their defaults, in the order
+    /// they appear in the file.
+    values: Vec<(String, String)>,
+}
+
+impl Config {
+    /// Reads the settings from `text`, skipping anything that isn't one.
+    fn parse(text: &str) -> Config {
+        let values = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| line.split_once('='))
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .filter(|(name, _)| find(name).is_some())
+            .collect();
+        Config { values }
+    }
+
+    /// Loads the settings, falling back on the defaults if there's no config
+    /// file.
+    pub fn load() -> Config {
+        fs::read_to_string(get_config_path())
+            .map(|text| Config::parse(&text))
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = get_config_path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut text = String::new();
+        for (name, value) in &self.values {
+            writeln!(text, "{name} = {value}").unwrap();
+        }
+        fs::write(path, text)
+    }
+
+    /// The value of the setting `name`.
+    ///
+    /// # Panics
+    ///
+    /// If there's no such setting. Setting names are known ahead of time, so
+    /// this is a bug.
+    pub fn get(&self, name: &str) -> &str {
+        let setting = find(name).unwrap_or_else(|| panic!("no such setting: {name}"));
+        self.values
+            .iter()
+            .find(|(n, _)| n == name)
+            .map_or(setting.default, |(_, value)| value.as_str())
+    }
+
+    /// Whether the on/off setting `name` is on. See [`Config::get`].
+    pub fn is_on(&self, name: &str) -> bool {
+        matches!(self.get(name), "on" | "yes" | "true" | "1")
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        if find(name).is_none() {
+            return Err(format!("config: {name}: no such setting"));
+        }
+        if name == "clipboard" && !matches!(value, "off" | "osc52") {
+            return Err(format!("config: clipboard: {value}: pick off or osc52"));
+        }
+        if name == "keymap" && !matches!(value, "emacs" | "vi") {
+            return Err(format!("config: keymap: {value}: pick emacs or vi"));
+        }
+        if name == "palette" && Palette::from_name(value).is_none() {
+            return Err(format!(
+                "config: palette: {value}: pick one of {}",
+                Palette::NAMES.join(", ")
+            ));
+        }
+        if name == "theme" && !theme::exists(value) {
+            return Err(format!("config: theme: {value}: no such theme"));
+        }
+        match self.values.iter_mut().find(|(n, _)| n == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.values.push((name.to_string(), value.to_string())),
+        }
+        Ok(())
+    }
+}
+
+/// Runs the `config` builtin, returning what should be printed.
+///
+/// - `config` lists every setting, its value and what it's for.
+/// - `config <name>` prints a setting's value.
+/// - `config <name> <value>` changes a setting.
+pub fn command(args: &[String]) -> Result<String, String> {
+    let mut config = Config::load();
+    match args {
+        [] => {
+            let mut output = String::new();
+            for setting in SETTINGS {
+                writeln!(
+                    output,
+                    "{} = {}\t# {}",
+                    setting.name,
+                    config.get(setting.name),
+                    setting.description
+                )
+                .unwrap();
+            }
+            Ok(output)
+        }
+        [name] => match find(name) {
+            Some(_) => Ok(format!("{}\n", config.get(name))),
+            None => Err(format!("config: {name}: no such setting")),
+        },
+        [name, value @ ..] => {
+            config.set(name, &value.join(" "))?;
+            config
+                .save()
+                .map_err(|error| format!("config: could not save: {error}"))?;
+            // settings are read once and remembered, so forget them
+            locale::reload();
+            theme::reload();
+            Ok(String::new())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_parse_with_defaults() {
+        let config = Config::parse("# comment\n  language = fr \nnonsense = 1\nnot a setting\n");
+        assert_eq!(config.get("language"), "fr");
+        assert_eq!(config.values.len(), 1);
+        assert_eq!(Config::default().get("language"), locale::DEFAULT_LANGUAGE);
+        assert!(!Config::default().is_on("accessible"));
+        assert!(Config::parse("accessible = yes").is_on("accessible"));
+
+        let mut config = Config::default();
+        assert!(config.set("nonsense", "1").is_err());
+        assert!(config.set("palette", "sepia").is_err());
+        assert!(config.set("theme", "../config").is_err());
+        config.set("language", "de").unwrap();
+        config.set("language", "fr").unwrap();
+        assert_eq!(
+            config.values,
+            [(String::from("language"), String::from("fr"))]
+        );
+    }
+}
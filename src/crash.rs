@@ -0,0 +1,147 @@
+//! What happens when rlsh panics.
+//!
+//! Without this, a panic while a line is being edited leaves the terminal in
+//! raw mode, and one in the middle of a battle loses whatever happened in it.
+//! Instead the panic hook puts the terminal back, saves the player the battle
+//! was holding on to, and writes a crash report with the backtrace and the
+//! last few commands to `crashes/` in the data directory before saying where
+//! it went.
+
+use std::{
+    backtrace::Backtrace,
+    collections::VecDeque,
+    fmt::Write as _,
+    fs,
+    io::{self, IsTerminal, Write},
+    panic::{self, PanicHookInfo},
+    path::PathBuf,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    game::player::{self, PlayerState},
+    update::VERSION,
+};
+
+/// How many commands a crash report shows.
+const RECENT_COMMANDS: usize = 20;
+
+/// The last few commands run, oldest first.
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// A player that's changed since it was last saved, like one in a battle.
+static UNSAVED: Mutex<Option<PlayerState>> = Mutex::new(None);
+
+/// Remembers `line` for crash reports.
+pub fn note_command(line: &str) {
+    let mut recent = RECENT.lock().unwrap_or_else(|e| e.into_inner());
+    if recent.len() == RECENT_COMMANDS {
+        recent.pop_front();
+    }
+    recent.push_back(line.to_string());
+}
+
+/// Keeps a copy of `player` to save if rlsh crashes before it's saved.
+pub fn hold(player: &PlayerState) {
+    *UNSAVED.lock().unwrap_or_else(|e| e.into_inner()) = Some(player.clone());
+}
+
+/// Forgets the player from [`hold`], once it's safe to.
+pub fn let_go() {
+    *UNSAVED.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// The text of a crash report.
+fn report(message: &str, backtrace: &str, commands: &[String]) -> String {
+    let mut out = format!("rlsh {VERSION} crashed: {message}\n\nlast commands:\n");
+    for command in commands {
+        writeln!(out, "  {command}").unwrap();
+    }
+    write!(out, "\nbacktrace:\n{backtrace}").unwrap();
+    out
+}
+
+fn write_report(text: &str) -> io::Result<PathBuf> {
+    let dir = player::get_data_dir().join("crashes");
+    fs::create_dir_all(&dir)?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let path = dir.join(format!("crash-{now}.txt"));
+    fs::write(&path, text)?;
+    Ok(path)
+}
+
+fn on_panic(info: &PanicHookInfo, restore_terminal: fn()) {
+    let _ = io::stdout().flush();
+    if io::stdout().is_terminal() {
+        // out of bracketed paste, on to a fresh line
+        print!("\x1b[?2004l\r\n");
+        let _ = io::stdout().flush();
+    }
+    restore_terminal();
+
+    // the panic could have happened with either of these locked
+    let saved = match UNSAVED.try_lock().ok().and_then(|mut p| p.take()) {
+        Some(player) => player.save().is_ok(),
+        None => true,
+    };
+    let commands: Vec<String> = RECENT
+        .try_lock()
+        .map(|recent| recent.iter().cloned().collect())
+        .unwrap_or_default();
+
+    let message = match info.location() {
+        Some(location) => format!("{} at {location}", panic_message(info)),
+        None => panic_message(info),
+    };
+    let backtrace = Backtrace::force_capture().to_string();
+    eprintln!("rlsh crashed: {message}");
+    if !saved {
+        eprintln!("your game couldn't be saved, sorry.");
+    }
+    match write_report(&report(&message, &backtrace, &commands)) {
+        Ok(path) => eprintln!("a crash report is in {}", path.display()),
+        Err(error) => eprintln!("the crash report couldn't be written: {error}"),
+    }
+}
+
+fn panic_message(info: &PanicHookInfo) -> String {
+    let payload = info.payload();
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| String::from("panic"))
+}
+
+/// Sets up the panic hook. `restore_terminal` puts the terminal back how it
+/// was before rlsh touched it.
+pub fn install(restore_terminal: fn()) {
+    panic::set_hook(Box::new(move |info| on_panic(info, restore_terminal)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_have_the_last_commands() {
+        for n in 0..RECENT_COMMANDS + 5 {
+            note_command(&format!("echo {n}"));
+        }
+        let commands: Vec<String> = RECENT.lock().unwrap().iter().cloned().collect();
+        assert_eq!(commands.len(), RECENT_COMMANDS);
+        assert_eq!(commands[0], "echo 5");
+
+        let text = report("oops at src/lib.rs:1:1", "  0: main\n", &commands[..2]);
+        assert_eq!(
+            text,
+            format!(
+                "rlsh {VERSION} crashed: oops at src/lib.rs:1:1\n\n\
+                 last commands:\n  echo 5\n  echo 6\n\nbacktrace:\n  0: main\n"
+            )
+        );
+    }
+}
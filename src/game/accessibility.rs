@@ -0,0 +1,119 @@
+//! Accessibility mode, for playing with a screen reader.
+//!
+//! Turned on with `config accessible on`, and always on in headless mode. Icons, colours and status lines
+//! don't make much sense read aloud, so in this mode they're replaced with
+//! plain words, and anything about the player that changes is announced in a
+//! sentence of its own rather than left for them to spot.
+
+use std::sync::Mutex;
+
+use super::{locale::t, player::PlayerState};
+use crate::{config::Config, headless};
+
+/// Whether accessibility mode is on.
+pub fn enabled() -> bool {
+    headless::enabled() || Config::load().is_on("accessible")
+}
+
+/// The parts of the player that get announced when they change.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Snapshot {
+    hp: i32,
+    mana: i32,
+    gold: u32,
+    level: u32,
+    items: Vec<String>,
+}
+
+impl Snapshot {
+    fn of(player: &PlayerState) -> Snapshot {
+        let mut items: Vec<String> = player.inventory.iter().map(|i| i.to_string()).collect();
+        items.sort();
+        Snapshot {
+            hp: player.hp,
+            mana: player.mana,
+            gold: player.gold,
+            level: player.level,
+            items,
+        }
+    }
+
+    /// Describes how the player got from `self` to `now`, one sentence each.
+    fn changes(&self, now: &Snapshot) -> Vec<String> {
+        let mut changes = Vec::new();
+        let mut number = |key: &str, from: i64, to: i64| {
+            if from != to {
+                changes.push(t!(key, from = from, to = to));
+            }
+        };
+        number("access.hp", self.hp.into(), now.hp.into());
+        number("access.mana", self.mana.into(), now.mana.into());
+        number("access.gold", self.gold.into(), now.gold.into());
+        number("access.level", self.level.into(), now.level.into());
+
+        let mut gained = now.items.clone();
+        let mut lost = Vec::new();
+        for item in &self.items {
+            match gained.iter().position(|i| i == item) {
+                Some(i) => {
+                    gained.remove(i);
+                }
+                None => lost.push(item.clone()),
+            }
+        }
+        for item in gained {
+            changes.push(t!("access.gained", item = item));
+        }
+        for item in lost {
+            changes.push(t!("access.lost", item = item));
+        }
+        changes
+    }
+}
+
+/// What the player was like the last time changes were announced.
+static LAST: Mutex<Option<Snapshot>> = Mutex::new(None);
+
+/// Announces everything about the player that's changed since last time,
+/// if accessibility mode is on. Called before each prompt.
+pub fn announce(player: &PlayerState) {
+    let now = Snapshot::of(player);
+    let mut last = LAST.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(last) = last.as_ref()
+        && enabled()
+    {
+        for change in last.changes(&now) {
+            println!("{change}");
+        }
+    }
+    *last = Some(now);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::inventory::Item;
+
+    #[test]
+    fn changes_are_described() {
+        let mut player = PlayerState::default();
+        player.inventory = vec![Item::new("bread"), Item::new("bread")];
+        let before = Snapshot::of(&player);
+        assert!(before.changes(&before).is_empty());
+
+        player.hp -= 5;
+        player.gold += 3;
+        player.inventory.remove(0);
+        player.inventory.push(Item::new("rope"));
+        let changes = before.changes(&Snapshot::of(&player));
+        assert_eq!(
+            changes,
+            [
+                "your hp went from 20 to 15.",
+                "your gold went from 10 to 13.",
+                "you now have a rope.",
+                "you no longer have a bread.",
+            ]
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! Turn-based battles against tougher enemies.
+//!
+//! Regular enemies are dealt with one `attack` at a time from the shell, but
+//! elites and bosses pull the player into a battle loop of their own. Until
+//! the battle is over, the shell prompt is replaced with a battle prompt that
+//! understands only a handful of combat verbs.
+
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use super::accessibility;
+use super::dice;
+use super::locale::t;
+use super::{
+    Component, Entity, despawn,
+    parser::Parser,
+    pet,
+    player::{MAX_HP, PlayerState},
+    spawn,
+};
+use crate::{crash, style::Palette};
+
+/// What an enemy hits for when it has no [`Component::Retaliates`].
+const DEFAULT_RETALIATION: i16 = 2;
+
+/// Items that can be used in battle, and how much HP they restore.
+const HEALING_ITEMS: &[(&str, i32)] = &[("potion", 8), ("bread", 3)];
+
+#[derive(Clone)]
+enum Verb {
+    Attack,
+    Defend,
+    Item,
+    Flee,
+    Help,
+}
+
+fn vocabulary() -> Parser<Verb> {
+    Parser::new()
+        .insert(Verb::Attack, "[a|attack|hit|punch|strike|fight]")
+        .insert(Verb::Attack, "[a|attack|hit|punch|strike|fight] ()")
+        .insert(Verb::Defend, "[d|defend|block|guard|parry]")
+        .insert(Verb::Item, "[i|item|use|drink|eat] ()")
+        .insert(Verb::Flee, "[f|flee|run|escape|retreat]")
+        .insert(Verb::Flee, "[run away]")
+        .insert(Verb::Help, "[h|help|?]")
+}
+
+impl Entity {
+    /// Whether this entity is tough enough to warrant a proper battle.
+    pub fn is_tough(&self) -> bool {
+        self.components
+            .iter()
+            .any(|c| matches!(c, Component::Elite | Component::Boss))
+    }
+
+    fn is_boss(&self) -> bool {
+        self.components.iter().any(|c| matches!(c, Component::Boss))
+    }
+
+    pub fn hp(&self) -> Option<i16> {
+        self.components.iter().find_map(|c| match c {
+            Component::TakesDamage(hp) => Some(*hp),
+            _ => None,
+        })
+    }
+
+    /// Returns a copy of this entity with its HP set to `hp`.
+    pub fn with_hp(&self, hp: i16) -> Entity {
+        Entity {
+            components: self
+                .components
+                .iter()
+                .filter(|c| !matches!(c, Component::TakesDamage(_)))
+                .cloned()
+                .chain([Component::TakesDamage(hp)])
+                .collect(),
+        }
+    }
+
+    fn retaliation(&self) -> i16 {
+        self.components
+            .iter()
+            .find_map(|c| match c {
+                Component::Retaliates(damage) => Some(*damage),
+                _ => None,
+            })
+            .unwrap_or(DEFAULT_RETALIATION)
+    }
+}
+
+/// How a battle ended.
+enum Outcome {
+    Won,
+    Lost,
+    Fled,
+}
+
+/// Fights the entity at `path` until one side drops or the player runs away.
+pub fn fight(player: &mut PlayerState, e: Entity, path: &Path) {
+    let name = path.file_name().unwrap().display().to_string();
+    let dir = path.parent().unwrap();
+    let parser = vocabulary();
+    let mut hp = e.hp().unwrap_or(1);
+    let max_hp = hp;
+
+    println!("{}", t!("battle.start", name = name));
+    println!("{}", t!("battle.options"));
+
+    let outcome = loop {
+        // the player isn't saved until the battle's over
+        crash::hold(player);
+        if accessibility::enabled() {
+            println!(
+                "{}",
+                t!("battle.status", name = name, hp = hp, player_hp = player.hp)
+            );
+            print!("{}", t!("battle.plain_prompt"));
+        } else {
+            let palette = Palette::current();
+            print!(
+                "{}",
+                t!(
+                    "battle.prompt",
+                    name = name,
+                    hp = hp,
+                    bar = palette.hp_bar(hp.into(), max_hp.into()),
+                    player_hp = player.hp,
+                    player_bar = palette.hp_bar(player.hp, MAX_HP)
+                )
+            );
+        }
+        io::stdout().flush().unwrap();
+
+        let mut input = String::new();
+        match io::stdin().read_line(&mut input) {
+            // treat EOF as running away so the shell can exit cleanly
+            Ok(0) | Err(_) => break Outcome::Fled,
+            Ok(_) => (),
+        }
+
+        let mut defending = false;
+        match parser.get(&input) {
+            Some((Verb::Attack, _)) => {
+                let damage = dice::random_range(1..5) + pet::assist(player, dir);
+                hp -= damage;
+                println!("{}", t!("battle.hit", name = name, damage = damage));
+                if hp <= 0 {
+                    break Outcome::Won;
+                }
+            }
+            Some((Verb::Defend, _)) => {
+                println!("{}", t!("battle.defend"));
+                defending = true;
+            }
+            Some((Verb::Item, args)) => {
+                if !use_item(player, &args[0]) {
+                    continue;
+                }
+            }
+            Some((Verb::Flee, _)) => {
+                let odds = if e.is_boss() { 4 } else { 2 };
+                if dice::random_ratio(1, odds) {
+                    println!("{}", t!("battle.fled", name = name));
+                    break Outcome::Fled;
+                }
+                println!("{}", t!("battle.cut_off", name = name));
+            }
+            Some((Verb::Help, _)) => {
+                for verb in ["attack", "defend", "item", "flee"] {
+                    println!("{}", t!(&format!("battle.help.{verb}")));
+                }
+                continue;
+            }
+            None => {
+                println!("{}", t!("battle.unknown"));
+                continue;
+            }
+        }
+
+        let mut damage = e.retaliation();
+        if defending {
+            damage /= 2;
+        }
+        println!("{}", t!("battle.hurt", name = name, damage = damage));
+        if player.hurt(damage.into()) {
+            break Outcome::Lost;
+        }
+    };
+
+    match outcome {
+        Outcome::Won => {
+            println!("{}", t!("battle.won", name = name));
+            if let Err(error) = despawn(path) {
+                println!("{}", t!("error.remove", name = name, error = error));
+                return;
+            }
+            super::defeat(player, &e, path);
+        }
+        Outcome::Lost | Outcome::Fled => {
+            if let Err(error) = spawn(e.with_hp(hp), path) {
+                println!("{}", t!("error.spawn", name = name, error = error));
+            }
+        }
+    }
+}
+
+/// Uses up a healing item, returning whether it took the player's turn.
+fn use_item(player: &mut PlayerState, item: &str) -> bool {
+    if !player.has(item) {
+        println!("{}", t!("battle.no_item", item = item));
+        return false;
+    }
+    let Some((_, heal)) = HEALING_ITEMS.iter().find(|(name, _)| *name == item) else {
+        println!("{}", t!("battle.useless_item", item = item));
+        return false;
+    };
+
+    player.remove_item(item, false);
+    player.hp = (player.hp + heal).min(MAX_HP);
+    println!("{}", t!("battle.used_item", item = item));
+    true
+}
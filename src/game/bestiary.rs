@@ -0,0 +1,172 @@
+//! Monsters the game knows how to make.
+//!
+//! Each template describes a kind of monster, and can be stamped out into as
+//! many entities as you like with `game spawn <template> [name]`.
+
+use super::locale::t;
+use super::{Component, Entity, battle, get_entity, player::PlayerState, spawn};
+
+/// Programs that get a little too close to the files they're given.
+const TOUCHY_COMMANDS: &[&str] = &["cat", "rm", "mv", "cp", "less", "more", "head", "tail"];
+
+/// A kind of monster.
+pub struct Template {
+    pub name: &'static str,
+    pub hp: i16,
+    /// How much damage it deals back when hit, if any.
+    pub retaliates: i16,
+    /// What the player finds on the body.
+    pub loot: &'static [&'static str],
+    /// Whether it's too tough to take out in one punch.
+    pub elite: bool,
+    /// Whether it roams around while the player rests.
+    pub wanders: bool,
+    /// Whether it pretends to be an ordinary file.
+    pub mimic: bool,
+    /// Whether it only comes out at night, see [`clock`](super::clock).
+    pub nocturnal: bool,
+    /// What the monster's file is called if nobody picks a name for it. Most
+    /// monsters go by their own name, but some would rather not.
+    pub file_name: &'static str,
+}
+
+pub const BESTIARY: &[Template] = &[
+    Template {
+        name: "goblin",
+        hp: 5,
+        retaliates: 0,
+        loot: &["bread"],
+        elite: false,
+        wanders: true,
+        mimic: false,
+        nocturnal: false,
+        file_name: "goblin",
+    },
+    Template {
+        name: "skeleton",
+        hp: 8,
+        retaliates: 2,
+        loot: &["bone", "sword"],
+        elite: true,
+        wanders: false,
+        mimic: false,
+        nocturnal: true,
+        file_name: "skeleton",
+    },
+    Template {
+        name: "mimic",
+        hp: 12,
+        retaliates: 3,
+        loot: &["potion", "ring"],
+        elite: true,
+        wanders: false,
+        mimic: true,
+        nocturnal: false,
+        file_name: "treasure.txt",
+    },
+];
+
+/// Looks up a template by name.
+pub fn find(name: &str) -> Option<&'static Template> {
+    BESTIARY
+        .iter()
+        .find(|template| template.name.eq_ignore_ascii_case(name))
+}
+
+impl Template {
+    /// Makes a fresh monster from this template.
+    pub fn entity(&self) -> Entity {
+        let mut components = vec![Component::Enemy, Component::TakesDamage(self.hp)];
+        if self.retaliates > 0 {
+            components.push(Component::Retaliates(self.retaliates));
+        }
+        if !self.loot.is_empty() {
+            components.push(Component::HasInventory(
+                self.loot.iter().map(|item| item.to_string()).collect(),
+            ));
+        }
+        if self.elite {
+            components.push(Component::Elite);
+        }
+        if self.wanders {
+            components.push(Component::Wanders);
+        }
+        if self.mimic {
+            components.push(Component::Mimic);
+        }
+        Entity { components }
+    }
+}
+
+impl Entity {
+    pub fn is_mimic(&self) -> bool {
+        self.components
+            .iter()
+            .any(|c| matches!(c, Component::Mimic))
+    }
+}
+
+/// Called by the shell before it runs `command` on `args`. If any of the files
+/// is a mimic, it springs to life and the player has to fight it. Returns
+/// whether the command was interrupted.
+pub fn ambush(command: &str, args: &[String]) -> bool {
+    if !TOUCHY_COMMANDS.contains(&command) {
+        return false;
+    }
+
+    let Some((path, e)) = args.iter().find_map(|arg| {
+        let e = get_entity(arg).ok().filter(Entity::is_mimic)?;
+        Some((std::path::absolute(arg).ok()?, e))
+    }) else {
+        return false;
+    };
+
+    let name = path.file_name().unwrap().display().to_string();
+    println!("as you reach for {name}, it sprouts teeth and lunges at you! it's a mimic!");
+    let mut player = PlayerState::load();
+    battle::fight(&mut player, e, &path);
+    if let Err(error) = player.save() {
+        println!("{}", t!("error.save", error = error));
+    }
+    true
+}
+
+/// Runs `game spawn <template> [name]`, putting a new monster in the current
+/// directory.
+pub fn command(args: &[String]) {
+    let Some(name) = args.first() else {
+        let names: Vec<&str> = BESTIARY.iter().map(|template| template.name).collect();
+        println!("spawn what? try one of: {}", names.join(", "));
+        return;
+    };
+    let Some(template) = find(name) else {
+        println!("there's no such thing as a {name}.");
+        return;
+    };
+
+    let file_name = match &args[1..] {
+        [] => template.file_name.to_string(),
+        rest => rest.join(" "),
+    };
+    if let Err(error) = spawn(template.entity(), &file_name) {
+        println!("{}", t!("error.spawn", name = file_name, error = error));
+        return;
+    }
+    println!("a {} appears as {file_name}.", template.name);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn templates_make_enemies() {
+        for template in BESTIARY {
+            let e = template.entity();
+            assert_eq!(e.hp(), Some(template.hp), "{}", template.name);
+            assert_eq!(e.is_tough(), template.elite, "{}", template.name);
+            assert_eq!(e.is_mimic(), template.mimic, "{}", template.name);
+            assert_eq!(find(template.name).unwrap().name, template.name);
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! Chests the player can stash things in, to get them out of their pack
+//! without leaving them lying around.
+//!
+//! `game chest <name> [capacity]` puts a chest in the current directory. The
+//! player can `store <item> in <chest>`, `retrieve <item> from <chest>` and
+//! `open <chest>` to see what's inside. Like the player's pack, a chest can
+//! only hold so much weight, but unlike the pack, it's a hard limit.
+//!
+//! What's in a chest is kept in the chest's own file, as whole items, so
+//! something that's been identified or uncursed stays that way.
+
+use super::{
+    Component, Entity, get_entity,
+    inventory::{Item, weight},
+    locale::t,
+    player::PlayerState,
+    spawn,
+};
+
+/// How much weight a chest holds, unless it's given another capacity.
+pub const CAPACITY: u32 = 30;
+
+impl Entity {
+    /// What's in this entity, if it's a chest, and how much weight it holds.
+    pub fn chest(&self) -> Option<(&[Item], u32)> {
+        self.components.iter().find_map(|c| match c {
+            Component::Chest { items, capacity } => Some((items.as_slice(), *capacity)),
+            _ => None,
+        })
+    }
+
+    fn chest_mut(&mut self) -> Option<&mut Vec<Item>> {
+        self.components.iter_mut().find_map(|c| match c {
+            Component::Chest { items, .. } => Some(items),
+            _ => None,
+        })
+    }
+}
+
+/// The total weight of `items`.
+fn total(items: &[Item]) -> u32 {
+    items.iter().map(|item| weight(&item.name)).sum()
+}
+
+/// The chest at `path`, saying so if it isn't one.
+fn load(path: &str) -> Option<Entity> {
+    match get_entity(path) {
+        Ok(e) if e.chest().is_some() => Some(e),
+        _ => {
+            println!("{}", t!("chest.not_a_chest", chest = path));
+            None
+        }
+    }
+}
+
+/// Puts the chest and the player back after something's moved between them.
+/// The player isn't saved if the chest couldn't be, so nothing's lost.
+fn save(e: Entity, path: &str, player: &PlayerState) {
+    if let Err(error) = spawn(e, path) {
+        println!("{}", t!("error.spawn", name = path, error = error));
+        return;
+    }
+    if let Err(error) = player.save() {
+        println!("{}", t!("error.save", error = error));
+    }
+}
+
+/// Runs the `store` verb, moving an item from the player's pack into a
+/// chest.
+pub fn store(name: &str, path: &str) {
+    let Some(mut e) = load(path) else {
+        return;
+    };
+    let (items, capacity) = e.chest().unwrap();
+    if total(items) + weight(name) > capacity {
+        println!("{}", t!("chest.full", chest = path, item = name));
+        return;
+    }
+    let mut player = PlayerState::load();
+    let Some(item) = player.remove_item(name, false) else {
+        if player.has(name) {
+            println!("you'll have to take off the {name} first.");
+        } else {
+            println!("you aren't carrying any {name}.");
+        }
+        return;
+    };
+    println!("{}", t!("chest.stored", chest = path, item = item));
+    e.chest_mut().unwrap().push(item);
+    save(e, path, &player);
+}
+
+/// Runs the `retrieve` verb, moving an item from a chest back into the
+/// player's pack. Items that would overload the player are left in the
+/// chest.
+pub fn retrieve(name: &str, path: &str) {
+    let Some(mut e) = load(path) else {
+        return;
+    };
+    let items = e.chest_mut().unwrap();
+    let Some(i) = items.iter().position(|item| item.name == name) else {
+        println!("{}", t!("chest.missing", chest = path, item = name));
+        return;
+    };
+    let mut player = PlayerState::load();
+    if player.carried_weight() + weight(name) > player.capacity() {
+        println!("you can't carry the {name} as well.");
+        return;
+    }
+    let item = items.remove(i);
+    println!("{}", t!("chest.retrieved", chest = path, item = item));
+    player.inventory.push(item);
+    save(e, path, &player);
+}
+
+/// Runs the `open` verb, listing what's in a chest.
+pub fn open(path: &str) {
+    let Some(e) = load(path) else {
+        return;
+    };
+    let (items, capacity) = e.chest().unwrap();
+    if items.is_empty() {
+        println!("{}", t!("chest.empty", chest = path));
+    }
+    for item in items {
+        println!("{item} ({})", weight(&item.name));
+    }
+    println!("holding {}/{capacity}", total(items));
+}
+
+/// Runs `game chest <name> [capacity]`.
+pub fn command(args: &[String]) {
+    let (name, capacity) = match args {
+        [name] => (name, CAPACITY),
+        [name, capacity] => match capacity.parse() {
+            Ok(capacity) => (name, capacity),
+            Err(_) => {
+                println!("game chest: {capacity} isn't a capacity");
+                return;
+            }
+        },
+        _ => {
+            println!("game chest: expected a name and maybe a capacity");
+            return;
+        }
+    };
+    let chest = Entity {
+        components: vec![Component::Chest {
+            items: Vec::new(),
+            capacity,
+        }],
+    };
+    if let Err(error) = spawn(chest, name) {
+        println!("{}", t!("error.spawn", name = name, error = error));
+        return;
+    }
+    println!("a sturdy chest appears as {name}.");
+}
+
+/// Whether `path` is a chest, for deciding if a verb is meant for the game.
+pub fn is_chest(path: &str) -> bool {
+    get_entity(path).is_ok_and(|e| e.chest().is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testworld::world;
+
+    #[test]
+    fn chests_hold_items_up_to_their_capacity() {
+        let world = world! {};
+        world.add(
+            "cellar",
+            "chest",
+            Entity {
+                components: vec![Component::Chest {
+                    items: Vec::new(),
+                    capacity: 10,
+                }],
+            },
+        );
+        let chest = world.path("cellar/chest");
+        let chest = chest.to_str().unwrap();
+        world.run(|| {
+            let mut player = PlayerState::default();
+            let mut sword = Item::found("sword");
+            sword.identified = true;
+            player.inventory = vec![sword.clone(), Item::new("armor")];
+            player.save().unwrap();
+
+            store("sword", chest);
+            // too heavy to fit as well
+            store("armor", chest);
+            assert_eq!(PlayerState::load().inventory, [Item::new("armor")]);
+            let e = get_entity(chest).unwrap();
+            assert_eq!(e.chest().unwrap().0, [sword.clone()]);
+
+            retrieve("sword", chest);
+            assert!(PlayerState::load().inventory.contains(&sword));
+            assert!(get_entity(chest).unwrap().chest().unwrap().0.is_empty());
+        });
+    }
+}
@@ -0,0 +1,275 @@
+//! Character classes, and the verbs that come with them.
+//!
+//! The class is picked when a new character is set up and can't be changed
+//! afterwards. Each class teaches the player a verb nobody else knows, which the
+//! shell learns by building its game parser with [`verbs`] at startup.
+
+use std::{
+    env,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::locale::t;
+use super::{
+    Component, Entity, chest, clock,
+    dice::{Advantage, Dice, DiceExpr, Skill},
+    entities_in, get_entity, inventory,
+    parser::Parser,
+    player::PlayerState,
+    respawn, rest, schedule, spell, strike,
+};
+
+/// The DC to sneak past the guards of a directory.
+const SNEAK_DC: i32 = 12;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub enum Class {
+    /// Hits hard, and can `bash` things.
+    #[default]
+    Warrior,
+    /// Light on their feet, and can `sneak` into guarded directories.
+    Rogue,
+    /// Knows the light spell from the start, to see in dark directories.
+    Mage,
+}
+
+impl Class {
+    const ALL: [Class; 3] = [Class::Warrior, Class::Rogue, Class::Mage];
+
+    fn name(self) -> &'static str {
+        match self {
+            Class::Warrior => "warrior",
+            Class::Rogue => "rogue",
+            Class::Mage => "mage",
+        }
+    }
+
+    fn blurb(self) -> &'static str {
+        match self {
+            Class::Warrior => "hits hard and can bash things to bits",
+            Class::Rogue => "can sneak past guards into places they shouldn't be",
+            Class::Mage => "can cast light to see in the dark",
+        }
+    }
+
+    /// Asks the player to pick a class on stdin, defaulting to warrior if they
+    /// can't decide.
+    pub fn choose() -> Class {
+        println!("who are you?");
+        for (i, class) in Class::ALL.iter().enumerate() {
+            println!("  {}) {}: {}", i + 1, class.name(), class.blurb());
+        }
+
+        loop {
+            print!("pick a class: ");
+            io::stdout().flush().unwrap();
+
+            let mut input = String::new();
+            if let Ok(0) | Err(_) = io::stdin().read_line(&mut input) {
+                return Class::default();
+            }
+
+            let input = input.trim().to_lowercase();
+            let chosen = Class::ALL
+                .iter()
+                .enumerate()
+                .find(|(i, class)| input == class.name() || input == (i + 1).to_string());
+            if let Some((_, class)) = chosen {
+                println!("welcome, {}.", class.name());
+                return *class;
+            }
+            println!("that's not one of the options.");
+        }
+    }
+}
+
+/// Game verbs the shell understands, on top of its builtins.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Verb {
+    Bash,
+    Sneak,
+    Cast,
+    Read,
+    Rest,
+    Inventory,
+    Drop,
+    Take,
+    Equip,
+    Unequip,
+    Identify,
+    Uncurse,
+    Ask,
+    Store,
+    Retrieve,
+    Open,
+}
+
+impl Verb {
+    /// What the verb does, in a few words.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Verb::Bash => "hit something really hard",
+            Verb::Sneak => "slip past a guard into a room",
+            Verb::Cast => "cast a spell",
+            Verb::Read => "read a scroll to learn a spell",
+            Verb::Rest => "sleep to get your hp back",
+            Verb::Inventory => "list what you're carrying",
+            Verb::Drop => "put something down",
+            Verb::Take => "pick something up",
+            Verb::Equip => "put on or wield something",
+            Verb::Unequip => "take something off",
+            Verb::Identify => "find out what something does",
+            Verb::Uncurse => "lift a curse from something",
+            Verb::Ask => "ask someone where to find someone",
+            Verb::Store => "put something in a chest",
+            Verb::Retrieve => "take something out of a chest",
+            Verb::Open => "look inside a chest",
+        }
+    }
+}
+
+/// Builds the parser for the verbs available to `class`.
+pub fn verbs(class: Class) -> Parser<Verb> {
+    let parser = Parser::new()
+        .insert(Verb::Cast, "[cast] () [on|at] ()")
+        .insert(Verb::Cast, "[cast] ()")
+        .insert(Verb::Read, "[read|study] ()")
+        .insert(Verb::Rest, "[rest|camp]")
+        .insert(Verb::Inventory, "[i|inv|inventory]")
+        .insert(Verb::Drop, "[drop|discard] ()")
+        .insert(Verb::Take, "[take|grab|get|pick up] ()")
+        .insert(Verb::Equip, "[equip|wear|wield|put on] ()")
+        .insert(Verb::Unequip, "[unequip|remove|take off] ()")
+        .insert(Verb::Identify, "[identify] ()")
+        .insert(Verb::Uncurse, "[uncurse|bless] ()")
+        .insert(Verb::Ask, "[ask] () [about|for|where] ()")
+        .insert(Verb::Store, "[store|stash] () [in|into] ()")
+        .insert(Verb::Retrieve, "[retrieve] () [from] ()")
+        .insert(Verb::Open, "[open|look in] ()");
+
+    match class {
+        Class::Warrior => parser.insert(Verb::Bash, "[bash|smash] ()"),
+        Class::Rogue => parser.insert(Verb::Sneak, "[sneak|creep|slip] ()"),
+        Class::Mage => parser,
+    }
+}
+
+/// Whether the verb makes sense here. Verbs that share a name with a real
+/// program, like `bash`, should only be taken over by the game when they are
+/// pointed at something in the game.
+pub fn applies(verb: &Verb, args: &[String]) -> bool {
+    match (verb, args) {
+        (Verb::Bash, [target]) => get_entity(target).is_ok(),
+        (Verb::Sneak, [dir]) => Path::new(dir).is_dir(),
+        (Verb::Cast, [spell]) => spell::knows(spell),
+        (Verb::Cast, [spell, target]) => spell::knows(spell) || get_entity(target).is_ok(),
+        (Verb::Read, [target]) => get_entity(target).is_ok_and(|e| e.scroll().is_some()),
+        (Verb::Rest, [_]) => true,
+        (Verb::Ask, [person, _]) => get_entity(person).is_ok(),
+        (Verb::Inventory, [_]) => true,
+        (Verb::Store | Verb::Retrieve, [_, target]) | (Verb::Open, [target]) => {
+            chest::is_chest(target)
+        }
+        (Verb::Take, [target]) => get_entity(target).is_ok_and(|e| e.item().is_some()),
+        (Verb::Drop | Verb::Equip | Verb::Unequip | Verb::Identify | Verb::Uncurse, [item]) => {
+            PlayerState::load().has(item)
+        }
+        _ => false,
+    }
+}
+
+/// Performs a verb returned by the parser from [`verbs`].
+pub fn act(verb: Verb, args: &[String]) {
+    match (verb, args) {
+        (Verb::Bash, [target]) => {
+            println!("you wind up for a mighty blow...");
+            let damage = DiceExpr {
+                count: 2,
+                sides: 4,
+                modifier: 1,
+            };
+            if strike(target, damage).is_err() {
+                println!("could not bash {target}.");
+            }
+        }
+        (Verb::Sneak, [dir]) => sneak(dir),
+        (Verb::Cast, [spell]) => spell::cast(spell, None),
+        (Verb::Cast, [spell, target]) => spell::cast(spell, Some(target)),
+        (Verb::Read, [target]) => spell::read(target),
+        (Verb::Rest, [_]) => rest::rest(),
+        (Verb::Inventory, [_]) => inventory::list(),
+        (Verb::Drop, [item]) => inventory::drop(item),
+        (Verb::Take, [target]) => inventory::take(target),
+        (Verb::Equip, [item]) => inventory::equip(item),
+        (Verb::Unequip, [item]) => inventory::unequip(item),
+        (Verb::Identify, [item]) => inventory::identify(item),
+        (Verb::Uncurse, [item]) => inventory::uncurse(item),
+        (Verb::Ask, [person, name]) => schedule::ask(person, name),
+        (Verb::Store, [item, target]) => chest::store(item, target),
+        (Verb::Retrieve, [item, target]) => chest::retrieve(item, target),
+        (Verb::Open, [target]) => chest::open(target),
+        _ => panic!("This should not be possible by parser postcondition."),
+    }
+}
+
+impl Entity {
+    pub fn is_guard(&self) -> bool {
+        self.components
+            .iter()
+            .any(|c| matches!(c, Component::Guard))
+    }
+
+    pub fn is_dark(&self) -> bool {
+        self.components.iter().any(|c| matches!(c, Component::Dark))
+    }
+}
+
+/// Returns the name of something guarding `dir`, if anything is.
+/// Guards whose faction trusts the player let them by.
+pub fn guarded_by(dir: &Path) -> Option<String> {
+    let player = PlayerState::load();
+    entities_in(dir).into_iter().find_map(|(path, e)| {
+        (e.is_guard() && !player.is_trusted_by(&e))
+            .then(|| path.file_name().unwrap().to_string_lossy().into_owned())
+    })
+}
+
+/// Whether `dir` is too dark to see in. Rooms are dark at night, unless the
+/// player has a lantern.
+pub fn is_dark(dir: &Path) -> bool {
+    if entities_in(dir).iter().any(|(_, e)| e.is_dark()) {
+        return true;
+    }
+    let player = PlayerState::load();
+    clock::Time::at_turn(player.turns).is_night() && respawn::is_room(dir) && !player.has("lantern")
+}
+
+/// Tries to slip into a guarded directory unnoticed. Getting caught hurts.
+fn sneak(dir: &str) {
+    let mut player = PlayerState::load();
+    let target = PathBuf::from(dir);
+
+    match guarded_by(&target) {
+        None => println!("there's nobody to sneak past, but you tiptoe in anyway."),
+        Some(guard) => {
+            if !Dice::new().check(&player, Skill::Stealth, SNEAK_DC, Advantage::Normal) {
+                println!("{guard} spots you and shoves you back out!");
+                player.hurt(2);
+                if let Err(error) = player.save() {
+                    println!("{}", t!("error.save", error = error));
+                }
+                return;
+            }
+            println!("you slip past {guard} without a sound.");
+        }
+    }
+
+    let from = env::current_dir().unwrap_or_default();
+    match env::set_current_dir(&target) {
+        Ok(()) => super::on_enter(&from, &env::current_dir().unwrap_or_default()),
+        Err(error) => println!("sneak errored: {error}"),
+    }
+}
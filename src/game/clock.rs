@@ -0,0 +1,148 @@
+//! The time of day in the game world, and what changes with it.
+//!
+//! The clock runs on the player's turns rather than on real time, so it only
+//! moves on when they do something and reads the same on every machine. Each
+//! turn is a quarter of an hour, and the first one is at eight in the
+//! morning. Turns are kept in the save, so the time is too.
+//!
+//! - After dark the undead come out: rooms only bring back their
+//!   [nocturnal](super::bestiary::Template::nocturnal) monsters at night.
+//! - Merchants don't sell anything at night.
+//! - Rooms made with `game room` are pitch black at night, unless the player
+//!   has a lantern.
+//! - `{clock}` in a theme's prompt shows the time, and `game time` says it.
+
+use std::fmt;
+
+use super::locale::t;
+use super::player::PlayerState;
+
+/// How many minutes each turn takes.
+const MINUTES_PER_TURN: u64 = 15;
+
+/// The time on the first turn, in minutes after midnight.
+const START: u64 = 8 * 60;
+
+const MINUTES_PER_DAY: u64 = 24 * 60;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    Dawn,
+    Day,
+    Dusk,
+    Night,
+}
+
+impl Phase {
+    /// The phase at `hour` o'clock.
+    fn at(hour: u64) -> Phase {
+        match hour {
+            5..7 => Phase::Dawn,
+            7..18 => Phase::Day,
+            18..20 => Phase::Dusk,
+            _ => Phase::Night,
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            Phase::Dawn => "dawn",
+            Phase::Day => "day",
+            Phase::Dusk => "dusk",
+            Phase::Night => "night",
+        }
+    }
+}
+
+/// A moment on the game's clock.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Time {
+    /// Minutes since midnight before the first turn.
+    minutes: u64,
+}
+
+impl Time {
+    /// The time on turn `turns`.
+    pub fn at_turn(turns: u64) -> Time {
+        Time {
+            minutes: START + turns * MINUTES_PER_TURN,
+        }
+    }
+
+    /// The time now, going by the player's save.
+    pub fn now() -> Time {
+        Time::at_turn(PlayerState::load().turns)
+    }
+
+    /// Which day it is, counting from 1.
+    pub fn day(self) -> u64 {
+        self.minutes / MINUTES_PER_DAY + 1
+    }
+
+    fn hour(self) -> u64 {
+        self.minutes % MINUTES_PER_DAY / 60
+    }
+
+    pub fn phase(self) -> Phase {
+        Phase::at(self.hour())
+    }
+
+    pub fn is_night(self) -> bool {
+        self.phase() == Phase::Night
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour(), self.minutes % 60)
+    }
+}
+
+/// Called every turn. Says so when the sun comes up or goes down.
+pub fn tick(player: &PlayerState) {
+    let Some(before) = player.turns.checked_sub(1) else {
+        return;
+    };
+    let phase = Time::at_turn(player.turns).phase();
+    if Time::at_turn(before).phase() != phase {
+        println!("{}", t!(&format!("clock.{}", phase.key())));
+    }
+}
+
+/// Runs `game time`, saying what time it is.
+pub fn command(args: &[String]) {
+    if !args.is_empty() {
+        println!("game time: expected nothing after it");
+        return;
+    }
+    let time = Time::now();
+    println!(
+        "{}",
+        t!(
+            "clock.now",
+            day = time.day(),
+            time = time,
+            phase = t!(&format!("clock.phase.{}", time.phase().key()))
+        )
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn days_go_round() {
+        let morning = Time::at_turn(0);
+        assert_eq!(morning.to_string(), "08:00");
+        assert_eq!((morning.day(), morning.phase()), (1, Phase::Day));
+        // ten hours on
+        assert_eq!(Time::at_turn(40).phase(), Phase::Dusk);
+        let midnight = Time::at_turn(64);
+        assert_eq!(midnight.to_string(), "00:00");
+        assert_eq!(midnight.day(), 2);
+        assert!(midnight.is_night());
+        assert_eq!(Time::at_turn(85).to_string(), "05:15");
+        assert_eq!(Time::at_turn(85).phase(), Phase::Dawn);
+    }
+}
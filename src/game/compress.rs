@@ -0,0 +1,94 @@
+//! Squeezing big entity files down.
+//!
+//! Entities with a lot in them, like a hoard's inventory or a merchant's
+//! stock, make for big files, and content packs are full of them. So after
+//! the file signature, an entity can be [`FLAG`] followed by its MessagePack,
+//! deflated. Small entities are written as they are, since deflating barely
+//! helps them, and so are ones it doesn't make any smaller. Files without the
+//! flag, like everything written before there was compression, go straight
+//! into the MessagePack and still load.
+//!
+//! Deflate rather than something newer like zstd, because its crate is pure
+//! Rust and already built for other dependencies.
+
+use std::borrow::Cow;
+
+use miniz_oxide::{deflate::compress_to_vec, inflate::decompress_to_vec_with_limit};
+
+/// Marks a compressed entity. MessagePack never uses this byte, so it can't
+/// be the start of an uncompressed one.
+pub const FLAG: u8 = 0xc1;
+
+/// Entities smaller than this, in bytes, aren't worth compressing.
+pub const THRESHOLD: usize = 256;
+
+/// How hard deflate tries, from 0 to 10.
+const LEVEL: u8 = 6;
+
+/// The most a compressed entity can inflate to, so a corrupt file can't eat
+/// all the memory.
+const LIMIT: usize = 64 << 20;
+
+/// What goes after the file signature for an entity that's `payload` in
+/// MessagePack.
+pub fn pack(payload: Vec<u8>) -> Vec<u8> {
+    if payload.len() < THRESHOLD {
+        return payload;
+    }
+    let compressed = compress_to_vec(&payload, LEVEL);
+    // the flag takes a byte too
+    if compressed.len() + 1 >= payload.len() {
+        return payload;
+    }
+    let mut body = Vec::with_capacity(compressed.len() + 1);
+    body.push(FLAG);
+    body.extend(compressed);
+    body
+}
+
+/// The MessagePack in `body`, everything after an entity's file signature,
+/// inflated if it was compressed.
+pub fn unpack(body: &[u8]) -> Result<Cow<'_, [u8]>, String> {
+    match body.split_first() {
+        Some((&FLAG, compressed)) => decompress_to_vec_with_limit(compressed, LIMIT)
+            .map(Cow::Owned)
+            .map_err(|error| format!("couldn't inflate the entity: {:?}", error.status)),
+        _ => Ok(Cow::Borrowed(body)),
+    }
+}
+
+/// Whether `body`, everything after an entity's file signature, was
+/// compressed.
+pub fn is_packed(body: &[u8]) -> bool {
+    body.first() == Some(&FLAG)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Component, Entity};
+
+    #[test]
+    fn only_big_entities_are_compressed() {
+        let small = Vec::from(Entity {
+            components: vec![Component::Enemy, Component::TakesDamage(5)],
+        });
+        assert_eq!(pack(small.clone()), small);
+        assert_eq!(unpack(&small).unwrap(), small);
+
+        let hoard = Vec::from(Entity {
+            components: vec![Component::HasInventory(vec![
+                String::from("gold coin");
+                500
+            ])],
+        });
+        let packed = pack(hoard.clone());
+        assert!(is_packed(&packed));
+        assert!(packed.len() < hoard.len() / 10);
+        assert_eq!(unpack(&packed).unwrap(), hoard);
+
+        let mut corrupt = packed;
+        corrupt.truncate(corrupt.len() / 2);
+        assert!(unpack(&corrupt).is_err());
+    }
+}
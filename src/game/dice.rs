@@ -0,0 +1,319 @@
+//! Tabletop-style dice, written like `2d6+3`, and the skill checks built on them.
+//!
+//! A skill check rolls a d20, adds the player's bonus for that skill, and
+//! succeeds if the total meets the check's difficulty class (DC). Rolling with
+//! advantage rolls twice and keeps the better result; disadvantage keeps the
+//! worse one.
+//!
+//! Rolls are random by default, but setting the `RLSH_SEED` environment
+//! variable makes every [`Dice`] produce the same sequence, which is useful for
+//! testing content.
+//!
+//! The rest of the game's randomness goes through [`random_range`] and
+//! friends, which are seeded too in headless mode (see [`crate::headless`]).
+
+use std::{fmt, str::FromStr, sync::Mutex};
+
+use rand::{
+    Rng, RngCore, SeedableRng,
+    distr::{Distribution, StandardUniform, uniform::SampleRange, uniform::SampleUniform},
+    rngs::StdRng,
+};
+
+use super::{class::Class, player::PlayerState};
+use crate::headless;
+
+/// Where the game's random numbers come from in headless mode, seeded the
+/// first time it's needed.
+static HEADLESS_RNG: Mutex<Option<StdRng>> = Mutex::new(None);
+
+/// Runs `f` with the game's random number generator: the thread's own, or
+/// one seeded with [`headless::SEED`] in headless mode.
+pub fn with_rng<T>(f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+    if !headless::enabled() {
+        return f(&mut rand::rng());
+    }
+    let mut rng = HEADLESS_RNG.lock().unwrap_or_else(|e| e.into_inner());
+    f(rng.get_or_insert_with(|| StdRng::seed_from_u64(headless::SEED)))
+}
+
+/// A random number in `range`.
+pub fn random_range<T: SampleUniform, R: SampleRange<T>>(range: R) -> T {
+    with_rng(|rng| rng.random_range(range))
+}
+
+/// True `numerator` times in `denominator`.
+pub fn random_ratio(numerator: u32, denominator: u32) -> bool {
+    with_rng(|rng| rng.random_ratio(numerator, denominator))
+}
+
+/// A random value of any type that can be made up from random bits.
+pub fn random<T>() -> T
+where
+    StandardUniform: Distribution<T>,
+{
+    with_rng(|rng| rng.random())
+}
+
+/// A dice expression like `2d6+3`: roll `count` dice with `sides` sides each,
+/// then add `modifier`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiceExpr {
+    pub count: u32,
+    pub sides: u32,
+    pub modifier: i32,
+}
+
+impl DiceExpr {
+    pub const D20: DiceExpr = DiceExpr {
+        count: 1,
+        sides: 20,
+        modifier: 0,
+    };
+}
+
+impl FromStr for DiceExpr {
+    type Err = String;
+
+    /// Parses `[count]d<sides>[(+|-)modifier]`. The count defaults to 1.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        let Some((count, rest)) = s.split_once('d') else {
+            return Err(format!("{s}: expected something like 2d6+3"));
+        };
+
+        let (sides, modifier) = match rest.find(['+', '-']) {
+            Some(i) => rest.split_at(i),
+            None => (rest, ""),
+        };
+
+        let count = match count {
+            "" => 1,
+            count => count
+                .parse()
+                .map_err(|_| format!("{s}: bad number of dice"))?,
+        };
+        let sides: u32 = sides
+            .parse()
+            .map_err(|_| format!("{s}: bad number of sides"))?;
+        let modifier = match modifier {
+            "" => 0,
+            // i32's FromStr accepts a leading + or -
+            modifier => modifier.parse().map_err(|_| format!("{s}: bad modifier"))?,
+        };
+
+        if count == 0 || sides == 0 {
+            return Err(format!("{s}: can't roll nothing"));
+        }
+        if count > 1000 {
+            return Err(format!("{s}: that's too many dice to hold"));
+        }
+
+        Ok(DiceExpr {
+            count,
+            sides,
+            modifier,
+        })
+    }
+}
+
+impl fmt::Display for DiceExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}d{}", self.count, self.sides)?;
+        match self.modifier {
+            0 => Ok(()),
+            m if m > 0 => write!(f, "+{m}"),
+            m => write!(f, "{m}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Advantage {
+    Normal,
+    /// Roll twice, keep the higher.
+    Advantage,
+    /// Roll twice, keep the lower.
+    Disadvantage,
+}
+
+/// Things the player can attempt a check on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Skill {
+    Perception,
+    Lockpicking,
+    Persuasion,
+    Stealth,
+}
+
+impl Skill {
+    /// The player's bonus to checks of this skill.
+    pub fn bonus(self, player: &PlayerState) -> i32 {
+        let class_bonus = match (self, player.class) {
+            (Skill::Stealth | Skill::Lockpicking, Class::Rogue) => 3,
+            (Skill::Persuasion, Class::Mage) => 1,
+            _ => 0,
+        };
+
+        class_bonus
+            + match self {
+                Skill::Perception => player.perception.into(),
+                Skill::Lockpicking if player.has("lockpick") => 2,
+                Skill::Lockpicking | Skill::Persuasion | Skill::Stealth => 0,
+            }
+    }
+}
+
+/// A source of dice rolls.
+pub struct Dice {
+    rng: StdRng,
+}
+
+impl Default for Dice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Dice {
+    /// Creates dice seeded from `RLSH_SEED` if it is set, from
+    /// [`headless::SEED`] in headless mode, or randomly otherwise.
+    pub fn new() -> Self {
+        let seed = std::env::var("RLSH_SEED").ok().and_then(|s| s.parse().ok());
+        match seed.or(headless::enabled().then_some(headless::SEED)) {
+            Some(seed) => Self::seeded(seed),
+            None => Dice {
+                rng: StdRng::from_os_rng(),
+            },
+        }
+    }
+
+    /// Creates dice that always produce the same rolls for the same seed.
+    pub fn seeded(seed: u64) -> Self {
+        Dice {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Rolls `expr` once, returning the total.
+    pub fn roll(&mut self, expr: DiceExpr) -> i32 {
+        let sum: i64 = (0..expr.count)
+            .map(|_| i64::from(self.rng.random_range(1..=expr.sides)))
+            .sum();
+        (sum + i64::from(expr.modifier)) as i32
+    }
+
+    /// Rolls `expr`, twice if there is (dis)advantage, returning the total kept.
+    pub fn roll_with(&mut self, expr: DiceExpr, advantage: Advantage) -> i32 {
+        let first = self.roll(expr);
+        match advantage {
+            Advantage::Normal => first,
+            Advantage::Advantage => first.max(self.roll(expr)),
+            Advantage::Disadvantage => first.min(self.roll(expr)),
+        }
+    }
+
+    /// Makes a skill check for the player against the difficulty class `dc`.
+    pub fn check(
+        &mut self,
+        player: &PlayerState,
+        skill: Skill,
+        dc: i32,
+        advantage: Advantage,
+    ) -> bool {
+        self.roll_with(DiceExpr::D20, advantage) + skill.bonus(player) >= dc
+    }
+}
+
+/// Runs the `roll` builtin: `roll [-a|-d] <dice>...`, where `-a` and `-d` roll
+/// with advantage and disadvantage respectively.
+pub fn command(args: &[String]) {
+    let mut advantage = Advantage::Normal;
+    let mut exprs = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "-a" => advantage = Advantage::Advantage,
+            "-d" => advantage = Advantage::Disadvantage,
+            arg => match arg.parse::<DiceExpr>() {
+                Ok(expr) => exprs.push(expr),
+                Err(error) => {
+                    println!("roll: {error}");
+                    return;
+                }
+            },
+        }
+    }
+
+    if exprs.is_empty() {
+        exprs.push(DiceExpr::D20);
+    }
+
+    let mut dice = Dice::new();
+    for expr in exprs {
+        println!("{expr}: {}", dice.roll_with(expr, advantage));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_expressions() {
+        let expr = |count, sides, modifier| DiceExpr {
+            count,
+            sides,
+            modifier,
+        };
+
+        assert_eq!("2d6+3".parse(), Ok(expr(2, 6, 3)));
+        assert_eq!("d20".parse(), Ok(expr(1, 20, 0)));
+        assert_eq!("3D8-1".parse(), Ok(expr(3, 8, -1)));
+        assert!("2d".parse::<DiceExpr>().is_err());
+        assert!("0d6".parse::<DiceExpr>().is_err());
+        assert!("2d6+".parse::<DiceExpr>().is_err());
+        assert!("six".parse::<DiceExpr>().is_err());
+
+        assert_eq!(expr(2, 6, 3).to_string(), "2d6+3");
+        assert_eq!(expr(1, 4, -2).to_string(), "1d4-2");
+    }
+
+    #[test]
+    fn rolls_stay_in_range() {
+        let mut dice = Dice::seeded(0);
+        let expr: DiceExpr = "3d6+2".parse().unwrap();
+        for _ in 0..1000 {
+            assert!((5..=20).contains(&dice.roll(expr)));
+        }
+    }
+
+    #[test]
+    fn seeded_dice_repeat() {
+        let rolls = |seed| {
+            let mut dice = Dice::seeded(seed);
+            (0..20)
+                .map(|_| dice.roll(DiceExpr::D20))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(rolls(42), rolls(42));
+    }
+
+    #[test]
+    fn advantage_keeps_the_better_roll() {
+        let mut plain = Dice::seeded(7);
+        let mut lucky = Dice::seeded(7);
+        let mut unlucky = Dice::seeded(7);
+        for _ in 0..100 {
+            let a = plain.roll(DiceExpr::D20);
+            let b = plain.roll(DiceExpr::D20);
+            assert_eq!(
+                lucky.roll_with(DiceExpr::D20, Advantage::Advantage),
+                a.max(b)
+            );
+            assert_eq!(
+                unlucky.roll_with(DiceExpr::D20, Advantage::Disadvantage),
+                a.min(b)
+            );
+        }
+    }
+}
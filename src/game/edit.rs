@@ -0,0 +1,156 @@
+//! Editing entities by hand.
+//!
+//! `game edit <path>` writes the entity out as JSON (see [`json`]), opens it in
+//! the user's editor, and spawns whatever comes back, as long as it's still an
+//! entity. This is how handcrafted content gets made.
+
+use std::{
+    env, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use super::{Entity, get_entity, json, spawn};
+
+/// The editor to fall back on when neither `$VISUAL` nor `$EDITOR` are set.
+const DEFAULT_EDITOR: &str = "vi";
+
+/// Turns an entity into the text that's shown in the editor.
+fn to_text(e: &Entity) -> Result<String, String> {
+    json::to_value(e)
+        .map(|value| value.to_string() + "\n")
+        .map_err(|error| error.to_string())
+}
+
+/// Turns edited text back into an entity, explaining what's wrong if it
+/// can't.
+fn from_text(text: &str) -> Result<Entity, String> {
+    let value = json::parse(text)?;
+    json::from_value(value).map_err(|error| error.to_string())
+}
+
+/// Opens `path` in the user's editor, returning once they're done with it.
+fn open_editor(path: &Path) -> Result<(), String> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| String::from(DEFAULT_EDITOR));
+    // the editor may come with arguments, like `code --wait`
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or(DEFAULT_EDITOR);
+
+    let status = Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()
+        .map_err(|error| format!("could not start {program}: {error}"))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{program} gave up ({status})"))
+    }
+}
+
+/// Asks a yes or no question, with no meaning no.
+fn confirm(question: &str) -> bool {
+    print!("{question} [y/n] ");
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).is_ok() && answer.trim().eq_ignore_ascii_case("y")
+}
+
+/// Where the entity being edited is kept while the editor has it.
+fn scratch_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    env::temp_dir().join(format!("rlsh-edit-{}-{name}.json", std::process::id()))
+}
+
+/// Runs `game edit <path>`. If there's no file at `path` yet, a new entity
+/// with no components is made there.
+pub fn command(args: &[String]) {
+    let [path] = args else {
+        println!("game edit: expected an entity");
+        return;
+    };
+    let path = Path::new(path);
+
+    let e = if path.exists() {
+        match get_entity(path) {
+            Ok(e) => e,
+            Err(_) => {
+                println!("game edit: {} isn't an entity", path.display());
+                return;
+            }
+        }
+    } else {
+        Entity {
+            components: Vec::new(),
+        }
+    };
+
+    let original = match to_text(&e) {
+        Ok(text) => text,
+        Err(error) => {
+            println!("game edit: could not write out {}: {error}", path.display());
+            return;
+        }
+    };
+    let scratch = scratch_path(path);
+    if let Err(error) = fs::write(&scratch, &original) {
+        println!("game edit: could not write {}: {error}", scratch.display());
+        return;
+    }
+
+    let edited = loop {
+        if let Err(error) = open_editor(&scratch) {
+            println!("game edit: {error}");
+            break None;
+        }
+        let text = fs::read_to_string(&scratch).unwrap_or_default();
+        if text == original {
+            println!("no changes.");
+            break None;
+        }
+        match from_text(&text) {
+            Ok(e) => break Some(e),
+            Err(error) => {
+                println!("that isn't an entity anymore: {error}");
+                if !confirm("edit it again?") {
+                    println!("your changes to {} were thrown out.", path.display());
+                    break None;
+                }
+            }
+        }
+    };
+    let _ = fs::remove_file(&scratch);
+
+    if let Some(e) = edited {
+        match spawn(e, path) {
+            Ok(()) => println!("saved {}.", path.display()),
+            Err(error) => println!("could not save {}: {error}", path.display()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Component;
+
+    #[test]
+    fn edited_text_is_validated() {
+        let e = Entity {
+            components: vec![Component::Enemy, Component::TakesDamage(5)],
+        };
+        let text = to_text(&e).unwrap();
+        let tougher = text.replace("5", "50");
+        let back = from_text(&tougher).unwrap();
+        assert_eq!(back.hp(), Some(50));
+
+        assert!(from_text(&text.replace("\"Enemy\"", "\"Enemy")).is_err());
+        assert!(from_text(&text.replace("TakesDamage", "TakesDamages")).is_err());
+    }
+}
@@ -0,0 +1,235 @@
+//! Random events that happen on a real-time schedule, whether or not the player
+//! is around to see them.
+//!
+//! The time each event is next due is kept in the save, so quitting rlsh doesn't
+//! reset the clock. Events are checked every time the prompt is printed.
+
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::locale::t;
+use super::{Component, Entity, despawn, get_entity, inventory::Item, player::PlayerState, spawn};
+use super::{dice, reputation};
+use crate::headless;
+
+/// Everything a merchant might have on offer, with prices in gold.
+const MERCHANT_GOODS: &[(&str, u32)] = &[
+    ("bread", 2),
+    ("bone", 3),
+    ("potion", 8),
+    ("rope", 4),
+    ("lantern", 12),
+    ("lockpick", 6),
+    ("map", 15),
+    ("identify scroll", 10),
+    ("holy water", 20),
+];
+
+/// How many goods a merchant brings with them on each visit.
+const MERCHANT_STOCK_SIZE: usize = 3;
+
+/// How long a merchant sticks around for, in seconds.
+const MERCHANT_STAY: u64 = 10 * 60;
+
+/// The name of the merchant's file in the player's home directory.
+const MERCHANT_FILE: &str = "merchant";
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Event {
+    /// A merchant sets up shop in the player's home directory for a while.
+    Merchant,
+}
+
+impl Event {
+    const ALL: [Event; 1] = [Event::Merchant];
+
+    /// How many seconds may pass between two occurrences of this event.
+    fn interval(self) -> Range<u64> {
+        match self {
+            Event::Merchant => 30 * 60..90 * 60,
+        }
+    }
+
+    fn start(self, now: u64) {
+        match self {
+            Event::Merchant => {
+                if let Some(path) = merchant_path()
+                    && open_shop(&path, now)
+                {
+                    println!("you hear the creak of a caravan pulling up at your home directory.");
+                }
+            }
+        }
+    }
+}
+
+/// Sets a merchant up at `path`, returning whether they're there. Nothing
+/// that's already at `path` is replaced, unless it's a merchant from an
+/// earlier visit, and an unwritable home just means no visitors.
+fn open_shop(path: &Path, now: u64) -> bool {
+    if get_entity(path).is_ok_and(|e| e.stock().is_none()) {
+        return false;
+    }
+
+    let mut goods = MERCHANT_GOODS.to_vec();
+    let mut stock = Vec::new();
+    for _ in 0..MERCHANT_STOCK_SIZE {
+        let (item, price) = goods.swap_remove(dice::random_range(0..goods.len()));
+        stock.push((item.to_string(), price));
+    }
+
+    let merchant = Entity {
+        components: vec![
+            Component::Merchant {
+                stock,
+                leaves_at: now + MERCHANT_STAY,
+            },
+            Component::Faction(reputation::TRADERS.to_string()),
+        ],
+    };
+    spawn(merchant, path).is_ok()
+}
+
+/// Returns the current time in seconds since the Unix epoch. In headless mode
+/// time stands still at the epoch.
+pub fn now() -> u64 {
+    if headless::enabled() {
+        return 0;
+    }
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn merchant_path() -> Option<PathBuf> {
+    let mut path = dirs::home_dir()?;
+    path.push(MERCHANT_FILE);
+    Some(path)
+}
+
+impl Entity {
+    /// Returns the merchant's stock if this entity is a merchant.
+    pub fn stock(&self) -> Option<&Vec<(String, u32)>> {
+        self.components.iter().find_map(|c| match c {
+            Component::Merchant { stock, .. } => Some(stock),
+            _ => None,
+        })
+    }
+}
+
+/// Starts any events that are due and cleans up the ones that are over.
+pub fn tick(player: &mut PlayerState) {
+    if headless::enabled() {
+        return;
+    }
+    let now = now();
+
+    if let Some(path) = merchant_path()
+        && let Ok(e) = get_entity(&path)
+    {
+        let gone = e.components.iter().any(|c| match c {
+            Component::Merchant { leaves_at, .. } => *leaves_at <= now,
+            _ => false,
+        });
+        if gone && despawn(&path).is_ok() {
+            println!("the merchant packs up their wares and moves on.");
+        }
+    }
+
+    for event in Event::ALL {
+        match player.next_events.get(&event) {
+            Some(&due) if due > now => continue,
+            // don't spring an event on a brand new player, just schedule it
+            Some(_) => event.start(now),
+            None => (),
+        }
+        let delay = dice::random_range(event.interval());
+        player.next_events.insert(event, now + delay);
+    }
+}
+
+/// Runs the `buy` builtin, buying an item from a merchant in the current directory.
+pub fn buy(item: &str) {
+    let Some((_, merchant)) = super::entities_in(".")
+        .into_iter()
+        .find(|(_, e)| e.stock().is_some())
+    else {
+        println!("there's nobody here to buy from.");
+        return;
+    };
+
+    let mut player = PlayerState::load();
+    if super::clock::Time::at_turn(player.turns).is_night() {
+        println!("the merchant's stall is shuttered for the night. come back in the morning.");
+        return;
+    }
+    // what the merchant's faction thinks of the player is reflected in the price
+    let standing = merchant
+        .faction()
+        .map_or(0, |faction| player.standing(faction));
+    let stock = merchant.stock().unwrap();
+    let Some(price) = stock
+        .iter()
+        .find(|(name, _)| name == item)
+        .map(|(_, price)| reputation::price(*price, standing))
+    else {
+        let wares: Vec<String> = stock
+            .iter()
+            .map(|(name, price)| format!("{name} ({}g)", reputation::price(*price, standing)))
+            .collect();
+        println!(
+            "the merchant doesn't sell that. on offer: {}",
+            wares.join(", ")
+        );
+        return;
+    };
+
+    if player.gold < price {
+        println!("you can't afford that. you have {} gold.", player.gold);
+        return;
+    }
+
+    player.gold -= price;
+    player.inventory.push(Item::new(item));
+    println!("you buy a {item} for {price} gold.");
+    super::inventory::warn_if_full(&player);
+    if let Err(error) = player.save() {
+        println!("{}", t!("error.save", error = error));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{
+        testworld::{enemy, world},
+        vfs,
+    };
+
+    #[test]
+    fn merchants_only_move_into_free_spots() {
+        let world = world! {};
+        let path = world.path("home/merchant");
+        world.run(|| {
+            assert!(open_shop(&path, 0));
+            // back for another visit
+            assert!(open_shop(&path, 100));
+            despawn(&path).unwrap();
+
+            vfs::with(|fs| fs.write(&path, b"my own notes")).unwrap();
+            assert!(!open_shop(&path, 0));
+            assert_eq!(vfs::with(|fs| fs.read(&path)).unwrap(), b"my own notes");
+            vfs::with(|fs| fs.remove(&path)).unwrap();
+        });
+
+        world.add("home", "merchant", enemy(3));
+        world.run(|| assert!(!open_shop(&path, 0)));
+        assert!(world.entity("home/merchant").unwrap().stock().is_none());
+    }
+}
@@ -0,0 +1,233 @@
+//! Stable identities for entities.
+//!
+//! Entities live in files, and files get moved around and renamed, so a path
+//! is a poor way to keep track of a particular entity. Every entity is given a
+//! [`Uuid`] when it is first spawned, which stays with it wherever it goes.
+//!
+//! The index remembers where each entity was last seen. It's only updated
+//! lazily: when an entity isn't where the index says it should be, we go
+//! looking for it and write down where we found it.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::dice;
+use super::{Component, Entity, FILE_SIGNATURE, get_entity, player, vfs};
+
+/// How many directories we're willing to search through for a lost entity.
+const MAX_SEARCH_DIRS: usize = 2000;
+
+/// A random (version 4) UUID.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Uuid(u128);
+
+impl Uuid {
+    pub fn new() -> Uuid {
+        let bits: u128 = dice::random();
+        // set the version to 4 and the variant to RFC 4122
+        let bits = (bits & !(0xf << 76)) | (0x4 << 76);
+        let bits = (bits & !(0x3 << 62)) | (0x2 << 62);
+        Uuid(bits)
+    }
+}
+
+impl Default for Uuid {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = format!("{:032x}", self.0);
+        write!(
+            f,
+            "{}-{}-{}-{}-{}",
+            &hex[0..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..32]
+        )
+    }
+}
+
+impl FromStr for Uuid {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex: String = s.chars().filter(|&c| c != '-').collect();
+        if hex.len() != 32 {
+            return Err(format!("{s} isn't a uuid"));
+        }
+        u128::from_str_radix(&hex, 16)
+            .map(Uuid)
+            .map_err(|_| format!("{s} isn't a uuid"))
+    }
+}
+
+impl Entity {
+    pub fn id(&self) -> Option<Uuid> {
+        self.components.iter().find_map(|c| match c {
+            Component::Id(id) => Some(*id),
+            _ => None,
+        })
+    }
+}
+
+/// Where every entity was last seen.
+#[derive(Default, Deserialize, Serialize)]
+struct Index {
+    paths: HashMap<Uuid, PathBuf>,
+}
+
+fn get_index_path() -> PathBuf {
+    let mut path = player::get_data_dir();
+    path.push("index.cfg");
+    path
+}
+
+impl Index {
+    fn load() -> Index {
+        vfs::with(|fs| fs.read(&get_index_path()))
+            .ok()
+            .and_then(|contents| {
+                let body = contents.strip_prefix(FILE_SIGNATURE)?;
+                rmp_serde::from_slice(body).ok()
+            })
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let path = get_index_path();
+        let mut contents = Vec::new();
+        contents.extend_from_slice(FILE_SIGNATURE);
+        contents.extend(rmp_serde::to_vec(self).map_err(io::Error::other)?);
+        vfs::with(|fs| {
+            if let Some(dir) = path.parent() {
+                fs.create_dir_all(dir)?;
+            }
+            fs.write(&path, &contents)
+        })
+    }
+}
+
+/// Whether the file at `path` is the entity `id`.
+fn is_at(id: Uuid, path: &Path) -> bool {
+    get_entity(path).is_ok_and(|e| e.id() == Some(id))
+}
+
+/// Notes down that the entity `id` is at `path`.
+pub fn record(id: Uuid, path: &Path) {
+    let mut index = Index::load();
+    if index.paths.get(&id).is_some_and(|known| known == path) {
+        return;
+    }
+    index.paths.insert(id, path.to_path_buf());
+    if let Err(error) = index.save() {
+        println!("could not save the entity index: {error}");
+    }
+}
+
+/// The entities the index last saw in `dir`, and their paths. Ones that
+/// have left since are skipped, but nothing else in `dir` is looked at.
+pub fn in_dir(dir: &Path) -> Vec<(Uuid, PathBuf)> {
+    Index::load()
+        .paths
+        .into_iter()
+        .filter(|(id, path)| path.parent() == Some(dir) && is_at(*id, path))
+        .collect()
+}
+
+/// Finds where the entity `id` is now. Looks where it was last seen first,
+/// then searches outwards from there, and finally through the home directory.
+pub fn find(id: Uuid) -> Option<PathBuf> {
+    let last_seen = Index::load().paths.get(&id).cloned();
+    if let Some(path) = &last_seen
+        && is_at(id, path)
+    {
+        return Some(path.clone());
+    }
+
+    let mut roots = Vec::new();
+    if let Some(dir) = last_seen.as_deref().and_then(Path::parent) {
+        roots.extend(dir.parent().map(Path::to_path_buf));
+        roots.push(dir.to_path_buf());
+    }
+    roots.extend(dirs::home_dir());
+
+    let found = roots.iter().find_map(|root| search(id, root))?;
+    record(id, &found);
+    Some(found)
+}
+
+/// Looks for the entity `id` under `root`, breadth first.
+fn search(id: Uuid, root: &Path) -> Option<PathBuf> {
+    let mut queue = VecDeque::from([root.to_path_buf()]);
+    let mut searched = 0;
+    while let Some(dir) = queue.pop_front() {
+        searched += 1;
+        if searched > MAX_SEARCH_DIRS {
+            return None;
+        }
+
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                queue.push_back(entry.path());
+            } else if file_type.is_file() && is_at(id, &entry.path()) {
+                return Some(entry.path());
+            }
+        }
+    }
+    None
+}
+
+/// Runs `game id <entity>`, printing an entity's id, or `game id <uuid>`,
+/// printing where that entity is now.
+pub fn command(args: &[String]) {
+    let [arg] = args else {
+        println!("game id: expected an entity or a uuid");
+        return;
+    };
+
+    if let Ok(id) = arg.parse::<Uuid>() {
+        match find(id) {
+            Some(path) => println!("{}", path.display()),
+            None => println!("game id: nothing answers to {id} anymore"),
+        }
+        return;
+    }
+    match get_entity(arg).map(|e| e.id()) {
+        Ok(Some(id)) => println!("{id}"),
+        Ok(None) => println!("game id: {arg} is too old to have an id"),
+        Err(_) => println!("game id: {arg} isn't an entity"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uuids_round_trip() {
+        let id = Uuid::new();
+        let text = id.to_string();
+        assert_eq!(text.len(), 36);
+        assert_eq!(text.as_bytes()[14], b'4');
+        assert!(matches!(text.as_bytes()[19], b'8' | b'9' | b'a' | b'b'));
+        assert_eq!(text.parse(), Ok(id));
+        assert!("not-a-uuid".parse::<Uuid>().is_err());
+    }
+}
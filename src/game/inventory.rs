@@ -0,0 +1,499 @@
+//! What the player carries, and how much of it they can carry.
+//!
+//! Every item has a weight, and the player can only carry so much depending on
+//! their strength and level. Going over the limit doesn't stop the player from
+//! doing anything, but it does slow them down.
+//!
+//! Equipment found out in the world may be blessed or cursed, but the player
+//! can't tell which until they put it on or read an identify scroll over it.
+//! Cursed equipment can't be taken off again until the curse is lifted with
+//! holy water.
+
+use std::{fmt, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use super::dice;
+use super::locale::t;
+use super::{Component, Entity, despawn, get_entity, player::PlayerState, spawn};
+
+/// Items that can be worn or wielded.
+const EQUIPMENT: &[&str] = &["sword", "shield", "armor", "helmet", "ring", "amulet"];
+
+/// Consumed to reveal whether an item is blessed or cursed.
+pub const IDENTIFY_SCROLL: &str = "identify scroll";
+
+/// Consumed to lift the curse from an item.
+pub const HOLY_WATER: &str = "holy water";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Blessing {
+    Blessed,
+    Uncursed,
+    Cursed,
+}
+
+/// Something the player can carry.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "ItemRepr")]
+pub struct Item {
+    pub name: String,
+    pub blessing: Blessing,
+    /// Whether the player knows the item's blessing.
+    pub identified: bool,
+    pub equipped: bool,
+}
+
+/// Inventories used to be lists of item names, so accept those too.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ItemRepr {
+    Name(String),
+    Full {
+        name: String,
+        blessing: Blessing,
+        identified: bool,
+        equipped: bool,
+    },
+    /// Entity files leave out field names, and untagged structs can't be read
+    /// without them.
+    Compact(String, Blessing, bool, bool),
+}
+
+impl From<ItemRepr> for Item {
+    fn from(repr: ItemRepr) -> Self {
+        match repr {
+            ItemRepr::Name(name) => Item::new(&name),
+            ItemRepr::Full {
+                name,
+                blessing,
+                identified,
+                equipped,
+            } => Item {
+                name,
+                blessing,
+                identified,
+                equipped,
+            },
+            ItemRepr::Compact(name, blessing, identified, equipped) => Item {
+                name,
+                blessing,
+                identified,
+                equipped,
+            },
+        }
+    }
+}
+
+impl Item {
+    /// Creates a plain item that the player knows all about, like one bought
+    /// from a merchant.
+    pub fn new(name: &str) -> Self {
+        Item {
+            name: name.to_string(),
+            blessing: Blessing::Uncursed,
+            identified: true,
+            equipped: false,
+        }
+    }
+
+    /// Creates an item found lying around. Equipment found this way might be
+    /// blessed or cursed, and the player won't know which.
+    pub fn found(name: &str) -> Self {
+        if !is_equipment(name) {
+            return Item::new(name);
+        }
+
+        let blessing = match dice::random_range(0..6) {
+            0 => Blessing::Blessed,
+            1 => Blessing::Cursed,
+            _ => Blessing::Uncursed,
+        };
+        Item {
+            blessing,
+            identified: false,
+            ..Item::new(name)
+        }
+    }
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.identified, self.blessing) {
+            (false, _) => write!(f, "{} (unidentified)", self.name)?,
+            (true, Blessing::Blessed) => write!(f, "blessed {}", self.name)?,
+            (true, Blessing::Cursed) => write!(f, "cursed {}", self.name)?,
+            (true, Blessing::Uncursed) => write!(f, "{}", self.name)?,
+        }
+        if self.equipped {
+            write!(f, " (equipped)")?;
+        }
+        Ok(())
+    }
+}
+
+pub fn is_equipment(name: &str) -> bool {
+    EQUIPMENT.contains(&name)
+}
+
+/// How much items weigh. Anything not listed weighs 1.
+const WEIGHTS: &[(&str, u32)] = &[
+    ("bread", 1),
+    ("bone", 1),
+    ("potion", 1),
+    ("key", 0),
+    ("lockpick", 0),
+    ("map", 0),
+    ("rope", 3),
+    ("lantern", 4),
+    ("shield", 8),
+    ("sword", 6),
+    ("armor", 12),
+];
+
+pub fn weight(item: &str) -> u32 {
+    WEIGHTS
+        .iter()
+        .find(|(name, _)| *name == item)
+        .map_or(1, |(_, weight)| *weight)
+}
+
+impl PlayerState {
+    /// The total weight of everything the player is carrying.
+    pub fn carried_weight(&self) -> u32 {
+        self.inventory.iter().map(|item| weight(&item.name)).sum()
+    }
+
+    /// Whether the player is carrying an item called `name`.
+    pub fn has(&self, name: &str) -> bool {
+        self.inventory.iter().any(|item| item.name == name)
+    }
+
+    /// Takes an item called `name` out of the player's inventory, preferring
+    /// ones that aren't equipped. Equipped items are only taken if `equipped`
+    /// is set.
+    pub fn remove_item(&mut self, name: &str, equipped: bool) -> Option<Item> {
+        let i = self
+            .inventory
+            .iter()
+            .position(|item| item.name == name && !item.equipped)
+            .or_else(|| {
+                self.inventory
+                    .iter()
+                    .position(|item| item.name == name && equipped)
+            })?;
+        Some(self.inventory.remove(i))
+    }
+
+    /// Suggests the heaviest item the player could drop, if any.
+    fn suggest_drop(&self) {
+        let heaviest = self
+            .inventory
+            .iter()
+            .filter(|item| !item.equipped)
+            .max_by_key(|item| weight(&item.name));
+        if let Some(heaviest) = heaviest {
+            println!("try `drop {}` to lighten your load.", heaviest.name);
+        }
+    }
+
+    /// How much weight the player can carry before they are slowed down.
+    pub fn capacity(&self) -> u32 {
+        10 + 2 * u32::from(self.strength) + 2 * self.level
+    }
+
+    pub fn is_encumbered(&self) -> bool {
+        self.carried_weight() > self.capacity()
+    }
+}
+
+/// Whether the saved player is carrying too much.
+pub fn encumbered() -> bool {
+    PlayerState::load().is_encumbered()
+}
+
+/// Tells the player to lighten their load if they are carrying too much,
+/// suggesting the heaviest thing they could drop.
+pub fn warn_if_full(player: &PlayerState) {
+    if !player.is_encumbered() {
+        return;
+    }
+
+    println!(
+        "your pack is too heavy ({}/{}). you strain under the weight.",
+        player.carried_weight(),
+        player.capacity()
+    );
+    player.suggest_drop();
+}
+
+impl Entity {
+    /// Returns the item if this entity is one lying on the ground.
+    pub fn item(&self) -> Option<&Item> {
+        self.components.iter().find_map(|c| match c {
+            Component::Item(item) => Some(item),
+            _ => None,
+        })
+    }
+}
+
+/// Runs the `inventory` verb.
+pub fn list() {
+    let player = PlayerState::load();
+    if player.inventory.is_empty() {
+        println!("you aren't carrying anything.");
+    }
+    for item in &player.inventory {
+        println!("{item} ({})", weight(&item.name));
+    }
+    println!(
+        "{} gold, carrying {}/{}",
+        player.gold,
+        player.carried_weight(),
+        player.capacity()
+    );
+}
+
+/// Runs the `drop` verb, leaving an item in the current directory.
+pub fn drop(item: &str) {
+    let mut player = PlayerState::load();
+    let Some(dropped) = player.remove_item(item, false) else {
+        if player.has(item) {
+            println!("you'll have to take off the {item} first.");
+        } else {
+            println!("you aren't carrying any {item}.");
+        }
+        return;
+    };
+
+    // don't clobber whatever is already there
+    let mut name = item.to_string();
+    let mut n = 1;
+    while Path::new(&name).exists() {
+        n += 1;
+        name = format!("{item}{n}");
+    }
+
+    let dropped = Entity {
+        components: vec![Component::Item(dropped)],
+    };
+    if let Err(error) = spawn(dropped, &name) {
+        println!("{}", t!("error.spawn", name = name, error = error));
+        return;
+    }
+    println!("you drop the {item}.");
+    if let Err(error) = player.save() {
+        println!("{}", t!("error.save", error = error));
+    }
+}
+
+/// Runs the `take` verb, picking up an item lying in the current directory.
+/// Items that would overload the player are left where they are.
+pub fn take(target: &str) {
+    let Some(item) = get_entity(target).ok().and_then(|e| e.item().cloned()) else {
+        println!("you can't take {target}.");
+        return;
+    };
+
+    let mut player = PlayerState::load();
+    if player.carried_weight() + weight(&item.name) > player.capacity() {
+        println!("you can't carry the {} as well.", item.name);
+        player.suggest_drop();
+        return;
+    }
+
+    if let Err(error) = despawn(target) {
+        println!("could not take {target}: {error}");
+        return;
+    }
+    println!("you pick up the {item}.");
+    player.inventory.push(Item {
+        equipped: false,
+        ..item
+    });
+    if let Err(error) = player.save() {
+        println!("{}", t!("error.save", error = error));
+    }
+}
+
+/// Runs the `equip` verb. Putting something on reveals whether it is cursed,
+/// usually too late.
+pub fn equip(name: &str) {
+    let mut player = PlayerState::load();
+    let Some(item) = player
+        .inventory
+        .iter_mut()
+        .find(|item| item.name == name && !item.equipped)
+    else {
+        println!("you don't have a {name} to put on.");
+        return;
+    };
+    if !is_equipment(name) {
+        println!("you can't wear a {name}.");
+        return;
+    }
+
+    item.equipped = true;
+    let discovered = !item.identified;
+    item.identified = true;
+    match item.blessing {
+        Blessing::Cursed => println!("you put on the {name}. it tightens around you. cursed!"),
+        Blessing::Blessed if discovered => {
+            println!("you put on the {name}. it glows softly. blessed!")
+        }
+        _ => println!("you put on the {name}."),
+    }
+    if let Err(error) = player.save() {
+        println!("{}", t!("error.save", error = error));
+    }
+}
+
+/// Runs the `unequip` verb. Cursed items refuse to come off.
+pub fn unequip(name: &str) {
+    let mut player = PlayerState::load();
+    let Some(item) = player
+        .inventory
+        .iter_mut()
+        .find(|item| item.name == name && item.equipped)
+    else {
+        println!("you aren't wearing a {name}.");
+        return;
+    };
+
+    if item.blessing == Blessing::Cursed {
+        println!("you tug at the {name}, but it won't come off!");
+        return;
+    }
+    item.equipped = false;
+    println!("you take off the {name}.");
+    if let Err(error) = player.save() {
+        println!("{}", t!("error.save", error = error));
+    }
+}
+
+/// Runs the `identify` verb, using up an identify scroll to learn the nature
+/// of an item.
+pub fn identify(name: &str) {
+    let mut player = PlayerState::load();
+    if !player
+        .inventory
+        .iter()
+        .any(|item| item.name == name && !item.identified)
+    {
+        println!("you don't have a {name} that needs identifying.");
+        return;
+    }
+    if player.remove_item(IDENTIFY_SCROLL, false).is_none() {
+        println!("you need an {IDENTIFY_SCROLL} for that.");
+        return;
+    }
+
+    let item = player
+        .inventory
+        .iter_mut()
+        .find(|item| item.name == name && !item.identified)
+        .unwrap();
+    item.identified = true;
+    println!("the scroll crumbles to dust. it was a {item}.");
+    if let Err(error) = player.save() {
+        println!("{}", t!("error.save", error = error));
+    }
+}
+
+/// Runs the `uncurse` verb, using up holy water to lift a curse from an item.
+pub fn uncurse(name: &str) {
+    let mut player = PlayerState::load();
+    let cursed = |item: &Item| item.name == name && item.blessing == Blessing::Cursed;
+    if !player.inventory.iter().any(cursed) {
+        println!("you don't have a cursed {name}.");
+        return;
+    }
+    if player.remove_item(HOLY_WATER, false).is_none() {
+        println!("you need some {HOLY_WATER} for that.");
+        return;
+    }
+
+    let item = player
+        .inventory
+        .iter_mut()
+        .find(|item| cursed(item))
+        .unwrap();
+    item.blessing = Blessing::Uncursed;
+    item.identified = true;
+    println!("you sprinkle the {HOLY_WATER} over the {name}. the curse lifts.");
+    if let Err(error) = player.save() {
+        println!("{}", t!("error.save", error = error));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heavy_packs_encumber() {
+        let mut player = PlayerState::default();
+        assert_eq!(player.capacity(), 18);
+        assert!(!player.is_encumbered());
+
+        player.inventory = vec![Item::new("armor"), Item::new("lantern")];
+        assert_eq!(player.carried_weight(), 16);
+        assert!(!player.is_encumbered());
+
+        player.inventory.push(Item::new("mystery meat"));
+        player.inventory.push(Item::new("rope"));
+        assert_eq!(player.carried_weight(), 20);
+        assert!(player.is_encumbered());
+
+        player.level += 1;
+        assert!(!player.is_encumbered());
+    }
+
+    #[test]
+    fn old_inventories_still_load() {
+        let names = rmp_serde::to_vec(&["sword"]).unwrap();
+        let items: Vec<Item> = rmp_serde::from_slice(&names).unwrap();
+        assert_eq!(items, vec![Item::new("sword")]);
+
+        let mut cursed = Item::found("sword");
+        cursed.blessing = Blessing::Cursed;
+        let full = rmp_serde::to_vec_named(&[&cursed]).unwrap();
+        let items: Vec<Item> = rmp_serde::from_slice(&full).unwrap();
+        assert_eq!(items, vec![cursed]);
+    }
+
+    #[test]
+    fn equipped_items_are_removed_last() {
+        let mut player = PlayerState::default();
+        player.inventory = vec![
+            Item {
+                equipped: true,
+                ..Item::new("ring")
+            },
+            Item::new("ring"),
+        ];
+
+        assert_eq!(player.remove_item("ring", false), Some(Item::new("ring")));
+        assert_eq!(player.remove_item("ring", false), None);
+        assert!(player.remove_item("ring", true).is_some());
+    }
+
+    #[test]
+    fn taking_an_item_moves_it_into_the_inventory() {
+        use super::super::testworld::{item, trap, world};
+
+        let world = world! {
+            "cave" => { "rope": item("rope"), "tripwire": trap(3) },
+        };
+        let rope = world.path("cave/rope");
+        world.run(|| take(rope.to_str().unwrap()));
+        assert!(world.entity("cave/rope").is_none());
+        let player = world.run(PlayerState::load);
+        assert!(player.inventory.iter().any(|item| item.name == "rope"));
+
+        // traps can't be picked up
+        let tripwire = world.path("cave/tripwire");
+        world.run(|| take(tripwire.to_str().unwrap()));
+        assert!(world.entity("cave/tripwire").is_some());
+    }
+}
@@ -0,0 +1,785 @@
+//! Just enough JSON to show entities to people.
+//!
+//! Entities are stored as MessagePack, which is compact but unreadable. When
+//! someone wants to edit one by hand, we turn it into JSON instead, going
+//! through [`Value`] in both directions. Enums are written the same way
+//! `serde_json` writes them: unit variants as strings, and everything else as
+//! an object with the variant's name as its only key.
+
+use std::fmt::{self, Write};
+
+use serde::{
+    Deserialize, Serialize,
+    de::{self, IntoDeserializer, value::Error},
+    ser,
+};
+
+/// A JSON value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    /// A number, kept as it was written so that big ones (like ids) don't lose
+    /// precision.
+    Number(String),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+/// Turns anything serializable into JSON.
+pub fn to_value<T: ?Sized + Serialize>(value: &T) -> Result<Value, Error> {
+    value.serialize(ValueSerializer)
+}
+
+/// Turns JSON back into something deserializable.
+pub fn from_value<'de, T: Deserialize<'de>>(value: Value) -> Result<T, Error> {
+    T::deserialize(value)
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+impl Value {
+    fn write(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        let pad = "  ".repeat(indent + 1);
+        match self {
+            Value::Null => f.write_str("null"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Number(n) => f.write_str(n),
+            Value::String(s) => write_string(f, s),
+            Value::Array(values) if values.is_empty() => f.write_str("[]"),
+            Value::Object(fields) if fields.is_empty() => f.write_str("{}"),
+            Value::Array(values) => {
+                f.write_str("[\n")?;
+                for (i, value) in values.iter().enumerate() {
+                    f.write_str(&pad)?;
+                    value.write(f, indent + 1)?;
+                    f.write_str(if i + 1 < values.len() { ",\n" } else { "\n" })?;
+                }
+                write!(f, "{}]", "  ".repeat(indent))
+            }
+            Value::Object(fields) => {
+                f.write_str("{\n")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    f.write_str(&pad)?;
+                    write_string(f, key)?;
+                    f.write_str(": ")?;
+                    value.write(f, indent + 1)?;
+                    f.write_str(if i + 1 < fields.len() { ",\n" } else { "\n" })?;
+                }
+                write!(f, "{}}}", "  ".repeat(indent))
+            }
+        }
+    }
+}
+
+/// A value written as JSON all on one line, from [`Value::compact`].
+pub struct Compact<'a>(&'a Value);
+
+impl fmt::Display for Compact<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Value::Array(values) => {
+                f.write_char('[')?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", value.compact())?;
+                }
+                f.write_char(']')
+            }
+            Value::Object(fields) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ": {}", value.compact())?;
+                }
+                f.write_char('}')
+            }
+            // nothing else spans lines anyway
+            value => value.write(f, 0),
+        }
+    }
+}
+
+impl Value {
+    /// The value as JSON on one line, for formats with a value per line.
+    pub fn compact(&self) -> Compact<'_> {
+        Compact(self)
+    }
+}
+
+/// Writes the value as pretty printed JSON.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+/// Reads JSON text. Errors say which line and column things went wrong on.
+pub fn parse(text: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        text,
+        chars: text.char_indices().peekable(),
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    match parser.chars.peek() {
+        Some(&(at, _)) => Err(parser.error(at, "expected the end of the file")),
+        None => Ok(value),
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+}
+
+impl Parser<'_> {
+    fn error(&self, at: usize, message: &str) -> String {
+        let before = &self.text[..at];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+        format!("line {line}, column {column}: {message}")
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some((_, c)) if c == expected => Ok(()),
+            Some((at, _)) => Err(self.error(at, &format!("expected '{expected}'"))),
+            None => Err(self.error(self.text.len(), &format!("expected '{expected}'"))),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        let Some(&(at, c)) = self.chars.peek() else {
+            return Err(self.error(self.text.len(), "expected a value"));
+        };
+        match c {
+            '{' => self.object(),
+            '[' => self.array(),
+            '"' => self.string().map(Value::String),
+            '-' | '0'..='9' => {
+                let mut end = at;
+                while let Some((i, c)) = self
+                    .chars
+                    .next_if(|(_, c)| matches!(c, '-' | '+' | '.' | 'e' | 'E' | '0'..='9'))
+                {
+                    end = i + c.len_utf8();
+                }
+                let number = &self.text[at..end];
+                match number.parse::<f64>() {
+                    Ok(_) => Ok(Value::Number(number.to_string())),
+                    Err(_) => Err(self.error(at, &format!("{number} isn't a number"))),
+                }
+            }
+            _ => {
+                let word: String = std::iter::from_fn(|| {
+                    self.chars
+                        .next_if(|(_, c)| c.is_ascii_alphabetic())
+                        .map(|(_, c)| c)
+                })
+                .collect();
+                match word.as_str() {
+                    "null" => Ok(Value::Null),
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    _ => Err(self.error(at, "expected a value")),
+                }
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            let Some((at, c)) = self.chars.next() else {
+                return Err(self.error(self.text.len(), "unterminated string"));
+            };
+            match c {
+                '"' => return Ok(s),
+                '\\' => match self.chars.next().map(|(_, c)| c) {
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('u') => {
+                        let hex: String = (0..4)
+                            .filter_map(|_| self.chars.next())
+                            .map(|(_, c)| c)
+                            .collect();
+                        let c = u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| self.error(at, "bad unicode escape"))?;
+                        s.push(c);
+                    }
+                    Some(c @ ('"' | '\\' | '/')) => s.push(c),
+                    _ => return Err(self.error(at, "bad escape")),
+                },
+                c => s.push(c),
+            }
+        }
+    }
+
+    /// Parses a comma separated list of items, up to `close`.
+    fn list(
+        &mut self,
+        open: char,
+        close: char,
+        mut item: impl FnMut(&mut Self) -> Result<(), String>,
+    ) -> Result<(), String> {
+        self.expect(open)?;
+        self.skip_whitespace();
+        if self.chars.next_if(|&(_, c)| c == close).is_some() {
+            return Ok(());
+        }
+        loop {
+            item(self)?;
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some((_, ',')) => continue,
+                Some((_, c)) if c == close => return Ok(()),
+                Some((at, _)) => return Err(self.error(at, &format!("expected ',' or '{close}'"))),
+                None => return Err(self.error(self.text.len(), &format!("expected '{close}'"))),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        let mut values = Vec::new();
+        self.list('[', ']', |parser| {
+            values.push(parser.value()?);
+            Ok(())
+        })?;
+        Ok(Value::Array(values))
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        let mut fields = Vec::new();
+        self.list('{', '}', |parser| {
+            let key = parser.string()?;
+            parser.expect(':')?;
+            fields.push((key, parser.value()?));
+            Ok(())
+        })?;
+        Ok(Value::Object(fields))
+    }
+}
+
+struct ValueSerializer;
+
+/// Collects the elements of a sequence, or of a variant's tuple, which gets
+/// wrapped up in an object named after the variant.
+struct SeqSerializer {
+    variant: Option<&'static str>,
+    values: Vec<Value>,
+}
+
+/// Collects the fields of a map or struct, likewise.
+struct MapSerializer {
+    variant: Option<&'static str>,
+    fields: Vec<(String, Value)>,
+    key: Option<String>,
+}
+
+fn tagged(variant: Option<&'static str>, value: Value) -> Value {
+    match variant {
+        Some(variant) => Value::Object(vec![(variant.to_string(), value)]),
+        None => value,
+    }
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = Error;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = MapSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, Error> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, Error> {
+        Ok(Value::Number(v.to_string()))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Value, Error> {
+        Ok(Value::Number(v.to_string()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, Error> {
+        Ok(Value::Number(v.to_string()))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Value, Error> {
+        Ok(Value::Number(v.to_string()))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, Error> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, Error> {
+        if !v.is_finite() {
+            return Err(ser::Error::custom("json can't hold infinite numbers"));
+        }
+        Ok(Value::Number(format!("{v:?}")))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, Error> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, Error> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, Error> {
+        Ok(Value::Array(
+            v.iter().map(|b| Value::Number(b.to_string())).collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> Result<Value, Error> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, Error> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, Error> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Value, Error> {
+        Ok(Value::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        Ok(tagged(Some(variant), value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, Error> {
+        Ok(SeqSerializer {
+            variant: None,
+            values: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, Error> {
+        Ok(SeqSerializer {
+            variant: Some(variant),
+            values: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer, Error> {
+        Ok(MapSerializer {
+            variant: None,
+            fields: Vec::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<MapSerializer, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<MapSerializer, Error> {
+        Ok(MapSerializer {
+            variant: Some(variant),
+            fields: Vec::new(),
+            key: None,
+        })
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.values.push(to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(tagged(self.variant, Value::Array(self.values)))
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        match to_value(key)? {
+            Value::String(key) | Value::Number(key) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => Err(ser::Error::custom("json keys have to be strings")),
+        }
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| <Error as ser::Error>::custom("value without a key"))?;
+        self.fields.push((key, to_value(value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(tagged(self.variant, Value::Object(self.fields)))
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.fields.push((key.to_string(), to_value(value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for MapSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Value;
+
+    fn into_deserializer(self) -> Value {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Null => visitor.visit_unit(),
+            Value::Bool(b) => visitor.visit_bool(b),
+            Value::Number(n) => {
+                if let Ok(n) = n.parse::<u64>() {
+                    visitor.visit_u64(n)
+                } else if let Ok(n) = n.parse::<i64>() {
+                    visitor.visit_i64(n)
+                } else if let Ok(n) = n.parse::<u128>() {
+                    visitor.visit_u128(n)
+                } else if let Ok(n) = n.parse::<i128>() {
+                    visitor.visit_i128(n)
+                } else {
+                    // the parser only lets through things that parse as floats
+                    visitor.visit_f64(n.parse().unwrap_or(f64::NAN))
+                }
+            }
+            Value::String(s) => visitor.visit_string(s),
+            Value::Array(values) => {
+                de::value::SeqDeserializer::new(values.into_iter()).deserialize_any(visitor)
+            }
+            Value::Object(fields) => {
+                de::value::MapDeserializer::new(fields.into_iter()).deserialize_any(visitor)
+            }
+        }
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Null => visitor.visit_none(),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self {
+            Value::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+            Value::Object(mut fields) if fields.len() == 1 => {
+                let (variant, value) = fields.remove(0);
+                visitor.visit_enum(Variant { variant, value })
+            }
+            _ => Err(de::Error::custom(
+                "expected a variant name, or an object with a variant name as its only key",
+            )),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+/// A variant written as `{"Variant": value}`.
+struct Variant {
+    variant: String,
+    value: Value,
+}
+
+impl<'de> de::EnumAccess<'de> for Variant {
+    type Error = Error;
+    type Variant = Value;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Value), Error> {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+        Ok((variant, self.value))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Value {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: de::Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: de::Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Component, Entity, id::Uuid, inventory::Item};
+
+    #[test]
+    fn json_round_trips() {
+        let text = r#"{"a": [1, -2.5e3, true, null], "b\n": {"c": "é\"x"}}"#;
+        let value = parse(text).unwrap();
+        assert_eq!(parse(&value.to_string()), Ok(value));
+
+        assert_eq!(
+            parse("{\n  \"a\": [1,]\n}"),
+            Err(String::from("line 2, column 11: expected a value"))
+        );
+        assert!(parse("[1] 2").is_err());
+        assert!(parse("\"unterminated").is_err());
+    }
+
+    #[test]
+    fn entities_round_trip() {
+        let e = Entity {
+            components: vec![
+                Component::Enemy,
+                Component::TakesDamage(-3),
+                Component::Item(Item::found("sword")),
+                Component::Merchant {
+                    stock: vec![(String::from("rope"), 5)],
+                    leaves_at: 1_700_000_000,
+                },
+                Component::Id(Uuid::new()),
+            ],
+        };
+        let value = to_value(&e).unwrap();
+        let text = value.to_string();
+        assert!(text.contains("\"Enemy\""));
+        assert!(text.contains("\"TakesDamage\": -3"));
+
+        let back: Entity = from_value(parse(&text).unwrap()).unwrap();
+        assert_eq!(to_value(&back).unwrap(), value);
+        assert!(from_value::<Entity>(parse(r#"{"components": ["Dragon"]}"#).unwrap()).is_err());
+    }
+}
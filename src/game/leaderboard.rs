@@ -0,0 +1,279 @@
+//! Personal bests for clearing dungeons.
+//!
+//! A dungeon is any directory holding an entity with a [`Component::Dungeon`]
+//! marker. A run starts when the player first walks into one and ends when the
+//! last enemy in it dies, at which point the time taken and number of kills
+//! are compared against the leaderboard file in the data directory.
+//!
+//! Dungeons are identified by a hash of their seed rather than their path, so
+//! that two players who generated the same dungeon can compare their results.
+
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    Component, FILE_SIGNATURE, entities_in, events, is_cleared,
+    player::{self, PlayerState},
+    vfs,
+};
+
+/// The first line of an exported leaderboard, so that imports can tell what
+/// they're looking at.
+const EXPORT_HEADER: &str = "rlsh-leaderboard v1";
+
+/// A dungeon the player is partway through clearing.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Run {
+    /// When the run started, in seconds since the Unix epoch.
+    pub started: u64,
+    pub kills: u32,
+}
+
+/// The best results for a single dungeon.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Record {
+    /// The name of the dungeon's directory when it was last cleared.
+    pub name: String,
+    /// The fastest clear, in seconds.
+    pub best_time: u64,
+    /// The most kills in a single clear.
+    pub most_kills: u32,
+    pub clears: u32,
+}
+
+/// All records, by dungeon id.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Leaderboard {
+    pub records: BTreeMap<u64, Record>,
+}
+
+/// Hashes a dungeon seed into a dungeon id.
+///
+/// This is 64-bit FNV-1a, which unlike [`std::hash::DefaultHasher`] is
+/// guaranteed to give the same result on every machine and Rust version.
+pub fn dungeon_id(seed: &str) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    seed.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
+/// Returns the id of the dungeon in `dir`, if `dir` is a dungeon.
+fn dungeon_in(dir: &Path) -> Option<u64> {
+    entities_in(dir).iter().find_map(|(_, e)| {
+        e.components.iter().find_map(|c| match c {
+            Component::Dungeon { seed } => Some(dungeon_id(seed)),
+            _ => None,
+        })
+    })
+}
+
+/// Formats a number of seconds like `1h02m03s`, leaving off empty leading units.
+pub fn format_time(secs: u64) -> String {
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{s}s"),
+        (0, m, s) => format!("{m}m{s:02}s"),
+        (h, m, s) => format!("{h}h{m:02}m{s:02}s"),
+    }
+}
+
+impl Leaderboard {
+    fn path() -> PathBuf {
+        let mut path = player::get_data_dir();
+        path.push("leaderboard");
+        path
+    }
+
+    /// Loads the leaderboard, or an empty one if there is none yet.
+    pub fn load() -> Self {
+        vfs::with(|fs| fs.read(&Self::path()))
+            .ok()
+            .and_then(|contents| {
+                let body = contents.strip_prefix(FILE_SIGNATURE)?;
+                rmp_serde::from_slice(body).ok()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path();
+        let mut contents = Vec::new();
+        contents.extend_from_slice(FILE_SIGNATURE);
+        contents.extend(rmp_serde::to_vec_named(self).map_err(io::Error::other)?);
+        vfs::with(|fs| {
+            if let Some(dir) = path.parent() {
+                fs.create_dir_all(dir)?;
+            }
+            fs.write(&path, &contents)
+        })
+    }
+
+    /// Records a finished run, returning whether it set a new best time.
+    fn record(&mut self, id: u64, name: String, time: u64, kills: u32) -> bool {
+        match self.records.get_mut(&id) {
+            Some(record) => {
+                let best = time < record.best_time;
+                record.name = name;
+                record.best_time = record.best_time.min(time);
+                record.most_kills = record.most_kills.max(kills);
+                record.clears += 1;
+                best
+            }
+            None => {
+                self.records.insert(
+                    id,
+                    Record {
+                        name,
+                        best_time: time,
+                        most_kills: kills,
+                        clears: 1,
+                    },
+                );
+                true
+            }
+        }
+    }
+
+    /// Writes the leaderboard in a plain text format meant for pasting into
+    /// chats and forums. Each line is `<id> <time> <kills> <name>`.
+    fn export(&self) -> String {
+        let mut out = String::from(EXPORT_HEADER);
+        for (id, record) in &self.records {
+            out += &format!(
+                "\n{id:016x} {} {} {}",
+                record.best_time, record.most_kills, record.name
+            );
+        }
+        out
+    }
+}
+
+/// Starts a run if the player just walked into a dungeon they aren't already
+/// clearing.
+pub fn enter(player: &mut PlayerState, dir: &Path) {
+    if let Some(id) = dungeon_in(dir) {
+        player.runs.entry(id).or_insert_with(|| {
+            println!("you feel a chill as you step into the dungeon. the clock is ticking.");
+            Run {
+                started: events::now(),
+                kills: 0,
+            }
+        });
+    }
+}
+
+/// Counts a kill in `dir` towards the run there, finishing the run if nothing
+/// hostile is left.
+pub fn kill(player: &mut PlayerState, dir: &Path) {
+    let Some(id) = dungeon_in(dir) else {
+        return;
+    };
+
+    let run = player.runs.entry(id).or_insert(Run {
+        started: events::now(),
+        kills: 0,
+    });
+    run.kills += 1;
+
+    if !is_cleared(dir) {
+        return;
+    }
+
+    let run = player.runs.remove(&id).unwrap();
+    let time = events::now().saturating_sub(run.started);
+    let name = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    println!(
+        "dungeon cleared in {} with {} kills!",
+        format_time(time),
+        run.kills
+    );
+
+    let mut leaderboard = Leaderboard::load();
+    if leaderboard.record(id, name, time, run.kills) {
+        println!("that's a new personal best.");
+    }
+    if let Err(error) = leaderboard.save() {
+        println!("could not save the leaderboard: {error}");
+    }
+}
+
+/// Runs the `leaderboard` builtin. `leaderboard export` prints the shareable
+/// format instead of the table.
+pub fn command(args: &[String]) {
+    let leaderboard = Leaderboard::load();
+
+    match args.first().map(String::as_str) {
+        None => {
+            if leaderboard.records.is_empty() {
+                println!("no dungeons cleared yet.");
+                return;
+            }
+
+            println!(
+                "{:<16}  {:>9}  {:>5}  {:>6}  dungeon",
+                "id", "best", "kills", "clears"
+            );
+            for (id, record) in &leaderboard.records {
+                println!(
+                    "{id:016x}  {:>9}  {:>5}  {:>6}  {}",
+                    format_time(record.best_time),
+                    record.most_kills,
+                    record.clears,
+                    record.name
+                );
+            }
+        }
+        Some("export") => println!("{}", leaderboard.export()),
+        Some(other) => println!("leaderboard: unknown subcommand {other}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dungeon_ids_are_stable() {
+        // reference values for 64-bit FNV-1a
+        assert_eq!(dungeon_id(""), 0xcbf29ce484222325);
+        assert_eq!(dungeon_id("a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(dungeon_id("foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn records_keep_the_best() {
+        let mut leaderboard = Leaderboard::default();
+        assert!(leaderboard.record(1, "crypt".to_string(), 100, 3));
+        assert!(!leaderboard.record(1, "crypt".to_string(), 120, 5));
+        assert!(leaderboard.record(1, "crypt".to_string(), 90, 2));
+
+        let record = &leaderboard.records[&1];
+        assert_eq!(record.best_time, 90);
+        assert_eq!(record.most_kills, 5);
+        assert_eq!(record.clears, 3);
+
+        assert_eq!(
+            leaderboard.export(),
+            "rlsh-leaderboard v1\n0000000000000001 90 5 crypt"
+        );
+    }
+
+    #[test]
+    fn times_are_formatted() {
+        assert_eq!(format_time(7), "7s");
+        assert_eq!(format_time(65), "1m05s");
+        assert_eq!(format_time(3723), "1h02m03s");
+    }
+}
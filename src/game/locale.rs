@@ -0,0 +1,274 @@
+//! Translations of the game's text.
+//!
+//! Every message the game shows goes through [`t!`] with a key, like
+//! `battle.hit`. The English text for each key is built in; other languages
+//! are read from `lang/<language>.lang` in the data directory, where
+//! `<language>` comes from the `language` setting (see [`crate::config`]).
+//!
+//! Language files have one `key = text` per line, and lines starting with `#`
+//! are comments. Text can use `{name}` to put in the values a message is given,
+//! like the name of the monster being fought. Keys a language file leaves out
+//! stay in English, so a translation can be done a bit at a time. `game
+//! strings` prints every key with its English text, as a place to start.
+//!
+//! Room descriptions work the same way: a `room.<name>` key is shown when the
+//! player walks into a directory called `<name>`.
+
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use super::player;
+use crate::config::Config;
+
+/// The language the game is written in.
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// Every message, in English.
+const ENGLISH: &[(&str, &str)] = &[
+    (
+        "battle.start",
+        "{name} blocks your way. there's no punching your way out of this one in a single go.",
+    ),
+    ("battle.options", "(attack, defend, item <name>, or flee)"),
+    (
+        "battle.prompt",
+        "[{name}: {bar} {hp} hp | you: {player_bar} {player_hp} hp] battle> ",
+    ),
+    ("battle.hit", "you hit {name} for {damage} damage."),
+    ("battle.defend", "you brace yourself."),
+    ("battle.fled", "you get away from {name}."),
+    ("battle.cut_off", "you try to run, but {name} cuts you off!"),
+    ("battle.help.attack", "attack: hit the enemy"),
+    ("battle.help.defend", "defend: take half damage this turn"),
+    (
+        "battle.help.item",
+        "item <name>: use something from your inventory",
+    ),
+    ("battle.help.flee", "flee: try to run away"),
+    ("battle.unknown", "that won't help you here."),
+    ("battle.hurt", "{name} hits you for {damage} damage."),
+    ("battle.won", "{name} falls. the battle is over."),
+    ("battle.no_item", "you aren't carrying any {item}."),
+    (
+        "battle.useless_item",
+        "the {item} won't do you any good right now.",
+    ),
+    (
+        "battle.used_item",
+        "you use the {item} and recover some hp.",
+    ),
+    ("strike.hp", "the dude has {hp} hp."),
+    ("strike.killed", "you punched him so hard he died. yikes."),
+    (
+        "strike.hit",
+        "you punched him with some amount of force, knocking out about {damage} teeth.",
+    ),
+    ("strike.hp_left", "the poor sod only has {hp} left."),
+    ("loot.gold", "you find {gold} gold on the body."),
+    ("loot.item", "you find a {item} on the body."),
+    ("enter.locked", "{door} is locked tight."),
+    ("enter.guarded", "{guard} blocks the way in."),
+    (
+        "battle.status",
+        "{name} has {hp} hp. you have {player_hp} hp.",
+    ),
+    ("battle.plain_prompt", "battle> "),
+    ("access.hp", "your hp went from {from} to {to}."),
+    ("access.mana", "your mana went from {from} to {to}."),
+    ("access.gold", "your gold went from {from} to {to}."),
+    ("access.level", "you went from level {from} to level {to}."),
+    ("access.gained", "you now have a {item}."),
+    ("access.lost", "you no longer have a {item}."),
+    ("ls.directory", "{name} (directory)"),
+    ("ls.fifo", "{name} (named pipe)"),
+    ("ls.socket", "{name} (socket)"),
+    ("ls.device", "{name} (device)"),
+    ("ls.enemy", "{name} (enemy)"),
+    ("ls.door", "{name} (door)"),
+    ("ls.locked_door", "{name} (locked door)"),
+    ("ls.trap", "{name} (trap)"),
+    ("ls.item", "{name} (item)"),
+    ("ls.person", "{name} (person)"),
+    ("ls.chest", "{name} (chest)"),
+    ("clock.dawn", "the sky pales. dawn is breaking."),
+    ("clock.day", "the sun is up."),
+    ("clock.dusk", "the light is fading."),
+    ("clock.night", "night falls. things stir in the dark."),
+    ("clock.now", "it's {time} on day {day}. {phase}"),
+    ("clock.phase.dawn", "dawn is breaking."),
+    ("clock.phase.day", "the sun is up."),
+    ("clock.phase.dusk", "dusk is setting in."),
+    ("clock.phase.night", "it's night."),
+    ("schedule.leaves", "{name} heads off to {dir}."),
+    ("schedule.arrives", "{name} arrives."),
+    (
+        "schedule.routine",
+        "{person} says {name} spends the day in {day} and the night in {night}.",
+    ),
+    ("schedule.unknown", "{person} has never heard of {name}."),
+    ("chest.stored", "you put the {item} in {chest}."),
+    ("chest.retrieved", "you take the {item} out of {chest}."),
+    ("chest.full", "there's no room in {chest} for the {item}."),
+    ("chest.missing", "there's no {item} in {chest}."),
+    ("chest.empty", "{chest} is empty."),
+    ("chest.not_a_chest", "{chest} isn't a chest."),
+    ("reputation.kill", "the {faction} won't forget this."),
+    ("reputation.better", "the {faction} think better of you."),
+    ("reputation.worse", "the {faction} think less of you."),
+    ("watch.appeared", "a {name} has appeared!"),
+    ("watch.gone", "the {name} is gone."),
+    ("error.remove", "could not remove {name}: {error}"),
+    ("error.save", "could not save: {error}"),
+    ("error.spawn", "could not put {name} there: {error}"),
+];
+
+/// The text for each key in the current language.
+#[derive(Debug, Default)]
+struct Locale {
+    messages: HashMap<String, String>,
+}
+
+static LOCALE: Mutex<Option<Locale>> = Mutex::new(None);
+
+fn get_lang_path(language: &str) -> PathBuf {
+    let mut path = player::get_data_dir();
+    path.push("lang");
+    path.push(format!("{language}.lang"));
+    path
+}
+
+/// Reads the messages in a language file.
+fn parse(text: &str) -> HashMap<String, String> {
+    text.lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, text)| (key.trim().to_string(), text.trim().to_string()))
+        .collect()
+}
+
+impl Locale {
+    fn load(language: &str) -> Locale {
+        let messages = if language == DEFAULT_LANGUAGE {
+            HashMap::new()
+        } else {
+            fs::read_to_string(get_lang_path(language))
+                .map(|text| parse(&text))
+                .unwrap_or_default()
+        };
+        Locale { messages }
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.messages
+            .get(key)
+            .map(String::as_str)
+            .or_else(|| english(key))
+    }
+}
+
+fn english(key: &str) -> Option<&'static str> {
+    ENGLISH
+        .iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, text)| *text)
+}
+
+/// Forgets the loaded language, so the next message is looked up afresh.
+/// Called when the language setting changes.
+pub fn reload() {
+    *LOCALE.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Looks up `key` in the current language, loading it if needed.
+fn lookup(key: &str) -> Option<String> {
+    let mut locale = LOCALE.lock().unwrap_or_else(|e| e.into_inner());
+    let locale = locale.get_or_insert_with(|| Locale::load(Config::load().get("language")));
+    locale.get(key).map(String::from)
+}
+
+/// Fills in the `{name}`s in `text` with `args`. Unknown names are left as
+/// they are.
+fn fill(text: &str, args: &[(&str, String)]) -> String {
+    let mut out = text.to_string();
+    for (name, value) in args {
+        out = out.replace(&format!("{{{name}}}"), value);
+    }
+    out
+}
+
+/// Returns the message for `key` in the current language, with `args` filled
+/// in. Use [`t!`] rather than calling this directly.
+pub fn format(key: &str, args: &[(&str, String)]) -> String {
+    match lookup(key) {
+        Some(text) => fill(&text, args),
+        // a missing key is a bug, but it's better to show something
+        None => key.to_string(),
+    }
+}
+
+/// Returns a translated message, like `t!("battle.hit", name = name, damage =
+/// damage)`. See [the module docs](self).
+macro_rules! t {
+    ($key:expr $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::game::locale::format($key, &[$((stringify!($name), $value.to_string())),*])
+    };
+}
+pub(crate) use t;
+
+/// The description of the directory `dir`, if the current language has one.
+pub fn room_description(dir: &Path) -> Option<String> {
+    let name = dir.file_name()?.to_string_lossy();
+    lookup(&format!("room.{name}"))
+}
+
+/// Runs `game strings`, printing every message in a form that can be saved as
+/// a language file and translated.
+pub fn command(args: &[String]) {
+    if !args.is_empty() {
+        println!("game strings: didn't expect any arguments");
+        return;
+    }
+    let mut out = String::from("# copy this to the lang folder in the data directory as\n");
+    out.push_str("# <language>.lang, translate it, then run `config language <language>`\n");
+    for (key, text) in ENGLISH {
+        writeln!(out, "{key} = {text}").unwrap();
+    }
+    print!("{out}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translations_fall_back_to_english() {
+        let locale = Locale {
+            messages: parse(
+                "# arr\nbattle.hit = ye strike {name} fer {damage}!\nroom.crypt=spooky\n",
+            ),
+        };
+        assert_eq!(
+            fill(
+                locale.get("battle.hit").unwrap(),
+                &[("name", String::from("goblin")), ("damage", 3.to_string())]
+            ),
+            "ye strike goblin fer 3!"
+        );
+        assert_eq!(locale.get("battle.defend"), Some("you brace yourself."));
+        assert_eq!(locale.get("room.crypt"), Some("spooky"));
+        assert_eq!(locale.get("no.such.key"), None);
+    }
+
+    #[test]
+    fn english_keys_are_unique() {
+        let mut keys: Vec<&str> = ENGLISH.iter().map(|(key, _)| *key).collect();
+        keys.sort();
+        keys.dedup();
+        assert_eq!(keys.len(), ENGLISH.len());
+    }
+}
@@ -1,24 +1,58 @@
 use std::{
-    env,
-    fs::{self, File},
-    io::Read,
+    env, fmt,
+    io::{self, IsTerminal},
     path::{Path, PathBuf},
 };
 
 use serde::{Deserialize, Serialize};
 
+use crate::headless;
+
+pub mod accessibility;
+pub mod battle;
+pub mod bestiary;
+pub mod chest;
+pub mod class;
+pub mod clock;
+pub mod compress;
+pub mod dice;
+pub mod edit;
+pub mod events;
+pub mod id;
+pub mod inventory;
+pub mod json;
+pub mod leaderboard;
+pub mod locale;
 pub mod parser;
+pub mod pet;
+pub mod player;
+pub mod quest;
+pub mod remote;
+pub mod reputation;
+pub mod respawn;
+pub mod rest;
+pub mod schedule;
+pub mod season;
+pub mod sftp;
+pub mod spell;
+#[cfg(test)]
+mod testworld;
+pub mod trap;
+pub mod trigger;
+pub mod validate;
+pub mod vfs;
+pub mod watch;
+
+use dice::{Dice, DiceExpr};
+use locale::t;
+use player::PlayerState;
 
 pub const FILE_SIGNATURE: &[u8; 8] = b"\x00rlsh0.1";
 
 pub const LOCKED_DOOR_ICON: &'static str = "󱂯";
 pub const UNLOCKED_DOOR_ICON: &'static str = "󰠛";
 pub const PERSON_ICON: &'static str = "";
-
-#[derive(Deserialize, Serialize)]
-struct Config {
-    hp: i32,
-}
+pub const TRAP_ICON: &str = "󰈸";
 
 /// An action that
 enum Action {
@@ -54,95 +88,574 @@ pub enum Component {
     TakesDamage(i16),
     Retaliates(i16),
     HasInventory(Vec<String>),
+    /// Goes off when the player lists or enters its directory, unless it has
+    /// been [`Component::Revealed`].
+    Trap(trap::TrapKind),
+    /// Marks a hidden entity, like a trap, as spotted by the player.
+    Revealed,
+    /// Sells the player items from its stock (item name and price in gold)
+    /// until the given time, in seconds since the Unix epoch.
+    Merchant {
+        stock: Vec<(String, u32)>,
+        leaves_at: u64,
+    },
+    /// Marks the directory this entity is in as a dungeon. Dungeons generated
+    /// from the same seed are considered the same for the leaderboard.
+    Dungeon {
+        seed: String,
+    },
+    /// A tougher enemy that has to be fought in a proper battle.
+    Elite,
+    /// Like [`Component::Elite`], but even harder to run away from.
+    Boss,
+    /// Keeps the player from walking into this entity's directory, unless
+    /// they sneak past.
+    Guard,
+    /// Makes this entity's directory too dark to see in.
+    Dark,
+    /// Teaches the player a spell when read.
+    SpellScroll(spell::Spell),
+    /// Lets the entity roam between neighbouring directories as time passes.
+    Wanders,
+    /// An item lying on the ground, waiting to be picked up.
+    Item(inventory::Item),
+    /// Passes itself off as an ordinary file until someone touches it.
+    Mimic,
+    /// Keeps track of the monsters living in this entity's directory, so they
+    /// can come back after being cleared out.
+    Room(respawn::Room),
+    /// Can't be opened or walked through until something unlocks it.
+    Locked,
+    /// Blocks the way into its directory while it's [`Component::Locked`].
+    Door,
+    /// Does something to another entity when something happens to this one.
+    Trigger(trigger::Trigger),
+    /// Tells this entity apart from every other, wherever it's moved to. Given
+    /// out when the entity is first spawned.
+    Id(id::Uuid),
+    /// Moves this entity between directories as the day goes by.
+    Schedule(schedule::Schedule),
+    /// Makes this entity one of a faction, which will remember how the
+    /// player treats it.
+    Faction(String),
+    /// Keeps what the player stores in it, up to `capacity` in weight, see
+    /// [chest].
+    Chest {
+        items: Vec<inventory::Item>,
+        capacity: u32,
+    },
+}
+
+impl fmt::Display for Component {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Component::Enemy => write!(f, "enemy"),
+            Component::TakesDamage(hp) => write!(f, "hp: {hp}"),
+            Component::Retaliates(damage) => write!(f, "retaliates: {damage}"),
+            Component::HasInventory(items) => write!(f, "inventory: {}", items.join(", ")),
+            Component::Trap(trap::TrapKind::Damage(damage)) => {
+                write!(f, "trap: deals {damage} damage")
+            }
+            Component::Trap(trap::TrapKind::Theft) => write!(f, "trap: steals an item"),
+            Component::Revealed => write!(f, "revealed"),
+            Component::Merchant { stock, leaves_at } => {
+                let stock: Vec<String> = stock
+                    .iter()
+                    .map(|(item, price)| format!("{item} ({price}g)"))
+                    .collect();
+                write!(f, "merchant: {}, leaves at {leaves_at}", stock.join(", "))
+            }
+            Component::Dungeon { seed } => write!(f, "dungeon: seed {seed}"),
+            Component::Elite => write!(f, "elite"),
+            Component::Boss => write!(f, "boss"),
+            Component::Guard => write!(f, "guard"),
+            Component::Dark => write!(f, "dark"),
+            Component::SpellScroll(spell) => write!(f, "spell scroll: {}", spell.name()),
+            Component::Wanders => write!(f, "wanders"),
+            Component::Item(item) => write!(f, "item: {item}"),
+            Component::Mimic => write!(f, "mimic"),
+            Component::Room(room) => {
+                let roster: Vec<String> = room
+                    .roster
+                    .iter()
+                    .map(|(file, template)| format!("{file} ({template})"))
+                    .collect();
+                write!(
+                    f,
+                    "room: {}, every {}, cleared {} times",
+                    roster.join(", "),
+                    leaderboard::format_time(room.delay),
+                    room.clears
+                )?;
+                match room.cleared_at {
+                    Some(at) => write!(f, ", last at {at}"),
+                    None => Ok(()),
+                }
+            }
+            Component::Locked => write!(f, "locked"),
+            Component::Door => write!(f, "door"),
+            Component::Trigger(trigger) => {
+                let target = match &trigger.target {
+                    trigger::Reference::Path(path) => path.display().to_string(),
+                    trigger::Reference::Uuid(id) => id.to_string(),
+                };
+                let when = format!("{:?}", trigger.when).to_lowercase();
+                let effect = format!("{:?}", trigger.effect).to_lowercase();
+                write!(f, "trigger: when {when}, {effect} {target}")
+            }
+            Component::Id(id) => write!(f, "id: {id}"),
+            Component::Faction(faction) => write!(f, "faction: {faction}"),
+            Component::Chest { items, capacity } => {
+                let items: Vec<String> = items.iter().map(ToString::to_string).collect();
+                write!(f, "chest ({capacity}): {}", items.join(", "))
+            }
+            Component::Schedule(schedule) => write!(
+                f,
+                "schedule: days in {}, nights in {}",
+                schedule.day.display(),
+                schedule.night.display()
+            ),
+        }
+    }
 }
 
 /// "Spawns" an entity in the specified path (relative to the current working directory),
 /// Its name will be its filename.
-pub fn spawn(e: Entity, path: impl AsRef<Path>) {
-    let mut abs_path = env::current_dir().unwrap();
+/// Whatever is already at the path has to be an entity too, so the player's own
+/// files are never written over.
+pub fn spawn(mut e: Entity, path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut abs_path = env::current_dir()?;
     abs_path.push(path);
 
+    vfs::with(|fs| match fs.starts_with(&abs_path, FILE_SIGNATURE) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "{} is already there, and it isn't an entity",
+                path.display()
+            ),
+        )),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(error),
+    })?;
+
+    let id = match e.id() {
+        Some(id) => id,
+        None => {
+            let id = id::Uuid::new();
+            e.components.push(Component::Id(id));
+            id
+        }
+    };
+
     let mut contents = Vec::new();
     contents.extend_from_slice(FILE_SIGNATURE);
-    contents.extend(Vec::from(e).into_iter());
-    fs::write(abs_path, contents).unwrap();
+    contents.extend(compress::pack(Vec::from(e)));
+    vfs::with(|fs| fs.write(&abs_path, &contents))?;
+    id::record(id, &abs_path);
+    Ok(())
+}
+
+/// Takes the entity at `path` out of the world.
+pub fn despawn(path: impl AsRef<Path>) -> io::Result<()> {
+    vfs::with(|fs| fs.remove(path.as_ref()))
 }
 
 pub fn get_entity(path: impl AsRef<Path>) -> Result<Entity, Box<dyn std::error::Error>> {
-    let mut entity_buffer = Vec::new();
-    {
-        let mut f = File::open(path)?;
-        let mut file_sig_check_buffer = [0; FILE_SIGNATURE.len()];
-        f.read_exact(&mut file_sig_check_buffer)?;
-        if file_sig_check_buffer != *FILE_SIGNATURE {
-            return Err(Box::from("lol"));
+    let path = path.as_ref();
+    let contents = vfs::with(|fs| {
+        // most files aren't entities, so there's no need to read all of them
+        match fs.starts_with(path, FILE_SIGNATURE)? {
+            true => fs.read(path).map(Some),
+            false => Ok(None),
         }
+    })?;
+    let Some(contents) = contents else {
+        return Err(Box::from("lol"));
+    };
 
-        f.read_to_end(&mut entity_buffer)?;
-        // the file is closed at the end of this scope
-    }
-
-    match Entity::try_from(&entity_buffer[..]) {
+    let body = compress::unpack(&contents[FILE_SIGNATURE.len()..])?;
+    match Entity::try_from(&body[..]) {
         Ok(e) => Ok(e),
         Err(_) => Err(Box::from("yikes")),
     }
 }
 
 pub fn attack(path: impl AsRef<Path>) -> Result<(), Box<dyn std::error::Error>> {
+    strike(
+        path,
+        DiceExpr {
+            count: 1,
+            sides: 3,
+            modifier: 0,
+        },
+    )
+}
+
+/// Hits the entity at `path` for `damage`, or starts a battle with it if it is
+/// too tough to take out that easily.
+pub fn strike(path: impl AsRef<Path>, damage: DiceExpr) -> Result<(), Box<dyn std::error::Error>> {
     let mut abs_path = env::current_dir().unwrap();
     abs_path.push(&path);
 
     let e = get_entity(&abs_path)?;
+    let mut player = PlayerState::load();
+
+    if e.is_tough() {
+        battle::fight(&mut player, e, &abs_path);
+        player.save()?;
+        crate::crash::let_go();
+        return Ok(());
+    }
+
+    if let Some(hp) = e.hp() {
+        despawn(&abs_path)?;
+        println!("{}", t!("strike.hp", hp = hp));
+        let damage = Dice::new().roll(damage) as i16;
+        let bonus = pet::assist(&player, abs_path.parent().unwrap());
+        let new_hp = hp - damage - bonus;
+        if new_hp <= 0 {
+            println!("{}", t!("strike.killed"));
+            defeat(&mut player, &e, &abs_path);
+            player.save()?;
+            return Ok(());
+        }
+        println!("{}", t!("strike.hit", damage = damage));
+        println!("{}", t!("strike.hp_left", hp = new_hp));
+        spawn(e.with_hp(new_hp), &abs_path)?;
+    }
+
+    Ok(())
+}
+
+/// Rewards the player for killing `e`, which was at `path`. The entity's file
+/// should already be gone.
+fn defeat(player: &mut PlayerState, e: &Entity, path: &Path) {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    loot(player, e);
+    leaderboard::kill(player, dir);
+    respawn::on_kill(dir);
+    trigger::fire(e, dir, trigger::When::Killed);
+    reputation::on_kill(player, e);
+    quest::on_kill(player, path);
+}
+
+/// Moves everything in a dead entity's inventory into the player's, along
+/// with a bit of pocket change.
+fn loot(player: &mut PlayerState, e: &Entity) {
+    let gold = dice::random_range(1..=5);
+    println!("{}", t!("loot.gold", gold = gold));
+    player.gold += gold;
 
     for c in &e.components {
-        match c {
-            Component::TakesDamage(hp) => {
-                fs::remove_file(&abs_path)?;
-                println!("the dude has {} hp.", hp);
-                let damage = rand::random_range(1..4);
-                let new_hp = hp - damage;
-                if new_hp <= 0 {
-                    println!("you punched him so hard he died. yikes.");
-                    return Ok(());
-                }
-                println!(
-                    "you punched him with some amount of force, knocking out about {} teeth.",
-                    damage
-                );
-                println!("the poor sod only has {} left.", new_hp);
-                spawn(
-                    Entity {
-                        components: e
-                            .components
-                            .clone()
-                            .into_iter()
-                            .filter(|c| {
-                                std::mem::discriminant(c)
-                                    != std::mem::discriminant(&Component::TakesDamage(0))
-                            })
-                            .chain([Component::TakesDamage(new_hp)].into_iter())
-                            .collect(),
-                    },
-                    &abs_path,
-                );
+        if let Component::HasInventory(items) = c {
+            for item in items {
+                println!("{}", t!("loot.item", item = item));
             }
-            _ => (),
+            player
+                .inventory
+                .extend(items.iter().map(|item| inventory::Item::found(item)));
         }
     }
+    inventory::warn_if_full(player);
+}
 
-    Ok(())
+/// Describes the entity at `path` component by component, for content authors
+/// poking at what they've made. Returns `None` if it isn't an entity.
+pub fn describe(path: impl AsRef<Path>) -> Option<String> {
+    let e = get_entity(path).ok()?;
+    let mut description = String::from("Entity:\n");
+    for c in &e.components {
+        description.push_str(&format!("  {c}\n"));
+    }
+    Some(description)
 }
 
-/// Returns the path to the file we use for all rlsh data.
-/// This includes game data like the current HP and configuration data like
-/// any name or preference changes.
-fn get_data_path() -> PathBuf {
-    let mut path = dirs::data_local_dir().expect("Could not find the data path :(");
-    path.push("/rlsh");
-    path.push("save.cfg");
-    path
+/// Returns every entity directly inside `dir` along with its path.
+/// Files that aren't entities are skipped.
+pub fn entities_in(dir: impl AsRef<Path>) -> Vec<(PathBuf, Entity)> {
+    let Ok(paths) = vfs::with(|fs| fs.list(dir.as_ref())) else {
+        return Vec::new();
+    };
+
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let e = get_entity(&path).ok()?;
+            Some((path, e))
+        })
+        .collect()
+}
+
+/// Whether there's nothing hostile left in `dir`.
+pub fn is_cleared(dir: impl AsRef<Path>) -> bool {
+    !entities_in(dir)
+        .iter()
+        .any(|(_, e)| e.components.iter().any(|c| matches!(c, Component::Enemy)))
 }
 
+/// Which files `ls` should show, going by what's in them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    /// `--entities`: anything from the game.
+    Entities,
+    /// `--enemies`: things that want to hurt you.
+    Enemies,
+    /// `--items`: things lying around waiting to be picked up.
+    Items,
+    /// `--no-game`: only plain old files and directories.
+    NoGame,
+}
+
+impl Filter {
+    pub fn from_flag(flag: &str) -> Option<Filter> {
+        match flag {
+            "--entities" => Some(Filter::Entities),
+            "--enemies" => Some(Filter::Enemies),
+            "--items" => Some(Filter::Items),
+            "--no-game" => Some(Filter::NoGame),
+            _ => None,
+        }
+    }
+
+    /// Whether a file should be listed, given the entity in it if there is
+    /// one. Things pretending to be plain files are treated as plain files, so
+    /// filtering can't be used to sniff them out.
+    pub fn keeps(self, e: Option<&Entity>) -> bool {
+        let e = e.filter(|e| !e.is_disguised());
+        match (self, e) {
+            (Filter::NoGame, e) => e.is_none(),
+            (_, None) => false,
+            (Filter::Entities, Some(_)) => true,
+            (Filter::Enemies, Some(e)) => {
+                e.components.iter().any(|c| matches!(c, Component::Enemy))
+            }
+            (Filter::Items, Some(e)) => e.item().is_some(),
+        }
+    }
+}
+
+impl Entity {
+    /// Whether this entity looks like an ordinary file to the player.
+    pub fn is_disguised(&self) -> bool {
+        self.is_mimic() || (self.trap().is_some() && !self.is_revealed())
+    }
+}
+
+/// Called by the shell every time before it prints the prompt.
+pub fn tick() {
+    let mut player = PlayerState::load();
+    player.turns += 1;
+    clock::tick(&player);
+    schedule::tick(&mut player);
+    spell::regenerate(&mut player);
+    events::tick(&mut player);
+    quest::update(&mut player);
+    accessibility::announce(&player);
+    if let Err(error) = player.save() {
+        println!("{}", t!("error.save", error = error));
+    }
+}
+
+/// Called by the shell after the player lists the contents of `dir`.
+pub fn on_list(dir: &Path) {
+    respawn::check(dir);
+    let mut player = PlayerState::load();
+    trap::spring(&mut player, dir);
+    if let Err(error) = player.save() {
+        println!("{}", t!("error.save", error = error));
+    }
+}
+
+/// Called by the shell after the player changes directory from `from` to `dir`.
+pub fn on_enter(from: &Path, dir: &Path) {
+    respawn::check(dir);
+    let mut player = PlayerState::load();
+    pet::follow(&mut player, from, dir);
+    trap::spring(&mut player, dir);
+    leaderboard::enter(&mut player, dir);
+    quest::on_enter(&mut player, dir);
+    if let Some(description) = locale::room_description(dir) {
+        println!("{description}");
+    }
+    if let Err(error) = player.save() {
+        println!("{}", t!("error.save", error = error));
+    }
+}
+
+/// Sets up a new character if there isn't a save file yet. The player is only
+/// asked to pick a class at a terminal. Scripts, piped input and headless
+/// sessions get the default class, so the question doesn't end up in their
+/// output or take a line of their input.
 pub fn check_setup() {
-    let f = fs::read_to_string(get_data_path());
+    if player::get_data_path().exists() {
+        return;
+    }
+
+    let class = match io::stdin().is_terminal() && !headless::enabled() {
+        true => class::Class::choose(),
+        false => class::Class::default(),
+    };
+    let player = PlayerState::new(class);
+    if let Err(error) = player.save() {
+        println!("{}", t!("error.save", error = error));
+    }
+}
+
+/// Builds the parser for the game verbs the player knows.
+pub fn verbs() -> parser::Parser<class::Verb> {
+    class::verbs(PlayerState::load().class)
+}
+
+/// Runs the `game` builtin, for poking at the game world directly.
+///
+/// - `game spawn <template> [name]` makes a monster from the
+///   [bestiary](bestiary::BESTIARY).
+/// - `game room <template>... [every <seconds>]` makes the current directory
+///   a room that [fills back up](respawn) after it's cleared.
+/// - `game door <name>` puts a locked door in the current directory.
+/// - `game npc <name> <day dir> <night dir>` makes someone who keeps to a
+///   [schedule].
+/// - `game faction <entity> <faction>` makes an entity one of a faction, see
+///   [reputation].
+/// - `game chest <name> [capacity]` puts a [chest] in the current directory.
+/// - `game link <source> [killed|unlocked] <effect> <target>` sets up a
+///   [trigger] between two entities.
+/// - `game id <entity|uuid>` looks up an entity's [id], or where an id is now.
+/// - `game edit <path>` opens an entity in `$EDITOR`, see [edit].
+/// - `game validate [dir]` checks the content under a directory for mistakes,
+///   see [validate].
+/// - `game quests` lists the [quests](quest) the player is on.
+/// - `game time` says what time it is on the game's [clock].
+/// - `game events` lists the [seasonal](season) quests that are on or coming
+///   up.
+/// - `game strings` prints the game's text, for [translating](locale).
+pub fn command(args: &[String]) {
+    match args.first().map(String::as_str) {
+        Some("spawn") => bestiary::command(&args[1..]),
+        Some("room") => respawn::command(&args[1..]),
+        Some("door") => trigger::door(&args[1..]),
+        Some("npc") => schedule::command(&args[1..]),
+        Some("faction") => reputation::command(&args[1..]),
+        Some("chest") => chest::command(&args[1..]),
+        Some("link") => trigger::link(&args[1..]),
+        Some("id") => id::command(&args[1..]),
+        Some("edit") => edit::command(&args[1..]),
+        Some("validate") => validate::command(&args[1..]),
+        Some("quests") => quest::command(&args[1..]),
+        Some("time") => clock::command(&args[1..]),
+        Some("events") => season::command(&args[1..]),
+        Some("remote") => remote::command(&args[1..]),
+        Some("strings") => locale::command(&args[1..]),
+        Some(other) => println!("game: unknown subcommand {other}"),
+        None => println!("game: expected a subcommand"),
+    }
+}
+
+/// Whether the player may walk into `dir`. Prints why not if they can't.
+pub fn may_enter(dir: &Path) -> bool {
+    if let Some(door) = trigger::locked_door(dir) {
+        println!("{}", t!("enter.locked", door = door));
+        return false;
+    }
+    match class::guarded_by(dir) {
+        Some(guard) => {
+            println!("{}", t!("enter.guarded", guard = guard));
+            false
+        }
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_see_through_disguises_like_the_player() {
+        let goblin = bestiary::find("goblin").unwrap().entity();
+        let mimic = bestiary::find("mimic").unwrap().entity();
+        let item = Entity {
+            components: vec![Component::Item(inventory::Item::new("rope"))],
+        };
+
+        assert!(Filter::Enemies.keeps(Some(&goblin)));
+        assert!(!Filter::Enemies.keeps(Some(&mimic)));
+        assert!(!Filter::Enemies.keeps(Some(&item)));
+        assert!(Filter::Items.keeps(Some(&item)));
+        assert!(Filter::Entities.keeps(Some(&item)));
+        assert!(!Filter::Entities.keeps(None));
+        assert!(Filter::NoGame.keeps(None));
+        assert!(Filter::NoGame.keeps(Some(&mimic)));
+        assert!(!Filter::NoGame.keeps(Some(&goblin)));
+    }
+
+    #[test]
+    fn components_describe_themselves() {
+        assert_eq!(Component::TakesDamage(5).to_string(), "hp: 5");
+        assert_eq!(
+            Component::HasInventory(vec![String::from("bone"), String::from("sword")]).to_string(),
+            "inventory: bone, sword"
+        );
+        let trigger = Component::Trigger(trigger::Trigger {
+            when: trigger::When::Killed,
+            effect: trigger::Effect::Unlock,
+            target: trigger::Reference::Path(PathBuf::from("../door")),
+        });
+        assert_eq!(trigger.to_string(), "trigger: when killed, unlock ../door");
+    }
+
+    #[test]
+    fn entities_can_live_in_memory() {
+        let memory = vfs::MemoryFs::default();
+        let dungeon = Path::new("/dungeon");
+        vfs::using(memory.clone(), || {
+            spawn(
+                bestiary::find("goblin").unwrap().entity(),
+                dungeon.join("goblin"),
+            )
+            .unwrap();
+            vfs::with(|fs| fs.write(&dungeon.join("notes.txt"), b"not an entity")).unwrap();
+            // the player's own files aren't written over
+            let skeleton = bestiary::find("skeleton").unwrap().entity();
+            let error = spawn(skeleton, dungeon.join("notes.txt")).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+
+            let goblin = get_entity(dungeon.join("goblin")).unwrap();
+            assert!(goblin.id().is_some());
+            assert!(get_entity(dungeon.join("notes.txt")).is_err());
+            assert_eq!(entities_in(dungeon).len(), 1);
+            despawn(dungeon.join("goblin")).unwrap();
+            assert!(entities_in(dungeon).is_empty());
+        });
+        // the id index was written to memory too, rather than to disk
+        assert!(
+            memory
+                .paths()
+                .iter()
+                .any(|path| path.ends_with("index.cfg"))
+        );
+    }
+
+    #[test]
+    fn weak_enemies_die_in_one_hit() {
+        use testworld::{enemy, item, world};
+
+        let world = world! {
+            "dungeon" => { "goblin": enemy(1), "chest": item("key") },
+        };
+        let hit = DiceExpr {
+            count: 1,
+            sides: 3,
+            modifier: 0,
+        };
+        world
+            .run(|| strike(world.path("dungeon/goblin"), hit))
+            .unwrap();
+        assert!(world.entity("dungeon/goblin").is_none());
+        assert!(world.entity("dungeon/chest").is_some());
+        assert!(world.run(PlayerState::load).gold > 0);
+    }
 }
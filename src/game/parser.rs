@@ -14,13 +14,33 @@
 //! - [77 Verbs](https://ifdb.org/viewgame?id=p3rd5133qm5cwfd)
 //! - [Lost Pig](https://ifdb.org/viewgame?id=mohwfk47yjzii14w)
 
+/// Words that are ignored at the start of a capturing group, so that
+/// "look at the book" captures just "book".
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "the", "at", "to", "on", "in", "into", "with", "from", "toward",
+];
+
+/// One whitespace-delimited group of a phrase.
+enum Group {
+    /// Matches any one of the alternatives, each of which is a sequence of words.
+    Words(Vec<Vec<String>>),
+    /// Captures one or more words, optionally restricted to some alternatives.
+    Capture(Option<Vec<Vec<String>>>),
+}
+
 /// Build a parser for a game.
 ///
 /// This is accomplished by adding phrases to the parser, which are regex-like
 /// patterns it will search for. The parser will also strip prepositions and
 /// other unneeded words from its input, such as "the," "a," "to," and so on.
 pub struct Parser<T> {
-    todo: Vec<T>,
+    phrases: Vec<(T, Vec<Group>)>,
+}
+
+impl<T> Default for Parser<T> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<T> Parser<T> {
@@ -33,13 +53,15 @@ impl<T> Parser<T> {
     /// ```
     /// use rlsh::game::parser::Parser;
     ///
-    /// let parser = Parser::new();
+    /// let parser = Parser::<()>::new();
     ///
     /// assert_eq!(parser.get("anything"), None);
     /// ```
     ///
     pub fn new() -> Parser<T> {
-        todo!()
+        Parser {
+            phrases: Vec::new(),
+        }
     }
 
     /// Adds a phrase to the parser, binding the phrase to a token. This token
@@ -61,9 +83,11 @@ impl<T> Parser<T> {
     /// `Parser` will do runtime error checking for incorrect grammar insertion:
     ///
     /// ```should_panic
+    /// # use rlsh::game::parser::Parser;
     /// Parser::new().insert((), "[unclosed brace");
     /// ```
     /// ```should_panic
+    /// # use rlsh::game::parser::Parser;
     /// Parser::new().insert((), "*incorrect symbols//");
     /// ```
     ///
@@ -72,6 +96,7 @@ impl<T> Parser<T> {
     /// ```
     /// use rlsh::game::parser::Parser;
     ///
+    /// #[derive(Clone)]
     /// enum Token {
     ///     Examine,
     ///     Inventory,
@@ -85,17 +110,63 @@ impl<T> Parser<T> {
     ///     .insert(Token::GoCardinally, "[go cardinally] (n|north|s|south|e|east|w|west)")
     ///     .insert(Token::Move, "[move] () [to] ()");
     /// ```
-    pub fn insert(self, token: T, phrase: &str) -> Self {
-        todo!()
+    pub fn insert(mut self, token: T, phrase: &str) -> Self {
+        let mut groups = Vec::new();
+        let mut chars = phrase.chars();
+
+        while let Some(c) = chars.next() {
+            let close = match c {
+                c if c.is_whitespace() => continue,
+                '[' => ']',
+                '(' => ')',
+                c => panic!("unexpected {c:?} outside of a group in phrase {phrase:?}"),
+            };
+
+            let mut body = String::new();
+            loop {
+                match chars.next() {
+                    Some(c) if c == close => break,
+                    Some(c @ ('[' | ']' | '(' | ')')) => {
+                        panic!("unexpected {c:?} inside of a group in phrase {phrase:?}")
+                    }
+                    Some(c) => body.push(c),
+                    None => panic!("unclosed group in phrase {phrase:?}"),
+                }
+            }
+
+            let alternatives: Vec<Vec<String>> = body
+                .split('|')
+                .map(|alt| alt.split_whitespace().map(str::to_lowercase).collect())
+                .collect();
+            let empty = alternatives.iter().any(Vec::is_empty);
+
+            groups.push(match close {
+                ']' if empty => panic!("empty alternative in phrase {phrase:?}"),
+                ']' => Group::Words(alternatives),
+                _ if body.trim().is_empty() => Group::Capture(None),
+                _ if empty => panic!("empty alternative in phrase {phrase:?}"),
+                _ => Group::Capture(Some(alternatives)),
+            });
+        }
+
+        self.phrases.push((token, groups));
+        self
     }
+}
 
+impl<T: Clone> Parser<T> {
     /// Parses an input and returns the corresponding token if it matched one.
     ///
+    /// If several phrases match, the one that was inserted first wins. A phrase
+    /// without any capturing groups gives a single empty capture, so there's
+    /// always something in the `Vec`.
+    ///
     /// # Example
     ///
     /// ```
     /// #  use rlsh::game::parser::Parser;
     /// #
+    /// #  #[derive(Clone)]
     /// #  enum Token {
     /// #      Examine,
     /// #      Inventory,
@@ -113,7 +184,7 @@ impl<T> Parser<T> {
     /// match parser.get("x mary sue") {
     ///     Some((token, args)) => match (token, args.as_slice()) {
     ///         (Token::Examine, [thing]) => println!("examining {thing}"),
-    ///         (Token::Inventory, []) => todo!(),
+    ///         (Token::Inventory, [_]) => todo!(),
     ///         (Token::GoCardinally, [direction]) => todo!(),
     ///         (Token::Move, [src, dest]) => todo!(),
     ///         _ => panic!("This should not be possible by parser postcondition."),
@@ -123,7 +194,95 @@ impl<T> Parser<T> {
     ///
     /// ```
     pub fn get(&self, input: &str) -> Option<(T, Vec<String>)> {
-        todo!()
+        let words: Vec<&str> = input.split_whitespace().collect();
+
+        self.phrases.iter().find_map(|(token, groups)| {
+            let mut captures = Vec::new();
+            if !matches(groups, &words, &mut captures) {
+                return None;
+            }
+            if captures.is_empty() {
+                captures.push(String::new());
+            }
+            Some((token.clone(), captures))
+        })
+    }
+
+    /// The words a phrase can start with, and the token each belongs to, in
+    /// the order they were inserted. Useful for completing input.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rlsh::game::parser::Parser;
+    ///
+    /// let parser = Parser::new()
+    ///     .insert('x', "[x|examine] ()")
+    ///     .insert('m', "[move|pick up] () [to] ()");
+    ///
+    /// let words: Vec<_> = parser.first_words().into_iter().map(|(w, _)| w).collect();
+    /// assert_eq!(words, ["x", "examine", "move", "pick"]);
+    /// ```
+    pub fn first_words(&self) -> Vec<(String, T)> {
+        let mut words: Vec<(String, T)> = Vec::new();
+        for (token, groups) in &self.phrases {
+            let Some(Group::Words(alternatives)) = groups.first() else {
+                continue;
+            };
+            for alt in alternatives {
+                if !words.iter().any(|(word, _)| *word == alt[0]) {
+                    words.push((alt[0].clone(), token.clone()));
+                }
+            }
+        }
+        words
+    }
+}
+
+/// Whether `words` starts with the (lowercase) `prefix`, ignoring case.
+fn starts_with(words: &[&str], prefix: &[String]) -> bool {
+    words.len() >= prefix.len()
+        && words
+            .iter()
+            .zip(prefix)
+            .all(|(word, p)| word.to_lowercase() == *p)
+}
+
+/// Tries to match all of `words` against `groups`, backtracking over how many
+/// words each capturing group takes. Captured text is pushed onto `captures`.
+fn matches(groups: &[Group], words: &[&str], captures: &mut Vec<String>) -> bool {
+    let Some((group, rest)) = groups.split_first() else {
+        return words.is_empty();
+    };
+
+    match group {
+        Group::Words(alternatives) => alternatives
+            .iter()
+            .any(|alt| starts_with(words, alt) && matches(rest, &words[alt.len()..], captures)),
+        Group::Capture(allowed) => (1..=words.len()).any(|n| {
+            let captured: Vec<&str> = words[..n]
+                .iter()
+                .copied()
+                .skip_while(|word| STOP_WORDS.contains(&word.to_lowercase().as_str()))
+                .collect();
+            if captured.is_empty() {
+                return false;
+            }
+            if let Some(allowed) = allowed
+                && !allowed
+                    .iter()
+                    .any(|alt| alt.len() == captured.len() && starts_with(&captured, alt))
+            {
+                return false;
+            }
+
+            captures.push(captured.join(" "));
+            if matches(rest, &words[n..], captures) {
+                return true;
+            }
+            captures.pop();
+            false
+        }),
     }
 }
 
@@ -132,9 +291,8 @@ mod tests {
     use super::*;
 
     #[test]
-    #[should_panic]
     fn query_for_phrases() {
-        #[derive(Debug, PartialEq)]
+        #[derive(Clone, Debug, PartialEq)]
         enum Token {
             Examine,
             Inventory,
@@ -0,0 +1,143 @@
+//! The player's companion.
+//!
+//! The pet doesn't live in the filesystem like other entities do. Instead it is
+//! stored in the save and remembers which directory it is in, trotting after
+//! the player whenever they `cd` as long as it is fed well enough to bother.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::dice;
+use super::locale::t;
+use super::player::PlayerState;
+
+/// How full a pet can get.
+pub const MAX_SATIETY: u8 = 10;
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Pet {
+    pub name: String,
+    /// The directory the pet is currently in.
+    pub location: PathBuf,
+    /// Drops as the pet follows the player around. A starving pet will not
+    /// follow, and a well fed one is more eager to help in a fight.
+    pub satiety: u8,
+}
+
+impl Pet {
+    pub fn new(name: &str, location: PathBuf) -> Self {
+        Pet {
+            name: name.to_string(),
+            location,
+            satiety: MAX_SATIETY,
+        }
+    }
+
+    fn mood(&self) -> &'static str {
+        match self.satiety {
+            0 => "starving",
+            1..=3 => "hungry",
+            4..=7 => "content",
+            _ => "well fed",
+        }
+    }
+}
+
+/// Runs the `pet` builtin.
+///
+/// - `pet` or `pet status` describes the pet,
+/// - `pet name <name>` renames it, and
+/// - `pet feed <item>` feeds it an item from the player's inventory.
+pub fn command(args: &[String]) {
+    let mut player = PlayerState::load();
+    let Some(pet) = &mut player.pet else {
+        println!("you don't have a pet. how lonely.");
+        return;
+    };
+
+    match args.first().map(String::as_str) {
+        None | Some("status") => {
+            let here = std::env::current_dir().is_ok_and(|cwd| cwd == pet.location);
+            if here {
+                println!("{} is right here with you.", pet.name);
+            } else {
+                println!(
+                    "{} is waiting for you in {}.",
+                    pet.name,
+                    pet.location.display()
+                );
+            }
+            println!("{} looks {}.", pet.name, pet.mood());
+            return;
+        }
+        Some("name") => {
+            let name = args[1..].join(" ");
+            if name.is_empty() {
+                println!("pet: name it what?");
+                return;
+            }
+            println!("{} will now answer to {name}. probably.", pet.name);
+            pet.name = name;
+        }
+        Some("feed") => {
+            let item = args[1..].join(" ");
+            let Some(i) = player
+                .inventory
+                .iter()
+                .position(|it| it.name == item && !it.equipped)
+            else {
+                println!("you aren't carrying any {item}.");
+                return;
+            };
+            player.inventory.remove(i);
+            pet.satiety = (pet.satiety + 3).min(MAX_SATIETY);
+            println!("{} happily devours the {item}.", pet.name);
+        }
+        Some(other) => {
+            println!("pet: unknown subcommand {other}");
+            return;
+        }
+    }
+
+    if let Err(error) = player.save() {
+        println!("{}", t!("error.save", error = error));
+    }
+}
+
+/// Moves the pet into `dir` if it is with the player and fed well enough to
+/// keep up. Pets get a little hungrier every time they follow.
+pub fn follow(player: &mut PlayerState, from: &Path, dir: &Path) {
+    let Some(pet) = &mut player.pet else {
+        return;
+    };
+    if pet.location != from {
+        return;
+    }
+
+    if pet.satiety == 0 {
+        println!("{} is too hungry to follow you.", pet.name);
+        return;
+    }
+
+    pet.location = dir.to_path_buf();
+    if dice::random_ratio(1, 3) {
+        pet.satiety -= 1;
+    }
+}
+
+/// Gives the pet a chance to join in on an attack, returning the extra damage
+/// it dealt. Only a pet in the current directory can help, and hungry pets
+/// help less often.
+pub fn assist(player: &PlayerState, dir: &Path) -> i16 {
+    let Some(pet) = &player.pet else {
+        return 0;
+    };
+    if pet.location != dir || !dice::random_ratio(pet.satiety.into(), 2 * MAX_SATIETY as u32) {
+        return 0;
+    }
+
+    let damage = dice::random_range(1..3);
+    println!("{} leaps in and bites for {damage} damage!", pet.name);
+    damage
+}
@@ -0,0 +1,170 @@
+//! Everything about the player that has to survive between sessions.
+//!
+//! Unlike entities, which live as files scattered around the filesystem, the
+//! player is stored in a single save file in the data directory.
+
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    FILE_SIGNATURE,
+    class::Class,
+    events::Event,
+    id::Uuid,
+    inventory::Item,
+    leaderboard::Run,
+    pet::Pet,
+    quest::QuestState,
+    spell::{MAX_MANA, Spell},
+    vfs,
+};
+
+/// The HP a fresh character starts with.
+pub const MAX_HP: i32 = 20;
+
+/// The player's persistent state.
+///
+/// New fields should be given a sensible `Default` so that older save files
+/// keep loading after an update.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PlayerState {
+    pub class: Class,
+    pub level: u32,
+    /// How much the player can carry, see [`PlayerState::capacity`].
+    pub strength: u8,
+    pub hp: i32,
+    pub mana: i32,
+    /// The spells the player knows how to cast.
+    pub spells: Vec<Spell>,
+    /// How many commands the player has run, which is how the game keeps time.
+    pub turns: u64,
+    /// The turn the player last finished resting on.
+    pub rested_at: Option<u64>,
+    /// The bonus to perception checks, for noticing hidden things.
+    pub perception: u8,
+    /// Names of the items the player is carrying.
+    pub inventory: Vec<Item>,
+    pub gold: u32,
+    /// The player's companion, if they still have one.
+    pub pet: Option<Pet>,
+    /// When each random event is next due, in seconds since the Unix epoch.
+    pub next_events: HashMap<Event, u64>,
+    /// Dungeons the player is partway through, by dungeon id.
+    pub runs: HashMap<u64, Run>,
+    /// How far along the player is with each quest, by name.
+    pub quests: HashMap<String, QuestState>,
+    /// Everyone who keeps to a [schedule](super::schedule), by id.
+    pub npcs: Vec<Uuid>,
+    /// Where the player stands with each faction, see
+    /// [reputation](super::reputation).
+    pub reputation: HashMap<String, i32>,
+}
+
+impl Default for PlayerState {
+    fn default() -> Self {
+        PlayerState {
+            class: Class::default(),
+            level: 1,
+            strength: 3,
+            hp: MAX_HP,
+            mana: MAX_MANA,
+            spells: Vec::new(),
+            turns: 0,
+            rested_at: None,
+            perception: 3,
+            inventory: Vec::new(),
+            gold: 10,
+            pet: Some(Pet::new("dog", dirs::home_dir().unwrap_or_default())),
+            next_events: HashMap::new(),
+            runs: HashMap::new(),
+            quests: HashMap::new(),
+            npcs: Vec::new(),
+            reputation: HashMap::new(),
+        }
+    }
+}
+
+impl PlayerState {
+    /// Creates a fresh character of the given class.
+    pub fn new(class: Class) -> Self {
+        let spells = match class {
+            Class::Mage => vec![Spell::Light],
+            Class::Warrior | Class::Rogue => Vec::new(),
+        };
+        let strength = match class {
+            Class::Warrior => 5,
+            Class::Rogue => 3,
+            Class::Mage => 2,
+        };
+
+        PlayerState {
+            class,
+            spells,
+            strength,
+            ..PlayerState::default()
+        }
+    }
+
+    /// Loads the player from the save file, starting a new character if there
+    /// is no save file or it could not be understood.
+    pub fn load() -> Self {
+        Self::load_from(get_data_path()).unwrap_or_default()
+    }
+
+    fn load_from(path: impl AsRef<Path>) -> Option<Self> {
+        let contents = vfs::with(|fs| fs.read(path.as_ref())).ok()?;
+        let body = contents.strip_prefix(FILE_SIGNATURE)?;
+        rmp_serde::from_slice(body).ok()
+    }
+
+    /// Deals damage to the player, returning whether it knocked them out.
+    /// A player that drops to 0 HP wakes back up at full health, none the wiser.
+    pub fn hurt(&mut self, damage: i32) -> bool {
+        self.hp -= damage;
+        if self.hp <= 0 {
+            println!("everything goes dark...");
+            println!("you wake up some time later, feeling oddly refreshed.");
+            self.hp = MAX_HP;
+            return true;
+        }
+        println!("you have {} hp left.", self.hp);
+        false
+    }
+
+    /// Writes the player to the save file, creating the data directory if needed.
+    pub fn save(&self) -> io::Result<()> {
+        let path = get_data_path();
+        let mut contents = Vec::new();
+        contents.extend_from_slice(FILE_SIGNATURE);
+        // named fields keep the save readable when fields are added or reordered
+        contents.extend(rmp_serde::to_vec_named(self).map_err(io::Error::other)?);
+        vfs::with(|fs| {
+            if let Some(dir) = path.parent() {
+                fs.create_dir_all(dir)?;
+            }
+            fs.write(&path, &contents)
+        })
+    }
+}
+
+/// Returns the path to the file we use for all rlsh data.
+/// This includes game data like the current HP and configuration data like
+/// any name or preference changes.
+pub fn get_data_path() -> PathBuf {
+    let mut path = get_data_dir();
+    path.push("save.cfg");
+    path
+}
+
+/// Returns the directory all of rlsh's files are kept in.
+pub fn get_data_dir() -> PathBuf {
+    let mut path = dirs::data_local_dir().expect("Could not find the data path :(");
+    path.push("rlsh");
+    path
+}
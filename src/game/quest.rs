@@ -0,0 +1,539 @@
+//! Quests, written in a little language so that content packs can add their
+//! own without touching the code.
+//!
+//! Quests are loaded from `.quest` files anywhere under the `quests` folder in
+//! the data directory, so a content pack is just a folder dropped in there.
+//! A file holds any number of quests, one line per instruction:
+//!
+//! ```text
+//! # lines starting with # are ignored
+//! quest goblin trouble
+//! start visit tavern        # doesn't begin until these are all true
+//! say the innkeeper begs you to deal with the goblins.
+//! goal kill goblin 3        # finished once these are all true
+//! goal have bread
+//! reward gold 20
+//! reward item sword
+//! done the village is safe, for now.
+//!
+//! quest the winter feast
+//! season 12-20 01-05        # can only start between these dates
+//! goal have bread 3
+//! ```
+//!
+//! Conditions, used by `start` and `goal`, are:
+//!
+//! - `kill <monster> [count]`: kill some monsters, counted from when the quest
+//!   starts. Monsters go by their file names, without any numbers on the end,
+//!   so `goblin2` counts as a `goblin`.
+//! - `have <item> [count]`: carry some of an item.
+//! - `gold <amount>`: have at least that much gold.
+//! - `visit <directory name>`: walk into a directory with that name.
+//! - `quest <name>`: finish another quest first.
+//!
+//! Rewards are `gold <amount>`, `item <name>` and `reputation <faction>
+//! <amount>`, which can be less than 0. A quest without any `start`
+//! lines begins as soon as it's loaded, or as soon as its [season] comes
+//! round.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    inventory::{self, Item},
+    locale::t,
+    player::{self, PlayerState},
+    season::{self, Date},
+};
+
+/// The extension quest files have to have.
+pub const EXTENSION: &str = "quest";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Condition {
+    Kill { monster: String, count: u32 },
+    Have { item: String, count: u32 },
+    Gold(u32),
+    Visit(String),
+    Quest(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Reward {
+    Gold(u32),
+    Item(String),
+    Reputation { faction: String, change: i32 },
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Quest {
+    pub name: String,
+    /// Said when the quest starts.
+    pub intro: Vec<String>,
+    /// Said when the quest is finished.
+    pub outro: Vec<String>,
+    pub start: Vec<Condition>,
+    pub goals: Vec<Condition>,
+    pub rewards: Vec<Reward>,
+    /// When in the year the quest can start, if it's seasonal.
+    pub season: Option<season::Window>,
+}
+
+/// What the player has done towards a quest.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Progress {
+    /// How many of each monster have been killed.
+    pub kills: HashMap<String, u32>,
+    /// The names of the directories visited.
+    pub visited: Vec<String>,
+}
+
+/// Where the player is with a quest. Quests the player hasn't heard of are
+/// [`QuestState::Waiting`].
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum QuestState {
+    /// Waiting for its start conditions, which progress is kept for.
+    Waiting(Progress),
+    Active(Progress),
+    Done,
+}
+
+/// Splits a trailing count off `words`, like the `3` in `kill goblin 3`.
+fn with_count(words: &[&str]) -> Option<(String, u32)> {
+    match words {
+        [] => None,
+        [name @ .., count] if !name.is_empty() && count.parse::<u32>().is_ok() => {
+            Some((name.join(" "), count.parse().ok()?))
+        }
+        name => Some((name.join(" "), 1)),
+    }
+}
+
+impl Condition {
+    fn parse(words: &[&str]) -> Result<Condition, String> {
+        let (&kind, rest) = words
+            .split_first()
+            .ok_or_else(|| String::from("expected a condition"))?;
+        let name = || match rest.join(" ") {
+            name if name.is_empty() => Err(format!("{kind} needs a name")),
+            name => Ok(name),
+        };
+        match kind {
+            "kill" => {
+                let (monster, count) =
+                    with_count(rest).ok_or_else(|| String::from("kill needs a monster"))?;
+                Ok(Condition::Kill { monster, count })
+            }
+            "have" => {
+                let (item, count) =
+                    with_count(rest).ok_or_else(|| String::from("have needs an item"))?;
+                Ok(Condition::Have { item, count })
+            }
+            "gold" => match rest {
+                [amount] => amount
+                    .parse()
+                    .map(Condition::Gold)
+                    .map_err(|_| format!("{amount} isn't an amount of gold")),
+                _ => Err(String::from("gold needs an amount")),
+            },
+            "visit" => name().map(Condition::Visit),
+            "quest" => name().map(Condition::Quest),
+            other => Err(format!("{other} isn't a condition")),
+        }
+    }
+
+    /// Whether the player has done this, with `progress` towards the quest
+    /// it's part of.
+    fn is_met(&self, player: &PlayerState, progress: &Progress) -> bool {
+        match self {
+            Condition::Kill { monster, count } => {
+                progress.kills.get(monster).copied().unwrap_or(0) >= *count
+            }
+            Condition::Have { item, count } => {
+                let carried = player.inventory.iter().filter(|i| i.name == *item).count();
+                carried >= *count as usize
+            }
+            Condition::Gold(amount) => player.gold >= *amount,
+            Condition::Visit(dir) => progress.visited.contains(dir),
+            Condition::Quest(name) => player.quests.get(name) == Some(&QuestState::Done),
+        }
+    }
+
+    /// Describes the condition with how far along the player is.
+    fn describe(&self, player: &PlayerState, progress: &Progress) -> String {
+        let check = if self.is_met(player, progress) {
+            "x"
+        } else {
+            " "
+        };
+        let text = match self {
+            Condition::Kill { monster, count } => {
+                let killed = progress.kills.get(monster).copied().unwrap_or(0);
+                format!("kill {monster} ({}/{count})", killed.min(*count))
+            }
+            Condition::Have { item, count: 1 } => format!("find a {item}"),
+            Condition::Have { item, count } => format!("find {count} {item}"),
+            Condition::Gold(amount) => format!("save up {amount} gold"),
+            Condition::Visit(dir) => format!("visit {dir}"),
+            Condition::Quest(name) => format!("finish {name}"),
+        };
+        format!("[{check}] {text}")
+    }
+}
+
+impl Reward {
+    fn parse(words: &[&str]) -> Result<Reward, String> {
+        match words {
+            ["gold", amount] => amount
+                .parse()
+                .map(Reward::Gold)
+                .map_err(|_| format!("{amount} isn't an amount of gold")),
+            ["item", name @ ..] if !name.is_empty() => Ok(Reward::Item(name.join(" "))),
+            ["reputation", faction @ .., change] if !faction.is_empty() => change
+                .parse()
+                .map(|change| Reward::Reputation {
+                    faction: faction.join(" "),
+                    change,
+                })
+                .map_err(|_| format!("{change} isn't an amount of reputation")),
+            [other, ..] => Err(format!("{other} isn't a reward")),
+            [] => Err(String::from("expected a reward")),
+        }
+    }
+
+    fn give(&self, player: &mut PlayerState) {
+        match self {
+            Reward::Gold(amount) => {
+                println!("you get {amount} gold.");
+                player.gold += amount;
+            }
+            Reward::Item(name) => {
+                println!("you get a {name}.");
+                player.inventory.push(Item::new(name));
+                inventory::warn_if_full(player);
+            }
+            Reward::Reputation { faction, change } => {
+                let key = match change {
+                    0.. => "reputation.better",
+                    _ => "reputation.worse",
+                };
+                println!("{}", t!(key, faction = faction));
+                player.change_standing(faction, *change);
+            }
+        }
+    }
+}
+
+/// Parses the quests in a quest file. Errors say which line is wrong.
+pub fn parse(text: &str) -> Result<Vec<Quest>, String> {
+    let mut quests: Vec<Quest> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.split_once('#').map_or(line, |(line, _)| line);
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((&instruction, rest)) = words.split_first() else {
+            continue;
+        };
+        let error = |message: String| format!("line {}: {message}", i + 1);
+
+        if instruction == "quest" {
+            if rest.is_empty() {
+                return Err(error(String::from("quest needs a name")));
+            }
+            quests.push(Quest {
+                name: rest.join(" "),
+                ..Quest::default()
+            });
+            continue;
+        }
+        let Some(quest) = quests.last_mut() else {
+            return Err(error(format!(
+                "{instruction} has to come after a quest line"
+            )));
+        };
+        match instruction {
+            "say" => quest.intro.push(rest.join(" ")),
+            "done" => quest.outro.push(rest.join(" ")),
+            "start" => quest.start.push(Condition::parse(rest).map_err(error)?),
+            "goal" => quest.goals.push(Condition::parse(rest).map_err(error)?),
+            "reward" => quest.rewards.push(Reward::parse(rest).map_err(error)?),
+            "season" => quest.season = Some(season::Window::parse(rest).map_err(error)?),
+            other => return Err(error(format!("{other} isn't an instruction"))),
+        }
+    }
+    Ok(quests)
+}
+
+/// Finds every quest file under `dir`, content packs and all.
+pub fn files_in(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files = Vec::new();
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => files.extend(files_in(&path)),
+            Ok(_) if path.extension().is_some_and(|ext| ext == EXTENSION) => files.push(path),
+            _ => {}
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Where quest files and content packs go.
+pub fn get_quests_dir() -> PathBuf {
+    let mut path = player::get_data_dir();
+    path.push("quests");
+    path
+}
+
+/// Loads every quest there is. Files that don't parse are skipped; `game
+/// validate` will say what's wrong with them.
+pub fn load() -> Vec<Quest> {
+    files_in(&get_quests_dir())
+        .iter()
+        .filter_map(|path| parse(&fs::read_to_string(path).ok()?).ok())
+        .flatten()
+        .collect()
+}
+
+/// The name a monster goes by for `kill` goals, from its file name.
+pub(super) fn monster_name(path: &Path) -> String {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    stem.trim_end_matches(|c: char| c.is_ascii_digit())
+        .to_string()
+}
+
+/// Calls `f` on the progress of every quest that isn't done yet.
+fn each_progress(player: &mut PlayerState, quests: &[Quest], mut f: impl FnMut(&mut Progress)) {
+    for quest in quests {
+        let state = player
+            .quests
+            .entry(quest.name.clone())
+            .or_insert_with(|| QuestState::Waiting(Progress::default()));
+        if let QuestState::Waiting(progress) | QuestState::Active(progress) = state {
+            f(progress);
+        }
+    }
+}
+
+/// Called when the player kills the monster at `path`.
+pub fn on_kill(player: &mut PlayerState, path: &Path) {
+    let quests = load();
+    let monster = monster_name(path);
+    each_progress(player, &quests, |progress| {
+        *progress.kills.entry(monster.clone()).or_default() += 1;
+    });
+    update_with(player, &quests, season::today());
+}
+
+/// Called when the player walks into `dir`.
+pub fn on_enter(player: &mut PlayerState, dir: &Path) {
+    let quests = load();
+    let Some(name) = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+    else {
+        return;
+    };
+    each_progress(player, &quests, |progress| {
+        if !progress.visited.contains(&name) {
+            progress.visited.push(name.clone());
+        }
+    });
+    update_with(player, &quests, season::today());
+}
+
+/// Starts and finishes quests as their conditions are met.
+pub fn update(player: &mut PlayerState) {
+    update_with(player, &load(), season::today());
+}
+
+/// Starts and finishes `quests` as their conditions are met, with the date
+/// `today` for seasonal ones.
+fn update_with(player: &mut PlayerState, quests: &[Quest], today: Date) {
+    // finishing one quest can start or finish another, so keep going until
+    // nothing changes
+    loop {
+        let mut changed = false;
+        for quest in quests {
+            let state = player
+                .quests
+                .get(&quest.name)
+                .cloned()
+                .unwrap_or_else(|| QuestState::Waiting(Progress::default()));
+            match state {
+                QuestState::Waiting(progress)
+                    if quest.season.is_none_or(|window| window.contains(today))
+                        && quest.start.iter().all(|c| c.is_met(player, &progress)) =>
+                {
+                    println!("new quest: {}", quest.name);
+                    for line in &quest.intro {
+                        println!("{line}");
+                    }
+                    player
+                        .quests
+                        .insert(quest.name.clone(), QuestState::Active(Progress::default()));
+                    changed = true;
+                }
+                QuestState::Active(progress)
+                    if quest.goals.iter().all(|c| c.is_met(player, &progress)) =>
+                {
+                    println!("quest complete: {}", quest.name);
+                    for line in &quest.outro {
+                        println!("{line}");
+                    }
+                    player.quests.insert(quest.name.clone(), QuestState::Done);
+                    for reward in &quest.rewards {
+                        reward.give(player);
+                    }
+                    changed = true;
+                }
+                _ => {}
+            }
+        }
+        if !changed {
+            return;
+        }
+    }
+}
+
+/// Runs `game quests`, listing the quests the player is on and has finished.
+pub fn command(args: &[String]) {
+    if !args.is_empty() {
+        println!("game quests: didn't expect any arguments");
+        return;
+    }
+    let player = PlayerState::load();
+    let quests = load();
+    let mut any = false;
+    for quest in &quests {
+        match player.quests.get(&quest.name) {
+            Some(QuestState::Active(progress)) => {
+                println!("{}", quest.name);
+                for goal in &quest.goals {
+                    println!("  {}", goal.describe(&player, progress));
+                }
+            }
+            Some(QuestState::Done) => println!("{} (done)", quest.name),
+            _ => continue,
+        }
+        any = true;
+    }
+    if !any {
+        println!("you aren't on any quests. go talk to people.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUESTS: &str = "
+        # a test quest
+        quest goblin trouble
+        say the innkeeper begs you to deal with the goblins.
+        goal kill goblin 2
+        goal have holy water
+        reward gold 20
+        reward item sword
+        done the village is safe.
+
+        quest the sequel
+        start quest goblin trouble
+        goal gold 100
+    ";
+
+    #[test]
+    fn quests_parse() {
+        let quests = parse(QUESTS).unwrap();
+        assert_eq!(quests.len(), 2);
+        assert_eq!(quests[0].name, "goblin trouble");
+        assert_eq!(
+            quests[0].goals,
+            [
+                Condition::Kill {
+                    monster: String::from("goblin"),
+                    count: 2
+                },
+                Condition::Have {
+                    item: String::from("holy water"),
+                    count: 1
+                },
+            ]
+        );
+        assert_eq!(
+            quests[1].start,
+            [Condition::Quest(String::from("goblin trouble"))]
+        );
+
+        assert_eq!(
+            parse("say hi"),
+            Err(String::from("line 1: say has to come after a quest line"))
+        );
+        assert_eq!(
+            parse("quest q\n\ngoal slay dragon"),
+            Err(String::from("line 3: slay isn't a condition"))
+        );
+        assert!(parse("quest q\nreward gold lots").is_err());
+    }
+
+    #[test]
+    fn quests_progress() {
+        let quests = parse(QUESTS).unwrap();
+        let mut player = PlayerState::default();
+        let gold = player.gold;
+        let today = Date::new(6, 1).unwrap();
+
+        update_with(&mut player, &quests, today);
+        assert!(matches!(
+            player.quests["goblin trouble"],
+            QuestState::Active(_)
+        ));
+        assert!(!player.quests.contains_key("the sequel"));
+
+        for goblin in ["goblin", "goblin2.txt"] {
+            assert_eq!(monster_name(Path::new(goblin)), "goblin");
+            each_progress(&mut player, &quests, |progress| {
+                *progress.kills.entry(String::from("goblin")).or_default() += 1;
+            });
+        }
+        update_with(&mut player, &quests, today);
+        assert!(matches!(
+            player.quests["goblin trouble"],
+            QuestState::Active(_)
+        ));
+
+        player.inventory.push(Item::new("holy water"));
+        update_with(&mut player, &quests, today);
+        assert_eq!(player.quests["goblin trouble"], QuestState::Done);
+        assert_eq!(player.gold, gold + 20);
+        assert!(player.has("sword"));
+        assert!(matches!(player.quests["the sequel"], QuestState::Active(_)));
+    }
+
+    #[test]
+    fn seasonal_quests_wait_for_their_season() {
+        let quests = parse("quest feast\nseason 12-20 01-05\ngoal gold 1000").unwrap();
+        assert_eq!(quests[0].season.unwrap().to_string(), "12-20 to 01-05");
+        assert!(parse("quest feast\nseason 13-01").is_err());
+
+        let mut player = PlayerState::default();
+        update_with(&mut player, &quests, Date::new(6, 1).unwrap());
+        assert!(!player.quests.contains_key("feast"));
+        update_with(&mut player, &quests, Date::new(1, 2).unwrap());
+        assert!(matches!(player.quests["feast"], QuestState::Active(_)));
+        // it isn't called off once the season's over
+        update_with(&mut player, &quests, Date::new(6, 1).unwrap());
+        assert!(matches!(player.quests["feast"], QuestState::Active(_)));
+    }
+}
@@ -0,0 +1,119 @@
+//! Dungeons that live on another machine, reached over SFTP.
+//!
+//! Entities are only files, so a dungeon on a server can be raided by anyone
+//! who can log in to it: `game remote mount dungeon.example.org:crypt` keeps
+//! the game's files under a local directory in an [`SftpFs`] on the server,
+//! and makes the dungeon's rooms there so they can be walked into. Every
+//! player sees the same canonical dungeon, and a goblin slain by one of them
+//! is gone for the rest. Mounts last until they're unmounted or the shell
+//! exits.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::{player, sftp::SftpFs, vfs};
+
+/// Splits `spec`, like `[user@]host:path`, into where and what to mount.
+fn parse_remote(spec: &str) -> Option<(&str, &str)> {
+    let (host, path) = spec.split_once(':')?;
+    // ssh would take a host starting with - for an option
+    if host.is_empty() || host.starts_with('-') {
+        return None;
+    }
+    Some((host, path))
+}
+
+/// Where the dungeon on `host` is mounted unless told otherwise.
+fn default_mount_point(host: &str) -> PathBuf {
+    player::get_data_dir()
+        .join("remote")
+        .join(host.replace('/', "_"))
+}
+
+fn mount(spec: &str, dir: Option<&String>) {
+    let Some((host, path)) = parse_remote(spec) else {
+        println!("game remote: {spec} should look like [user@]host:path");
+        return;
+    };
+    let dir = dir.map_or_else(|| default_mount_point(host), PathBuf::from);
+    // mounts are found by where they are, however they're got to
+    let dir = match fs::create_dir_all(&dir).and_then(|()| dir.canonicalize()) {
+        Ok(dir) => dir,
+        Err(error) => {
+            println!("game remote: {}: {error}", dir.display());
+            return;
+        }
+    };
+    let remote = match SftpFs::connect(host, Path::new(path)) {
+        Ok(remote) => remote,
+        Err(error) => {
+            println!("game remote: couldn't reach {spec}: {error}");
+            return;
+        }
+    };
+    let made = remote.rooms().and_then(|rooms| {
+        rooms
+            .iter()
+            .try_for_each(|room| fs::create_dir_all(dir.join(room)))
+    });
+    if let Err(error) = made {
+        println!("game remote: couldn't make {spec}'s rooms: {error}");
+        return;
+    }
+    vfs::mount(dir.clone(), spec.to_string(), remote);
+    println!(
+        "{spec} is mounted at {}. cd there to raid it.",
+        dir.display()
+    );
+}
+
+fn unmount(dir: &Path) {
+    // it was mounted by its canonical path
+    let canonical = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    match vfs::unmount(&canonical) {
+        true => println!("unmounted {}.", dir.display()),
+        false => println!("game remote: nothing is mounted at {}", dir.display()),
+    }
+}
+
+/// Runs `game remote`:
+///
+/// - `game remote` lists the remote dungeons mounted.
+/// - `game remote mount [user@]host:path [dir]` mounts one, in the data
+///   directory unless `dir` is given.
+/// - `game remote unmount <dir>` unmounts one.
+pub fn command(args: &[String]) {
+    match args {
+        [] => {
+            let mounts = vfs::mounts();
+            if mounts.is_empty() {
+                println!("no remote dungeons are mounted.");
+            }
+            for (source, target) in mounts {
+                println!("{source} at {}", target.display());
+            }
+        }
+        [verb, spec] if verb == "mount" => mount(spec, None),
+        [verb, spec, dir] if verb == "mount" => mount(spec, Some(dir)),
+        [verb, dir] if verb == "unmount" => unmount(Path::new(dir)),
+        _ => println!("game remote: usage: game remote [mount <host:path> [dir] | unmount <dir>]"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remotes_are_parsed() {
+        assert_eq!(
+            parse_remote("raid@dungeon.example.org:crypt"),
+            Some(("raid@dungeon.example.org", "crypt"))
+        );
+        assert_eq!(parse_remote("host:"), Some(("host", "")));
+        assert_eq!(parse_remote("crypt"), None);
+        assert_eq!(parse_remote("-oProxyCommand=sh:x"), None);
+    }
+}
@@ -0,0 +1,162 @@
+//! How the world's factions feel about the player.
+//!
+//! An entity can belong to a faction, set with `game faction <entity>
+//! <faction>`. Killing one of its members makes the faction think less of
+//! the player, and finishing quests that `reward reputation <faction>
+//! <amount>` makes it think more of them. Standings go from -100 to 100,
+//! starting at 0, and are kept in the save.
+//!
+//! - Merchants charge more the less their faction likes the player, and give
+//!   a discount to friends. The merchants that visit are [`TRADERS`].
+//! - Guards let the player walk right past once their faction trusts them.
+//! - The `reputation` builtin lists where the player stands with everyone.
+
+use super::{Component, Entity, get_entity, locale::t, player::PlayerState};
+
+/// The faction of the merchants that visit the player's home.
+pub const TRADERS: &str = "traders";
+
+/// How much a faction's opinion drops when one of its members is killed.
+const KILL_PENALTY: i32 = 10;
+
+/// The standing at which a faction's guards let the player past.
+const TRUSTED: i32 = 25;
+
+/// The lowest and highest standings there are.
+const LIMIT: i32 = 100;
+
+impl Entity {
+    /// The faction this entity belongs to, if any.
+    pub fn faction(&self) -> Option<&str> {
+        self.components.iter().find_map(|c| match c {
+            Component::Faction(faction) => Some(faction.as_str()),
+            _ => None,
+        })
+    }
+}
+
+impl PlayerState {
+    /// Where the player stands with `faction`.
+    pub fn standing(&self, faction: &str) -> i32 {
+        self.reputation.get(faction).copied().unwrap_or(0)
+    }
+
+    /// Changes where the player stands with `faction` by `change`.
+    pub fn change_standing(&mut self, faction: &str, change: i32) {
+        let standing = (self.standing(faction) + change).clamp(-LIMIT, LIMIT);
+        self.reputation.insert(faction.to_string(), standing);
+    }
+
+    /// Whether `e` would let the player past, as a guard.
+    pub fn is_trusted_by(&self, e: &Entity) -> bool {
+        e.faction()
+            .is_some_and(|faction| self.standing(faction) >= TRUSTED)
+    }
+}
+
+/// What something that costs `base` gold costs someone with `standing`. Each
+/// point below 0 adds a percent, up to double, and each point above it takes
+/// half a percent off, up to a quarter.
+pub fn price(base: u32, standing: i32) -> u32 {
+    let percent = match standing {
+        ..0 => 100 + standing.unsigned_abs().min(100),
+        _ => 100 - standing.unsigned_abs().min(50) / 2,
+    };
+    (base * percent).div_ceil(100).max(1)
+}
+
+/// How a faction with `standing` sees the player, in a word.
+fn describe(standing: i32) -> &'static str {
+    match standing {
+        ..=-50 => "hated",
+        -49..0 => "disliked",
+        0 => "neutral",
+        1..TRUSTED => "liked",
+        _ => "trusted",
+    }
+}
+
+/// Called when the player kills `e`.
+pub fn on_kill(player: &mut PlayerState, e: &Entity) {
+    let Some(faction) = e.faction() else {
+        return;
+    };
+    player.change_standing(faction, -KILL_PENALTY);
+    println!("{}", t!("reputation.kill", faction = faction));
+}
+
+/// Runs `game faction <entity> <faction>`, making an entity a member of a
+/// faction, or `game faction <entity> none` to take it out of one.
+pub fn command(args: &[String]) {
+    let [path, faction @ ..] = args else {
+        println!("game faction: expected <entity> <faction>");
+        return;
+    };
+    if faction.is_empty() {
+        println!("game faction: which faction?");
+        return;
+    }
+    let faction = faction.join(" ");
+    let Ok(mut e) = get_entity(path) else {
+        println!("game faction: {path} isn't an entity");
+        return;
+    };
+    e.components.retain(|c| !matches!(c, Component::Faction(_)));
+    if faction == "none" {
+        println!("{path} doesn't belong to any faction now.");
+    } else {
+        println!("{path} is one of the {faction} now.");
+        e.components.push(Component::Faction(faction));
+    }
+    if let Err(error) = super::spawn(e, path) {
+        println!("{}", t!("error.spawn", name = path, error = error));
+    }
+}
+
+/// Runs the `reputation` builtin, listing where the player stands with each
+/// faction they've had dealings with.
+pub fn report(args: &[String]) {
+    if !args.is_empty() {
+        println!("reputation: didn't expect any arguments");
+        return;
+    }
+    let player = PlayerState::load();
+    let mut standings: Vec<(&String, &i32)> = player.reputation.iter().collect();
+    if standings.is_empty() {
+        println!("nobody has an opinion of you yet.");
+        return;
+    }
+    standings.sort();
+    for (faction, &standing) in standings {
+        println!("{faction:<16} {standing:>4}  {}", describe(standing));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standing_changes_prices_and_guards() {
+        assert_eq!(price(10, 0), 10);
+        assert_eq!(price(10, -30), 13);
+        assert_eq!(price(10, -100), 20);
+        assert_eq!(price(10, 20), 9);
+        assert_eq!(price(10, 100), 8);
+        assert_eq!(price(1, 100), 1);
+
+        let mut player = PlayerState::default();
+        let guard = Entity {
+            components: vec![Component::Guard, Component::Faction(String::from("watch"))],
+        };
+        assert!(!player.is_trusted_by(&guard));
+        player.change_standing("watch", 30);
+        assert!(player.is_trusted_by(&guard));
+        on_kill(&mut player, &guard);
+        assert_eq!(player.standing("watch"), 20);
+        assert!(!player.is_trusted_by(&guard));
+        player.change_standing("watch", -500);
+        assert_eq!(player.standing("watch"), -LIMIT);
+        assert_eq!(describe(player.standing("watch")), "hated");
+    }
+}
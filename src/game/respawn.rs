@@ -0,0 +1,235 @@
+//! Rooms that fill back up with monsters some time after they're cleared.
+//!
+//! A room is a hidden entity ([`ROOM_FILE`]) sitting in a directory, which
+//! remembers which monsters from the [bestiary](super::bestiary) live there.
+//! Once the player kills the last of them, the room starts counting down, and
+//! the next time the player looks around after the delay is up the monsters
+//! are back. They come back a little tougher every time. Nocturnal ones wait
+//! for [nightfall](super::clock), so a room can fill back up in two goes.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::locale::t;
+use super::{
+    Component, Entity, bestiary, clock, events, get_entity, is_cleared, leaderboard, spawn,
+};
+
+/// The name of the room's file in its directory.
+const ROOM_FILE: &str = ".room";
+
+/// How long a room stays empty by default, in seconds.
+const DEFAULT_DELAY: u64 = 30 * 60;
+
+/// The extra HP monsters get each time their room has been cleared.
+const HP_PER_CLEAR: i16 = 2;
+
+/// How many clears it takes for monsters to hit back one harder.
+const CLEARS_PER_RETALIATION: u32 = 2;
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Room {
+    /// The monsters that live here, as (file name, template name).
+    pub roster: Vec<(String, String)>,
+    /// How long the room stays empty after being cleared, in seconds.
+    pub delay: u64,
+    /// How many times the room has been cleared.
+    pub clears: u32,
+    /// When the room was last cleared, in seconds since the Unix epoch, if it
+    /// is empty right now.
+    pub cleared_at: Option<u64>,
+}
+
+impl Room {
+    /// When the monsters are due back, if they're gone.
+    fn due(&self) -> Option<u64> {
+        self.cleared_at.map(|at| at + self.delay)
+    }
+
+    /// Whether any of the monsters only come out at night.
+    fn has_nocturnal(&self) -> bool {
+        self.roster
+            .iter()
+            .any(|(_, template)| bestiary::find(template).is_some_and(|t| t.nocturnal))
+    }
+
+    /// Makes the monster from `template`, toughened up for how many times
+    /// this room has been cleared.
+    fn monster(&self, template: &bestiary::Template) -> Entity {
+        let e = template.entity();
+        let hp = template.hp + HP_PER_CLEAR * self.clears as i16;
+        let bonus = (self.clears / CLEARS_PER_RETALIATION) as i16;
+
+        let mut e = e.with_hp(hp);
+        if bonus > 0 {
+            e.components
+                .retain(|c| !matches!(c, Component::Retaliates(_)));
+            e.components
+                .push(Component::Retaliates(template.retaliates + bonus));
+        }
+        e
+    }
+}
+
+impl Entity {
+    /// Returns the room if this entity is one.
+    pub fn room(&self) -> Option<&Room> {
+        self.components.iter().find_map(|c| match c {
+            Component::Room(room) => Some(room),
+            _ => None,
+        })
+    }
+}
+
+fn load(dir: &Path) -> Option<Room> {
+    get_entity(dir.join(ROOM_FILE))
+        .ok()
+        .and_then(|e| e.room().cloned())
+}
+
+/// Whether `dir` is a room.
+pub fn is_room(dir: &Path) -> bool {
+    load(dir).is_some()
+}
+
+fn save(dir: &Path, room: Room) {
+    let e = Entity {
+        components: vec![Component::Room(room)],
+    };
+    if let Err(error) = spawn(e, dir.join(ROOM_FILE)) {
+        println!("{}", t!("error.spawn", name = ROOM_FILE, error = error));
+    }
+}
+
+/// Called after something in `dir` is killed. Starts the countdown if that was
+/// the last monster in the room.
+pub fn on_kill(dir: &Path) {
+    let Some(mut room) = load(dir) else {
+        return;
+    };
+    if room.cleared_at.is_some() || !is_cleared(dir) {
+        return;
+    }
+
+    room.clears += 1;
+    room.cleared_at = Some(events::now());
+    println!("the room falls quiet. for now.");
+    save(dir, room);
+}
+
+/// Brings the monsters back to `dir` if they're due.
+pub fn check(dir: &Path) {
+    let Some(mut room) = load(dir) else {
+        return;
+    };
+    if room.due().is_none_or(|due| due > events::now()) {
+        return;
+    }
+
+    let night = clock::Time::now().is_night();
+    if !populate(dir, &room, night) {
+        return;
+    }
+    println!("something has moved back in while you were away.");
+    // by day the room's only partly back, and the rest come after dark
+    if night || !room.has_nocturnal() {
+        room.cleared_at = None;
+    }
+    save(dir, room);
+}
+
+/// Puts the monsters on the room's roster back in `dir`, leaving out the
+/// nocturnal ones unless it's `night`. Returns whether any came back.
+fn populate(dir: &Path, room: &Room, night: bool) -> bool {
+    let mut any = false;
+    for (name, template) in &room.roster {
+        let Some(template) = bestiary::find(template) else {
+            continue;
+        };
+        // don't trample on anything that wandered in while the room was empty
+        if dir.join(name).exists() || (template.nocturnal && !night) {
+            continue;
+        }
+        any |= spawn(room.monster(template), dir.join(name)).is_ok();
+    }
+    any
+}
+
+/// Runs `game room <template>... [every <seconds>]`, turning the current
+/// directory into a room that repopulates with the given monsters.
+pub fn command(args: &[String]) {
+    let (templates, delay) = match args {
+        [templates @ .., every, delay] if every == "every" => match delay.parse() {
+            Ok(delay) => (templates, delay),
+            Err(_) => {
+                println!("game room: {delay} isn't a number of seconds");
+                return;
+            }
+        },
+        templates => (templates, DEFAULT_DELAY),
+    };
+    if templates.is_empty() {
+        println!("game room: which monsters live here?");
+        return;
+    }
+
+    let mut roster = Vec::new();
+    for (i, name) in templates.iter().enumerate() {
+        let Some(template) = bestiary::find(name) else {
+            println!("there's no such thing as a {name}.");
+            return;
+        };
+        let file_name = match templates[..i].iter().filter(|t| *t == name).count() {
+            0 => template.file_name.to_string(),
+            n => format!("{}{}", template.file_name, n + 1),
+        };
+        roster.push((file_name, template.name.to_string()));
+    }
+
+    let room = Room {
+        roster,
+        delay,
+        clears: 0,
+        cleared_at: None,
+    };
+    let dir = Path::new(".");
+    // everyone moves in to start with, whatever the time
+    populate(dir, &room, true);
+    let names: Vec<&str> = room.roster.iter().map(|(name, _)| name.as_str()).collect();
+    println!(
+        "this room is now home to {}. they'll be back {} after they're cleared out.",
+        names.join(", "),
+        leaderboard::format_time(room.delay)
+    );
+    save(dir, room);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monsters_toughen_up() {
+        let goblin = bestiary::find("goblin").unwrap();
+        let mut room = Room {
+            roster: vec![("goblin".to_string(), "goblin".to_string())],
+            delay: 60,
+            clears: 0,
+            cleared_at: None,
+        };
+        assert_eq!(room.monster(goblin).hp(), Some(goblin.hp));
+        assert_eq!(room.due(), None);
+
+        room.clears = 3;
+        room.cleared_at = Some(100);
+        assert_eq!(room.monster(goblin).hp(), Some(goblin.hp + 6));
+        assert!(
+            room.monster(goblin)
+                .components
+                .iter()
+                .any(|c| matches!(c, Component::Retaliates(1)))
+        );
+        assert_eq!(room.due(), Some(160));
+    }
+}
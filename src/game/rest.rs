@@ -0,0 +1,108 @@
+//! Resting to recover, at the cost of letting the world move on without you.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::dice;
+use super::locale::t;
+use super::{
+    Component, Entity, entities_in,
+    player::{MAX_HP, PlayerState},
+    spell::MAX_MANA,
+};
+
+/// How many turns pass while the player rests.
+const REST_TURNS: u64 = 10;
+
+/// How many turns the player has to wait after resting before they can rest again.
+const REST_COOLDOWN: u64 = 30;
+
+impl Entity {
+    pub fn wanders(&self) -> bool {
+        self.components
+            .iter()
+            .any(|c| matches!(c, Component::Wanders))
+    }
+}
+
+/// Runs the `rest` verb, restoring the player's HP and mana.
+pub fn rest() {
+    let mut player = PlayerState::load();
+
+    if let Some(rested_at) = player.rested_at {
+        let ready_at = rested_at + REST_COOLDOWN;
+        if player.turns < ready_at {
+            println!(
+                "you're too restless to sleep. try again in {} turns.",
+                ready_at - player.turns
+            );
+            return;
+        }
+    }
+
+    println!("you settle down and rest for a while...");
+    player.turns += REST_TURNS;
+    player.rested_at = Some(player.turns);
+    player.hp = MAX_HP;
+    player.mana = MAX_MANA;
+
+    if let Ok(cwd) = std::env::current_dir() {
+        for _ in 0..REST_TURNS {
+            wander(&cwd);
+        }
+    }
+
+    println!("you wake up feeling refreshed.");
+    if let Err(error) = player.save() {
+        println!("{}", t!("error.save", error = error));
+    }
+}
+
+/// Returns the directories next to `dir`: its parent and its subdirectories.
+fn neighbours(dir: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+                .map(|entry| entry.path())
+                .collect()
+        })
+        .unwrap_or_default();
+    if let Some(parent) = dir.parent() {
+        dirs.push(parent.to_path_buf());
+    }
+    dirs
+}
+
+/// Gives every wandering entity around `dir` a chance to move one directory over
+/// for a single turn. The player hears about anything that wanders into `dir`.
+fn wander(dir: &Path) {
+    let around = neighbours(dir);
+    for from in around.iter().chain([&dir.to_path_buf()]) {
+        for (path, e) in entities_in(from) {
+            if !e.wanders() || !dice::random_ratio(1, 10) {
+                continue;
+            }
+
+            let options = neighbours(from);
+            if options.is_empty() {
+                continue;
+            }
+            let to = &options[dice::random_range(0..options.len())];
+            let name = path.file_name().unwrap();
+            let dest = to.join(name);
+            if dest.exists() || fs::rename(&path, &dest).is_err() {
+                continue;
+            }
+
+            if to == dir {
+                println!("a noise stirs you. {} wanders in.", name.display());
+            } else if from == dir {
+                println!("{} wanders off while you sleep.", name.display());
+            }
+        }
+    }
+}
@@ -0,0 +1,220 @@
+//! People who keep to a routine, moving between directories as the day goes
+//! by.
+//!
+//! `game npc <name> <day dir> <night dir>` makes someone who spends the day
+//! in one directory and the night in another, going by the game's
+//! [clock](super::clock). Every turn they're moved along to where they
+//! should be, so finding them means knowing their routine, or asking someone
+//! with `ask <person> about <name>`.
+//!
+//! The save remembers everyone's id, and the [index](super::id) where each
+//! of them is, so they can be kept to their schedules without searching for
+//! them. Someone who can't be found anymore is let go.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    Component, Entity,
+    clock::{Phase, Time},
+    despawn, get_entity, id,
+    locale::t,
+    player::PlayerState,
+    spawn, vfs,
+};
+
+/// Where someone spends their time.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Schedule {
+    pub day: PathBuf,
+    pub night: PathBuf,
+}
+
+impl Schedule {
+    /// Where they should be at `time`. They head home at dusk, and aren't
+    /// out again until dawn.
+    pub fn dir_at(&self, time: Time) -> &Path {
+        match time.phase() {
+            Phase::Dawn | Phase::Day => &self.day,
+            Phase::Dusk | Phase::Night => &self.night,
+        }
+    }
+}
+
+impl Entity {
+    /// Returns the schedule if this entity keeps one.
+    pub fn schedule(&self) -> Option<&Schedule> {
+        self.components.iter().find_map(|c| match c {
+            Component::Schedule(schedule) => Some(schedule),
+            _ => None,
+        })
+    }
+}
+
+/// The name of a directory, for telling the player about it.
+fn dir_name(dir: &Path) -> String {
+    dir.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| dir.display().to_string())
+}
+
+/// Called every turn. Moves everyone to where their schedule says they
+/// should be, letting the player know about anyone who leaves or arrives
+/// where they are.
+pub fn tick(player: &mut PlayerState) {
+    let time = Time::at_turn(player.turns);
+    let cwd = std::env::current_dir().unwrap_or_default();
+    player.npcs.retain(|&npc| {
+        let Some(path) = id::find(npc) else {
+            return false;
+        };
+        let Ok(e) = get_entity(&path) else {
+            return false;
+        };
+        let (Some(schedule), Some(name), Some(from)) =
+            (e.schedule(), path.file_name(), path.parent())
+        else {
+            return false;
+        };
+        let to = schedule.dir_at(time).to_path_buf();
+        if from == to {
+            return true;
+        }
+        let dest = to.join(name);
+        // the directory has to be there, and nothing in the way
+        match vfs::with(|fs| fs.list(&to)) {
+            Ok(files) if !files.contains(&dest) => {}
+            _ => return true,
+        }
+        let name = name.to_string_lossy().into_owned();
+        let from = from.to_path_buf();
+        if spawn(e, &dest).is_err() {
+            return true;
+        }
+        // they stay put if they can't leave, rather than being in both places
+        if despawn(&path).is_err() {
+            let _ = despawn(&dest);
+            return true;
+        }
+        if from == cwd {
+            println!(
+                "{}",
+                t!("schedule.leaves", name = name, dir = dir_name(&to))
+            );
+        } else if to == cwd {
+            println!("{}", t!("schedule.arrives", name = name));
+        }
+        true
+    });
+}
+
+/// Runs `game npc <name> <day dir> <night dir>`, making someone who keeps
+/// to a schedule. They start off wherever they should be now.
+pub fn command(args: &[String]) {
+    let [name, day, night] = args else {
+        println!("game npc: expected <name> <day dir> <night dir>");
+        return;
+    };
+    if let Some(dir) = [day, night]
+        .into_iter()
+        .find(|dir| !Path::new(dir).is_dir())
+    {
+        println!("game npc: {dir} isn't a directory");
+        return;
+    }
+    let (Ok(day), Ok(night)) = (std::path::absolute(day), std::path::absolute(night)) else {
+        println!("game npc: couldn't work out where those directories are");
+        return;
+    };
+
+    let mut player = PlayerState::load();
+    let schedule = Schedule { day, night };
+    let dir = schedule.dir_at(Time::at_turn(player.turns)).to_path_buf();
+    let mut e = Entity {
+        components: vec![Component::Schedule(schedule)],
+    };
+    let id = id::Uuid::new();
+    e.components.push(Component::Id(id));
+    if let Err(error) = spawn(e, dir.join(name)) {
+        println!("{}", t!("error.spawn", name = name, error = error));
+        return;
+    }
+    player.npcs.push(id);
+    println!("{name} moves in. they're in {} right now.", dir_name(&dir));
+    if let Err(error) = player.save() {
+        println!("{}", t!("error.save", error = error));
+    }
+}
+
+/// Runs the `ask` verb, asking `person` where to find `name`.
+pub fn ask(person: &str, name: &str) {
+    if get_entity(person).is_err() {
+        println!("there's nobody called {person} here to ask.");
+        return;
+    }
+    let routine = PlayerState::load().npcs.iter().find_map(|&npc| {
+        let path = id::find(npc)?;
+        let e = get_entity(&path).ok()?;
+        (path.file_name()? == name).then(|| e.schedule().cloned())?
+    });
+    match routine {
+        Some(schedule) => println!(
+            "{}",
+            t!(
+                "schedule.routine",
+                person = person,
+                name = name,
+                day = dir_name(&schedule.day),
+                night = dir_name(&schedule.night)
+            )
+        ),
+        None => println!("{}", t!("schedule.unknown", person = person, name = name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::testworld::{item, world};
+
+    #[test]
+    fn people_keep_to_their_schedules() {
+        let world = world! {
+            // something in each, so the directories are there
+            "market" => { "stall": item("apple") },
+            "tavern" => { "barrel": item("ale") },
+        };
+        let schedule = Schedule {
+            day: world.path("market"),
+            night: world.path("tavern"),
+        };
+        world.add(
+            "market",
+            "bard",
+            Entity {
+                components: vec![Component::Schedule(schedule)],
+            },
+        );
+        let id = world.entity("market/bard").unwrap().id().unwrap();
+        let mut player = PlayerState {
+            npcs: vec![id],
+            ..PlayerState::default()
+        };
+
+        world.run(|| tick(&mut player));
+        assert!(world.entity("market/bard").is_some());
+
+        // midnight
+        player.turns = 64;
+        world.run(|| tick(&mut player));
+        assert!(world.entity("market/bard").is_none());
+        assert!(world.entity("tavern/bard").is_some());
+        assert_eq!(player.npcs, [id]);
+
+        // gone for good
+        world.run(|| despawn(world.path("tavern/bard"))).unwrap();
+        world.run(|| tick(&mut player));
+        assert!(player.npcs.is_empty());
+    }
+}
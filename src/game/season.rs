@@ -0,0 +1,199 @@
+//! Content that's only around at certain times of year, like holiday quests.
+//!
+//! A quest in a content pack can be given a window with a `season` line:
+//! `season 12-20 01-05` runs from the 20th of December to the 5th of January,
+//! and `season 10-31` is for the one day. Windows come round every year, and
+//! can run over the new year. A quest only starts while its window is open,
+//! going by the real date, so every player gets the same content on the same
+//! day. Once it's started it can be finished whenever.
+//!
+//! `game events` lists what's on today and what's coming up.
+
+use std::{fmt, str::FromStr};
+
+use super::{
+    events,
+    player::PlayerState,
+    quest::{self, QuestState},
+};
+use crate::{headless, shell::date::DateTime};
+
+/// How many days there are before each month, in a leap year.
+const MONTH_STARTS: [u32; 12] = [0, 31, 60, 91, 121, 152, 182, 213, 244, 274, 305, 335];
+
+/// How many days there are in each month, in a leap year.
+const MONTH_LENGTHS: [u32; 12] = [31, 29, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+
+/// A day of the year, like `12-25`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    /// From 1 to 12.
+    month: u32,
+    /// From 1 to 31.
+    day: u32,
+}
+
+impl Date {
+    pub fn new(month: u32, day: u32) -> Option<Date> {
+        let length = MONTH_LENGTHS.get(month.checked_sub(1)? as usize)?;
+        (1..=*length).contains(&day).then_some(Date { month, day })
+    }
+
+    /// How far into the year this is, counting the 29th of February every
+    /// year so that dates always line up.
+    fn ordinal(self) -> u32 {
+        MONTH_STARTS[self.month as usize - 1] + self.day - 1
+    }
+}
+
+impl FromStr for Date {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || format!("{s} isn't a date like 12-25");
+        let (month, day) = s.split_once('-').ok_or_else(error)?;
+        let (month, day) = (
+            month.parse().map_err(|_| error())?,
+            day.parse().map_err(|_| error())?,
+        );
+        Date::new(month, day).ok_or_else(error)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}-{:02}", self.month, self.day)
+    }
+}
+
+/// The days of the year something is around for, from `from` to `to` and
+/// including both.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Window {
+    pub from: Date,
+    pub to: Date,
+}
+
+impl Window {
+    /// Parses the words after `season`.
+    pub fn parse(words: &[&str]) -> Result<Window, String> {
+        match words {
+            [day] => {
+                let day = day.parse()?;
+                Ok(Window { from: day, to: day })
+            }
+            [from, to] => Ok(Window {
+                from: from.parse()?,
+                to: to.parse()?,
+            }),
+            _ => Err(String::from(
+                "season needs a date, or a first and last date",
+            )),
+        }
+    }
+
+    pub fn contains(self, date: Date) -> bool {
+        match self.from <= self.to {
+            true => self.from <= date && date <= self.to,
+            // over the new year
+            false => self.from <= date || date <= self.to,
+        }
+    }
+
+    /// How many days it is from `date` until the window opens, 0 if it's
+    /// open.
+    pub fn days_until(self, date: Date) -> u32 {
+        match self.contains(date) {
+            true => 0,
+            false => (self.from.ordinal() + 366 - date.ordinal()) % 366,
+        }
+    }
+}
+
+impl fmt::Display for Window {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.from == self.to {
+            true => write!(f, "{}", self.from),
+            false => write!(f, "{} to {}", self.from, self.to),
+        }
+    }
+}
+
+/// Today's date where the player is. Headless it's always the day of the
+/// epoch, in UTC, since time stands still there.
+pub fn today() -> Date {
+    let now = events::now() as i64;
+    let date = match headless::enabled() {
+        true => DateTime::utc(now),
+        false => DateTime::local(now),
+    };
+    Date {
+        month: date.month,
+        day: date.day,
+    }
+}
+
+/// Runs `game events`, listing the seasonal content that's on now and what's
+/// coming up, soonest first.
+pub fn command(args: &[String]) {
+    if !args.is_empty() {
+        println!("game events: didn't expect any arguments");
+        return;
+    }
+    let today = today();
+    let player = PlayerState::load();
+    let quests = quest::load();
+    let mut seasonal: Vec<(u32, &str, Window)> = quests
+        .iter()
+        .filter_map(|quest| {
+            let window = quest.season?;
+            Some((window.days_until(today), quest.name.as_str(), window))
+        })
+        .collect();
+    if seasonal.is_empty() {
+        println!("nothing special is coming up. content packs can add seasonal quests.");
+        return;
+    }
+    seasonal.sort_by_key(|&(days, name, _)| (days, name));
+    for (days, name, window) in seasonal {
+        let done = match player.quests.get(name) {
+            Some(QuestState::Done) => " (done)",
+            _ => "",
+        };
+        match days {
+            0 => println!("{name}: on now, until {}{done}", window.to),
+            1 => println!("{name}: tomorrow, {window}{done}"),
+            days => println!("{name}: in {days} days, {window}{done}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> Date {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn windows_come_round_every_year() {
+        let winter = Window::parse(&["12-20", "01-05"]).unwrap();
+        assert!(winter.contains(date("12-25")));
+        assert!(winter.contains(date("01-05")));
+        assert!(!winter.contains(date("01-06")));
+        assert_eq!(winter.days_until(date("01-01")), 0);
+        assert_eq!(winter.days_until(date("12-10")), 10);
+        assert_eq!(winter.to_string(), "12-20 to 01-05");
+
+        let halloween = Window::parse(&["10-31"]).unwrap();
+        assert!(halloween.contains(date("10-31")));
+        assert_eq!(halloween.days_until(date("11-01")), 365);
+        assert_eq!(halloween.to_string(), "10-31");
+
+        for bad in ["13-01", "02-30", "12", "xmas", "00-10"] {
+            assert!(bad.parse::<Date>().is_err(), "{bad}");
+        }
+        assert!(Window::parse(&[]).is_err());
+    }
+}
@@ -0,0 +1,681 @@
+//! A small SFTP client, enough to keep a dungeon's files on another machine.
+//!
+//! It speaks version 3 of the protocol, the one OpenSSH's `sftp-server`
+//! does, over any pair of pipes. [`SftpFs::connect`] gets them from
+//! `ssh -s <host> sftp`, so logging in works however the player's ssh is set
+//! up, keys, agent, config and all. Each request is answered before the next
+//! one is made.
+
+use std::{
+    cell::RefCell,
+    ffi::OsStr,
+    io::{self, Read, Write},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+};
+
+use super::vfs::GameFs;
+
+// the packets, from the client
+const INIT: u8 = 1;
+const OPEN: u8 = 3;
+const CLOSE: u8 = 4;
+const READ: u8 = 5;
+const WRITE: u8 = 6;
+const OPENDIR: u8 = 11;
+const READDIR: u8 = 12;
+const REMOVE: u8 = 13;
+const MKDIR: u8 = 14;
+const STAT: u8 = 17;
+
+// and from the server
+const VERSION: u8 = 2;
+const STATUS: u8 = 101;
+const HANDLE: u8 = 102;
+const DATA: u8 = 103;
+const NAME: u8 = 104;
+const ATTRS: u8 = 105;
+
+// what a STATUS says
+const OK: u32 = 0;
+const EOF: u32 = 1;
+const NO_SUCH_FILE: u32 = 2;
+const PERMISSION_DENIED: u32 = 3;
+
+// how to open a file
+const FOR_READING: u32 = 0x01;
+const FOR_WRITING: u32 = 0x02;
+const CREATE: u32 = 0x08;
+const TRUNCATE: u32 = 0x10;
+
+// which attributes are there
+const HAS_SIZE: u32 = 0x01;
+const HAS_OWNER: u32 = 0x02;
+const HAS_PERMISSIONS: u32 = 0x04;
+const HAS_TIMES: u32 = 0x08;
+const HAS_EXTENDED: u32 = 0x8000_0000;
+
+// what kind of file the permissions say it is
+const KIND: u32 = 0o170000;
+const DIRECTORY: u32 = 0o040000;
+const REGULAR: u32 = 0o100000;
+
+/// How much is read or written at a time. Servers have to take at least this
+/// much.
+const CHUNK: usize = 32 * 1024;
+
+/// Packets bigger than this are taken to be garbage rather than waited for.
+const MAX_PACKET: usize = 256 * 1024;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("sftp: {message}"))
+}
+
+/// A packet being put together to send.
+struct Packet(Vec<u8>);
+
+impl Packet {
+    fn new(kind: u8) -> Self {
+        Packet(vec![kind])
+    }
+
+    fn u32(mut self, n: u32) -> Self {
+        self.0.extend(n.to_be_bytes());
+        self
+    }
+
+    fn u64(mut self, n: u64) -> Self {
+        self.0.extend(n.to_be_bytes());
+        self
+    }
+
+    fn bytes(self, bytes: &[u8]) -> Self {
+        let mut packet = self.u32(bytes.len() as u32);
+        packet.0.extend(bytes);
+        packet
+    }
+
+    fn path(self, path: &Path) -> Self {
+        self.bytes(path.as_os_str().as_bytes())
+    }
+}
+
+/// The fields of a packet that came back, read in order.
+struct Fields<'a>(&'a [u8]);
+
+impl Fields<'_> {
+    fn take(&mut self, n: usize) -> io::Result<&[u8]> {
+        if self.0.len() < n {
+            return Err(invalid("packet cut short"));
+        }
+        let (taken, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn bytes(&mut self) -> io::Result<&[u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    /// Skips over a file's attributes, returning its permissions, which say
+    /// what kind of file it is, if they're there.
+    fn attributes(&mut self) -> io::Result<Option<u32>> {
+        let flags = self.u32()?;
+        if flags & HAS_SIZE != 0 {
+            self.take(8)?;
+        }
+        if flags & HAS_OWNER != 0 {
+            self.take(8)?;
+        }
+        let permissions = match flags & HAS_PERMISSIONS {
+            0 => None,
+            _ => Some(self.u32()?),
+        };
+        if flags & HAS_TIMES != 0 {
+            self.take(8)?;
+        }
+        if flags & HAS_EXTENDED != 0 {
+            for _ in 0..self.u32()? {
+                self.bytes()?;
+                self.bytes()?;
+            }
+        }
+        Ok(permissions)
+    }
+}
+
+/// A conversation with an SFTP server.
+struct Session {
+    reader: Box<dyn Read>,
+    writer: Box<dyn Write>,
+    next_id: u32,
+}
+
+impl Session {
+    /// Says hello to the server on the other end of `reader` and `writer`.
+    fn new(reader: impl Read + 'static, writer: impl Write + 'static) -> io::Result<Self> {
+        let mut session = Session {
+            reader: Box::new(reader),
+            writer: Box::new(writer),
+            next_id: 0,
+        };
+        session.send(Packet::new(INIT).u32(3))?;
+        let (kind, body) = session.receive()?;
+        let version = Fields(&body).u32()?;
+        if kind != VERSION || version != 3 {
+            return Err(invalid("the server doesn't speak version 3"));
+        }
+        Ok(session)
+    }
+
+    fn send(&mut self, packet: Packet) -> io::Result<()> {
+        self.writer
+            .write_all(&(packet.0.len() as u32).to_be_bytes())?;
+        self.writer.write_all(&packet.0)?;
+        self.writer.flush()
+    }
+
+    fn receive(&mut self) -> io::Result<(u8, Vec<u8>)> {
+        let mut len = [0; 4];
+        self.reader.read_exact(&mut len)?;
+        let len = u32::from_be_bytes(len) as usize;
+        if len == 0 || len > MAX_PACKET {
+            return Err(invalid("the server sent garbage"));
+        }
+        let mut packet = vec![0; len];
+        self.reader.read_exact(&mut packet)?;
+        let body = packet.split_off(1);
+        Ok((packet[0], body))
+    }
+
+    /// Makes a request of the kind `kind`, with the fields `fields` adds, and
+    /// returns the answer if it's a `want`. A status other than OK is an
+    /// error, and the end of a file or directory is an `UnexpectedEof`.
+    fn request(
+        &mut self,
+        kind: u8,
+        fields: impl FnOnce(Packet) -> Packet,
+        want: u8,
+    ) -> io::Result<Vec<u8>> {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.send(fields(Packet::new(kind).u32(id)))?;
+        let (kind, body) = self.receive()?;
+        let mut fields = Fields(&body);
+        if fields.u32()? != id {
+            return Err(invalid("an answer came back for another request"));
+        }
+        if kind == want {
+            return Ok(fields.0.to_vec());
+        }
+        if kind != STATUS {
+            return Err(invalid("the server answered with the wrong kind of packet"));
+        }
+        let code = fields.u32()?;
+        let message = String::from_utf8_lossy(fields.bytes().unwrap_or_default());
+        let kind = match code {
+            OK if want == STATUS => return Ok(Vec::new()),
+            EOF => io::ErrorKind::UnexpectedEof,
+            NO_SUCH_FILE => io::ErrorKind::NotFound,
+            PERMISSION_DENIED => io::ErrorKind::PermissionDenied,
+            _ => io::ErrorKind::Other,
+        };
+        Err(io::Error::new(kind, format!("sftp: {message}")))
+    }
+
+    fn open(&mut self, path: &Path, flags: u32) -> io::Result<Vec<u8>> {
+        // no attributes, so new files get the server's default permissions
+        self.request(OPEN, |p| p.path(path).u32(flags).u32(0), HANDLE)
+            .and_then(|body| Ok(Fields(&body).bytes()?.to_vec()))
+    }
+
+    fn close(&mut self, handle: &[u8]) -> io::Result<()> {
+        self.request(CLOSE, |p| p.bytes(handle), STATUS).map(drop)
+    }
+
+    /// Reads up to `len` bytes of the open file from `offset`, or nothing
+    /// past its end.
+    fn read(&mut self, handle: &[u8], offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let read = self.request(READ, |p| p.bytes(handle).u64(offset).u32(len as u32), DATA);
+        match read {
+            Ok(body) => Ok(Fields(&body).bytes()?.to_vec()),
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => Ok(Vec::new()),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Reads the open file from the start, stopping once there's `limit`.
+    fn read_all(&mut self, handle: &[u8], limit: usize) -> io::Result<Vec<u8>> {
+        let mut contents = Vec::new();
+        while contents.len() < limit {
+            let want = CHUNK.min(limit - contents.len());
+            let chunk = self.read(handle, contents.len() as u64, want)?;
+            if chunk.is_empty() {
+                break;
+            }
+            contents.extend(chunk);
+        }
+        Ok(contents)
+    }
+
+    /// What kind of file is at `path`, going by its permissions, if the
+    /// server says.
+    fn stat(&mut self, path: &Path) -> io::Result<Option<u32>> {
+        let body = self.request(STAT, |p| p.path(path), ATTRS)?;
+        Fields(&body).attributes()
+    }
+
+    /// The names of everything in the directory at `path`, and their
+    /// permissions if the server says.
+    fn names(&mut self, path: &Path) -> io::Result<Vec<(PathBuf, Option<u32>)>> {
+        let body = self.request(OPENDIR, |p| p.path(path), HANDLE)?;
+        let handle = Fields(&body).bytes()?.to_vec();
+        let mut names = Vec::new();
+        let listed = loop {
+            let body = match self.request(READDIR, |p| p.bytes(&handle), NAME) {
+                Ok(body) => body,
+                Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => break Ok(()),
+                Err(error) => break Err(error),
+            };
+            let mut fields = Fields(&body);
+            let read = (0..fields.u32()?).try_for_each(|_| {
+                let name = PathBuf::from(OsStr::from_bytes(fields.bytes()?));
+                // the long name is for people, like `ls -l`
+                fields.bytes()?;
+                let permissions = fields.attributes()?;
+                if name != Path::new(".") && name != Path::new("..") {
+                    names.push((name, permissions));
+                }
+                Ok(())
+            });
+            if let Err(error) = read {
+                break Err(error);
+            }
+        };
+        self.close(&handle)?;
+        listed.map(|()| names)
+    }
+}
+
+/// A dungeon's files, kept on an SFTP server under `root`. Paths given to it
+/// are relative to `root`.
+pub struct SftpFs {
+    session: RefCell<Session>,
+    root: PathBuf,
+    /// The ssh it's talking through, if it's talking through one.
+    ssh: Option<Child>,
+}
+
+impl SftpFs {
+    /// Logs in to `host`, which can have a `user@` in front, with ssh, and
+    /// keeps the files under `root` on it. `root` is relative to the home
+    /// directory there unless it's absolute.
+    pub fn connect(host: &str, root: &Path) -> io::Result<SftpFs> {
+        // ssh would take a host starting with - for an option
+        if host.starts_with('-') {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{host} isn't a host"),
+            ));
+        }
+        let mut ssh = Command::new("ssh")
+            .args(["-s", "--", host, "sftp"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let (Some(stdout), Some(stdin)) = (ssh.stdout.take(), ssh.stdin.take()) else {
+            unreachable!("ssh's stdin and stdout are piped");
+        };
+        let session = Session::new(stdout, stdin).inspect_err(|_| {
+            let _ = ssh.kill();
+            let _ = ssh.wait();
+        })?;
+        let fs = SftpFs {
+            session: RefCell::new(session),
+            root: root.to_path_buf(),
+            ssh: Some(ssh),
+        };
+        fs.session.borrow_mut().names(&fs.remote(Path::new("")))?;
+        Ok(fs)
+    }
+
+    /// Talks to the server on the other end of `reader` and `writer`.
+    pub fn over(
+        reader: impl Read + 'static,
+        writer: impl Write + 'static,
+        root: &Path,
+    ) -> io::Result<SftpFs> {
+        Ok(SftpFs {
+            session: RefCell::new(Session::new(reader, writer)?),
+            root: root.to_path_buf(),
+            ssh: None,
+        })
+    }
+
+    /// Every directory under the root, so they can be made where the dungeon
+    /// is mounted and walked into.
+    pub fn rooms(&self) -> io::Result<Vec<PathBuf>> {
+        let mut rooms = Vec::new();
+        let mut unvisited = vec![PathBuf::new()];
+        while let Some(room) = unvisited.pop() {
+            for (name, permissions) in self.session.borrow_mut().names(&self.remote(&room))? {
+                if permissions.is_some_and(|mode| mode & KIND == DIRECTORY) {
+                    unvisited.push(room.join(&name));
+                    rooms.push(room.join(name));
+                }
+            }
+        }
+        Ok(rooms)
+    }
+
+    /// Where `path` is on the server.
+    fn remote(&self, path: &Path) -> PathBuf {
+        match self.root.join(path) {
+            path if path.as_os_str().is_empty() => PathBuf::from("."),
+            path => path,
+        }
+    }
+}
+
+impl Drop for SftpFs {
+    fn drop(&mut self) {
+        if let Some(ssh) = &mut self.ssh {
+            let _ = ssh.kill();
+            let _ = ssh.wait();
+        }
+    }
+}
+
+impl GameFs for SftpFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let mut session = self.session.borrow_mut();
+        let handle = session.open(&self.remote(path), FOR_READING)?;
+        let contents = session.read_all(&handle, usize::MAX);
+        session.close(&handle)?;
+        contents
+    }
+
+    fn starts_with(&self, path: &Path, prefix: &[u8]) -> io::Result<bool> {
+        let path = self.remote(path);
+        let mut session = self.session.borrow_mut();
+        // directories can't be opened as files
+        if session
+            .stat(&path)?
+            .is_some_and(|mode| mode & KIND != REGULAR)
+        {
+            return Ok(false);
+        }
+        let handle = session.open(&path, FOR_READING)?;
+        let start = session.read_all(&handle, prefix.len());
+        session.close(&handle)?;
+        Ok(start? == prefix)
+    }
+
+    fn write(&mut self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let path = self.remote(path);
+        let session = self.session.get_mut();
+        let handle = session.open(&path, FOR_WRITING | CREATE | TRUNCATE)?;
+        let written = (0..contents.len()).step_by(CHUNK).try_for_each(|offset| {
+            let chunk = &contents[offset..contents.len().min(offset + CHUNK)];
+            let write = |p: Packet| p.bytes(&handle).u64(offset as u64).bytes(chunk);
+            session.request(WRITE, write, STATUS).map(drop)
+        });
+        session.close(&handle)?;
+        written
+    }
+
+    fn remove(&mut self, path: &Path) -> io::Result<()> {
+        let path = self.remote(path);
+        let session = self.session.get_mut();
+        session.request(REMOVE, |p| p.path(&path), STATUS).map(drop)
+    }
+
+    fn list(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let names = self.session.borrow_mut().names(&self.remote(dir))?;
+        Ok(names.into_iter().map(|(name, _)| dir.join(name)).collect())
+    }
+
+    fn create_dir_all(&mut self, dir: &Path) -> io::Result<()> {
+        let dir = self.remote(dir);
+        let session = self.session.get_mut();
+        for dir in dir.ancestors().collect::<Vec<_>>().into_iter().rev() {
+            if dir.as_os_str().is_empty() || session.stat(dir).is_ok() {
+                continue;
+            }
+            // no attributes, so it gets the server's default permissions
+            session.request(MKDIR, |p| p.path(dir).u32(0), STATUS)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, os::unix::net::UnixStream, thread};
+
+    use super::*;
+    use crate::game::{self, bestiary, vfs};
+
+    /// What's on the fake server: files, and directories with `None` in them.
+    type Files = BTreeMap<PathBuf, Option<Vec<u8>>>;
+
+    fn crypt() -> Files {
+        BTreeMap::from([
+            (PathBuf::from("crypt"), None),
+            (PathBuf::from("crypt/hall"), None),
+        ])
+    }
+
+    fn path(fields: &mut Fields) -> PathBuf {
+        PathBuf::from(OsStr::from_bytes(fields.bytes().unwrap()))
+    }
+
+    fn u64(fields: &mut Fields) -> u64 {
+        u64::from_be_bytes(fields.take(8).unwrap().try_into().unwrap())
+    }
+
+    /// Handles are four bytes, an index into what's open.
+    fn handle(fields: &mut Fields) -> usize {
+        assert_eq!(fields.u32().unwrap(), 4);
+        fields.u32().unwrap() as usize
+    }
+
+    fn status(id: u32, code: u32) -> Packet {
+        Packet::new(STATUS).u32(id).u32(code).bytes(b"").bytes(b"")
+    }
+
+    fn permissions(file: &Option<Vec<u8>>) -> u32 {
+        match file {
+            Some(_) => REGULAR | 0o644,
+            None => DIRECTORY | 0o755,
+        }
+    }
+
+    /// Answers one request, given its kind and the fields after its id.
+    /// Handles are indexes into `open`, which has each one's path and, for
+    /// directories, the names left to list.
+    fn answer(
+        files: &mut Files,
+        open: &mut Vec<(PathBuf, Vec<PathBuf>)>,
+        kind: u8,
+        id: u32,
+        mut fields: Fields,
+    ) -> Packet {
+        match kind {
+            OPEN => {
+                let path = path(&mut fields);
+                let flags = fields.u32().unwrap();
+                match files.get(&path) {
+                    Some(Some(_)) if flags & TRUNCATE == 0 => {}
+                    Some(Some(_)) | None if flags & CREATE != 0 => {
+                        files.insert(path.clone(), Some(Vec::new()));
+                    }
+                    _ => return status(id, NO_SUCH_FILE),
+                }
+                open.push((path, Vec::new()));
+                Packet::new(HANDLE)
+                    .u32(id)
+                    .u32(4)
+                    .u32(open.len() as u32 - 1)
+            }
+            CLOSE => status(id, OK),
+            READ => {
+                let (path, _) = &open[handle(&mut fields)];
+                let offset = u64(&mut fields) as usize;
+                let len = fields.u32().unwrap() as usize;
+                let contents = files[path].as_ref().unwrap();
+                match offset < contents.len() {
+                    true => {
+                        let end = contents.len().min(offset + len);
+                        Packet::new(DATA).u32(id).bytes(&contents[offset..end])
+                    }
+                    false => status(id, EOF),
+                }
+            }
+            WRITE => {
+                let path = open[handle(&mut fields)].0.clone();
+                let offset = u64(&mut fields) as usize;
+                let data = fields.bytes().unwrap();
+                let contents = files.get_mut(&path).unwrap().as_mut().unwrap();
+                contents.resize(contents.len().max(offset + data.len()), 0);
+                contents[offset..offset + data.len()].copy_from_slice(data);
+                status(id, OK)
+            }
+            OPENDIR => {
+                let dir = path(&mut fields);
+                if files.get(&dir) != Some(&None) {
+                    return status(id, NO_SUCH_FILE);
+                }
+                let mut names = vec![PathBuf::from("."), PathBuf::from("..")];
+                names.extend(
+                    files
+                        .keys()
+                        .filter(|path| path.parent() == Some(&dir))
+                        .map(|path| PathBuf::from(path.file_name().unwrap())),
+                );
+                open.push((dir, names));
+                Packet::new(HANDLE)
+                    .u32(id)
+                    .u32(4)
+                    .u32(open.len() as u32 - 1)
+            }
+            READDIR => {
+                let (dir, names) = &mut open[handle(&mut fields)];
+                if names.is_empty() {
+                    return status(id, EOF);
+                }
+                let mut packet = Packet::new(NAME).u32(id).u32(names.len() as u32);
+                for name in names.drain(..) {
+                    let mode = files.get(&dir.join(&name)).map_or(DIRECTORY, permissions);
+                    packet = packet.path(&name).bytes(b"").u32(HAS_PERMISSIONS).u32(mode);
+                }
+                packet
+            }
+            REMOVE => {
+                let path = path(&mut fields);
+                match files.get(&path) {
+                    Some(Some(_)) => {
+                        files.remove(&path);
+                        status(id, OK)
+                    }
+                    _ => status(id, NO_SUCH_FILE),
+                }
+            }
+            MKDIR => {
+                files.insert(path(&mut fields), None);
+                status(id, OK)
+            }
+            STAT => match files.get(&path(&mut fields)) {
+                Some(file) => Packet::new(ATTRS)
+                    .u32(id)
+                    .u32(HAS_PERMISSIONS)
+                    .u32(permissions(file)),
+                None => status(id, NO_SUCH_FILE),
+            },
+            _ => status(id, 8),
+        }
+    }
+
+    /// Serves `files` until the client hangs up, then gives back what's left.
+    fn serve(mut stream: UnixStream, mut files: Files) -> Files {
+        let mut open = Vec::new();
+        loop {
+            let mut len = [0; 4];
+            if stream.read_exact(&mut len).is_err() {
+                return files;
+            }
+            let mut packet = vec![0; u32::from_be_bytes(len) as usize];
+            stream.read_exact(&mut packet).unwrap();
+            let mut fields = Fields(&packet[1..]);
+            let id = fields.u32().unwrap();
+            let reply = match packet[0] {
+                INIT => Packet::new(VERSION).u32(3),
+                kind => answer(&mut files, &mut open, kind, id, fields),
+            };
+            stream
+                .write_all(&(reply.0.len() as u32).to_be_bytes())
+                .unwrap();
+            stream.write_all(&reply.0).unwrap();
+        }
+    }
+
+    fn connect(files: Files) -> (SftpFs, thread::JoinHandle<Files>) {
+        let (client, server) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || serve(server, files));
+        let remote = SftpFs::over(client.try_clone().unwrap(), client, Path::new("crypt")).unwrap();
+        (remote, server)
+    }
+
+    #[test]
+    fn files_are_kept_on_the_server() {
+        let (mut remote, server) = connect(crypt());
+        // more than is sent at once
+        let hoard = vec![7; CHUNK * 2 + 5];
+        remote.write(Path::new("hoard"), &hoard).unwrap();
+        assert_eq!(remote.read(Path::new("hoard")).unwrap(), hoard);
+        assert!(remote.starts_with(Path::new("hoard"), &[7, 7]).unwrap());
+        assert!(!remote.starts_with(Path::new("hall"), b"").unwrap());
+        assert_eq!(
+            remote.list(Path::new("")).unwrap(),
+            [PathBuf::from("hall"), PathBuf::from("hoard")]
+        );
+
+        remote.create_dir_all(Path::new("hall/stairs")).unwrap();
+        assert_eq!(
+            remote.rooms().unwrap(),
+            [PathBuf::from("hall"), PathBuf::from("hall/stairs")]
+        );
+        remote.remove(Path::new("hoard")).unwrap();
+        let error = remote.read(Path::new("hoard")).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+
+        drop(remote);
+        let files = server.join().unwrap();
+        assert_eq!(
+            files.keys().collect::<Vec<_>>(),
+            ["crypt", "crypt/hall", "crypt/hall/stairs"].map(Path::new)
+        );
+    }
+
+    #[test]
+    fn entities_can_live_on_a_server() {
+        let (remote, server) = connect(crypt());
+        let dungeon = Path::new("/dungeon");
+        vfs::using(vfs::MemoryFs::default(), || {
+            vfs::mount(dungeon.to_path_buf(), String::from("host:crypt"), remote);
+            let goblin = bestiary::find("goblin").unwrap().entity();
+            game::spawn(goblin, dungeon.join("hall/goblin")).unwrap();
+            assert_eq!(game::entities_in(dungeon.join("hall")).len(), 1);
+            assert!(vfs::unmount(dungeon));
+        });
+        let files = server.join().unwrap();
+        let goblin = files[Path::new("crypt/hall/goblin")].as_ref().unwrap();
+        assert!(goblin.starts_with(game::FILE_SIGNATURE));
+    }
+}
@@ -0,0 +1,224 @@
+//! Spells, cast with mana.
+//!
+//! Spells are learned by reading scrolls ([`Component::SpellScroll`]) found
+//! lying around, except for mages, who know how to make light from the start.
+//! Mana trickles back as the player runs commands.
+
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use super::locale::t;
+use super::{
+    Component, Entity,
+    dice::{Dice, DiceExpr},
+    entities_in, get_entity,
+    player::{MAX_HP, PlayerState},
+    spawn,
+};
+
+/// The most mana a player can hold.
+pub const MAX_MANA: i32 = 10;
+
+/// How many turns it takes to get a point of mana back.
+const MANA_REGEN_TURNS: u64 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Spell {
+    /// Hurls fire at a target.
+    Firebolt,
+    /// Restores some of the caster's HP.
+    Heal,
+    /// Drives the darkness out of the current directory for good.
+    Light,
+}
+
+impl Spell {
+    const ALL: [Spell; 3] = [Spell::Firebolt, Spell::Heal, Spell::Light];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Spell::Firebolt => "firebolt",
+            Spell::Heal => "heal",
+            Spell::Light => "light",
+        }
+    }
+
+    fn cost(self) -> i32 {
+        match self {
+            Spell::Firebolt => 4,
+            Spell::Heal => 3,
+            Spell::Light => 1,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Spell> {
+        Spell::ALL
+            .into_iter()
+            .find(|spell| spell.name().eq_ignore_ascii_case(name))
+    }
+}
+
+impl Entity {
+    /// Returns the spell this entity teaches if it is a scroll.
+    pub fn scroll(&self) -> Option<Spell> {
+        self.components.iter().find_map(|c| match c {
+            Component::SpellScroll(spell) => Some(*spell),
+            _ => None,
+        })
+    }
+}
+
+/// Gives back a point of mana every few turns.
+pub fn regenerate(player: &mut PlayerState) {
+    if player.turns.is_multiple_of(MANA_REGEN_TURNS) {
+        player.mana = (player.mana + 1).min(MAX_MANA);
+    }
+}
+
+/// Whether the player knows the spell called `name`.
+pub fn knows(name: &str) -> bool {
+    Spell::from_name(name).is_some_and(|spell| PlayerState::load().spells.contains(&spell))
+}
+
+/// Runs the `cast` verb: `cast <spell> [on <target>]`.
+pub fn cast(name: &str, target: Option<&str>) {
+    let mut player = PlayerState::load();
+    let Some(spell) = Spell::from_name(name) else {
+        println!("you wave your hands around, but {name} isn't a spell.");
+        return;
+    };
+    if !player.spells.contains(&spell) {
+        println!("you don't know how to cast {name}.");
+        return;
+    }
+    if player.mana < spell.cost() {
+        println!(
+            "you don't have enough mana. {} needs {}, you have {}.",
+            spell.name(),
+            spell.cost(),
+            player.mana
+        );
+        return;
+    }
+
+    let cast = match (spell, target) {
+        (Spell::Firebolt, Some(target)) => firebolt(&mut player, target),
+        (Spell::Firebolt, None) => {
+            println!("cast firebolt on what?");
+            false
+        }
+        (Spell::Heal, _) => {
+            let healed = Dice::new().roll(DiceExpr {
+                count: 2,
+                sides: 4,
+                modifier: 2,
+            });
+            player.hp = (player.hp + healed).min(MAX_HP);
+            println!("warmth spreads through you. you have {} hp.", player.hp);
+            true
+        }
+        (Spell::Light, _) => {
+            light();
+            true
+        }
+    };
+
+    if cast {
+        player.mana -= spell.cost();
+    }
+    if let Err(error) = player.save() {
+        println!("{}", t!("error.save", error = error));
+    }
+}
+
+/// Burns the entity at `target`, returning whether the spell went off.
+fn firebolt(player: &mut PlayerState, target: &str) -> bool {
+    let (Ok(path), Ok(e)) = (std::path::absolute(target), get_entity(target)) else {
+        println!("there's no {target} here.");
+        return false;
+    };
+    let Some(hp) = e.hp() else {
+        println!("{target} isn't worth setting on fire.");
+        return false;
+    };
+
+    let damage = Dice::new().roll(DiceExpr {
+        count: 2,
+        sides: 6,
+        modifier: 0,
+    }) as i16;
+    println!("a bolt of fire slams into {target} for {damage} damage!");
+
+    if hp - damage > 0 {
+        println!(
+            "{target} is still standing, smouldering, with {} hp.",
+            hp - damage
+        );
+        if let Err(error) = spawn(e.with_hp(hp - damage), &path) {
+            println!("{}", t!("error.spawn", name = target, error = error));
+        }
+        return true;
+    }
+
+    println!("{target} is reduced to a pile of ash.");
+    if let Err(error) = fs::remove_file(&path) {
+        println!("could not remove {target}: {error}");
+        return true;
+    }
+    super::defeat(player, &e, &path);
+    true
+}
+
+/// Banishes the darkness from the current directory.
+fn light() {
+    let mut lit = false;
+    for (path, mut e) in entities_in(".") {
+        if !e.is_dark() {
+            continue;
+        }
+
+        e.components.retain(|c| !matches!(c, Component::Dark));
+        if e.components.is_empty() {
+            fs::remove_file(&path).ok();
+        } else if let Err(error) = spawn(e, &path) {
+            println!(
+                "{}",
+                t!("error.spawn", name = path.display(), error = error)
+            );
+        }
+        lit = true;
+    }
+
+    if !lit {
+        println!("a ball of light flickers above your hand. it's already bright enough here.");
+    } else {
+        println!("a ball of light bursts from your hand, chasing the darkness away.");
+    }
+}
+
+/// Runs the `read` verb on a scroll, learning its spell and using it up.
+pub fn read(target: &str) {
+    let Some(spell) = get_entity(target).ok().and_then(|e| e.scroll()) else {
+        println!("there's nothing to learn from {target}.");
+        return;
+    };
+
+    let mut player = PlayerState::load();
+    if player.spells.contains(&spell) {
+        println!("you already know {}.", spell.name());
+        return;
+    }
+
+    println!(
+        "the words on {target} glow, then fade away. you've learned {}!",
+        spell.name()
+    );
+    player.spells.push(spell);
+    if let Err(error) = fs::remove_file(target) {
+        println!("could not remove {target}: {error}");
+    }
+    if let Err(error) = player.save() {
+        println!("{}", t!("error.save", error = error));
+    }
+}
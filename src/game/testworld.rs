@@ -0,0 +1,80 @@
+//! Small worlds for tests, kept in memory.
+//!
+//! ```ignore
+//! let world = world! {
+//!     "dungeon" => { "goblin": enemy(5), "chest": item("key") },
+//! };
+//! world.run(|| attack(world.path("dungeon/goblin")));
+//! ```
+//!
+//! Everything the game reads and writes while in [`World::run`], the player's
+//! save included, stays in the world's [`MemoryFs`], so tests don't touch the
+//! disk or each other.
+
+use std::path::{Path, PathBuf};
+
+use super::{Component, Entity, get_entity, inventory::Item, spawn, trap::TrapKind, vfs};
+use vfs::MemoryFs;
+
+/// Where worlds are rooted. It's never created on disk.
+const ROOT: &str = "/world";
+
+/// Builds a [`World`] from directories and the entities in them.
+macro_rules! world {
+    ($($dir:literal => { $($name:literal : $entity:expr),* $(,)? }),* $(,)?) => {{
+        let world = $crate::game::testworld::World::default();
+        $($(world.add($dir, $name, $entity);)*)*
+        world
+    }};
+}
+pub(crate) use world;
+
+#[derive(Default)]
+pub struct World {
+    fs: MemoryFs,
+}
+
+impl World {
+    /// Spawns `entity` as `name` in `dir`, both relative to the world's root.
+    pub fn add(&self, dir: &str, name: &str, entity: Entity) {
+        let path = self.path(dir).join(name);
+        self.run(|| spawn(entity, path)).unwrap();
+    }
+
+    /// The absolute path of `path`, relative to the world's root.
+    pub fn path(&self, path: impl AsRef<Path>) -> PathBuf {
+        Path::new(ROOT).join(path)
+    }
+
+    /// Runs `f` with the game's files in this world.
+    pub fn run<T>(&self, f: impl FnOnce() -> T) -> T {
+        vfs::using(self.fs.clone(), f)
+    }
+
+    /// The entity at `path`, relative to the world's root, if there is one.
+    pub fn entity(&self, path: impl AsRef<Path>) -> Option<Entity> {
+        let path = self.path(path);
+        self.run(|| get_entity(path).ok())
+    }
+}
+
+/// A plain monster with `hp`.
+pub fn enemy(hp: i16) -> Entity {
+    Entity {
+        components: vec![Component::Enemy, Component::TakesDamage(hp)],
+    }
+}
+
+/// `name` lying on the ground.
+pub fn item(name: &str) -> Entity {
+    Entity {
+        components: vec![Component::Item(Item::new(name))],
+    }
+}
+
+/// A hidden trap that deals `damage`.
+pub fn trap(damage: i16) -> Entity {
+    Entity {
+        components: vec![Component::Trap(TrapKind::Damage(damage))],
+    }
+}
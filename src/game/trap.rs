@@ -0,0 +1,128 @@
+//! Traps hide among ordinary files and go off when the player lists or enters
+//! the directory they are in. A careful player can find them first, either by
+//! passing a perception check or on purpose with `search`, which rolls with
+//! advantage.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::dice;
+use super::locale::t;
+use super::{
+    Component, Entity,
+    dice::{Advantage, Dice, Skill},
+    entities_in,
+    player::PlayerState,
+    spawn,
+};
+
+/// The perception DC to notice a trap.
+const TRAP_DC: i32 = 15;
+
+#[derive(Clone, Deserialize, Serialize)]
+pub enum TrapKind {
+    /// Hurts the player for this much HP.
+    Damage(i16),
+    /// Makes off with a random item from the player's inventory.
+    Theft,
+}
+
+impl Entity {
+    /// Returns what kind of trap this entity is, if it is a trap at all.
+    pub fn trap(&self) -> Option<&TrapKind> {
+        self.components.iter().find_map(|c| match c {
+            Component::Trap(kind) => Some(kind),
+            _ => None,
+        })
+    }
+
+    pub fn is_revealed(&self) -> bool {
+        self.components
+            .iter()
+            .any(|c| matches!(c, Component::Revealed))
+    }
+}
+
+/// Marks the trap at `path` as revealed so that it is shown by ls and no
+/// longer goes off.
+fn reveal(mut e: Entity, path: &Path) {
+    e.components.push(Component::Revealed);
+    if let Err(error) = spawn(e, path) {
+        println!(
+            "{}",
+            t!("error.spawn", name = path.display(), error = error)
+        );
+    }
+}
+
+/// Sets off every hidden trap in `dir`, unless the player happens to notice it
+/// in time. Traps are used up once they go off.
+pub fn spring(player: &mut PlayerState, dir: &Path) {
+    let mut dice = Dice::new();
+    for (path, e) in entities_in(dir) {
+        let Some(kind) = e.trap().cloned() else {
+            continue;
+        };
+        if e.is_revealed() {
+            continue;
+        }
+
+        let name = path.file_name().unwrap().display();
+        if dice.check(player, Skill::Perception, TRAP_DC, Advantage::Normal) {
+            println!("you spot a trap! {name} is not what it seems.");
+            reveal(e, &path);
+            continue;
+        }
+
+        match kind {
+            TrapKind::Damage(damage) => {
+                println!("{name} was a trap! it hits you for {damage} damage.");
+                player.hurt(damage.into());
+            }
+            TrapKind::Theft => {
+                // equipped items are too well attached to be pinched
+                let loose: Vec<usize> = (0..player.inventory.len())
+                    .filter(|&i| !player.inventory[i].equipped)
+                    .collect();
+                if loose.is_empty() {
+                    println!("{name} was a trap! something rummages through your empty pockets.");
+                } else {
+                    let i = loose[dice::random_range(0..loose.len())];
+                    let item = player.inventory.remove(i);
+                    println!("{name} was a trap! your {} goes missing.", item.name);
+                }
+            }
+        }
+
+        if let Err(error) = super::despawn(&path) {
+            println!("could not clean up the trap: {error}");
+        }
+    }
+}
+
+/// Runs the `search` builtin, carefully looking for hidden traps in the
+/// current directory. This is much more reliable than stumbling upon them.
+pub fn search() {
+    let player = PlayerState::load();
+    let Ok(cwd) = std::env::current_dir() else {
+        return;
+    };
+
+    let mut dice = Dice::new();
+    let mut found = 0;
+    for (path, e) in entities_in(&cwd) {
+        if e.trap().is_none() || e.is_revealed() {
+            continue;
+        }
+        if dice.check(&player, Skill::Perception, TRAP_DC, Advantage::Advantage) {
+            println!("you found a trap: {}", path.file_name().unwrap().display());
+            reveal(e, &path);
+            found += 1;
+        }
+    }
+
+    if found == 0 {
+        println!("you search around but find nothing out of the ordinary.");
+    }
+}
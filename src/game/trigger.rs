@@ -0,0 +1,297 @@
+//! Entities that do something to other entities when something happens to them.
+//!
+//! A [`Trigger`] sits on one entity and points at another. When the first
+//! entity is killed or unlocked, the trigger fires and changes the state of the
+//! second: killing the jailer unlocks the cell door, unlocking the vault opens
+//! the chest inside. Since firing a trigger is itself a change of state,
+//! triggers can set each other off in a chain.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::locale::t;
+use super::{
+    Component, Entity, despawn, get_entity,
+    id::{self, Uuid},
+    inventory::Item,
+    spawn,
+};
+
+/// How many triggers can set each other off before we assume they're going in
+/// circles.
+const MAX_CHAIN: usize = 16;
+
+/// Points at another entity.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Reference {
+    /// A path, relative to the directory of the entity holding the reference.
+    Path(PathBuf),
+    /// An entity's id, which keeps pointing at it even after it's moved.
+    Uuid(Uuid),
+}
+
+impl Reference {
+    /// Finds where the referenced entity is, from the directory of the entity
+    /// holding the reference.
+    fn resolve(&self, dir: &Path) -> Option<PathBuf> {
+        match self {
+            Reference::Path(path) => Some(dir.join(path)),
+            Reference::Uuid(id) => id::find(*id),
+        }
+    }
+}
+
+/// Something that can happen to an entity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum When {
+    Killed,
+    Unlocked,
+}
+
+/// What a trigger does to its target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Effect {
+    /// Takes the lock off the target.
+    Unlock,
+    /// Puts a lock on the target.
+    Lock,
+    /// Spills the target's contents onto the floor and gets rid of it.
+    Open,
+}
+
+impl Effect {
+    fn from_name(name: &str) -> Option<Effect> {
+        match name {
+            "unlock" => Some(Effect::Unlock),
+            "lock" => Some(Effect::Lock),
+            "open" => Some(Effect::Open),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Trigger {
+    pub when: When,
+    pub effect: Effect,
+    pub target: Reference,
+}
+
+impl Entity {
+    pub fn triggers(&self) -> impl Iterator<Item = &Trigger> {
+        self.components.iter().filter_map(|c| match c {
+            Component::Trigger(trigger) => Some(trigger),
+            _ => None,
+        })
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.components
+            .iter()
+            .any(|c| matches!(c, Component::Locked))
+    }
+
+    pub fn is_door(&self) -> bool {
+        self.components.iter().any(|c| matches!(c, Component::Door))
+    }
+}
+
+/// Fires the triggers on `e`, which lives in `dir`, for `when`. The entity may
+/// already be gone from disk, like when it has just been killed.
+pub fn fire(e: &Entity, dir: &Path, when: When) {
+    let mut pending: Vec<(PathBuf, Trigger)> = e
+        .triggers()
+        .filter(|trigger| trigger.when == when)
+        .filter_map(|trigger| Some((trigger.target.resolve(dir)?, trigger.clone())))
+        .collect();
+
+    let mut fired = 0;
+    while let Some((path, trigger)) = pending.pop() {
+        fired += 1;
+        if fired > MAX_CHAIN {
+            println!("you hear a grinding of gears, then nothing.");
+            return;
+        }
+
+        let Ok(mut target) = get_entity(&path) else {
+            continue;
+        };
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let target_dir = path.parent().unwrap_or(Path::new("."));
+
+        match trigger.effect {
+            Effect::Unlock => {
+                if !target.is_locked() {
+                    continue;
+                }
+                target
+                    .components
+                    .retain(|c| !matches!(c, Component::Locked));
+                println!("somewhere, {name} clicks open.");
+                pending.extend(
+                    target
+                        .triggers()
+                        .filter(|trigger| trigger.when == When::Unlocked)
+                        .filter_map(|trigger| {
+                            Some((trigger.target.resolve(target_dir)?, trigger.clone()))
+                        }),
+                );
+                if let Err(error) = spawn(target, &path) {
+                    println!("{}", t!("error.spawn", name = name, error = error));
+                }
+            }
+            Effect::Lock => {
+                if target.is_locked() {
+                    continue;
+                }
+                target.components.push(Component::Locked);
+                println!("somewhere, {name} slams shut.");
+                if let Err(error) = spawn(target, &path) {
+                    println!("{}", t!("error.spawn", name = name, error = error));
+                }
+            }
+            Effect::Open => {
+                if despawn(&path).is_err() {
+                    continue;
+                }
+                println!("{name} creaks open, spilling its contents.");
+                for c in &target.components {
+                    let Component::HasInventory(items) = c else {
+                        continue;
+                    };
+                    for item in items {
+                        let item_path = target_dir.join(item);
+                        if item_path.exists() {
+                            continue;
+                        }
+                        let found = Entity {
+                            components: vec![Component::Item(Item::found(item))],
+                        };
+                        if let Err(error) = spawn(found, item_path) {
+                            println!("{}", t!("error.spawn", name = item, error = error));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns the name of a locked door in `dir`, if there is one.
+pub fn locked_door(dir: &Path) -> Option<String> {
+    super::entities_in(dir).into_iter().find_map(|(path, e)| {
+        (e.is_door() && e.is_locked())
+            .then(|| path.file_name().unwrap().to_string_lossy().into_owned())
+    })
+}
+
+/// Runs `game door <name>`, putting a locked door in the current directory
+/// which keeps the player out until something unlocks it.
+pub fn door(args: &[String]) {
+    let [name] = args else {
+        println!("game door: expected a name for the door");
+        return;
+    };
+    let door = Entity {
+        components: vec![Component::Door, Component::Locked],
+    };
+    if let Err(error) = spawn(door, name) {
+        println!("{}", t!("error.spawn", name = name, error = error));
+        return;
+    }
+    println!("a heavy door appears as {name}. it's locked.");
+}
+
+/// Runs `game link <source> [killed|unlocked] <effect> <target>`, making
+/// something happen to `target` when `source` is killed (the default) or
+/// unlocked. The target's path is taken relative to the current directory.
+pub fn link(args: &[String]) {
+    let (source, when, effect, target) = match args {
+        [source, effect, target] => (source, When::Killed, effect, target),
+        [source, when, effect, target] => {
+            let when = match when.as_str() {
+                "killed" => When::Killed,
+                "unlocked" => When::Unlocked,
+                _ => {
+                    println!(
+                        "game link: {when} isn't something that can happen. try killed or unlocked"
+                    );
+                    return;
+                }
+            };
+            (source, when, effect, target)
+        }
+        _ => {
+            println!("game link: expected <source> [killed|unlocked] <effect> <target>");
+            return;
+        }
+    };
+    let Some(effect) = Effect::from_name(effect) else {
+        println!("game link: {effect} isn't an effect. try unlock, lock or open");
+        return;
+    };
+    let Ok(mut e) = get_entity(source) else {
+        println!("game link: {source} isn't an entity");
+        return;
+    };
+    let Ok(target_entity) = get_entity(target) else {
+        println!("game link: {target} isn't an entity");
+        return;
+    };
+
+    let reference = match target_entity.id() {
+        Some(id) => Reference::Uuid(id),
+        // entities from before ids were handed out can only be found by path,
+        // relative to the source's directory rather than ours
+        None => {
+            let source_dir = std::path::absolute(source)
+                .ok()
+                .and_then(|path| path.parent().map(Path::to_path_buf))
+                .unwrap_or_default();
+            let Ok(target_path) = std::path::absolute(target) else {
+                println!("game link: could not find {target}");
+                return;
+            };
+            let relative = target_path
+                .strip_prefix(&source_dir)
+                .map(Path::to_path_buf)
+                .unwrap_or(target_path);
+            Reference::Path(relative)
+        }
+    };
+
+    e.components.push(Component::Trigger(Trigger {
+        when,
+        effect,
+        target: reference,
+    }));
+    if let Err(error) = spawn(e, source) {
+        println!("{}", t!("error.spawn", name = source, error = error));
+        return;
+    }
+    println!("{source} is now linked to {target}.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn references_are_relative_to_their_entity() {
+        let reference = Reference::Path(PathBuf::from("vault/chest"));
+        assert_eq!(
+            reference.resolve(Path::new("/dungeon")),
+            Some(PathBuf::from("/dungeon/vault/chest"))
+        );
+
+        let reference = Reference::Path(PathBuf::from("/elsewhere/door"));
+        assert_eq!(
+            reference.resolve(Path::new("/dungeon")),
+            Some(PathBuf::from("/elsewhere/door"))
+        );
+    }
+}
@@ -0,0 +1,484 @@
+//! Checking content for mistakes before players find them.
+//!
+//! `game validate <dir>` looks through every entity under a directory and
+//! reports anything that's broken (files that don't decode, triggers pointing
+//! at nothing, rooms full of monsters that don't exist) or that can't be done
+//! (rooms behind doors that nothing unlocks, quests for monsters that aren't
+//! there). Quest files are checked along with the entities.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    Component, Entity, FILE_SIGNATURE, bestiary, get_entity,
+    id::Uuid,
+    quest::{self, Condition, Quest},
+    trigger::{Effect, Reference, When},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Something that's definitely broken.
+    Error,
+    /// Something that's probably a mistake.
+    Warning,
+}
+
+/// Something wrong with a piece of content.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Problem {
+    pub path: PathBuf,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{severity}: {}: {}", self.path.display(), self.message)
+    }
+}
+
+/// Everything found under the directory being validated.
+#[derive(Default)]
+struct Tree {
+    dirs: Vec<PathBuf>,
+    entities: Vec<(PathBuf, Entity)>,
+    quests: Vec<(PathBuf, Quest)>,
+    problems: Vec<Problem>,
+}
+
+impl Tree {
+    fn problem(&mut self, path: &Path, severity: Severity, message: impl Into<String>) {
+        self.problems.push(Problem {
+            path: path.to_path_buf(),
+            severity,
+            message: message.into(),
+        });
+    }
+
+    /// Walks everything under `root`, breadth first so that parents come
+    /// before their children. Symlinks aren't followed.
+    fn scan(root: &Path) -> Tree {
+        let mut tree = Tree::default();
+        let mut i = 0;
+        tree.dirs.push(root.to_path_buf());
+        while let Some(dir) = tree.dirs.get(i).cloned() {
+            i += 1;
+            let Ok(entries) = fs::read_dir(&dir) else {
+                tree.problem(&dir, Severity::Error, "could not be read");
+                continue;
+            };
+            let mut entries: Vec<_> = entries.filter_map(|entry| entry.ok()).collect();
+            entries.sort_by_key(|entry| entry.file_name());
+            for entry in entries {
+                let path = entry.path();
+                match entry.file_type() {
+                    Ok(file_type) if file_type.is_dir() => tree.dirs.push(path),
+                    Ok(file_type) if file_type.is_file() => tree.load(path),
+                    _ => {}
+                }
+            }
+        }
+        tree
+    }
+
+    /// Loads the entity or quests at `path`, if it's meant to be either.
+    fn load(&mut self, path: PathBuf) {
+        if path.extension().is_some_and(|ext| ext == quest::EXTENSION) {
+            let parsed = fs::read_to_string(&path)
+                .map_err(|error| error.to_string())
+                .and_then(|text| quest::parse(&text));
+            match parsed {
+                Ok(quests) => self
+                    .quests
+                    .extend(quests.into_iter().map(|q| (path.clone(), q))),
+                Err(error) => self.problem(&path, Severity::Error, error),
+            }
+            return;
+        }
+
+        let mut signature = [0; FILE_SIGNATURE.len()];
+        let is_entity = File::open(&path)
+            .and_then(|mut f| f.read_exact(&mut signature))
+            .is_ok_and(|()| signature == *FILE_SIGNATURE);
+        if !is_entity {
+            return;
+        }
+        match get_entity(&path) {
+            Ok(e) => self.entities.push((path, e)),
+            Err(_) => self.problem(&path, Severity::Error, "is an entity that doesn't decode"),
+        }
+    }
+}
+
+/// Whether the player could ever kill `e`.
+fn is_killable(e: &Entity) -> bool {
+    e.hp().is_some()
+}
+
+/// Checks one entity on its own.
+fn check_entity(tree: &mut Tree, path: &Path, e: &Entity) {
+    if e.components.iter().any(|c| matches!(c, Component::Enemy)) && !is_killable(e) {
+        tree.problem(path, Severity::Warning, "is an enemy with no hp");
+    }
+    for c in &e.components {
+        match c {
+            Component::Room(room) => {
+                for (file, template) in &room.roster {
+                    if bestiary::find(template).is_none() {
+                        tree.problem(
+                            path,
+                            Severity::Error,
+                            format!(
+                                "has {file} in its room, but there's no such monster as {template}"
+                            ),
+                        );
+                    }
+                }
+            }
+            Component::Merchant { stock, .. } if stock.is_empty() => {
+                tree.problem(
+                    path,
+                    Severity::Warning,
+                    "is a merchant with nothing to sell",
+                );
+            }
+            Component::Schedule(schedule) if !schedule.day.is_dir() || !schedule.night.is_dir() => {
+                tree.problem(
+                    path,
+                    Severity::Warning,
+                    "keeps a schedule in a directory that isn't there",
+                );
+            }
+            Component::Trigger(trigger) if trigger.when == When::Killed && !is_killable(e) => {
+                tree.problem(
+                    path,
+                    Severity::Warning,
+                    "has a trigger for when it's killed, but it can't be killed",
+                );
+            }
+            Component::Trigger(trigger) if trigger.when == When::Unlocked && !e.is_locked() => {
+                tree.problem(
+                    path,
+                    Severity::Warning,
+                    "has a trigger for when it's unlocked, but it isn't locked",
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Validates everything under `root`, returning the problems found in order
+/// of where they are. `root` should be canonical, see [`fs::canonicalize`].
+pub fn validate(root: &Path) -> Vec<Problem> {
+    let mut tree = Tree::scan(root);
+    let entities = std::mem::take(&mut tree.entities);
+
+    let mut ids: HashMap<Uuid, &Path> = HashMap::new();
+    for (path, e) in &entities {
+        check_entity(&mut tree, path, e);
+        if let Some(id) = e.id()
+            && let Some(other) = ids.insert(id, path)
+        {
+            tree.problem(
+                path,
+                Severity::Error,
+                format!("has the same id as {}, was it copied?", other.display()),
+            );
+        }
+    }
+
+    // where each trigger ends up, as (source, when, effect, target)
+    let mut links = Vec::new();
+    for (path, e) in &entities {
+        let dir = path.parent().unwrap_or(root);
+        for trigger in e.triggers() {
+            let target = match &trigger.target {
+                // canonicalizing takes care of any `..`s, so the path can be
+                // compared against the ones we scanned
+                Reference::Path(relative) => fs::canonicalize(dir.join(relative))
+                    .ok()
+                    .filter(|target| entities.iter().any(|(path, _)| path == target)),
+                Reference::Uuid(id) => ids.get(id).map(|path| path.to_path_buf()),
+            };
+            let Some(target) = target else {
+                let target = match &trigger.target {
+                    Reference::Path(relative) => relative.display().to_string(),
+                    Reference::Uuid(id) => id.to_string(),
+                };
+                tree.problem(
+                    path,
+                    Severity::Error,
+                    format!("has a trigger pointing at {target}, which isn't an entity here"),
+                );
+                continue;
+            };
+
+            let target_entity = entities.iter().find(|(p, _)| *p == target).map(|(_, e)| e);
+            if trigger.effect == Effect::Unlock && target_entity.is_some_and(|e| !e.is_locked()) {
+                tree.problem(
+                    path,
+                    Severity::Warning,
+                    format!(
+                        "has a trigger to unlock {}, which isn't locked",
+                        target.display()
+                    ),
+                );
+            }
+            links.push((path.clone(), trigger.when, trigger.effect, target));
+        }
+    }
+
+    check_reachable(&mut tree, &entities, &links);
+    check_quests(&mut tree, &entities);
+
+    let mut problems = tree.problems;
+    problems.sort();
+    problems
+}
+
+/// Works out which directories the player could ever get into, given which
+/// locked doors can be opened, and complains about the rest.
+fn check_reachable(
+    tree: &mut Tree,
+    entities: &[(PathBuf, Entity)],
+    links: &[(PathBuf, When, Effect, PathBuf)],
+) {
+    let locked_doors: Vec<&Path> = entities
+        .iter()
+        .filter(|(_, e)| e.is_door() && e.is_locked())
+        .map(|(path, _)| path.as_path())
+        .collect();
+    let killable: HashSet<&Path> = entities
+        .iter()
+        .filter(|(_, e)| is_killable(e))
+        .map(|(path, _)| path.as_path())
+        .collect();
+
+    let mut reachable: HashSet<&Path> = HashSet::new();
+    let mut unlocked: HashSet<&Path> = HashSet::new();
+    // keep going until nothing new opens up
+    loop {
+        let before = (reachable.len(), unlocked.len());
+
+        for (i, dir) in tree.dirs.iter().enumerate() {
+            // the directory being validated is where the player starts
+            let parent_reachable = i == 0 || dir.parent().is_some_and(|p| reachable.contains(p));
+            let doors_open = locked_doors
+                .iter()
+                .filter(|door| door.parent() == Some(dir))
+                .all(|door| unlocked.contains(door));
+            if parent_reachable && (i == 0 || doors_open) {
+                reachable.insert(dir);
+            }
+        }
+
+        for (source, when, effect, target) in links {
+            let source_dir = source.parent().unwrap_or(Path::new(""));
+            let can_fire = reachable.contains(source_dir)
+                && match when {
+                    When::Killed => killable.contains(source.as_path()),
+                    When::Unlocked => unlocked.contains(source.as_path()),
+                };
+            if can_fire && *effect == Effect::Unlock {
+                unlocked.insert(target);
+            }
+        }
+
+        if (reachable.len(), unlocked.len()) == before {
+            break;
+        }
+    }
+
+    // only the outermost unreachable directories are worth mentioning
+    let unreachable: Vec<PathBuf> = tree
+        .dirs
+        .iter()
+        .filter(|dir| {
+            !reachable.contains(dir.as_path())
+                && dir.parent().is_some_and(|p| reachable.contains(p))
+        })
+        .cloned()
+        .collect();
+    for dir in unreachable {
+        tree.problem(
+            &dir,
+            Severity::Error,
+            "can't be reached, nothing unlocks the door in the way",
+        );
+    }
+}
+
+/// Looks for quests that can't be finished.
+fn check_quests(tree: &mut Tree, entities: &[(PathBuf, Entity)]) {
+    let quests = std::mem::take(&mut tree.quests);
+    for (path, q) in &quests {
+        for condition in q.start.iter().chain(&q.goals) {
+            match condition {
+                Condition::Kill { monster, .. } => {
+                    let exists = bestiary::find(monster).is_some()
+                        || entities
+                            .iter()
+                            .any(|(path, _)| quest::monster_name(path) == *monster);
+                    if !exists {
+                        tree.problem(
+                            path,
+                            Severity::Warning,
+                            format!("{} needs {monster} killed, but there are none", q.name),
+                        );
+                    }
+                }
+                Condition::Quest(name) if *name == q.name => tree.problem(
+                    path,
+                    Severity::Error,
+                    format!("{} needs itself finished first", q.name),
+                ),
+                Condition::Quest(name) if !quests.iter().any(|(_, q)| q.name == *name) => tree
+                    .problem(
+                        path,
+                        Severity::Error,
+                        format!(
+                            "{} needs {name} finished, but there's no such quest",
+                            q.name
+                        ),
+                    ),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Runs `game validate [dir]`, checking the current directory if none is
+/// given.
+pub fn command(args: &[String]) {
+    let root = match args {
+        [] => PathBuf::from("."),
+        [dir] => PathBuf::from(dir),
+        _ => {
+            println!("game validate: expected a directory");
+            return;
+        }
+    };
+    let root = match fs::canonicalize(&root) {
+        Ok(root) if root.is_dir() => root,
+        _ => {
+            println!("game validate: {} isn't a directory", root.display());
+            return;
+        }
+    };
+
+    let problems = validate(&root);
+    for problem in &problems {
+        println!("{problem}");
+    }
+    let errors = problems
+        .iter()
+        .filter(|problem| problem.severity == Severity::Error)
+        .count();
+    let warnings = problems.len() - errors;
+    match (errors, warnings) {
+        (0, 0) => println!("all good. ship it."),
+        (errors, warnings) => println!("{errors} error(s), {warnings} warning(s)."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{respawn::Room, trigger::Trigger};
+
+    fn write(path: &Path, components: Vec<Component>) {
+        let mut contents = FILE_SIGNATURE.to_vec();
+        contents.extend(Vec::from(Entity { components }));
+        fs::write(path, contents).unwrap();
+    }
+
+    fn messages(root: &Path) -> Vec<String> {
+        validate(root)
+            .iter()
+            .map(|problem| {
+                let path = problem.path.strip_prefix(root).unwrap();
+                format!("{}: {}", path.display(), problem.message)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn content_is_validated() {
+        let root = std::env::temp_dir().join(format!("rlsh-validate-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("vault/inner")).unwrap();
+        let root = fs::canonicalize(root).unwrap();
+
+        write(
+            &root.join("vault/door"),
+            vec![Component::Door, Component::Locked],
+        );
+        write(
+            &root.join("room"),
+            vec![Component::Room(Room {
+                roster: vec![(String::from("dragon"), String::from("dragon"))],
+                delay: 0,
+                clears: 0,
+                cleared_at: None,
+            })],
+        );
+        fs::write(
+            root.join("broken"),
+            [&FILE_SIGNATURE[..], b"nonsense"].concat(),
+        )
+        .unwrap();
+        fs::write(root.join("notes.txt"), "not an entity").unwrap();
+        fs::write(
+            root.join("vault/inner/main.quest"),
+            "quest q\ngoal kill goblin\ngoal kill dragon\nstart quest nope",
+        )
+        .unwrap();
+        assert_eq!(
+            messages(&root),
+            [
+                "broken: is an entity that doesn't decode",
+                "room: has dragon in its room, but there's no such monster as dragon",
+                "vault: can't be reached, nothing unlocks the door in the way",
+                "vault/inner/main.quest: q needs nope finished, but there's no such quest",
+                "vault/inner/main.quest: q needs dragon killed, but there are none",
+            ]
+        );
+
+        let unlock = |target: &str| {
+            Component::Trigger(Trigger {
+                when: When::Killed,
+                effect: Effect::Unlock,
+                target: Reference::Path(PathBuf::from(target)),
+            })
+        };
+        fs::remove_file(root.join("room")).unwrap();
+        fs::remove_file(root.join("broken")).unwrap();
+        write(
+            &root.join("guard"),
+            vec![Component::TakesDamage(5), unlock("vault/inner/../door")],
+        );
+        write(&root.join("statue"), vec![unlock("nowhere")]);
+        fs::write(root.join("vault/inner/main.quest"), "quest q\n\nnonsense").unwrap();
+        assert_eq!(
+            messages(&root),
+            [
+                "statue: has a trigger pointing at nowhere, which isn't an entity here",
+                "statue: has a trigger for when it's killed, but it can't be killed",
+                "vault/inner/main.quest: line 3: nonsense isn't an instruction",
+            ]
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,352 @@
+//! Where the game keeps its files.
+//!
+//! Entities and the id index are read and written through a [`GameFs`]
+//! rather than straight off the disk. Normally that's [`RealFs`], the real
+//! filesystem, but tests can swap in a [`MemoryFs`] with [`using`] so they
+//! don't leave goblins lying around, and other backends (an overlay, or a
+//! dungeon on a server) only need to implement the trait.
+//!
+//! A backend can also be [`mount`]ed on a directory, like the
+//! [`SftpFs`](super::sftp::SftpFs) a remote dungeon is kept in. Files under
+//! that directory are then kept in it instead, until it's [`unmount`]ed.
+
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fs,
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+    rc::Rc,
+};
+
+/// A place to keep the game's files.
+pub trait GameFs {
+    /// The contents of the file at `path`.
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Whether the file at `path` starts with `prefix`, like an entity's
+    /// signature. Backends can override this to avoid reading whole files.
+    fn starts_with(&self, path: &Path, prefix: &[u8]) -> io::Result<bool> {
+        Ok(self.read(path)?.starts_with(prefix))
+    }
+
+    /// Replaces the contents of the file at `path`, creating it if needed.
+    fn write(&mut self, path: &Path, contents: &[u8]) -> io::Result<()>;
+
+    fn remove(&mut self, path: &Path) -> io::Result<()>;
+
+    /// The paths of everything directly inside `dir`.
+    fn list(&self, dir: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Makes sure `dir` and everything above it exists.
+    fn create_dir_all(&mut self, dir: &Path) -> io::Result<()>;
+}
+
+/// The real filesystem.
+pub struct RealFs;
+
+impl GameFs for RealFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn starts_with(&self, path: &Path, prefix: &[u8]) -> io::Result<bool> {
+        // reading from a pipe, like a `<(command)`, would take what's in it
+        // away from whoever it's for, and opening a named pipe waits for
+        // someone to write into it
+        if !fs::metadata(path)?.is_file() {
+            return Ok(false);
+        }
+        let mut file = fs::File::open(path)?;
+        let mut start = vec![0; prefix.len()];
+        match file.read_exact(&mut start) {
+            Ok(()) => Ok(start == prefix),
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn write(&mut self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        fs::write(path, contents)
+    }
+
+    fn remove(&mut self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn list(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect()
+    }
+
+    fn create_dir_all(&mut self, dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(dir)
+    }
+}
+
+/// Files kept in memory, for tests. Clones share the same files, so a test
+/// can keep one to look at what the game did with the other.
+#[derive(Clone, Default)]
+pub struct MemoryFs {
+    files: Rc<RefCell<BTreeMap<PathBuf, Vec<u8>>>>,
+}
+
+impl MemoryFs {
+    /// The paths of every file, in order.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.files.borrow().keys().cloned().collect()
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} doesn't exist", path.display()),
+    )
+}
+
+impl GameFs for MemoryFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.files
+            .borrow()
+            .get(path)
+            .cloned()
+            .ok_or_else(|| not_found(path))
+    }
+
+    fn write(&mut self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.files
+            .borrow_mut()
+            .insert(path.to_path_buf(), contents.to_vec());
+        Ok(())
+    }
+
+    fn remove(&mut self, path: &Path) -> io::Result<()> {
+        match self.files.borrow_mut().remove(path) {
+            Some(_) => Ok(()),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn list(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        Ok(self
+            .files
+            .borrow()
+            .keys()
+            .filter(|path| path.parent() == Some(dir))
+            .cloned()
+            .collect())
+    }
+
+    fn create_dir_all(&mut self, _dir: &Path) -> io::Result<()> {
+        // directories only exist through the files in them
+        Ok(())
+    }
+}
+
+/// A backend mounted on a directory.
+struct Mount {
+    dir: PathBuf,
+    /// What's mounted, to tell the player.
+    source: String,
+    fs: Box<dyn GameFs>,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Box<dyn GameFs>> = RefCell::new(Box::new(RealFs));
+    static MOUNTS: RefCell<Vec<Mount>> = const { RefCell::new(Vec::new()) };
+}
+
+/// `path` from the root, without any `.` or `..` in it. Nothing is looked up,
+/// so the mounted directories should be canonical already.
+fn absolute(path: &Path) -> PathBuf {
+    let mut absolute = PathBuf::new();
+    for component in std::path::absolute(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .components()
+    {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                absolute.pop();
+            }
+            component => absolute.push(component),
+        }
+    }
+    absolute
+}
+
+/// The filesystem the game is using, with each mounted backend in charge of
+/// the files under its directory.
+struct Routed<'a> {
+    base: &'a mut dyn GameFs,
+    mounts: &'a mut [Mount],
+}
+
+impl Routed<'_> {
+    /// Which mount `path` is under, the innermost if there are a few, and
+    /// where it is in there.
+    fn mount_of(&self, path: &Path) -> Option<(usize, PathBuf)> {
+        let path = absolute(path);
+        self.mounts
+            .iter()
+            .enumerate()
+            .filter_map(|(i, mount)| Some((i, path.strip_prefix(&mount.dir).ok()?)))
+            .min_by_key(|(_, relative)| relative.components().count())
+            .map(|(i, relative)| (i, relative.to_path_buf()))
+    }
+
+    /// Runs `f` on the backend in charge of `path`, with the path it goes by
+    /// there.
+    fn route<T>(&mut self, path: &Path, f: impl FnOnce(&mut dyn GameFs, &Path) -> T) -> T {
+        match self.mount_of(path) {
+            Some((i, relative)) => f(self.mounts[i].fs.as_mut(), &relative),
+            None => f(&mut *self.base, path),
+        }
+    }
+
+    fn route_ref<T>(&self, path: &Path, f: impl FnOnce(&dyn GameFs, &Path) -> T) -> T {
+        match self.mount_of(path) {
+            Some((i, relative)) => f(self.mounts[i].fs.as_ref(), &relative),
+            None => f(&*self.base, path),
+        }
+    }
+}
+
+impl GameFs for Routed<'_> {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.route_ref(path, |fs, path| fs.read(path))
+    }
+
+    fn starts_with(&self, path: &Path, prefix: &[u8]) -> io::Result<bool> {
+        self.route_ref(path, |fs, path| fs.starts_with(path, prefix))
+    }
+
+    fn write(&mut self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.route(path, |fs, path| fs.write(path, contents))
+    }
+
+    fn remove(&mut self, path: &Path) -> io::Result<()> {
+        self.route(path, |fs, path| fs.remove(path))
+    }
+
+    fn list(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        // the paths are given back under `dir` as it was asked for, so they
+        // match what the rest of the shell calls them
+        let paths = self.route_ref(dir, |fs, dir| fs.list(dir))?;
+        Ok(paths
+            .into_iter()
+            .filter_map(|path| Some(dir.join(path.file_name()?)))
+            .collect())
+    }
+
+    fn create_dir_all(&mut self, dir: &Path) -> io::Result<()> {
+        self.route(dir, |fs, dir| fs.create_dir_all(dir))
+    }
+}
+
+/// Runs `f` on the filesystem the game is using.
+pub fn with<T>(f: impl FnOnce(&mut dyn GameFs) -> T) -> T {
+    CURRENT.with_borrow_mut(|fs| {
+        MOUNTS.with_borrow_mut(|mounts| match mounts.is_empty() {
+            true => f(fs.as_mut()),
+            false => f(&mut Routed {
+                base: fs.as_mut(),
+                mounts,
+            }),
+        })
+    })
+}
+
+/// Keeps the game's files under `dir`, which should be canonical, in `fs`
+/// from now on. `source` says what it is, for [`mounts`].
+pub fn mount(dir: PathBuf, source: String, fs: impl GameFs + 'static) {
+    unmount(&dir);
+    MOUNTS.with_borrow_mut(|mounts| {
+        mounts.push(Mount {
+            dir,
+            source,
+            fs: Box::new(fs),
+        })
+    });
+}
+
+/// Stops keeping the files under `dir` anywhere special, returning whether
+/// anything was mounted there.
+pub fn unmount(dir: &Path) -> bool {
+    let dir = absolute(dir);
+    MOUNTS.with_borrow_mut(|mounts| {
+        let before = mounts.len();
+        mounts.retain(|mount| mount.dir != dir);
+        mounts.len() != before
+    })
+}
+
+/// What's mounted, and where.
+pub fn mounts() -> Vec<(String, PathBuf)> {
+    MOUNTS.with_borrow(|mounts| {
+        mounts
+            .iter()
+            .map(|mount| (mount.source.clone(), mount.dir.clone()))
+            .collect()
+    })
+}
+
+/// Runs `f` with the game's files kept in `fs` instead. Only the current
+/// thread is affected, so tests running alongside each other don't see each
+/// other's files.
+pub fn using<T>(fs: impl GameFs + 'static, f: impl FnOnce() -> T) -> T {
+    let previous = CURRENT.replace(Box::new(fs));
+    let result = f();
+    CURRENT.set(previous);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_files_are_listed_by_directory() {
+        let mut memory = MemoryFs::default();
+        let dungeon = Path::new("/dungeon");
+        memory.write(&dungeon.join("goblin"), b"grr").unwrap();
+        memory.write(&dungeon.join("hall/rat"), b"squeak").unwrap();
+
+        assert_eq!(memory.list(dungeon).unwrap(), [dungeon.join("goblin")]);
+        assert!(memory.starts_with(&dungeon.join("goblin"), b"gr").unwrap());
+        memory.remove(&dungeon.join("goblin")).unwrap();
+        assert!(memory.read(&dungeon.join("goblin")).is_err());
+        assert_eq!(memory.paths(), [dungeon.join("hall/rat")]);
+    }
+
+    #[test]
+    fn mounted_files_are_kept_in_the_mount() {
+        let (base, crypt) = (MemoryFs::default(), MemoryFs::default());
+        using(base.clone(), || {
+            mount(
+                PathBuf::from("/dungeon/crypt"),
+                String::from("crypt"),
+                crypt.clone(),
+            );
+            with(|fs| {
+                fs.write(Path::new("/dungeon/goblin"), b"grr").unwrap();
+                fs.write(Path::new("/dungeon/hall/../crypt/rat"), b"squeak")
+                    .unwrap();
+                assert_eq!(
+                    fs.list(Path::new("/dungeon/crypt/.")).unwrap(),
+                    [PathBuf::from("/dungeon/crypt/./rat")]
+                );
+            });
+            assert_eq!(
+                mounts(),
+                [(String::from("crypt"), PathBuf::from("/dungeon/crypt"))]
+            );
+            assert!(unmount(Path::new("/dungeon/crypt/")));
+            assert!(!unmount(Path::new("/dungeon/crypt")));
+        });
+        assert_eq!(base.paths(), [PathBuf::from("/dungeon/goblin")]);
+        assert_eq!(crypt.paths(), [PathBuf::from("rat")]);
+    }
+}
@@ -0,0 +1,94 @@
+//! Noticing entities come and go from the current directory, for
+//! `set -o watch`.
+//!
+//! After each command the shell compares what's in the directory with what
+//! was there at the last prompt. Rather than reading every file again, it
+//! asks the [entity index](super::id), which spawning keeps up to date, so
+//! only files known to be entities are opened. Entities that are too old to
+//! have an id, or that were moved in by hand and haven't been looked for
+//! since, go unnoticed.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use super::{id, locale::t};
+
+/// What was in the directory at the last prompt.
+#[derive(Debug, Default)]
+pub struct Watch {
+    dir: Option<PathBuf>,
+    seen: HashMap<id::Uuid, String>,
+}
+
+impl Watch {
+    /// Says what's appeared in and gone from `dir` since the last check.
+    /// Nothing is said the first time, or after moving to another directory.
+    pub fn check(&mut self, dir: &Path) -> Vec<String> {
+        let now: HashMap<id::Uuid, String> = id::in_dir(dir)
+            .into_iter()
+            .filter_map(|(id, path)| {
+                let name = path.file_name()?.to_string_lossy().into_owned();
+                (!name.starts_with('.')).then_some((id, name))
+            })
+            .collect();
+        let mut messages = Vec::new();
+        if self.dir.as_deref() == Some(dir) {
+            let mut appeared: Vec<&String> = now
+                .iter()
+                .filter(|(id, _)| !self.seen.contains_key(id))
+                .map(|(_, name)| name)
+                .collect();
+            let mut gone: Vec<&String> = self
+                .seen
+                .iter()
+                .filter(|(id, _)| !now.contains_key(id))
+                .map(|(_, name)| name)
+                .collect();
+            appeared.sort();
+            gone.sort();
+            messages.extend(
+                appeared
+                    .iter()
+                    .map(|name| t!("watch.appeared", name = name)),
+            );
+            messages.extend(gone.iter().map(|name| t!("watch.gone", name = name)));
+        }
+        self.dir = Some(dir.to_path_buf());
+        self.seen = now;
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{
+        testworld::{enemy, item, world},
+        vfs,
+    };
+
+    #[test]
+    fn changes_are_noticed() {
+        let world = world! {
+            "dungeon" => { "chest": item("key") },
+            "crypt" => { "ghoul": enemy(3) },
+        };
+        let (dungeon, crypt) = (world.path("dungeon"), world.path("crypt"));
+        let mut watch = Watch::default();
+        assert!(world.run(|| watch.check(&dungeon)).is_empty());
+
+        world.add("dungeon", "goblin", enemy(5));
+        world
+            .run(|| vfs::with(|fs| fs.remove(&dungeon.join("chest"))))
+            .unwrap();
+        assert_eq!(
+            world.run(|| watch.check(&dungeon)),
+            ["a goblin has appeared!", "the chest is gone."]
+        );
+        assert!(world.run(|| watch.check(&dungeon)).is_empty());
+        // moving on isn't news
+        assert!(world.run(|| watch.check(&crypt)).is_empty());
+    }
+}
@@ -0,0 +1,25 @@
+//! Headless mode, for running rlsh in CI and golden-file tests.
+//!
+//! `rlsh --headless` turns off everything that would make two runs with the
+//! same input print different things. Colours and icons are off, as in
+//! accessibility mode, and the prompt is a plain `$ ` rather than naming the
+//! machine. Random events don't happen, the game's clock stands still at the
+//! epoch, every random number comes from [`SEED`], and jobs are named without
+//! their process ids.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// What the random number generator is seeded with in headless mode.
+pub const SEED: u64 = 0;
+
+static HEADLESS: AtomicBool = AtomicBool::new(false);
+
+/// Turns on headless mode, for the rest of the run.
+pub fn enable() {
+    HEADLESS.store(true, Ordering::Relaxed);
+}
+
+/// Whether headless mode is on.
+pub fn enabled() -> bool {
+    HEADLESS.load(Ordering::Relaxed)
+}
@@ -1,4 +1,10 @@
+mod config;
+mod crash;
 pub mod game;
+pub mod headless;
 mod shell;
+mod style;
+mod theme;
+pub mod update;
 
-pub use shell::App;
+pub use shell::{App, words_in};
@@ -1,5 +1,38 @@
-use rlsh::App;
+use std::{env, process::ExitCode};
 
-fn main() {
-    App::new().run();
+use rlsh::{App, headless, update};
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match *args.as_slice() {
+        [] => App::new().run(),
+        ["--headless"] => {
+            headless::enable();
+            App::new().run()
+        }
+        [script] | ["--headless", script] if !script.starts_with('-') && script != "update" => {
+            if args[0] == "--headless" {
+                headless::enable();
+            }
+            let status = App::new().run_script(script);
+            return ExitCode::from(status.clamp(0, 255) as u8);
+        }
+        ["--version"] => print!("{}", update::version(false)),
+        ["--version", "--verbose"] | ["--verbose", "--version"] => {
+            print!("{}", update::version(true))
+        }
+        ["update"] => match update::update() {
+            Ok(out) => print!("{out}"),
+            Err(error) => {
+                eprintln!("rlsh: {error}");
+                return ExitCode::FAILURE;
+            }
+        },
+        _ => {
+            eprintln!("usage: rlsh [--headless] [script] | update | --version [--verbose]");
+            return ExitCode::from(2);
+        }
+    }
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,91 @@
+//! Abbreviations, added with the `abbr` builtin.
+//!
+//! Unlike an alias, an abbreviation is expanded in the line editor as soon as
+//! a space is typed after it, so the full command can be seen and changed
+//! before it's run. Only command names are expanded: `gs` on its own becomes
+//! `git status`, but `echo gs` is left alone.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+#[derive(Clone, Debug, Default)]
+pub struct Abbreviations {
+    expansions: BTreeMap<String, String>,
+}
+
+/// Takes the quotes off `text` if it's wrapped in a matching pair.
+fn unquote(text: &str) -> &str {
+    for quote in ['\'', '"'] {
+        if let Some(inner) = text
+            .strip_prefix(quote)
+            .and_then(|text| text.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+    text
+}
+
+impl Abbreviations {
+    /// What `word` expands to, if it's an abbreviation.
+    pub fn get(&self, word: &str) -> Option<&str> {
+        self.expansions.get(word).map(String::as_str)
+    }
+
+    /// Runs the `abbr` builtin, returning what should be printed.
+    ///
+    /// - `abbr` lists the abbreviations.
+    /// - `abbr <word> <expansion>` adds one.
+    /// - `abbr -e <word>` erases one.
+    pub fn command(&mut self, args: &[String]) -> Result<String, String> {
+        match args {
+            [] => {
+                let mut out = String::new();
+                for (word, expansion) in &self.expansions {
+                    writeln!(out, "abbr {word} '{expansion}'").unwrap();
+                }
+                Ok(out)
+            }
+            [flag, word] if flag == "-e" => match self.expansions.remove(word) {
+                Some(_) => Ok(String::new()),
+                None => Err(format!("abbr: {word}: no such abbreviation")),
+            },
+            [word] => Err(format!("abbr: {word}: missing expansion")),
+            [word, expansion @ ..] => {
+                if word.starts_with('-') {
+                    return Err(format!("abbr: {word}: unknown option"));
+                }
+                // arguments were split on spaces, so put quoted ones back
+                let expansion = expansion.join(" ");
+                self.expansions
+                    .insert(word.clone(), unquote(&expansion).to_string());
+                Ok(String::new())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(abbreviations: &mut Abbreviations, args: &str) -> Result<String, String> {
+        let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+        abbreviations.command(&args)
+    }
+
+    #[test]
+    fn abbreviations_are_added_and_erased() {
+        let mut abbreviations = Abbreviations::default();
+        run(&mut abbreviations, "gs 'git status'").unwrap();
+        run(&mut abbreviations, "l ls -l").unwrap();
+        assert_eq!(abbreviations.get("gs"), Some("git status"));
+        assert_eq!(
+            run(&mut abbreviations, "").unwrap(),
+            "abbr gs 'git status'\nabbr l 'ls -l'\n"
+        );
+        run(&mut abbreviations, "-e gs").unwrap();
+        assert_eq!(abbreviations.get("gs"), None);
+        assert!(run(&mut abbreviations, "-e gs").is_err());
+        assert!(run(&mut abbreviations, "gs").is_err());
+    }
+}
@@ -0,0 +1,192 @@
+//! Aliases, added with the `alias` builtin and removed with `unalias`.
+//!
+//! Unlike an abbreviation, an alias is expanded when the command line is
+//! parsed, before it's checked for builtins, so `alias ll='ls -l'` makes
+//! `ll` run the `ls` builtin, and the line that's kept in the history is the
+//! one that was typed. Only the first word of each command in a pipeline is
+//! expanded, and only if it isn't quoted. An alias can expand to another one,
+//! but not to itself, so `alias ls='ls -a'` works.
+//!
+//! Aliases are kept with the rest of the shell's state from one command to
+//! the next, so ones defined by a file the shell runs at startup stay
+//! defined afterwards.
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashSet},
+    fmt::Write,
+};
+
+#[derive(Clone, Debug, Default)]
+pub struct Aliases {
+    definitions: BTreeMap<String, String>,
+}
+
+/// Whether `name` can be an alias. Anything the tokenizer would treat
+/// specially can't be in one, or it'd never be seen as a plain word.
+fn is_valid(name: &str) -> bool {
+    !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| c.is_whitespace() || "/$`\\='\"|&;<>(){}".contains(c))
+}
+
+/// Quotes `text` for printing, so what `alias` lists can be run again.
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+impl Aliases {
+    /// What `name` expands to, if it's an alias.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.definitions.get(name).map(String::as_str)
+    }
+
+    /// Runs the `alias` builtin, returning what should be printed.
+    ///
+    /// - `alias` lists the aliases.
+    /// - `alias <name>=<value>` defines one.
+    /// - `alias <name>` shows one.
+    pub fn alias(&mut self, args: &[String]) -> Result<String, String> {
+        if args.is_empty() {
+            let mut out = String::new();
+            for (name, value) in &self.definitions {
+                writeln!(out, "alias {name}={}", quote(value)).unwrap();
+            }
+            return Ok(out);
+        }
+        let mut out = String::new();
+        let mut errors = Vec::new();
+        for arg in args {
+            match arg.split_once('=') {
+                Some((name, _)) if !is_valid(name) => {
+                    errors.push(format!("alias: `{name}': invalid alias name"));
+                }
+                Some((name, value)) => {
+                    self.definitions.insert(name.to_string(), value.to_string());
+                }
+                None => match self.definitions.get(arg) {
+                    Some(value) => writeln!(out, "alias {arg}={}", quote(value)).unwrap(),
+                    None => errors.push(format!("alias: {arg}: not found")),
+                },
+            }
+        }
+        match errors.is_empty() {
+            true => Ok(out),
+            false => Err(out + &errors.join("\n")),
+        }
+    }
+
+    /// Runs the `unalias` builtin: `unalias <name>...` removes aliases, and
+    /// `unalias -a` removes all of them.
+    pub fn unalias(&mut self, args: &[String]) -> Result<(), String> {
+        match args {
+            [] => Err(String::from("unalias: usage: unalias [-a] name...")),
+            [flag] if flag == "-a" => {
+                self.definitions.clear();
+                Ok(())
+            }
+            names => {
+                let missing: Vec<String> = names
+                    .iter()
+                    .filter(|name| self.definitions.remove(*name).is_none())
+                    .map(|name| format!("unalias: {name}: not found"))
+                    .collect();
+                match missing.is_empty() {
+                    true => Ok(()),
+                    false => Err(missing.join("\n")),
+                }
+            }
+        }
+    }
+
+    /// Expands the alias at the start of `command`, and any alias that
+    /// starts, in turn, until one would expand to itself.
+    fn expand_command(&self, command: &str) -> Option<String> {
+        let mut expanded = command.to_string();
+        let mut seen = HashSet::new();
+        loop {
+            let start = expanded.len() - expanded.trim_start().len();
+            let rest = &expanded[start..];
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let name = &rest[..end];
+            let Some(value) = self.get(name).filter(|_| seen.insert(name.to_string())) else {
+                break;
+            };
+            expanded.replace_range(start..start + end, value);
+        }
+        (!seen.is_empty()).then_some(expanded)
+    }
+
+    /// Expands the aliases in `line`, see the module docs.
+    pub fn expand<'l>(&self, line: &'l str) -> Cow<'l, str> {
+        if self.definitions.is_empty() {
+            return Cow::Borrowed(line);
+        }
+        let stages = super::App::split_pipeline(line);
+        if stages
+            .iter()
+            .all(|stage| self.expand_command(stage).is_none())
+        {
+            return Cow::Borrowed(line);
+        }
+        let stages: Vec<Cow<str>> = stages
+            .into_iter()
+            .map(|stage| match self.expand_command(stage) {
+                Some(expanded) => Cow::Owned(expanded),
+                None => Cow::Borrowed(stage),
+            })
+            .collect();
+        Cow::Owned(stages.join("|"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(aliases: &mut Aliases, args: &[&str]) -> Result<String, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        aliases.alias(&args)
+    }
+
+    #[test]
+    fn aliases_are_defined_and_removed() {
+        let mut aliases = Aliases::default();
+        run(&mut aliases, &["ll=ls -l", "say=echo it's"]).unwrap();
+        assert_eq!(aliases.get("ll"), Some("ls -l"));
+        assert_eq!(
+            run(&mut aliases, &[]).unwrap(),
+            "alias ll='ls -l'\nalias say='echo it'\\''s'\n"
+        );
+        assert_eq!(run(&mut aliases, &["ll"]).unwrap(), "alias ll='ls -l'\n");
+        assert_eq!(
+            run(&mut aliases, &["nope", "a/b=c"]),
+            Err(String::from(
+                "alias: nope: not found\nalias: `a/b': invalid alias name"
+            ))
+        );
+
+        aliases.unalias(&[String::from("ll")]).unwrap();
+        assert_eq!(aliases.get("ll"), None);
+        assert!(aliases.unalias(&[String::from("ll")]).is_err());
+        aliases.unalias(&[String::from("-a")]).unwrap();
+        assert_eq!(aliases.get("say"), None);
+    }
+
+    #[test]
+    fn only_command_names_are_expanded() {
+        let mut aliases = Aliases::default();
+        run(
+            &mut aliases,
+            &["ll=ls -l", "l=ll -a", "ls=ls -F", "loop=loop"],
+        )
+        .unwrap();
+        assert_eq!(aliases.expand("ll dungeon"), "ls -F -l dungeon");
+        assert_eq!(aliases.expand("l"), "ls -F -l -a");
+        assert_eq!(aliases.expand("echo ll | ll"), "echo ll | ls -F -l");
+        assert_eq!(aliases.expand("loop"), "loop");
+        // quoted, or not the whole word
+        assert!(matches!(aliases.expand("'ll' lll"), Cow::Borrowed(_)));
+    }
+}
@@ -0,0 +1,303 @@
+//! A log of every command run, for looking back on later with `audit query`.
+//!
+//! Unlike the history, which is for recalling lines to edit, the audit log is
+//! a record: each command gets a line saying when it started, how long it took,
+//! whether it succeeded, where it was run and its words. Lines are only ever
+//! appended. Once the log gets big it's moved aside to `audit.log.1`, and so on
+//! up to [`KEEP`] old logs.
+//!
+//! It can be turned off with `config audit off`.
+
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use super::date::{self, DateTime};
+use crate::{config::Config, game::player};
+
+/// How big the log gets before it's rotated, in bytes.
+const MAX_SIZE: u64 = 1 << 20;
+
+/// How many rotated logs are kept besides the current one.
+const KEEP: usize = 3;
+
+/// One command that was run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// When it started, in seconds since the Unix epoch.
+    pub timestamp: i64,
+    pub duration: Duration,
+    /// 0 if it succeeded.
+    pub status: i32,
+    pub cwd: String,
+    pub argv: Vec<String>,
+}
+
+/// Escapes tabs, newlines and backslashes so `field` fits in one column.
+fn escape(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+fn unescape(field: &str) -> String {
+    let mut out = String::new();
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        match (c, c == '\\') {
+            (_, true) => match chars.next() {
+                Some('t') => out.push('\t'),
+                Some('n') => out.push('\n'),
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            },
+            (c, false) => out.push(c),
+        }
+    }
+    out
+}
+
+impl Entry {
+    /// The entry as a line of the log, with tabs between the fields.
+    fn to_line(&self) -> String {
+        let mut fields = vec![
+            self.timestamp.to_string(),
+            self.duration.as_millis().to_string(),
+            self.status.to_string(),
+            escape(&self.cwd),
+        ];
+        fields.extend(self.argv.iter().map(|arg| escape(arg)));
+        fields.join("\t")
+    }
+
+    fn from_line(line: &str) -> Option<Entry> {
+        let mut fields = line.split('\t');
+        Some(Entry {
+            timestamp: fields.next()?.parse().ok()?,
+            duration: Duration::from_millis(fields.next()?.parse().ok()?),
+            status: fields.next()?.parse().ok()?,
+            cwd: unescape(fields.next()?),
+            argv: fields.map(unescape).collect(),
+        })
+    }
+}
+
+fn path() -> PathBuf {
+    player::get_data_dir().join("audit.log")
+}
+
+/// The `n`th old log, moved aside by rotation.
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
+
+/// Moves the log at `path` aside if it's too big, dropping the oldest.
+fn rotate(path: &Path) -> io::Result<()> {
+    if fs::metadata(path).map_or(true, |m| m.len() < MAX_SIZE) {
+        return Ok(());
+    }
+    for n in (1..KEEP).rev() {
+        let from = rotated(path, n);
+        if from.exists() {
+            fs::rename(from, rotated(path, n + 1))?;
+        }
+    }
+    fs::rename(path, rotated(path, 1))
+}
+
+fn append(path: &Path, entry: &Entry) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    rotate(path)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", entry.to_line())
+}
+
+/// Adds `entry` to the log, unless it's turned off. A log that can't be
+/// written to is left alone rather than getting in the way of commands.
+pub fn record(entry: &Entry) {
+    if Config::load().is_on("audit") {
+        let _ = append(&path(), entry);
+    }
+}
+
+/// Every entry in the logs at `path`, oldest first.
+fn read(path: &Path) -> Vec<Entry> {
+    let old = (1..=KEEP).rev().map(|n| rotated(path, n));
+    old.chain([path.to_path_buf()])
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .flat_map(|text| {
+            text.lines()
+                .filter_map(Entry::from_line)
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Reads a time for `--since` or `--until`: `@seconds`, an amount of time ago
+/// like `30m`, `2h` or `1d`, or a local date like `2024-02-29` or
+/// `2024-02-29T13:05`.
+fn parse_time(text: &str, now: i64) -> Option<i64> {
+    if let Some(seconds) = text.strip_prefix('@') {
+        return seconds.parse().ok();
+    }
+    let unit = match text.chars().last()? {
+        's' => Some(1),
+        'm' => Some(60),
+        'h' => Some(3600),
+        'd' => Some(86400),
+        _ => None,
+    };
+    if let Some(unit) = unit
+        && let Ok(amount) = text[..text.len() - 1].parse::<i64>()
+    {
+        return Some(now - amount * unit);
+    }
+
+    let (day, time) = text.split_once('T').unwrap_or((text, "00:00"));
+    let numbers =
+        |text: &str, sep| -> Option<Vec<u32>> { text.split(sep).map(|n| n.parse().ok()).collect() };
+    let (year, month, day) = match *numbers(day, '-')?.as_slice() {
+        [year, month, day] if (1..=12).contains(&month) && (1..=31).contains(&day) => {
+            (year, month, day)
+        }
+        _ => return None,
+    };
+    let seconds = match *numbers(time, ':')?.as_slice() {
+        [hour, minute] => hour * 3600 + minute * 60,
+        [hour, minute, second] => hour * 3600 + minute * 60 + second,
+        _ => return None,
+    };
+    let utc = date::days_from_civil(year as i64, month, day) * 86400 + seconds as i64;
+    Some(utc - DateTime::local(utc).offset)
+}
+
+/// Shows `entry` as a line of `audit query`.
+fn show(entry: &Entry) -> String {
+    format!(
+        "{}  {:>3}  {:>6}ms  {}  {}\n",
+        DateTime::local(entry.timestamp).format("%F %T"),
+        entry.status,
+        entry.duration.as_millis(),
+        entry.cwd,
+        entry.argv.join(" ")
+    )
+}
+
+fn query(entries: &[Entry], args: &[String], now: i64) -> Result<String, String> {
+    let (mut since, mut until) = (i64::MIN, i64::MAX);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let bound = match arg.as_str() {
+            "--since" => &mut since,
+            "--until" => &mut until,
+            other => return Err(format!("audit: {other}: unknown option")),
+        };
+        let time = args
+            .next()
+            .ok_or_else(|| format!("audit: {arg}: option requires an argument"))?;
+        *bound = parse_time(time, now).ok_or_else(|| format!("audit: {time}: invalid time"))?;
+    }
+    Ok(entries
+        .iter()
+        .filter(|entry| (since..=until).contains(&entry.timestamp))
+        .map(show)
+        .collect())
+}
+
+/// Runs the `audit` builtin: `audit query [--since time] [--until time]`,
+/// returning what should be printed.
+pub fn command(args: &[String]) -> Result<String, String> {
+    match args.split_first() {
+        Some((subcommand, args)) if subcommand == "query" => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs() as i64);
+            query(&read(&path()), args, now)
+        }
+        Some((subcommand, _)) => Err(format!("audit: {subcommand}: unknown command")),
+        None => Err(String::from(
+            "audit: usage: audit query [--since time] [--until time]",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(timestamp: i64, argv: &[&str]) -> Entry {
+        Entry {
+            timestamp,
+            duration: Duration::from_millis(12),
+            status: 0,
+            cwd: String::from("/home/me/dungeon"),
+            argv: argv.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn entries_round_trip() {
+        let tricky = entry(1709211909, &["printf", "a\tb\\n\n"]);
+        assert_eq!(Entry::from_line(&tricky.to_line()), Some(tricky.clone()));
+        assert!(!tricky.to_line().contains('\n'));
+        assert_eq!(Entry::from_line("garbage"), None);
+
+        let dir = std::env::temp_dir().join(format!("rlsh-audit-{}", std::process::id()));
+        let path = dir.join("audit.log");
+        let big = entry(1, &["x"; 1000]);
+        let lines = MAX_SIZE as usize / big.to_line().len() + 1;
+        for _ in 0..lines * (KEEP + 2) {
+            append(&path, &big).unwrap();
+        }
+        append(&path, &tricky).unwrap();
+        assert!(rotated(&path, KEEP).exists());
+        assert!(!rotated(&path, KEEP + 1).exists());
+        assert_eq!(read(&path).last(), Some(&tricky));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn queries_filter_by_time() {
+        let now = 1_000_000;
+        let entries = [
+            entry(now - 7200, &["ls"]),
+            entry(now - 600, &["cd", "dungeon"]),
+            entry(now - 10, &["bash", "goblin"]),
+        ];
+        let run = |args: &str| {
+            let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+            query(&entries, &args, now)
+        };
+        assert_eq!(run("").unwrap().lines().count(), 3);
+        let recent = run("--since 1h").unwrap();
+        assert_eq!(recent.lines().count(), 2);
+        assert!(recent.lines().next().unwrap().ends_with("  cd dungeon"));
+        assert_eq!(run("--since 1h --until 5m").unwrap().lines().count(), 1);
+        assert_eq!(
+            run(&format!("--until @{}", now - 3600))
+                .unwrap()
+                .lines()
+                .count(),
+            1
+        );
+        assert!(run("--since yesterday").is_err());
+        assert!(run("--since").is_err());
+
+        assert_eq!(parse_time("@42", now), Some(42));
+        assert_eq!(parse_time("2d", now), Some(now - 2 * 86400));
+        let day = parse_time("2024-02-29", now).unwrap();
+        assert_eq!(
+            parse_time("2024-02-29T13:05:09", now),
+            Some(day + 13 * 3600 + 309)
+        );
+        assert_eq!(parse_time("2024-13-01", now), None);
+    }
+}
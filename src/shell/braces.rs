@@ -0,0 +1,162 @@
+//! Brace expansion, like `room{1..9}.txt` and `dungeon/{loot,monsters}`.
+//!
+//! Like in bash, it happens before anything else, to the command line as it
+//! was typed: each word with unquoted braces in it is replaced by every word
+//! it stands for, quoting and all, so `"a b"{1,2}` is `"a b"1 "a b"2`. Braces
+//! without a `,` or `..` between them, and the ones of `${NAME}`, are left
+//! alone.
+
+use std::borrow::Cow;
+
+use super::tokenize;
+
+/// What the braces at the start of `masked` hold, and where they end: the
+/// byte ranges of what's between the commas, or `None` if they don't close.
+fn alternatives(masked: &str) -> Option<(Vec<(usize, usize)>, usize)> {
+    let mut depth = 0;
+    let mut start = 1;
+    let mut found = Vec::new();
+    for (i, c) in masked.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 1 => {
+                found.push((start, i));
+                return Some((found, i));
+            }
+            '}' => depth -= 1,
+            ',' if depth == 1 => {
+                found.push((start, i));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The words a sequence like `1..5`, `a..e` or `10..0..2` stands for, if
+/// `inner` is one. Numbers with leading zeros are padded to the same width.
+fn sequence(inner: &str) -> Option<Vec<String>> {
+    let parts: Vec<&str> = inner.split("..").collect();
+    let (from, to, step) = match parts[..] {
+        [from, to] => (from, to, None),
+        [from, to, step] => (from, to, Some(step.parse::<i64>().ok()?.unsigned_abs())),
+        _ => return None,
+    };
+    let step = step.unwrap_or(1).max(1) as usize;
+    if let (Ok(a), Ok(b)) = (from.parse::<i64>(), to.parse::<i64>()) {
+        let padded = |s: &str| s.trim_start_matches('-').starts_with('0') && s.len() > 1;
+        let width = match padded(from) || padded(to) {
+            true => from.len().max(to.len()),
+            false => 0,
+        };
+        let numbers: Vec<i64> = match a <= b {
+            true => (a..=b).step_by(step).collect(),
+            false => (b..=a).rev().step_by(step).collect(),
+        };
+        return Some(numbers.iter().map(|n| format!("{n:0width$}")).collect());
+    }
+    let (mut a, mut b) = (from.chars(), to.chars());
+    let (Some(a), None, Some(b), None) = (a.next(), a.next(), b.next(), b.next()) else {
+        return None;
+    };
+    if !a.is_ascii_alphabetic() || !b.is_ascii_alphabetic() {
+        return None;
+    }
+    let letters: Vec<char> = match a <= b {
+        true => (a..=b).step_by(step).collect(),
+        false => (b..=a).rev().step_by(step).collect(),
+    };
+    Some(letters.iter().map(char::to_string).collect())
+}
+
+/// Expands the braces in the word `raw`, which `masked` is the masked copy
+/// of, see [`tokenize::mask`].
+fn word(raw: &str, masked: &str) -> Vec<String> {
+    for (open, _) in masked.match_indices('{') {
+        // `${NAME}` isn't one
+        if masked[..open].ends_with('$') {
+            continue;
+        }
+        let Some((found, close)) = alternatives(&masked[open..]) else {
+            continue;
+        };
+        let (prefix, suffix) = (open, open + close + 1);
+        let mut words = Vec::new();
+        let mut expand = |middle: &str, masked_middle: &str| {
+            let raw = [&raw[..prefix], middle, &raw[suffix..]].concat();
+            let masked = [&masked[..prefix], masked_middle, &masked[suffix..]].concat();
+            words.extend(word(&raw, &masked));
+        };
+        if found.len() > 1 {
+            for (start, end) in found {
+                let (start, end) = (open + start, open + end);
+                expand(&raw[start..end], &masked[start..end]);
+            }
+            return words;
+        }
+        let inner = &masked[open + 1..open + close];
+        if let Some(sequence) = sequence(inner) {
+            for item in sequence {
+                expand(&item, &item);
+            }
+            return words;
+        }
+    }
+    vec![raw.to_string()]
+}
+
+/// Expands the braces in `line`, see the module docs.
+pub fn expand(line: &str) -> Cow<'_, str> {
+    if !line.contains('{') {
+        return Cow::Borrowed(line);
+    }
+    let masked = tokenize::mask(line);
+    let mut expanded = String::with_capacity(line.len());
+    let mut start = 0;
+    let spaces = masked.match_indices(char::is_whitespace);
+    for (end, space) in spaces.chain([(line.len(), "")]) {
+        if end > start {
+            expanded.push_str(&word(&line[start..end], &masked[start..end]).join(" "));
+        }
+        expanded.push_str(space);
+        start = end + space.len();
+    }
+    match expanded == line {
+        true => Cow::Borrowed(line),
+        false => Cow::Owned(expanded),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn braces_stand_for_every_word_in_them() {
+        assert_eq!(
+            expand("mkdir -p dungeon/{level1,level2}/{loot,monsters}"),
+            "mkdir -p dungeon/level1/loot dungeon/level1/monsters \
+             dungeon/level2/loot dungeon/level2/monsters"
+        );
+        assert_eq!(
+            expand("touch room{1..3}.txt"),
+            "touch room1.txt room2.txt room3.txt"
+        );
+        assert_eq!(expand("echo {a,b{c,d}}e"), "echo ae bce bde");
+        assert_eq!(
+            expand(r#"echo "a b"{1,2} '{x,y}'"#),
+            r#"echo "a b"1 "a b"2 '{x,y}'"#
+        );
+        assert_eq!(
+            expand("echo {5..1..2} {08..10} {c..a} {,s}"),
+            "echo 5 3 1 08 09 10 c b a  s"
+        );
+        // and the ones that aren't
+        assert_eq!(expand("echo ${HOME} {a} {a,b"), "echo ${HOME} {a} {a,b");
+        assert_eq!(
+            expand("echo {1..x} $(echo {1,2})"),
+            "echo {1..x} $(echo {1,2})"
+        );
+    }
+}
@@ -0,0 +1,1255 @@
+//! Builtins that don't need anything from the game, mostly for scripting.
+
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::{Component, Path, PathBuf},
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use rand::{Rng, seq::SliceRandom};
+
+use super::{
+    date::DateTime,
+    variables::{self, Variables},
+};
+use crate::game::{self, dice};
+
+/// Reads a line from the file descriptor `fd` onto the end of `line` a byte
+/// at a time, so nothing after the line is taken from whoever reads next.
+fn read_line_from(fd: i32, line: &mut String) -> io::Result<usize> {
+    let mut bytes = Vec::new();
+    let mut byte = 0u8;
+    loop {
+        // SAFETY: one byte is read into `byte`, which is one byte long
+        match unsafe { libc::read(fd, (&raw mut byte).cast(), 1) } {
+            -1 if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => continue,
+            -1 => return Err(io::Error::last_os_error()),
+            0 => break,
+            _ => {
+                bytes.push(byte);
+                if byte == b'\n' {
+                    break;
+                }
+            }
+        }
+    }
+    line.push_str(&String::from_utf8_lossy(&bytes));
+    Ok(bytes.len())
+}
+
+/// Runs the `read` builtin: `read [-s] [-p prompt] [-u fd] [name...]`.
+///
+/// Reads a line from stdin and splits it into words, one for each variable.
+/// The last variable gets whatever's left of the line. With no names, the whole
+/// line goes into `REPLY`. `-s` keeps the input from being echoed, for secrets.
+///
+/// `-u fd` reads from the file descriptor `fd` instead, like a
+/// coprocess's output.
+///
+/// Returns whether a line was read.
+pub fn read(args: &[String], variables: &mut Variables) -> Result<bool, String> {
+    let mut prompt = None;
+    let mut silent = false;
+    let mut fd = None;
+    let mut names = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-p" => match args.next() {
+                Some(p) => prompt = Some(p),
+                None => return Err(String::from("read: -p: option requires an argument")),
+            },
+            "-s" => silent = true,
+            "-u" => match args.next().and_then(|fd| fd.parse::<i32>().ok()) {
+                Some(n) => fd = Some(n),
+                None => return Err(String::from("read: -u: invalid file descriptor")),
+            },
+            flag if flag.starts_with('-') => return Err(format!("read: {flag}: invalid option")),
+            name => names.push(name),
+        }
+    }
+    if names.is_empty() {
+        names.push("REPLY");
+    }
+
+    if let Some(prompt) = prompt {
+        print!("{prompt}");
+        io::stdout().flush().unwrap();
+    }
+
+    if silent {
+        stty("-echo");
+    }
+    let mut line = String::new();
+    let result = match fd {
+        Some(fd) => read_line_from(fd, &mut line),
+        None => io::stdin().read_line(&mut line),
+    };
+    if silent {
+        stty("echo");
+        // the newline the user typed wasn't echoed either
+        println!();
+    }
+
+    match result {
+        Ok(0) => return Ok(false),
+        Ok(_) => (),
+        Err(error) => return Err(format!("read: {error}")),
+    }
+
+    let line = line.strip_suffix('\n').unwrap_or(&line);
+    for (name, value) in split_fields(line, &names) {
+        variables
+            .set(name, value)
+            .map_err(|error| format!("read: {error}"))?;
+    }
+    Ok(true)
+}
+
+/// Splits `line` into one field per name, with the last name getting the rest
+/// of the line. Names left over once the line runs out get empty strings.
+fn split_fields<'a>(line: &'a str, names: &[&'a str]) -> Vec<(&'a str, &'a str)> {
+    let mut rest = line.trim();
+    let mut fields = Vec::new();
+    for (i, name) in names.iter().enumerate() {
+        if i == names.len() - 1 {
+            fields.push((*name, rest));
+            break;
+        }
+        let (field, remainder) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        fields.push((*name, field));
+        rest = remainder.trim_start();
+    }
+    fields
+}
+
+/// Runs the `test` builtin (also known as `[`), returning whether the
+/// expression holds.
+///
+/// On top of the usual file, string and integer checks, `-E path` checks that
+/// a file is an entity and `-M path` that it's a monster. Neither sees through
+/// disguises any better than the player can.
+pub fn test(args: &[String]) -> Result<bool, String> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    evaluate(&args).map_err(|error| format!("test: {error}"))
+}
+
+/// Evaluates a `test` expression. `-o` binds looser than `-a`, which binds
+/// looser than `!`.
+fn evaluate(args: &[&str]) -> Result<bool, String> {
+    if let Some(i) = args.iter().rposition(|&arg| arg == "-o")
+        && i > 0
+    {
+        return Ok(evaluate(&args[..i])? | evaluate(&args[i + 1..])?);
+    }
+    if let Some(i) = args.iter().rposition(|&arg| arg == "-a")
+        && i > 0
+    {
+        return Ok(evaluate(&args[..i])? & evaluate(&args[i + 1..])?);
+    }
+
+    match args {
+        [] => Ok(false),
+        ["!", rest @ ..] => Ok(!evaluate(rest)?),
+        [string] => Ok(!string.is_empty()),
+        [op, operand] => unary(op, operand),
+        [left, op, right] => binary(left, op, right),
+        _ => Err(format!("too many arguments: {}", args.join(" "))),
+    }
+}
+
+fn unary(op: &str, operand: &str) -> Result<bool, String> {
+    use std::os::unix::fs::FileTypeExt;
+
+    let path = Path::new(operand);
+    let file_type = || path.metadata().map(|m| m.file_type());
+    Ok(match op {
+        "-e" => path.exists(),
+        "-f" => path.is_file(),
+        "-d" => path.is_dir(),
+        "-s" => path.metadata().is_ok_and(|m| m.len() > 0),
+        "-p" => file_type().is_ok_and(|t| t.is_fifo()),
+        "-S" => file_type().is_ok_and(|t| t.is_socket()),
+        "-b" => file_type().is_ok_and(|t| t.is_block_device()),
+        "-c" => file_type().is_ok_and(|t| t.is_char_device()),
+        "-z" => operand.is_empty(),
+        "-n" => !operand.is_empty(),
+        "-E" => game::get_entity(path).is_ok_and(|e| !e.is_disguised()),
+        "-M" => game::get_entity(path).is_ok_and(|e| game::Filter::Enemies.keeps(Some(&e))),
+        _ => return Err(format!("{op}: unary operator expected")),
+    })
+}
+
+fn binary(left: &str, op: &str, right: &str) -> Result<bool, String> {
+    let integers = || -> Result<(i64, i64), String> {
+        let parse = |s: &str| {
+            s.parse::<i64>()
+                .map_err(|_| format!("{s}: integer expression expected"))
+        };
+        Ok((parse(left)?, parse(right)?))
+    };
+
+    Ok(match op {
+        "=" | "==" => left == right,
+        "!=" => left != right,
+        "-eq" => integers().map(|(l, r)| l == r)?,
+        "-ne" => integers().map(|(l, r)| l != r)?,
+        "-lt" => integers().map(|(l, r)| l < r)?,
+        "-le" => integers().map(|(l, r)| l <= r)?,
+        "-gt" => integers().map(|(l, r)| l > r)?,
+        "-ge" => integers().map(|(l, r)| l >= r)?,
+        _ => return Err(format!("{op}: binary operator expected")),
+    })
+}
+
+/// Runs the `printf` builtin: `printf format [argument...]`, returning what
+/// should be printed.
+///
+/// Supports `%s`, `%d` (or `%i`), `%x`, `%X`, `%o`, `%c` and `%%`, with
+/// optional `-` and `0` flags and a width, plus the usual backslash escapes.
+/// Like coreutils, the format is reused until every argument has been used,
+/// and conversions without an argument left get an empty string or zero.
+pub fn printf(args: &[String]) -> Result<String, String> {
+    let Some((format, mut args)) = args.split_first() else {
+        return Err(String::from("printf: missing format"));
+    };
+    let format = unescape(format);
+
+    let mut output = String::new();
+    loop {
+        let used = format_once(&format, args, &mut output)?;
+        args = &args[used..];
+        if args.is_empty() || used == 0 {
+            return Ok(output);
+        }
+    }
+}
+
+/// Goes through `format` once, appending to `output`, and returns how many of
+/// `args` were used up.
+fn format_once(format: &str, args: &[String], output: &mut String) -> Result<usize, String> {
+    let mut args = args.iter();
+    let mut used = 0;
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            output.push(c);
+            continue;
+        }
+
+        let mut left_align = false;
+        let mut zero_pad = false;
+        while let Some(&flag) = chars.peek() {
+            match flag {
+                '-' => left_align = true,
+                '0' => zero_pad = true,
+                _ => break,
+            }
+            chars.next();
+        }
+        let mut width = 0;
+        while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+            width = width * 10 + digit as usize;
+            chars.next();
+        }
+
+        let conversion = chars
+            .next()
+            .ok_or_else(|| String::from("printf: missing conversion at the end of the format"))?;
+        if conversion == '%' {
+            output.push('%');
+            continue;
+        }
+
+        let arg = args.next().map(String::as_str);
+        used += arg.is_some() as usize;
+        let arg = arg.unwrap_or("");
+        let number = || -> Result<i64, String> {
+            match arg {
+                "" => Ok(0),
+                arg => arg
+                    .parse()
+                    .map_err(|_| format!("printf: {arg}: invalid number")),
+            }
+        };
+
+        let formatted = match conversion {
+            's' => arg.to_string(),
+            'c' => arg.chars().next().map(String::from).unwrap_or_default(),
+            'd' | 'i' => number()?.to_string(),
+            'x' => format!("{:x}", number()?),
+            'X' => format!("{:X}", number()?),
+            'o' => format!("{:o}", number()?),
+            other => return Err(format!("printf: %{other}: invalid conversion")),
+        };
+
+        let padding = width.saturating_sub(formatted.chars().count());
+        if left_align {
+            output.push_str(&formatted);
+            output.extend(std::iter::repeat_n(' ', padding));
+        } else if zero_pad && conversion != 's' && conversion != 'c' {
+            // keep the sign in front of the zeros
+            let (sign, digits) = match formatted.strip_prefix('-') {
+                Some(digits) => ("-", digits),
+                None => ("", formatted.as_str()),
+            };
+            output.push_str(sign);
+            output.extend(std::iter::repeat_n('0', padding));
+            output.push_str(digits);
+        } else {
+            output.extend(std::iter::repeat_n(' ', padding));
+            output.push_str(&formatted);
+        }
+    }
+    Ok(used)
+}
+
+/// Replaces backslash escapes like `\n` with the characters they stand for.
+fn unescape(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('a') => out.push('\x07'),
+            Some('b') => out.push('\x08'),
+            Some('e') => out.push('\x1b'),
+            Some('f') => out.push('\x0c'),
+            Some('v') => out.push('\x0b'),
+            Some('\\') => out.push('\\'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Parses the arguments to `sleep`, adding up durations like `1.5`, `30s`,
+/// `2m`, `1h` or `1d`.
+pub fn sleep_duration(args: &[String]) -> Result<Duration, String> {
+    if args.is_empty() {
+        return Err(String::from("sleep: missing operand"));
+    }
+
+    let mut total = 0.0;
+    for arg in args {
+        let (number, unit) = match arg.char_indices().last() {
+            Some((i, unit @ ('s' | 'm' | 'h' | 'd'))) => (&arg[..i], unit),
+            _ => (arg.as_str(), 's'),
+        };
+        let seconds: f64 = number
+            .parse()
+            .ok()
+            .filter(|n: &f64| n.is_finite() && *n >= 0.0)
+            .ok_or_else(|| format!("sleep: invalid time interval '{arg}'"))?;
+        total += seconds
+            * match unit {
+                'm' => 60.0,
+                'h' => 60.0 * 60.0,
+                'd' => 24.0 * 60.0 * 60.0,
+                _ => 1.0,
+            };
+    }
+    Duration::try_from_secs_f64(total).map_err(|_| String::from("sleep: that's too long"))
+}
+
+/// Works out the arguments to ssh for the `rsh` builtin: `rsh host [command
+/// [arg]...]`, which runs a command on another machine as a job of this
+/// shell, or logs in there if there's no command.
+///
+/// From a terminal, ssh is asked for a terminal on the other end too, so
+/// Ctrl-C is passed on to the remote command rather than killing ssh and
+/// leaving the command running.
+pub fn rsh(args: &[String], terminal: bool) -> Result<Vec<String>, String> {
+    let Some((host, command)) = args.split_first() else {
+        return Err(String::from("rsh: usage: rsh <host> [command]"));
+    };
+    // ssh would take it for an option
+    if host.starts_with('-') {
+        return Err(format!("rsh: {host}: not a host"));
+    }
+    let mut ssh = Vec::new();
+    if terminal {
+        ssh.push(String::from("-t"));
+    }
+    ssh.push(host.clone());
+    ssh.extend_from_slice(command);
+    Ok(ssh)
+}
+
+/// Runs the `seq` builtin: `seq [-s separator] [-w] [first [increment]] last`,
+/// returning what should be printed.
+///
+/// Numbers are printed with as many decimal places as the most precise
+/// argument, so `seq 0 0.5 2` counts in halves. `-w` pads them with zeros to
+/// the same width.
+pub fn seq(args: &[String]) -> Result<String, String> {
+    let mut separator = String::from("\n");
+    let mut equal_width = false;
+    let mut numbers = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-s" => {
+                let sep = args
+                    .next()
+                    .ok_or_else(|| String::from("seq: -s: option requires an argument"))?;
+                separator = unescape(sep);
+            }
+            "-w" => equal_width = true,
+            number => numbers.push(number),
+        }
+    }
+
+    let parse = |s: &str| {
+        s.parse::<f64>()
+            .ok()
+            .filter(|n| n.is_finite())
+            .ok_or_else(|| format!("seq: invalid floating point argument: '{s}'"))
+    };
+    let (first, increment, last) = match numbers[..] {
+        [last] => (1.0, 1.0, parse(last)?),
+        [first, last] => (parse(first)?, 1.0, parse(last)?),
+        [first, increment, last] => (parse(first)?, parse(increment)?, parse(last)?),
+        [] => return Err(String::from("seq: missing operand")),
+        _ => return Err(format!("seq: extra operand '{}'", numbers[3])),
+    };
+    if increment == 0.0 {
+        return Err(String::from("seq: invalid Zero increment value: '0'"));
+    }
+
+    let decimals = numbers
+        .iter()
+        .map(|n| n.split_once('.').map_or(0, |(_, fraction)| fraction.len()))
+        .max()
+        .unwrap_or(0);
+
+    let mut values = Vec::new();
+    // step by counting rather than adding, so float error doesn't build up
+    for i in 0.. {
+        let value = first + increment * i as f64;
+        if (increment > 0.0 && value > last) || (increment < 0.0 && value < last) {
+            break;
+        }
+        values.push(format!("{value:.decimals$}"));
+    }
+
+    if equal_width {
+        let width = values.iter().map(String::len).max().unwrap_or(0);
+        for value in &mut values {
+            let (sign, digits) = match value.strip_prefix('-') {
+                Some(digits) => ("-", digits),
+                None => ("", value.as_str()),
+            };
+            *value = format!("{sign}{digits:0>width$}", width = width - sign.len());
+        }
+    }
+
+    let mut output = values.join(&separator);
+    if !output.is_empty() {
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Runs the `shuf` builtin, returning what should be printed.
+///
+/// - `shuf [file]` shuffles the lines of a file, or of stdin.
+/// - `shuf -e args...` shuffles its arguments.
+/// - `shuf -i lo-hi` shuffles the numbers from `lo` to `hi`.
+///
+/// `-n count` only prints the first `count` lines.
+pub fn shuf(args: &[String]) -> Result<String, String> {
+    let mut count = None;
+    let mut echo = false;
+    let mut range = None;
+    let mut operands = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-n" => {
+                let n = args
+                    .next()
+                    .ok_or_else(|| String::from("shuf: -n: option requires an argument"))?;
+                count = Some(
+                    n.parse::<usize>()
+                        .map_err(|_| format!("shuf: invalid line count: '{n}'"))?,
+                );
+            }
+            "-e" => echo = true,
+            "-i" => {
+                let r = args
+                    .next()
+                    .ok_or_else(|| String::from("shuf: -i: option requires an argument"))?;
+                let invalid = || format!("shuf: invalid input range: '{r}'");
+                let (lo, hi) = r.split_once('-').ok_or_else(invalid)?;
+                let lo: u64 = lo.parse().map_err(|_| invalid())?;
+                let hi: u64 = hi.parse().map_err(|_| invalid())?;
+                if lo > hi {
+                    return Err(invalid());
+                }
+                range = Some(lo..=hi);
+            }
+            operand => operands.push(operand.to_string()),
+        }
+    }
+
+    let lines: Vec<String> = if let Some(range) = range {
+        range.map(|n| n.to_string()).collect()
+    } else if echo {
+        operands
+    } else {
+        let contents = match operands.as_slice() {
+            [] => {
+                let mut contents = String::new();
+                io::stdin()
+                    .read_to_string(&mut contents)
+                    .map_err(|error| format!("shuf: {error}"))?;
+                contents
+            }
+            [file] => fs::read_to_string(file).map_err(|error| format!("shuf: {file}: {error}"))?,
+            [_, extra, ..] => return Err(format!("shuf: extra operand '{extra}'")),
+        };
+        contents.lines().map(String::from).collect()
+    };
+
+    let mut output = String::new();
+    for line in dice::with_rng(|mut rng| shuffle(lines, count, &mut rng)) {
+        output.push_str(&line);
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Shuffles `lines`, keeping only the first `count` of them if given.
+fn shuffle(mut lines: Vec<String>, count: Option<usize>, rng: &mut impl Rng) -> Vec<String> {
+    lines.shuffle(rng);
+    if let Some(count) = count {
+        lines.truncate(count);
+    }
+    lines
+}
+
+/// Runs the `basename` builtin: `basename path [suffix]` or `basename -a
+/// [-s suffix] path...`, returning what should be printed.
+///
+/// Prints the last part of each path, with `suffix` taken off the end if it
+/// isn't the whole name.
+pub fn basename(args: &[String]) -> Result<String, String> {
+    let mut multiple = false;
+    let mut suffix = None;
+    let mut paths = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-a" => multiple = true,
+            "-s" => {
+                let s = args
+                    .next()
+                    .ok_or_else(|| String::from("basename: -s: option requires an argument"))?;
+                multiple = true;
+                suffix = Some(s.as_str());
+            }
+            path => paths.push(path),
+        }
+    }
+
+    if !multiple {
+        match paths[..] {
+            [path] => paths = vec![path],
+            [path, s] => {
+                paths = vec![path];
+                suffix = Some(s);
+            }
+            [] => return Err(String::from("basename: missing operand")),
+            _ => return Err(format!("basename: extra operand '{}'", paths[2])),
+        }
+    } else if paths.is_empty() {
+        return Err(String::from("basename: missing operand"));
+    }
+
+    let mut output = String::new();
+    for path in paths {
+        let trimmed = path.trim_end_matches('/');
+        let name = match trimmed.rsplit_once('/') {
+            Some((_, name)) => name,
+            // either there were no slashes, or it was all slashes
+            None if trimmed.is_empty() && !path.is_empty() => "/",
+            None => trimmed,
+        };
+        let name = match suffix {
+            Some(suffix) if name != suffix => name.strip_suffix(suffix).unwrap_or(name),
+            _ => name,
+        };
+        output.push_str(name);
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Runs the `dirname` builtin: `dirname path...`, returning what should be
+/// printed.
+///
+/// Prints each path without its last part, or `.` if there's nothing left.
+pub fn dirname(args: &[String]) -> Result<String, String> {
+    if args.is_empty() {
+        return Err(String::from("dirname: missing operand"));
+    }
+
+    let mut output = String::new();
+    for path in args {
+        let trimmed = path.trim_end_matches('/');
+        let dir = match trimmed.rsplit_once('/') {
+            Some((dir, _)) => match dir.trim_end_matches('/') {
+                "" => "/",
+                dir => dir,
+            },
+            None if trimmed.is_empty() && !path.is_empty() => "/",
+            None => ".",
+        };
+        output.push_str(dir);
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Runs the `realpath` builtin: `realpath [-m] path...`, returning what should
+/// be printed.
+///
+/// Prints each path made absolute, with symlinks resolved and `.` and `..`
+/// taken out. Every path has to exist, unless `-m` is given, in which case
+/// they're only tidied up and symlinks are left alone.
+pub fn realpath(args: &[String]) -> Result<String, String> {
+    let missing_ok = args.iter().any(|arg| arg == "-m");
+    let paths: Vec<&String> = args.iter().filter(|arg| *arg != "-m").collect();
+    if paths.is_empty() {
+        return Err(String::from("realpath: missing operand"));
+    }
+
+    let mut output = String::new();
+    for path in paths {
+        let resolved = if missing_ok {
+            let cwd = std::env::current_dir().map_err(|error| format!("realpath: {error}"))?;
+            normalize(&cwd.join(path))
+        } else {
+            fs::canonicalize(path).map_err(|error| format!("realpath: {path}: {error}"))?
+        };
+        output.push_str(&resolved.to_string_lossy());
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Takes `.` and `..` out of a path without looking at the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Runs the `stat` builtin: `stat [-L] path...`, returning what should be
+/// printed.
+///
+/// Shows each file's metadata. If the file is an entity, its components are
+/// listed after, which is handy for checking on game content. `-L` follows
+/// symlinks.
+pub fn stat(args: &[String]) -> Result<String, String> {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
+
+    let follow = args.iter().any(|arg| arg == "-L");
+    let paths: Vec<&String> = args.iter().filter(|arg| *arg != "-L").collect();
+    if paths.is_empty() {
+        return Err(String::from("stat: missing operand"));
+    }
+
+    let mut output = String::new();
+    for path in paths {
+        let metadata = if follow {
+            fs::metadata(path)
+        } else {
+            fs::symlink_metadata(path)
+        }
+        .map_err(|error| format!("stat: cannot stat '{path}': {error}"))?;
+
+        let file_type = metadata.file_type();
+        let kind = if file_type.is_symlink() {
+            "symbolic link"
+        } else if file_type.is_dir() {
+            "directory"
+        } else if file_type.is_fifo() {
+            "fifo"
+        } else if file_type.is_socket() {
+            "socket"
+        } else if file_type.is_block_device() {
+            "block special file"
+        } else if file_type.is_char_device() {
+            "character special file"
+        } else if metadata.len() == 0 {
+            "regular empty file"
+        } else {
+            "regular file"
+        };
+
+        let name = match fs::read_link(path) {
+            Ok(target) if !follow => format!("{path} -> {}", target.display()),
+            _ => path.clone(),
+        };
+        let time = |secs: i64| DateTime::local(secs).format("%F %T %z");
+
+        output.push_str(&format!("  File: {name}\n"));
+        output.push_str(&format!(
+            "  Size: {:<15} Blocks: {:<10} {kind}\n",
+            metadata.len(),
+            metadata.blocks()
+        ));
+        output.push_str(&format!(
+            "Device: {:<15} Inode: {:<11} Links: {}\n",
+            metadata.dev(),
+            metadata.ino(),
+            metadata.nlink()
+        ));
+        output.push_str(&format!(
+            "Access: ({:04o})  Uid: {:<8} Gid: {}\n",
+            metadata.permissions().mode() & 0o7777,
+            metadata.uid(),
+            metadata.gid()
+        ));
+        output.push_str(&format!("Access: {}\n", time(metadata.atime())));
+        output.push_str(&format!("Modify: {}\n", time(metadata.mtime())));
+        output.push_str(&format!("Change: {}\n", time(metadata.ctime())));
+
+        if metadata.is_file()
+            && let Some(description) = game::describe(path)
+        {
+            output.push_str(&description);
+        }
+    }
+    Ok(output)
+}
+
+/// Runs the `mkfifo` builtin: `mkfifo [-m mode] path...`.
+///
+/// Makes a named pipe at each path, which one command can write into while
+/// another reads from it, like `mkfifo ears; cat ears & echo psst > ears`.
+/// The mode is in octal, and is 666 less the umask if it isn't given.
+pub fn mkfifo(args: &[String]) -> Result<(), String> {
+    let (mode, paths) = match args {
+        [flag, mode, paths @ ..] if flag == "-m" => {
+            let mode = u32::from_str_radix(mode, 8)
+                .ok()
+                .filter(|mode| *mode <= 0o7777)
+                .ok_or_else(|| format!("mkfifo: invalid mode '{mode}'"))?;
+            (mode, paths)
+        }
+        [flag] if flag == "-m" => return Err(String::from("mkfifo: -m: missing mode")),
+        paths => (0o666, paths),
+    };
+    if paths.is_empty() {
+        return Err(String::from("mkfifo: missing operand"));
+    }
+    for path in paths {
+        let c_path = std::ffi::CString::new(path.as_str())
+            .map_err(|_| format!("mkfifo: cannot create fifo '{path}': invalid name"))?;
+        // SAFETY: c_path is a valid C string for the length of the call
+        if unsafe { libc::mkfifo(c_path.as_ptr(), mode as libc::mode_t) } == -1 {
+            let error = io::Error::last_os_error();
+            return Err(format!("mkfifo: cannot create fifo '{path}': {error}"));
+        }
+        // the umask only takes bits away, so a mode that was asked for is
+        // set again, like coreutils does
+        if args.first().is_some_and(|flag| flag == "-m") {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(path, fs::Permissions::from_mode(mode))
+                .map_err(|error| format!("mkfifo: {path}: {error}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Changes to the environment a command is run with, from `env` or from
+/// `NAME=value` assignments in front of the command.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EnvOverrides {
+    /// Start from an empty environment, like `env -i`.
+    pub clear: bool,
+    /// Variables to leave out, like `env -u NAME`.
+    pub unset: Vec<String>,
+    /// Variables to set.
+    pub set: Vec<(String, String)>,
+}
+
+/// Splits a `NAME=value` assignment, if `word` is one.
+pub fn assignment(word: &str) -> Option<(&str, &str)> {
+    word.split_once('=')
+        .filter(|(name, _)| variables::is_valid_name(name))
+}
+
+impl EnvOverrides {
+    /// Parses the arguments to `env`: `env [-i] [-u name]... [name=value]...
+    /// [command [arg]...]`. Returns the overrides and the command, if any.
+    pub fn parse(args: &[String]) -> Result<(EnvOverrides, &[String]), String> {
+        let mut overrides = EnvOverrides::default();
+        let mut i = 0;
+        while let Some(arg) = args.get(i) {
+            match arg.as_str() {
+                "-i" | "-" => overrides.clear = true,
+                "-u" => {
+                    i += 1;
+                    let name = args
+                        .get(i)
+                        .ok_or_else(|| String::from("env: -u: option requires an argument"))?;
+                    overrides.unset.push(name.clone());
+                }
+                flag if flag.starts_with('-') => {
+                    return Err(format!("env: {flag}: invalid option"));
+                }
+                word => match assignment(word) {
+                    Some((name, value)) => {
+                        overrides.set.push((name.to_string(), value.to_string()))
+                    }
+                    None => break,
+                },
+            }
+            i += 1;
+        }
+        Ok((overrides, &args[i..]))
+    }
+
+    /// Sets `name` to `value` for every command, like `export`.
+    pub fn export(&mut self, name: &str, value: &str) {
+        self.unset.retain(|unset| unset != name);
+        self.set.retain(|(set, _)| set != name);
+        self.set.push((name.to_string(), value.to_string()));
+    }
+
+    /// Leaves `name` out for every command, like `unset`.
+    pub fn remove(&mut self, name: &str) {
+        self.set.retain(|(set, _)| set != name);
+        if !self.unset.iter().any(|unset| unset == name) {
+            self.unset.push(name.to_string());
+        }
+    }
+
+    /// Whether `name` is left out, even if the shell itself was given it.
+    pub fn removes(&self, name: &str) -> bool {
+        self.clear || self.unset.iter().any(|unset| unset == name)
+    }
+
+    /// These overrides with `then` applied on top, like the shell's exports
+    /// and then a command's own `NAME=value`s.
+    pub fn then(&self, then: EnvOverrides) -> EnvOverrides {
+        if then.clear {
+            return then;
+        }
+        let mut set: Vec<(String, String)> = self
+            .set
+            .iter()
+            .filter(|(name, _)| !then.unset.contains(name))
+            .cloned()
+            .collect();
+        set.extend(then.set);
+        let mut unset = self.unset.clone();
+        unset.extend(then.unset);
+        EnvOverrides {
+            clear: self.clear,
+            unset,
+            set,
+        }
+    }
+
+    /// Applies the overrides to a command about to be spawned.
+    pub fn apply(&self, command: &mut tokio::process::Command) {
+        if self.clear {
+            command.env_clear();
+        }
+        for name in &self.unset {
+            command.env_remove(name);
+        }
+        command.envs(self.set.iter().map(|(name, value)| (name, value)));
+    }
+
+    /// Lists the environment with the overrides applied, one `NAME=value` per
+    /// line, like `env` without a command.
+    pub fn listing(&self) -> String {
+        let mut vars: Vec<(String, String)> = if self.clear {
+            Vec::new()
+        } else {
+            std::env::vars()
+                .filter(|(name, _)| !self.unset.contains(name))
+                .collect()
+        };
+        for (name, value) in &self.set {
+            vars.retain(|(existing, _)| existing != name);
+            vars.push((name.clone(), value.clone()));
+        }
+
+        let mut output = String::new();
+        for (name, value) in vars {
+            output.push_str(&format!("{name}={value}\n"));
+        }
+        output
+    }
+}
+
+/// Runs the `export` builtin: `export [name[=value]]...`.
+///
+/// Each variable is set in the shell and passed on to every command it runs
+/// from then on. A bare `name` exports the shell variable of that name. With
+/// no names, lists what commands are given, as `export` lines.
+pub fn export(
+    args: &[String],
+    variables: &mut Variables,
+    environment: &mut EnvOverrides,
+) -> Result<String, String> {
+    if args.is_empty() {
+        let listing = environment.listing();
+        return Ok(listing
+            .lines()
+            .map(|line| format!("export {line}\n"))
+            .collect());
+    }
+    for arg in args {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None),
+        };
+        if !variables::is_valid_name(name) {
+            return Err(format!("export: {arg}: not a valid identifier"));
+        }
+        match value {
+            Some(value) => {
+                variables.set(name, value)?;
+                environment.export(name, value);
+            }
+            None => {
+                if let Some(value) = variables.get(name) {
+                    environment.export(name, value);
+                }
+            }
+        }
+    }
+    Ok(String::new())
+}
+
+/// Runs the `unset` builtin: `unset name...`.
+///
+/// Each variable is removed from the shell, and left out of the environment
+/// of every command it runs from then on.
+pub fn unset(
+    args: &[String],
+    variables: &mut Variables,
+    environment: &mut EnvOverrides,
+) -> Result<(), String> {
+    for name in args {
+        if !variables::is_valid_name(name) {
+            return Err(format!("unset: {name}: not a valid identifier"));
+        }
+        variables.unset(name);
+        environment.remove(name);
+    }
+    Ok(())
+}
+
+/// Changes the terminal's settings with `stty`, if we're in a terminal.
+fn stty(setting: &str) {
+    Command::new("stty")
+        .arg(setting)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_str(expr: &str) -> Result<bool, String> {
+        let args: Vec<&str> = expr.split_whitespace().collect();
+        evaluate(&args)
+    }
+
+    #[test]
+    fn rsh_wraps_ssh() {
+        let args = |words: &[&str]| words.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            rsh(&args(&["cave", "ls", "-l"]), true),
+            Ok(args(&["-t", "cave", "ls", "-l"]))
+        );
+        assert_eq!(rsh(&args(&["cave"]), false), Ok(args(&["cave"])));
+        assert!(rsh(&args(&[]), true).is_err());
+        assert!(rsh(&args(&["-oProxyCommand=sh", "x"]), true).is_err());
+    }
+
+    #[test]
+    fn test_strings_and_integers() {
+        assert_eq!(test_str(""), Ok(false));
+        assert_eq!(test_str("goblin"), Ok(true));
+        assert_eq!(test_str("-n goblin"), Ok(true));
+        assert_eq!(test_str("goblin = goblin"), Ok(true));
+        assert_eq!(test_str("goblin != goblin"), Ok(false));
+        assert_eq!(test_str("3 -lt 10"), Ok(true));
+        assert_eq!(test_str("-3 -ge 10"), Ok(false));
+        assert!(test_str("three -lt 10").is_err());
+        assert!(test_str("1 -frob 2").is_err());
+    }
+
+    #[test]
+    fn test_combines_expressions() {
+        assert_eq!(test_str("! 1 -eq 2"), Ok(true));
+        assert_eq!(test_str("1 -eq 1 -a 2 -eq 3"), Ok(false));
+        assert_eq!(test_str("1 -eq 1 -o 2 -eq 3"), Ok(true));
+        assert_eq!(test_str("1 -eq 2 -o 1 -eq 1 -a ! 2 -eq 3"), Ok(true));
+    }
+
+    fn printf_str(args: &str) -> Result<String, String> {
+        let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+        printf(&args)
+    }
+
+    #[test]
+    fn printf_formats() {
+        assert_eq!(printf_str("%s:%d\\n goblin 5").unwrap(), "goblin:5\n");
+        assert_eq!(printf_str("%x/%X/%o 255 255 8").unwrap(), "ff/FF/10");
+        assert_eq!(printf_str("[%5s][%-5s] ab cd").unwrap(), "[   ab][cd   ]");
+        assert_eq!(printf_str("%03d|%04d 7 -7").unwrap(), "007|-007");
+        assert_eq!(printf_str("100%%").unwrap(), "100%");
+        assert_eq!(printf_str("\\tx\\\\").unwrap(), "\tx\\");
+    }
+
+    #[test]
+    fn printf_reuses_the_format() {
+        assert_eq!(printf_str("%s=%d; a 1 b 2 c").unwrap(), "a=1;b=2;c=0;");
+        assert_eq!(printf_str("hi a b").unwrap(), "hi");
+        assert_eq!(printf_str("%s-%s").unwrap(), "-");
+    }
+
+    #[test]
+    fn printf_validates() {
+        assert!(printf_str("").is_err());
+        assert!(printf_str("%d goblin").is_err());
+        assert!(printf_str("%q x").is_err());
+        assert!(printf_str("50%").is_err());
+    }
+
+    #[test]
+    fn sleep_durations_add_up() {
+        let duration = |args: &str| {
+            let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+            sleep_duration(&args)
+        };
+        assert_eq!(duration("1.5"), Ok(Duration::from_millis(1500)));
+        assert_eq!(duration("1m 30s"), Ok(Duration::from_secs(90)));
+        assert_eq!(duration("1h 1d"), Ok(Duration::from_secs(25 * 60 * 60)));
+        assert!(duration("").is_err());
+        assert!(duration("-1").is_err());
+        assert!(duration("soon").is_err());
+        assert!(duration("5y").is_err());
+    }
+
+    fn seq_str(args: &str) -> Result<String, String> {
+        let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+        seq(&args)
+    }
+
+    #[test]
+    fn seq_counts() {
+        assert_eq!(seq_str("3").unwrap(), "1\n2\n3\n");
+        assert_eq!(seq_str("-1 1").unwrap(), "-1\n0\n1\n");
+        assert_eq!(seq_str("10 -5 0").unwrap(), "10\n5\n0\n");
+        assert_eq!(seq_str("0 0.5 1.5").unwrap(), "0.0\n0.5\n1.0\n1.5\n");
+        assert_eq!(seq_str("-s , 3").unwrap(), "1,2,3\n");
+        assert_eq!(seq_str("-w 8 10").unwrap(), "08\n09\n10\n");
+        assert_eq!(seq_str("-w -1 1").unwrap(), "-1\n00\n01\n");
+        assert_eq!(seq_str("5 1").unwrap(), "");
+        assert!(seq_str("").is_err());
+        assert!(seq_str("1 0 5").is_err());
+        assert!(seq_str("one").is_err());
+    }
+
+    #[test]
+    fn shuf_keeps_every_line() {
+        use rand::{SeedableRng, rngs::StdRng};
+
+        let lines: Vec<String> = (1..=20).map(|n| n.to_string()).collect();
+        let mut rng = StdRng::seed_from_u64(7);
+
+        let mut shuffled = shuffle(lines.clone(), None, &mut rng);
+        assert_ne!(shuffled, lines);
+        shuffled.sort_by_key(|n| n.parse::<u32>().unwrap());
+        assert_eq!(shuffled, lines);
+
+        let some = shuffle(lines.clone(), Some(3), &mut rng);
+        assert_eq!(some.len(), 3);
+        assert!(some.iter().all(|n| lines.contains(n)));
+    }
+
+    fn strings(args: &str) -> Vec<String> {
+        args.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn paths_split() {
+        let basename_str = |args: &str| basename(&strings(args)).unwrap();
+        assert_eq!(basename_str("/usr/lib/"), "lib\n");
+        assert_eq!(basename_str("goblin.txt .txt"), "goblin\n");
+        assert_eq!(basename_str(".txt .txt"), ".txt\n");
+        assert_eq!(basename_str("//"), "/\n");
+        assert_eq!(basename_str("-s .txt a/x.txt b/y.txt"), "x\ny\n");
+        assert!(basename(&strings("a b c")).is_err());
+
+        let dirname_str = |args: &str| dirname(&strings(args)).unwrap();
+        assert_eq!(dirname_str("/usr/lib/ lib /lib a//b"), "/usr\n.\n/\na\n");
+        assert_eq!(dirname_str("/"), "/\n");
+    }
+
+    #[test]
+    fn paths_normalize() {
+        assert_eq!(
+            normalize(Path::new("/dungeon/./crypt/../hall/")),
+            Path::new("/dungeon/hall")
+        );
+        assert_eq!(normalize(Path::new("/../..")), Path::new("/"));
+    }
+
+    #[test]
+    fn exported_variables_reach_commands() {
+        let (mut variables, mut environment) = (Variables::default(), EnvOverrides::default());
+        variables.set("LEVEL", "3").unwrap();
+        let args = |s: &str| -> Vec<String> { s.split_whitespace().map(String::from).collect() };
+        export(
+            &args("MONSTER=goblin LEVEL"),
+            &mut variables,
+            &mut environment,
+        )
+        .unwrap();
+        assert_eq!(variables.get("MONSTER"), Some("goblin"));
+        assert_eq!(
+            environment.set,
+            [
+                (String::from("MONSTER"), String::from("goblin")),
+                (String::from("LEVEL"), String::from("3"))
+            ]
+        );
+        assert!(
+            export(&args("MONSTER=orc"), &mut variables, &mut environment)
+                .unwrap()
+                .is_empty()
+        );
+        let listing = export(&[], &mut variables, &mut environment).unwrap();
+        assert!(listing.contains("export MONSTER=orc\n"));
+        assert_eq!(
+            export(&args("2x=1"), &mut variables, &mut environment),
+            Err(String::from("export: 2x=1: not a valid identifier"))
+        );
+
+        unset(&args("MONSTER HOME"), &mut variables, &mut environment).unwrap();
+        assert_eq!(variables.get("MONSTER"), None);
+        assert!(environment.removes("HOME") && environment.removes("MONSTER"));
+        assert!(!environment.listing().contains("MONSTER="));
+    }
+
+    #[test]
+    fn env_parses_overrides() {
+        let args: Vec<String> = "-u HOME A=1 -i B=x=y ls -la"
+            .split_whitespace()
+            .map(String::from)
+            .collect();
+        let (overrides, command) = EnvOverrides::parse(&args).unwrap();
+        assert_eq!(
+            overrides,
+            EnvOverrides {
+                clear: true,
+                unset: vec![String::from("HOME")],
+                set: vec![
+                    (String::from("A"), String::from("1")),
+                    (String::from("B"), String::from("x=y"))
+                ],
+            }
+        );
+        assert_eq!(command, ["ls", "-la"]);
+
+        let listing = overrides.listing();
+        assert_eq!(listing, "A=1\nB=x=y\n");
+
+        let mut exported = EnvOverrides::default();
+        exported.export("A", "0");
+        exported.export("C", "3");
+        exported.remove("HOME");
+        let combined = exported.then(overrides);
+        assert_eq!(combined.listing(), "A=1\nB=x=y\n");
+        let mut without_c = EnvOverrides::default();
+        without_c.remove("C");
+        let combined = exported.then(without_c);
+        assert_eq!(combined.set, [(String::from("A"), String::from("0"))]);
+        assert!(combined.removes("HOME") && combined.removes("C"));
+
+        assert_eq!(assignment("9=x"), None);
+        assert_eq!(assignment("=x"), None);
+        assert_eq!(assignment("HP="), Some(("HP", "")));
+    }
+
+    #[test]
+    fn test_files() {
+        assert_eq!(test_str("-d ."), Ok(true));
+        assert_eq!(test_str("-f ."), Ok(false));
+        assert_eq!(test_str("-e Cargo.toml"), Ok(true));
+        assert_eq!(test_str("-E Cargo.toml"), Ok(false));
+        assert_eq!(test_str("-c /dev/null"), Ok(true));
+        assert_eq!(test_str("-p /dev/null"), Ok(false));
+    }
+
+    #[test]
+    fn fifos_are_made() {
+        let dir = std::env::temp_dir().join(format!("rlsh-mkfifo-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ears").display().to_string();
+        mkfifo(&strings(&format!("-m 600 {path}"))).unwrap();
+        assert_eq!(test_str(&format!("-p {path}")), Ok(true));
+        assert!(stat(&strings(&path)).unwrap().contains("fifo"));
+        assert!(mkfifo(&strings(&path)).unwrap_err().contains("File exists"));
+        assert!(mkfifo(&strings("-m 9 x")).is_err());
+        assert!(mkfifo(&[]).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn read_splits_fields() {
+        assert_eq!(
+            split_fields("  hello there  brave adventurer ", &["a", "b"]),
+            vec![("a", "hello"), ("b", "there  brave adventurer")]
+        );
+        assert_eq!(
+            split_fields("goblin", &["a", "b", "c"]),
+            vec![("a", "goblin"), ("b", ""), ("c", "")]
+        );
+        assert_eq!(
+            split_fields(" whole line ", &["REPLY"]),
+            vec![("REPLY", "whole line")]
+        );
+    }
+}
@@ -0,0 +1,81 @@
+//! Catching what the shell prints, for `$(command)`.
+//!
+//! Builtins print straight to stdout and programs inherit it, so rather than
+//! threading a writer through everything, stdout itself is pointed at a pipe
+//! while the command runs. What comes out of the other end is read on a
+//! blocking task as it arrives, so a command that prints more than a pipe
+//! holds doesn't wait forever for someone to read it.
+
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use tokio::task;
+
+/// How many captures are going on, since they can be nested.
+static CAPTURING: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether stdout is being caught, so colours and labels meant for people
+/// should be left out.
+pub fn active() -> bool {
+    CAPTURING.load(Ordering::Relaxed) > 0
+}
+
+/// Makes a pipe, returning its read and write ends. Neither is passed on to
+/// programs unless they're given to them.
+pub(super) fn pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    // SAFETY: pipe2 fills in fds with two new file descriptors, which are
+    // owned from here on
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) })
+}
+
+/// Points stdout at `fd`, returning a copy of where it pointed before.
+fn redirect_stdout(fd: &OwnedFd) -> io::Result<OwnedFd> {
+    io::stdout().flush()?;
+    // SAFETY: dup and dup2 only make new file descriptors, and the copy dup
+    // returns is owned from here on
+    unsafe {
+        let saved = libc::dup(libc::STDOUT_FILENO);
+        if saved == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let saved = OwnedFd::from_raw_fd(saved);
+        if libc::dup2(fd.as_raw_fd(), libc::STDOUT_FILENO) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(saved)
+    }
+}
+
+/// Runs `f` with everything printed to stdout caught, returning what it
+/// returned and what was printed.
+///
+/// Background jobs it starts print into it too, and it waits for them to
+/// finish, like in other shells.
+pub async fn stdout<T>(f: impl Future<Output = T>) -> io::Result<(T, String)> {
+    let (read, write) = pipe()?;
+    let saved = redirect_stdout(&write)?;
+    // only stdout is left writing to the pipe now
+    drop(write);
+    let reader = task::spawn_blocking(move || {
+        let mut output = Vec::new();
+        File::from(read).read_to_end(&mut output).map(|_| output)
+    });
+
+    CAPTURING.fetch_add(1, Ordering::Relaxed);
+    let result = f.await;
+    CAPTURING.fetch_sub(1, Ordering::Relaxed);
+
+    // putting stdout back closes the last of the pipe, so the reader finishes
+    drop(redirect_stdout(&saved)?);
+    drop(saved);
+    let output = reader.await.map_err(io::Error::other)??;
+    Ok((result, String::from_utf8_lossy(&output).into_owned()))
+}
@@ -0,0 +1,114 @@
+//! Sharing the line editor's kills with the system clipboard.
+//!
+//! With `config clipboard osc52`, text killed in the editor is also copied to
+//! the clipboard with an OSC 52 escape sequence, and yanking asks the terminal
+//! for what's on the clipboard first. This goes through the terminal rather
+//! than talking to the clipboard directly, so it works over SSH too. Terminals
+//! that won't share the clipboard are just ignored, and yanking falls back on
+//! the editor's own kills.
+
+use std::io::{self, Write};
+
+use super::editor::{input_waiting, read_byte};
+use crate::config::Config;
+
+/// How long to wait for the terminal to say what's on the clipboard.
+const REPLY_TIMEOUT_MS: i32 = 100;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Whether kills go to the system clipboard.
+pub fn enabled() -> bool {
+    Config::load().get("clipboard") == "osc52"
+}
+
+fn encode(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let (mut bits, mut n) = (0, 0u32);
+    for c in text.bytes().filter(|&c| c != b'=') {
+        let value = BASE64.iter().position(|&b| b == c)? as u32;
+        n = n << 6 | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+/// Puts `text` on the clipboard.
+pub fn copy(text: &str) {
+    let mut stdout = io::stdout();
+    let _ = write!(stdout, "\x1b]52;c;{}\x07", encode(text.as_bytes()));
+    let _ = stdout.flush();
+}
+
+/// Pulls the clipboard out of the terminal's reply to a query, which looks
+/// like `\x1b]52;c;<base64>` and then a bell or `\x1b\`.
+fn parse_reply(reply: &[u8]) -> Option<String> {
+    let reply = std::str::from_utf8(reply).ok()?;
+    let data = reply.strip_prefix("\x1b]52;")?.split_once(';')?.1;
+    let data = data
+        .strip_suffix('\x07')
+        .or_else(|| data.strip_suffix("\x1b\\"))?;
+    String::from_utf8(decode(data)?).ok()
+}
+
+/// Asks the terminal what's on the clipboard. The terminal must be in raw
+/// mode, so the reply can be read.
+pub fn paste() -> Option<String> {
+    let mut stdout = io::stdout();
+    let _ = write!(stdout, "\x1b]52;c;?\x07");
+    let _ = stdout.flush();
+
+    let mut reply = Vec::new();
+    while input_waiting(REPLY_TIMEOUT_MS) {
+        let byte = read_byte().ok()??;
+        reply.push(byte);
+        if byte == b'\x07' || reply.ends_with(b"\x1b\\") {
+            break;
+        }
+    }
+    parse_reply(&reply).filter(|text| !text.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clipboard_round_trips() {
+        for text in ["", "a", "ab", "abc", "git status", "ünïcödé"] {
+            assert_eq!(decode(&encode(text.as_bytes())).unwrap(), text.as_bytes());
+        }
+        assert_eq!(encode(b"ls -l"), "bHMgLWw=");
+        assert_eq!(
+            parse_reply(b"\x1b]52;c;bHMgLWw=\x07").as_deref(),
+            Some("ls -l")
+        );
+        assert_eq!(
+            parse_reply(b"\x1b]52;c;bHMgLWw=\x1b\\").as_deref(),
+            Some("ls -l")
+        );
+        assert_eq!(parse_reply(b""), None);
+    }
+}
@@ -0,0 +1,206 @@
+//! Working out what the word being typed could be, for Tab completion.
+//!
+//! The first word of a command completes to builtins, game verbs and programs
+//! on `$PATH`. Other words complete from the command's spec if it has one
+//! (see [`super::specs`]), and otherwise to file names.
+
+use std::{env, fs, os::unix::fs::PermissionsExt};
+
+use super::{highlight::is_separator, specs::Specs};
+
+/// Something the word being typed could be.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Candidate {
+    /// What the word would be replaced with.
+    pub text: String,
+    /// What it is, shown next to it in the menu. Can be empty.
+    pub description: String,
+}
+
+impl Candidate {
+    pub fn new(text: impl Into<String>, description: impl Into<String>) -> Candidate {
+        Candidate {
+            text: text.into(),
+            description: description.into(),
+        }
+    }
+}
+
+/// The ways the word at the end of a line could be finished.
+#[derive(Debug, Default)]
+pub struct Completions {
+    /// Where the word starts, as a character index into the line.
+    pub start: usize,
+    pub candidates: Vec<Candidate>,
+}
+
+/// Every program on `$PATH`.
+pub fn programs() -> Vec<String> {
+    let Some(paths) = env::var_os("PATH") else {
+        return Vec::new();
+    };
+    env::split_paths(&paths)
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .flatten()
+        .filter(|entry| {
+            entry
+                .metadata()
+                .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        })
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect()
+}
+
+/// Files whose path starts with `word`, with a `/` after directories.
+pub fn files(word: &str) -> Vec<Candidate> {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(i) => (&word[..=i], &word[i + 1..]),
+        None => ("", word),
+    };
+    let Ok(entries) = fs::read_dir(if dir.is_empty() { "." } else { dir }) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            // dotfiles only come up when asked for
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            Some(match entry.file_type().is_ok_and(|t| t.is_dir()) {
+                true => Candidate::new(format!("{dir}{name}/"), "directory"),
+                false => Candidate::new(format!("{dir}{name}"), ""),
+            })
+        })
+        .collect()
+}
+
+/// Completes the word at the end of `line`. `verbs` are the game verbs the
+/// player has, with what they do.
+pub fn complete(line: &str, verbs: &[(String, &str)], specs: &mut Specs) -> Completions {
+    let chars: Vec<char> = line.chars().collect();
+    let start = chars
+        .iter()
+        .rposition(|&c| c.is_whitespace() || is_separator(c))
+        .map_or(0, |i| i + 1);
+    let word: String = chars[start..].iter().collect();
+    let command_position = chars[..start]
+        .iter()
+        .rev()
+        .find(|&&c| !c.is_whitespace() || is_separator(c))
+        .is_none_or(|&c| is_separator(c));
+
+    let mut candidates = if command_position && !word.contains('/') {
+        let builtins = super::BUILTINS
+            .iter()
+            .map(|(name, description)| Candidate::new(*name, *description));
+        let verbs = verbs
+            .iter()
+            .map(|(name, description)| Candidate::new(name, *description));
+        let programs = programs().into_iter().map(|name| Candidate::new(name, ""));
+        builtins
+            .chain(verbs)
+            .chain(programs)
+            .filter(|candidate| candidate.text.starts_with(&word))
+            .collect()
+    } else {
+        let command_start = chars[..start]
+            .iter()
+            .rposition(|&c| is_separator(c))
+            .map_or(0, |i| i + 1);
+        let before: String = chars[command_start..start].iter().collect();
+        let mut words = before.split_whitespace();
+        let command = words.next().unwrap_or_default();
+        let typed: Vec<&str> = words.collect();
+        let mut from_spec = specs
+            .get(command)
+            .map(|spec| spec.complete(&typed, &word))
+            .unwrap_or_default();
+        specs.add_help(command, &word, &mut from_spec);
+        match from_spec.is_empty() {
+            true => files(&word),
+            false => from_spec,
+        }
+    };
+    // keep the first of each name, so builtins win over programs
+    candidates.sort_by(|a, b| a.text.cmp(&b.text));
+    candidates.dedup_by(|b, a| a.text == b.text);
+    Completions { start, candidates }
+}
+
+/// The longest text every candidate starts with.
+pub fn common_prefix(candidates: &[Candidate]) -> String {
+    let Some((first, rest)) = candidates.split_first() else {
+        return String::new();
+    };
+    let mut prefix = first.text.as_str();
+    for candidate in rest {
+        let len = prefix
+            .char_indices()
+            .zip(candidate.text.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(prefix.len().min(candidate.text.len()), |((i, _), _)| i);
+        prefix = &prefix[..len];
+    }
+    prefix.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words_complete_by_position() {
+        let verbs = [(String::from("rest"), "sleep to get your hp back")];
+        let mut specs = Specs::default();
+        let completions = complete("echo hi; re", &verbs, &mut specs);
+        assert_eq!(completions.start, 9);
+        let texts: Vec<&str> = completions
+            .candidates
+            .iter()
+            .map(|c| c.text.as_str())
+            .collect();
+        assert!(texts.contains(&"read"));
+        assert!(texts.contains(&"realpath"));
+        assert!(texts.contains(&"rest"));
+        assert!(texts.iter().all(|text| text.starts_with("re")));
+        assert_eq!(
+            completions.candidates.iter().find(|c| c.text == "rest"),
+            Some(&Candidate::new("rest", "sleep to get your hp back"))
+        );
+
+        let root = env!("CARGO_MANIFEST_DIR");
+        let completions = complete(&format!("ls {root}/sr"), &verbs, &mut specs);
+        assert_eq!(completions.start, 3);
+        assert_eq!(
+            completions.candidates,
+            [Candidate::new(format!("{root}/src/"), "directory")]
+        );
+        let completions = complete(
+            &format!("cat {root}/src/shell/complete."),
+            &verbs,
+            &mut specs,
+        );
+        assert_eq!(
+            completions.candidates,
+            [Candidate::new(format!("{root}/src/shell/complete.rs"), "")]
+        );
+        let completions = complete("ls; git sta", &verbs, &mut specs);
+        assert_eq!(completions.start, 8);
+        assert_eq!(
+            completions.candidates,
+            [
+                Candidate::new("stash", "put changes away for later"),
+                Candidate::new("status", "show the working tree status")
+            ]
+        );
+
+        assert_eq!(
+            common_prefix(&[Candidate::new("realpath", ""), Candidate::new("read", "")]),
+            "rea"
+        );
+        assert_eq!(common_prefix(&[Candidate::new("ab", "")]), "ab");
+    }
+}
@@ -0,0 +1,78 @@
+//! Coprocesses, started with `coproc`.
+//!
+//! `coproc NAME { command; }` starts `command` in the background with its
+//! stdin and stdout connected to the shell instead of the terminal, so a
+//! script can keep a helper running and talk to it. The shell sets
+//! `NAME_IN` to the file descriptor to write to it on, `NAME_OUT` to the one
+//! to read what it says from, and `NAME_PID` to its pid:
+//!
+//! ```text
+//! coproc oracle { sed -u s/?/!/; }
+//! echo "is the chest trapped?" >&$oracle_IN
+//! read -u $oracle_OUT answer
+//! ```
+//!
+//! Without a name it's `COPROC`, like in bash, and a simple command doesn't
+//! need the braces: `coproc bc -l`.
+
+use super::variables::is_valid_name;
+
+/// Splits the arguments of `coproc` into the coprocess's name and the words
+/// of its command.
+pub fn parse<'w>(args: &[&'w str]) -> Result<(&'w str, Vec<&'w str>), String> {
+    let (name, rest) = match args {
+        [name, "{", ..] => (*name, &args[1..]),
+        _ => ("COPROC", args),
+    };
+    if !is_valid_name(name) {
+        return Err(format!("coproc: {name}: not a valid identifier"));
+    }
+    let mut command = match rest {
+        ["{", inner @ .., "}"] => inner.to_vec(),
+        ["{", ..] => return Err(String::from("coproc: missing `}'")),
+        _ => rest.to_vec(),
+    };
+    // the `;` before the `}`
+    if let Some(last) = command.last_mut() {
+        *last = last.strip_suffix(';').unwrap_or(last);
+    }
+    command.retain(|word| !word.is_empty());
+    if command[..command.len().saturating_sub(1)]
+        .iter()
+        .any(|word| word.ends_with(';'))
+    {
+        return Err(String::from("coproc: a coprocess can only run one command"));
+    }
+    if command.is_empty() {
+        return Err(String::from("coproc: missing command"));
+    }
+    Ok((name, command))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coprocesses_are_named_and_braced() {
+        assert_eq!(
+            parse(&["oracle", "{", "sed", "-u", "s/?/!/;", "}"]),
+            Ok(("oracle", vec!["sed", "-u", "s/?/!/"]))
+        );
+        assert_eq!(parse(&["{", "cat", ";", "}"]), Ok(("COPROC", vec!["cat"])));
+        assert_eq!(parse(&["bc", "-l"]), Ok(("COPROC", vec!["bc", "-l"])));
+        assert_eq!(
+            parse(&["{", "a;", "b;", "}"]),
+            Err(String::from("coproc: a coprocess can only run one command"))
+        );
+        assert_eq!(
+            parse(&["x-y", "{", "cat;", "}"]),
+            Err(String::from("coproc: x-y: not a valid identifier"))
+        );
+        assert_eq!(
+            parse(&["{", "cat"]),
+            Err(String::from("coproc: missing `}'"))
+        );
+        assert_eq!(parse(&[]), Err(String::from("coproc: missing command")));
+    }
+}
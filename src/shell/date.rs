@@ -0,0 +1,302 @@
+//! Dates and times, for the `date` builtin and anything else that wants to
+//! show the time.
+//!
+//! We only need to turn a Unix timestamp into a calendar date and format it,
+//! so rather than pulling in a whole date crate, this does the arithmetic
+//! itself and asks libc for the local timezone.
+
+use std::{
+    fmt::Write,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const WEEKDAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// The format `date` uses when it isn't given one.
+pub const DEFAULT_FORMAT: &str = "%a %b %e %H:%M:%S %Z %Y";
+
+/// A moment in time, broken down into calendar fields in some timezone.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DateTime {
+    /// Seconds since the Unix epoch.
+    pub timestamp: i64,
+    pub year: i64,
+    /// From 1 to 12.
+    pub month: u32,
+    /// From 1 to 31.
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    /// Days since Sunday, from 0 to 6.
+    pub weekday: u32,
+    /// Days since the 1st of January, from 0 to 365.
+    pub yearday: u32,
+    /// Seconds east of UTC.
+    pub offset: i64,
+    /// The timezone's abbreviation, like `UTC` or `PST`.
+    pub zone: String,
+}
+
+/// Returns the year, month and day of a number of days since the Unix epoch.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+/// Returns the number of days since the Unix epoch of a year, month and day,
+/// the other way round from [`civil_from_days`].
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    // see http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = year - (month <= 2) as i64;
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+impl DateTime {
+    /// Breaks down `timestamp` in a timezone `offset` seconds east of UTC.
+    pub fn new(timestamp: i64, offset: i64, zone: impl Into<String>) -> DateTime {
+        let local = timestamp + offset;
+        let days = local.div_euclid(86400);
+        let secs = local.rem_euclid(86400) as u32;
+        let (year, month, day) = civil_from_days(days);
+
+        let month_starts = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+        let leap_day = (month > 2 && is_leap_year(year)) as u32;
+        DateTime {
+            timestamp,
+            year,
+            month,
+            day,
+            hour: secs / 3600,
+            minute: secs / 60 % 60,
+            second: secs % 60,
+            // the epoch was a Thursday
+            weekday: (days + 4).rem_euclid(7) as u32,
+            yearday: month_starts[month as usize - 1] + day - 1 + leap_day,
+            offset,
+            zone: zone.into(),
+        }
+    }
+
+    pub fn utc(timestamp: i64) -> DateTime {
+        DateTime::new(timestamp, 0, "UTC")
+    }
+
+    /// Breaks down `timestamp` in the local timezone.
+    pub fn local(timestamp: i64) -> DateTime {
+        let time = timestamp as libc::time_t;
+        // SAFETY: tm is plain old data, and localtime_r only writes into it
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        let result = unsafe { libc::localtime_r(&time, &mut tm) };
+        if result.is_null() {
+            return DateTime::utc(timestamp);
+        }
+
+        let zone = if tm.tm_zone.is_null() {
+            String::new()
+        } else {
+            // SAFETY: tm_zone points at a static, nul terminated string
+            unsafe { std::ffi::CStr::from_ptr(tm.tm_zone) }
+                .to_string_lossy()
+                .into_owned()
+        };
+        DateTime::new(timestamp, tm.tm_gmtoff as i64, zone)
+    }
+
+    pub fn now(utc: bool) -> DateTime {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        if utc {
+            DateTime::utc(timestamp)
+        } else {
+            DateTime::local(timestamp)
+        }
+    }
+
+    fn hour12(&self) -> u32 {
+        match self.hour % 12 {
+            0 => 12,
+            hour => hour,
+        }
+    }
+
+    /// Formats the date like `strftime`. Unknown specifiers are left as they
+    /// are.
+    pub fn format(&self, format: &str) -> String {
+        let mut out = String::new();
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                out.push(c);
+                continue;
+            }
+
+            // writing to a String can't fail
+            let _ = match chars.next() {
+                Some('Y') => write!(out, "{}", self.year),
+                Some('C') => write!(out, "{:02}", self.year.div_euclid(100)),
+                Some('y') => write!(out, "{:02}", self.year.rem_euclid(100)),
+                Some('m') => write!(out, "{:02}", self.month),
+                Some('d') => write!(out, "{:02}", self.day),
+                Some('e') => write!(out, "{:>2}", self.day),
+                Some('j') => write!(out, "{:03}", self.yearday + 1),
+                Some('H') => write!(out, "{:02}", self.hour),
+                Some('k') => write!(out, "{:>2}", self.hour),
+                Some('I') => write!(out, "{:02}", self.hour12()),
+                Some('l') => write!(out, "{:>2}", self.hour12()),
+                Some('M') => write!(out, "{:02}", self.minute),
+                Some('S') => write!(out, "{:02}", self.second),
+                Some('p') => write!(out, "{}", if self.hour < 12 { "AM" } else { "PM" }),
+                Some('P') => write!(out, "{}", if self.hour < 12 { "am" } else { "pm" }),
+                Some('a') => write!(out, "{}", &WEEKDAYS[self.weekday as usize][..3]),
+                Some('A') => write!(out, "{}", WEEKDAYS[self.weekday as usize]),
+                Some('b' | 'h') => write!(out, "{}", &MONTHS[self.month as usize - 1][..3]),
+                Some('B') => write!(out, "{}", MONTHS[self.month as usize - 1]),
+                Some('u') => write!(out, "{}", (self.weekday + 6) % 7 + 1),
+                Some('w') => write!(out, "{}", self.weekday),
+                Some('s') => write!(out, "{}", self.timestamp),
+                Some('Z') => write!(out, "{}", self.zone),
+                Some('z') => {
+                    let sign = if self.offset < 0 { '-' } else { '+' };
+                    let offset = self.offset.abs();
+                    write!(out, "{sign}{:02}{:02}", offset / 3600, offset / 60 % 60)
+                }
+                Some('F') => write!(out, "{}", self.format("%Y-%m-%d")),
+                Some('T') => write!(out, "{}", self.format("%H:%M:%S")),
+                Some('R') => write!(out, "{}", self.format("%H:%M")),
+                Some('D') => write!(out, "{}", self.format("%m/%d/%y")),
+                Some('c') => write!(out, "{}", self.format("%a %b %e %H:%M:%S %Y")),
+                Some('x') => write!(out, "{}", self.format("%m/%d/%y")),
+                Some('X') => write!(out, "{}", self.format("%H:%M:%S")),
+                Some('n') => writeln!(out),
+                Some('t') => write!(out, "\t"),
+                Some('%') => write!(out, "%"),
+                Some(other) => write!(out, "%{other}"),
+                None => write!(out, "%"),
+            };
+        }
+        out
+    }
+}
+
+/// Runs the `date` builtin: `date [-u] [-d @seconds] [+format]`, returning
+/// what should be printed.
+pub fn command(args: &[String]) -> Result<String, String> {
+    let mut utc = false;
+    let mut timestamp = None;
+    let mut format = DEFAULT_FORMAT;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-u" => utc = true,
+            "-d" => {
+                let date = args
+                    .next()
+                    .ok_or_else(|| String::from("date: -d: option requires an argument"))?;
+                let seconds = date
+                    .strip_prefix('@')
+                    .and_then(|seconds| seconds.parse::<i64>().ok())
+                    .ok_or_else(|| format!("date: invalid date '{date}'"))?;
+                timestamp = Some(seconds);
+            }
+            arg if arg.starts_with('+') => format = &arg[1..],
+            arg => return Err(format!("date: invalid date '{arg}'")),
+        }
+    }
+
+    let date = match (timestamp, utc) {
+        (Some(timestamp), true) => DateTime::utc(timestamp),
+        (Some(timestamp), false) => DateTime::local(timestamp),
+        (None, utc) => DateTime::now(utc),
+    };
+    Ok(date.format(format) + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_break_down() {
+        let epoch = DateTime::utc(0);
+        assert_eq!((epoch.year, epoch.month, epoch.day), (1970, 1, 1));
+        assert_eq!(epoch.weekday, 4);
+
+        // 2024-02-29T13:05:09Z, a leap day
+        let leap = DateTime::utc(1709211909);
+        assert_eq!((leap.year, leap.month, leap.day), (2024, 2, 29));
+        assert_eq!((leap.hour, leap.minute, leap.second), (13, 5, 9));
+        assert_eq!(leap.yearday, 59);
+
+        assert_eq!(
+            days_from_civil(2024, 2, 29) * 86400,
+            1709211909 - 13 * 3600 - 5 * 60 - 9
+        );
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+
+        let before = DateTime::utc(-1);
+        assert_eq!((before.year, before.month, before.day), (1969, 12, 31));
+        assert_eq!(before.hour, 23);
+    }
+
+    #[test]
+    fn dates_format() {
+        let date = DateTime::utc(1709211909);
+        assert_eq!(date.format(DEFAULT_FORMAT), "Thu Feb 29 13:05:09 UTC 2024");
+        assert_eq!(date.format("%F %T %z"), "2024-02-29 13:05:09 +0000");
+        assert_eq!(
+            date.format("%I:%M %p, %A %B %e, day %j"),
+            "01:05 PM, Thursday February 29, day 060"
+        );
+        assert_eq!(date.format("%s %% %q"), "1709211909 % %q");
+
+        let east = DateTime::new(1709211909, 5 * 3600 + 30 * 60, "IST");
+        assert_eq!(east.format("%H:%M %Z %z"), "18:35 IST +0530");
+        let west = DateTime::new(0, -8 * 3600, "PST");
+        assert_eq!(west.format("%F %R %z"), "1969-12-31 16:00 -0800");
+    }
+}
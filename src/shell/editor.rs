@@ -0,0 +1,1140 @@
+//! Reading commands a key at a time, so the line can be redrawn as it's typed.
+//!
+//! When stdin is a terminal, the shell puts it in raw mode while a command is
+//! being entered and does its own echoing, which lets it colour the line (see
+//! [`super::highlight`]). Anything else, like a script piped in, is read a line
+//! at a time as before.
+//!
+//! While the cursor is at the end of the line, the latest line from the
+//! history that starts the same way is shown dimmed after it, fish style.
+//! Right arrow or End takes the suggestion.
+//!
+//! Abbreviations (see [`super::abbr`]) are expanded when a space is typed
+//! after them, or when the line is entered.
+//!
+//! Pastes are bracketed, so pasting several lines puts them all in the line
+//! being edited instead of running each one as it arrives. They run together
+//! once Enter is pressed.
+//!
+//! Alt-F and Alt-B, or Ctrl with the arrows, move a word at a time, and
+//! Alt-D and Ctrl-W cut the word after or before the cursor.
+//!
+//! Text cut with keys like Ctrl-W goes on a kill ring to be pasted back with
+//! Ctrl-Y, and can be shared with the system clipboard (see
+//! [`super::clipboard`]).
+//!
+//! Every change to the line can be undone with Ctrl-_ and redone with Alt-/,
+//! and Up and Down go back through the history.
+//!
+//! Tab completes the word before the cursor (see [`super::complete`]). When
+//! there's more than one way to finish it, a menu of them opens below the
+//! line: Tab and the arrows move through it, typing narrows it down, Enter
+//! takes the selected one and Escape closes it.
+//!
+//! What each key does is looked up in the [`Keymap`], so it depends on the
+//! keymap setting and on `bind`.
+
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use super::{
+    abbr::Abbreviations,
+    clipboard,
+    complete::{self, Candidate, Completions},
+    highlight::is_separator,
+    history::History,
+    keymap::{Action, Keymap, Mode},
+    terminal,
+};
+use crate::style;
+
+/// How long highlighting may take on a keystroke before the rest of the line
+/// is drawn plain, so typing never lags behind.
+const HIGHLIGHT_BUDGET: Duration = Duration::from_millis(16);
+
+/// How many completions the menu shows at once.
+const MENU_ROWS: usize = 8;
+
+/// How many cut pieces of text the editor remembers.
+const KILL_RING_SIZE: usize = 16;
+
+/// How long to wait after an escape for the rest of an escape sequence before
+/// deciding the escape key was pressed on its own.
+const ESCAPE_TIMEOUT_MS: i32 = 50;
+
+/// How often to check whether the window's changed size while waiting for a
+/// key.
+const RESIZE_CHECK_MS: i32 = 100;
+
+/// A key press, decoded from the bytes the terminal sends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    /// A letter (in lowercase) or `_` pressed with Ctrl.
+    Ctrl(char),
+    /// A character pressed with Alt.
+    Alt(char),
+    Enter,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Escape,
+    /// Tab with Shift. Tab on its own is Ctrl-I.
+    BackTab,
+    /// The start of some pasted text, which [`read_paste`] reads.
+    Paste,
+    /// Not a key, but the window changing size.
+    Resize,
+    Unknown,
+}
+
+/// Puts the terminal in raw mode until dropped.
+struct RawMode {
+    original: libc::termios,
+}
+
+impl RawMode {
+    fn enable() -> io::Result<RawMode> {
+        // SAFETY: termios is plain data, and tcgetattr fills it in.
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut raw = original;
+        // no line buffering, echo, signals from Ctrl-C/Ctrl-Z, Ctrl-V or flow
+        // control. Output processing stays on so `\n` still starts a new line.
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+        raw.c_iflag &= !(libc::IXON | libc::ICRNL | libc::BRKINT | libc::INPCK | libc::ISTRIP);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // have pastes wrapped in `\x1b[200~` and `\x1b[201~`
+        print!("\x1b[?2004h");
+        Ok(RawMode { original })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        print!("\x1b[?2004l");
+        let _ = io::stdout().flush();
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &self.original) };
+    }
+}
+
+/// Reads one byte from stdin, or `None` at the end of input.
+pub(super) fn read_byte() -> io::Result<Option<u8>> {
+    next_byte(false)
+}
+
+/// Reads a byte like [`read_byte`], but if `stop_on_resize`, gives up with an
+/// [`io::ErrorKind::Interrupted`] error when the window changes size.
+fn next_byte(stop_on_resize: bool) -> io::Result<Option<u8>> {
+    // the resize could come just before the read starts, which wouldn't
+    // interrupt it, so check in now and then rather than only waiting on it
+    while stop_on_resize && !input_waiting(RESIZE_CHECK_MS) {
+        if terminal::resized() {
+            return Err(io::ErrorKind::Interrupted.into());
+        }
+    }
+    let mut byte = 0u8;
+    loop {
+        match unsafe { libc::read(libc::STDIN_FILENO, (&mut byte as *mut u8).cast(), 1) } {
+            1 => return Ok(Some(byte)),
+            0 => return Ok(None),
+            _ => {
+                let error = io::Error::last_os_error();
+                if error.kind() != io::ErrorKind::Interrupted
+                    || (stop_on_resize && terminal::resized())
+                {
+                    return Err(error);
+                }
+            }
+        }
+    }
+}
+
+/// Whether there's more input within `timeout_ms`.
+pub(super) fn input_waiting(timeout_ms: i32) -> bool {
+    let mut fd = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    unsafe { libc::poll(&mut fd, 1, timeout_ms) > 0 }
+}
+
+/// How many bytes a UTF-8 character starting with `byte` has.
+fn utf8_len(byte: u8) -> usize {
+    match byte.leading_ones() {
+        2 => 2,
+        3 => 3,
+        4 => 4,
+        _ => 1,
+    }
+}
+
+/// Decodes the part of an escape sequence after `\x1b[` or `\x1bO`.
+fn escape_sequence(seq: &[u8]) -> Key {
+    match seq {
+        b"A" => Key::Up,
+        b"B" => Key::Down,
+        b"C" => Key::Right,
+        b"D" => Key::Left,
+        b"H" | b"1~" | b"7~" => Key::Home,
+        b"F" | b"4~" | b"8~" => Key::End,
+        b"3~" => Key::Delete,
+        b"Z" => Key::BackTab,
+        // Ctrl or Alt with an arrow moves by words, like Alt-F and Alt-B
+        b"1;5C" | b"1;3C" => Key::Alt('f'),
+        b"1;5D" | b"1;3D" => Key::Alt('b'),
+        b"200~" => Key::Paste,
+        _ => Key::Unknown,
+    }
+}
+
+/// Decodes a key from the control byte `byte`.
+fn control(byte: u8) -> Key {
+    match byte {
+        b'\r' | b'\n' => Key::Enter,
+        0x7f | 0x08 => Key::Backspace,
+        0x1b => Key::Escape,
+        0x01..=0x1a => Key::Ctrl((b'a' + byte - 1) as char),
+        0x1f => Key::Ctrl('_'),
+        _ => Key::Unknown,
+    }
+}
+
+/// Reads the next key, or `None` at the end of input.
+fn read_key() -> io::Result<Option<Key>> {
+    let byte = match next_byte(true) {
+        Err(error) if error.kind() == io::ErrorKind::Interrupted => return Ok(Some(Key::Resize)),
+        byte => byte?,
+    };
+    let Some(byte) = byte else {
+        return Ok(None);
+    };
+    if byte == 0x1b {
+        if !input_waiting(ESCAPE_TIMEOUT_MS) {
+            return Ok(Some(Key::Escape));
+        }
+        match read_byte()? {
+            Some(b'[' | b'O') => (),
+            // terminals send Alt with a key as an escape first
+            Some(byte @ b'!'..=b'~') => return Ok(Some(Key::Alt(byte as char))),
+            _ => return Ok(Some(Key::Unknown)),
+        }
+        // parameters, then a final byte between @ and ~
+        let mut seq = Vec::new();
+        while let Some(byte) = read_byte()? {
+            seq.push(byte);
+            if (0x40..=0x7e).contains(&byte) {
+                break;
+            }
+        }
+        return Ok(Some(escape_sequence(&seq)));
+    }
+    if byte < 0x20 || byte == 0x7f {
+        return Ok(Some(control(byte)));
+    }
+
+    let mut bytes = vec![byte];
+    for _ in 1..utf8_len(byte) {
+        match read_byte()? {
+            Some(byte) => bytes.push(byte),
+            None => break,
+        }
+    }
+    Ok(Some(match String::from_utf8(bytes) {
+        Ok(s) => Key::Char(s.chars().next().unwrap()),
+        Err(_) => Key::Unknown,
+    }))
+}
+
+/// Reads pasted text up to the end of the paste, after [`Key::Paste`].
+fn read_paste() -> io::Result<String> {
+    const END: &[u8] = b"\x1b[201~";
+    let mut bytes = Vec::new();
+    while let Some(byte) = read_byte()? {
+        bytes.push(byte);
+        if bytes.ends_with(END) {
+            bytes.truncate(bytes.len() - END.len());
+            break;
+        }
+    }
+    Ok(clean_paste(&String::from_utf8_lossy(&bytes)))
+}
+
+/// Tidies pasted text for the editor: line endings become `\n`, tabs become
+/// spaces, other control characters are dropped, and so is a final newline,
+/// so the paste waits for Enter.
+fn clean_paste(text: &str) -> String {
+    text.replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .replace('\t', " ")
+        .chars()
+        .filter(|&c| c == '\n' || !c.is_control())
+        .collect::<String>()
+        .trim_end_matches('\n')
+        .to_string()
+}
+
+/// The completions shown below the line after Tab.
+#[derive(Debug)]
+struct Menu {
+    /// Where the word being completed starts.
+    start: usize,
+    /// Every way the word could be finished when Tab was pressed.
+    candidates: Vec<Candidate>,
+    /// Which of those still fit what's been typed since.
+    shown: Vec<usize>,
+    /// Which of `shown` is picked, if one is.
+    selected: Option<usize>,
+}
+
+/// Shows `prompt` and waits for a single key, returning the character typed,
+/// or `None` for anything else.
+pub fn ask(prompt: &str) -> io::Result<Option<char>> {
+    let _raw = RawMode::enable()?;
+    print!("{prompt}");
+    io::stdout().flush()?;
+    let answer = match read_key()? {
+        Some(Key::Char(c)) => Some(c),
+        _ => None,
+    };
+    print!("{}\r\n", answer.unwrap_or(' '));
+    Ok(answer)
+}
+
+/// The line being edited and where the cursor is in it.
+#[derive(Debug, Default)]
+pub struct Editor {
+    buffer: Vec<char>,
+    /// Index into `buffer` the cursor is before.
+    cursor: usize,
+    /// How many rows below the prompt's first row the cursor was drawn on.
+    cursor_row: usize,
+    /// Whether highlighting went over budget, so the line is drawn plain.
+    plain: bool,
+    history: History,
+    mode: Mode,
+    /// Text cut from lines, most recent last.
+    kills: Vec<String>,
+    /// Whether kills are shared with the system clipboard.
+    clipboard: bool,
+    /// How the line and cursor were before each change, to undo back to.
+    undo: Vec<(Vec<char>, usize)>,
+    /// Changes that were undone, to redo.
+    redo: Vec<(Vec<char>, usize)>,
+    /// Whether the last change was typing a character, so the next one can
+    /// be undone along with it.
+    typing: bool,
+    /// Which history entry is being shown, if one's been recalled.
+    recalled: Option<usize>,
+    /// The line as it was before recalling history, to come back to.
+    draft: Vec<char>,
+    menu: Option<Menu>,
+    /// What the next line starts out as, instead of empty.
+    prefilled: Option<String>,
+}
+
+impl Editor {
+    pub fn new() -> Editor {
+        Editor::default()
+    }
+
+    /// The lines entered so far, which Up and Down go back through.
+    pub fn history(&mut self) -> &mut History {
+        &mut self.history
+    }
+
+    /// Starts editing a new, empty line.
+    fn clear(&mut self) {
+        self.buffer.clear();
+        self.cursor = 0;
+        self.cursor_row = 0;
+        self.plain = false;
+        self.undo.clear();
+        self.redo.clear();
+        self.typing = false;
+        self.recalled = None;
+        self.menu = None;
+    }
+
+    fn line(&self) -> String {
+        self.buffer.iter().collect()
+    }
+
+    fn insert(&mut self, c: char) {
+        self.buffer.insert(self.cursor, c);
+        self.cursor += 1;
+    }
+
+    fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.buffer.remove(self.cursor);
+        }
+    }
+
+    fn delete(&mut self) {
+        if self.cursor < self.buffer.len() {
+            self.buffer.remove(self.cursor);
+        }
+    }
+
+    /// Expands the word before the cursor if it's an abbreviation in command
+    /// position, returning whether it was.
+    fn expand_abbreviation(&mut self, abbreviations: &Abbreviations) -> bool {
+        let before = &self.buffer[..self.cursor];
+        let start = before
+            .iter()
+            .rposition(|&c| c.is_whitespace() || is_separator(c))
+            .map_or(0, |i| i + 1);
+        let previous = before[..start]
+            .iter()
+            .rev()
+            .find(|&&c| !c.is_whitespace() || is_separator(c));
+        if start == self.cursor || previous.is_some_and(|&c| !is_separator(c)) {
+            return false;
+        }
+        let word: String = before[start..].iter().collect();
+        let Some(expansion) = abbreviations.get(&word) else {
+            return false;
+        };
+        self.buffer.splice(start..self.cursor, expansion.chars());
+        self.cursor = start + expansion.chars().count();
+        true
+    }
+
+    /// The rest of the line the history suggests, if the cursor is at the end
+    /// and there is one.
+    fn suggestion(&self) -> Option<String> {
+        if self.cursor != self.buffer.len() {
+            return None;
+        }
+        let line = self.line();
+        let entry = self.history.suggest(&line)?;
+        Some(entry[line.len()..].to_string())
+    }
+
+    /// Takes the suggested rest of the line, returning whether there was one.
+    fn accept_suggestion(&mut self) -> bool {
+        let Some(rest) = self.suggestion() else {
+            return false;
+        };
+        for c in rest.chars() {
+            self.insert(c);
+        }
+        true
+    }
+
+    /// Cuts `range` out of the line, keeping it to be yanked back.
+    fn kill(&mut self, range: std::ops::Range<usize>) {
+        let text: String = self.buffer.drain(range.clone()).collect();
+        self.cursor = range.start;
+        if text.is_empty() {
+            return;
+        }
+        if self.clipboard {
+            clipboard::copy(&text);
+        }
+        if self.kills.len() == KILL_RING_SIZE {
+            self.kills.remove(0);
+        }
+        self.kills.push(text);
+    }
+
+    /// Pastes what's on the clipboard, or failing that the last kill.
+    fn yank(&mut self) {
+        let text = self
+            .clipboard
+            .then(clipboard::paste)
+            .flatten()
+            .or_else(|| self.kills.last().cloned());
+        for c in text.unwrap_or_default().chars() {
+            self.insert(c);
+        }
+    }
+
+    /// Where the word after the cursor starts.
+    fn next_word(&self) -> usize {
+        let rest = &self.buffer[self.cursor..];
+        let word_end = rest
+            .iter()
+            .position(|c| c.is_whitespace())
+            .unwrap_or(rest.len());
+        let next = rest[word_end..]
+            .iter()
+            .position(|c| !c.is_whitespace())
+            .map_or(rest.len(), |i| word_end + i);
+        self.cursor + next
+    }
+
+    /// Where the word the cursor is in, or the next one, ends.
+    fn word_end(&self) -> usize {
+        let rest = &self.buffer[self.cursor..];
+        let word_start = rest
+            .iter()
+            .position(|c| !c.is_whitespace())
+            .unwrap_or(rest.len());
+        let end = rest[word_start..]
+            .iter()
+            .position(|c| c.is_whitespace())
+            .map_or(rest.len(), |i| word_start + i);
+        self.cursor + end
+    }
+
+    /// Where the word before the cursor starts.
+    fn previous_word(&self) -> usize {
+        let before = &self.buffer[..self.cursor];
+        let Some(word_end) = before.iter().rposition(|c| !c.is_whitespace()) else {
+            return 0;
+        };
+        before[..word_end]
+            .iter()
+            .rposition(|c| c.is_whitespace())
+            .map_or(0, |i| i + 1)
+    }
+
+    /// Remembers the line as it was `before` a change, so it can be undone.
+    /// Characters typed one after another are undone together, like a word.
+    fn record(&mut self, before: (Vec<char>, usize), typing: bool) {
+        if before.0 == self.buffer {
+            self.typing = false;
+            return;
+        }
+        if !(typing && self.typing) {
+            self.undo.push(before);
+        }
+        self.redo.clear();
+        self.typing = typing;
+    }
+
+    /// Swaps the line for the last state in `from`, saving the current one
+    /// in `to`. This is undo or redo, depending on which way round.
+    fn restore(&mut self, undo: bool) {
+        let (from, to) = if undo {
+            (&mut self.undo, &mut self.redo)
+        } else {
+            (&mut self.redo, &mut self.undo)
+        };
+        if let Some((buffer, cursor)) = from.pop() {
+            to.push((std::mem::replace(&mut self.buffer, buffer), self.cursor));
+            self.cursor = cursor;
+        }
+        self.typing = false;
+    }
+
+    /// Replaces the line with the history entry `index`, or the line that was
+    /// being typed for `None`.
+    fn recall(&mut self, index: Option<usize>) {
+        if self.recalled.is_none() {
+            self.draft = self.buffer.clone();
+        }
+        self.buffer = match index {
+            Some(index) => self.history.get(index).unwrap_or("").chars().collect(),
+            None => self.draft.clone(),
+        };
+        self.cursor = self.buffer.len();
+        self.recalled = index;
+    }
+
+    /// Swaps the word from `start` to the cursor for `text`.
+    fn replace_word(&mut self, start: usize, text: &str) {
+        self.buffer.splice(start..self.cursor, text.chars());
+        self.cursor = start + text.chars().count();
+    }
+
+    /// Finishes the word before the cursor with `completions`. One candidate
+    /// is put straight in, and more open the menu after filling in whatever
+    /// they all start with.
+    fn complete(&mut self, completions: Completions) {
+        let Completions { start, candidates } = completions;
+        match candidates.as_slice() {
+            [] => (),
+            [only] => {
+                self.replace_word(start, &only.text);
+                if !only.text.ends_with('/') {
+                    self.insert(' ');
+                }
+            }
+            _ => {
+                let prefix = complete::common_prefix(&candidates);
+                if prefix.chars().count() > self.cursor - start {
+                    self.replace_word(start, &prefix);
+                }
+                self.menu = Some(Menu {
+                    start,
+                    shown: (0..candidates.len()).collect(),
+                    candidates,
+                    selected: None,
+                });
+            }
+        }
+    }
+
+    /// Narrows the menu down to what fits the word as it's now typed, closing
+    /// it if nothing does.
+    fn filter_menu(&mut self) {
+        let Some(menu) = &mut self.menu else {
+            return;
+        };
+        if self.cursor < menu.start {
+            self.menu = None;
+            return;
+        }
+        let word: String = self.buffer[menu.start..self.cursor].iter().collect();
+        menu.shown = (0..menu.candidates.len())
+            .filter(|&i| menu.candidates[i].text.starts_with(&word))
+            .collect();
+        menu.selected = None;
+        if menu.shown.is_empty() {
+            self.menu = None;
+        }
+    }
+
+    /// Moves the menu's selection `forward` or back, wrapping around.
+    fn select(&mut self, forward: bool) {
+        let Some(menu) = &mut self.menu else {
+            return;
+        };
+        let len = menu.shown.len();
+        menu.selected = Some(match (menu.selected, forward) {
+            (None, true) => 0,
+            (None, false) => len - 1,
+            (Some(i), true) => (i + 1) % len,
+            (Some(i), false) => (i + len - 1) % len,
+        });
+    }
+
+    /// Handles `key` while the menu is open, returning whether that's all it
+    /// does. Keys that edit the line also filter the menu, and most others
+    /// close it and then do what they usually do.
+    fn menu_key(&mut self, key: Key) -> bool {
+        let Some(menu) = &self.menu else {
+            return false;
+        };
+        match key {
+            Key::Ctrl('i') | Key::Down => self.select(true),
+            Key::BackTab | Key::Up => self.select(false),
+            Key::Enter if menu.selected.is_some() => {
+                let menu = self.menu.take().unwrap();
+                let chosen = &menu.candidates[menu.shown[menu.selected.unwrap()]];
+                let before = (self.buffer.clone(), self.cursor);
+                self.replace_word(menu.start, &chosen.text);
+                self.record(before, false);
+            }
+            Key::Escape | Key::Ctrl('c' | 'g') => self.menu = None,
+            Key::Char(_) | Key::Backspace | Key::Ctrl('h') => return false,
+            _ => {
+                self.menu = None;
+                return false;
+            }
+        }
+        true
+    }
+
+    /// The lines of the menu, each fitting in `columns`.
+    fn menu_lines(&self, columns: usize) -> Vec<String> {
+        let Some(menu) = &self.menu else {
+            return Vec::new();
+        };
+        // scroll so the selection is on screen
+        let first = menu
+            .selected
+            .map_or(0, |i| (i + 1).saturating_sub(MENU_ROWS));
+        let rows = &menu.shown[first..menu.shown.len().min(first + MENU_ROWS)];
+        let width = rows
+            .iter()
+            .map(|&i| menu.candidates[i].text.chars().count())
+            .max()
+            .unwrap_or(0);
+        let fit = |text: &str, room: usize| -> String { text.chars().take(room).collect() };
+        let room = columns.saturating_sub(1);
+
+        let mut lines = Vec::new();
+        for (row, &i) in rows.iter().enumerate() {
+            let candidate = &menu.candidates[i];
+            let text = fit(&format!("{:width$}", candidate.text), room);
+            let description = match candidate.description.is_empty() {
+                true => String::new(),
+                false => fit(
+                    &format!("  {}", candidate.description),
+                    room.saturating_sub(text.chars().count()),
+                ),
+            };
+            let text = match menu.selected == Some(first + row) {
+                true => style::selected(&text),
+                false => text,
+            };
+            lines.push(format!("{text}{}", style::dim(&description)));
+        }
+        let hidden = menu.shown.len() - rows.len();
+        if hidden > 0 {
+            lines.push(style::dim(&fit(&format!("and {hidden} more"), room)));
+        }
+        lines
+    }
+
+    /// Does what `action` says to the line, so that it can be undone. `key`
+    /// is what was pressed.
+    fn perform(&mut self, action: Action, key: Key, abbreviations: &Abbreviations) {
+        match action {
+            Action::Undo => return self.restore(true),
+            Action::Redo => return self.restore(false),
+            _ => (),
+        }
+        let before = (self.buffer.clone(), self.cursor);
+        self.apply(action, key, abbreviations);
+        let typing = action == Action::SelfInsert && !matches!(key, Key::Char(' '));
+        self.record(before, typing);
+    }
+
+    fn apply(&mut self, action: Action, key: Key, abbreviations: &Abbreviations) {
+        let end = self.buffer.len();
+        match action {
+            Action::SelfInsert => {
+                if let Key::Char(c) = key {
+                    if c == ' ' {
+                        self.expand_abbreviation(abbreviations);
+                    }
+                    self.insert(c);
+                }
+            }
+            Action::DeleteOrEof | Action::DeleteChar => self.delete(),
+            Action::BackwardDeleteChar => self.backspace(),
+            Action::ForwardChar => {
+                if !self.accept_suggestion() {
+                    self.cursor = (self.cursor + 1).min(end);
+                }
+            }
+            Action::BackwardChar => self.cursor = self.cursor.saturating_sub(1),
+            Action::ForwardWord => self.cursor = self.next_word(),
+            Action::BackwardWord => self.cursor = self.previous_word(),
+            Action::BeginningOfLine => self.cursor = 0,
+            Action::EndOfLine => {
+                if self.cursor == end {
+                    self.accept_suggestion();
+                }
+                self.cursor = self.buffer.len();
+            }
+            Action::AcceptSuggestion => {
+                self.accept_suggestion();
+            }
+            Action::KillLine => self.kill(self.cursor..end),
+            Action::BackwardKillLine => self.kill(0..self.cursor),
+            Action::BackwardKillWord => self.kill(self.previous_word()..self.cursor),
+            Action::KillWord => self.kill(self.cursor..self.word_end()),
+            Action::Yank => self.yank(),
+            Action::PreviousHistory => match self.recalled {
+                Some(0) => (),
+                Some(index) => self.recall(Some(index - 1)),
+                None if self.history.len() > 0 => self.recall(Some(self.history.len() - 1)),
+                None => (),
+            },
+            Action::NextHistory => match self.recalled {
+                Some(index) if index + 1 < self.history.len() => self.recall(Some(index + 1)),
+                Some(_) => self.recall(None),
+                None => (),
+            },
+            Action::ViCommandMode => {
+                self.mode = Mode::ViNormal;
+                // like vi, leave insert mode on the last character typed
+                self.cursor = self.cursor.saturating_sub(1);
+            }
+            Action::ViInsert => self.mode = Mode::ViInsert,
+            Action::ViAppend => {
+                self.mode = Mode::ViInsert;
+                self.cursor = (self.cursor + 1).min(end);
+            }
+            Action::ViInsertAtStart => {
+                self.mode = Mode::ViInsert;
+                self.cursor = 0;
+            }
+            Action::ViAppendAtEnd => {
+                self.mode = Mode::ViInsert;
+                self.cursor = end;
+            }
+            // these finish the line, so the caller handles them
+            Action::AcceptLine | Action::Interrupt | Action::Complete => (),
+            Action::Undo | Action::Redo => (),
+        }
+    }
+
+    /// Redraws the prompt and line, leaving the terminal cursor where the
+    /// editor's cursor is. `suggest` is whether to show the suggestion.
+    fn render(&mut self, prompt: &str, highlight: &mut dyn FnMut(&str) -> String, suggest: bool) {
+        let prompt = format!("{}{prompt}", self.mode.indicator());
+        let line = self.line();
+        let shown = if self.plain {
+            line.clone()
+        } else {
+            let start = Instant::now();
+            let shown = highlight(&line);
+            self.plain = start.elapsed() > HIGHLIGHT_BUDGET;
+            shown
+        };
+
+        let columns = terminal::columns().unwrap_or(80);
+        let mut out = String::new();
+        // back to where the prompt starts and clear everything after it
+        if self.cursor_row > 0 {
+            out.push_str(&format!("\x1b[{}A", self.cursor_row));
+        }
+        out.push_str("\r\x1b[J");
+        out.push_str(&prompt);
+        out.push_str(&shown);
+        let suggestion = match suggest && self.menu.is_none() {
+            true => self.suggestion(),
+            false => None,
+        };
+        let suggestion = suggestion.unwrap_or_default();
+        out.push_str(&style::dim(&suggestion));
+
+        let (mut end_row, end_column) =
+            terminal::position(&format!("{prompt}{line}{suggestion}"), columns);
+        // a line that exactly fills its last row leaves the terminal cursor
+        // waiting to wrap, so wrap it now to keep the arithmetic simple
+        if end_column == columns {
+            out.push_str("\r\n");
+            end_row += 1;
+        }
+        for (i, line) in self.menu_lines(columns).iter().enumerate() {
+            // the wrap above already started a new row for the first one
+            if i > 0 || end_column != columns {
+                out.push_str("\r\n");
+                end_row += 1;
+            }
+            out.push_str(line);
+        }
+        let before: String = self.buffer[..self.cursor].iter().collect();
+        let (mut row, mut column) = terminal::position(&format!("{prompt}{before}"), columns);
+        if column == columns {
+            (row, column) = (row + 1, 0);
+        }
+        if end_row > row {
+            out.push_str(&format!("\x1b[{}A", end_row - row));
+        }
+        out.push('\r');
+        if column > 0 {
+            out.push_str(&format!("\x1b[{column}C"));
+        }
+        self.cursor_row = row;
+
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(out.as_bytes());
+        let _ = stdout.flush();
+    }
+
+    /// Starts the next line off as `text`, for it to be edited.
+    pub fn prefill(&mut self, text: &str) {
+        self.prefilled = Some(text.to_string());
+    }
+
+    /// Reads a line from the terminal after showing `prompt`, colouring it
+    /// with `highlight` as it's typed, finishing words with `complete`,
+    /// expanding `abbreviations` and handling keys with `keymap`. Returns
+    /// `None` at the end of input.
+    pub fn read_line(
+        &mut self,
+        prompt: &str,
+        highlight: &mut dyn FnMut(&str) -> String,
+        complete: &mut dyn FnMut(&str) -> Completions,
+        abbreviations: &Abbreviations,
+        keymap: &Keymap,
+    ) -> io::Result<Option<String>> {
+        let _raw = RawMode::enable()?;
+        self.clear();
+        if let Some(text) = self.prefilled.take() {
+            self.buffer = text.chars().collect();
+            self.cursor = self.buffer.len();
+        }
+        self.mode = Mode::initial();
+        self.clipboard = clipboard::enabled();
+        self.render(prompt, highlight, true);
+
+        loop {
+            let Some(key) = read_key()? else {
+                print!("\r\n");
+                return Ok(None);
+            };
+            if key == Key::Resize {
+                // the terminal has rewrapped what was on screen, so work out
+                // where the prompt starts now
+                let columns = terminal::columns().unwrap_or(80);
+                let before: String = self.buffer[..self.cursor].iter().collect();
+                let shown = format!("{}{prompt}{before}", self.mode.indicator());
+                self.cursor_row = terminal::position(&shown, columns).0;
+                self.render(prompt, highlight, true);
+                continue;
+            }
+            if key == Key::Paste {
+                let before = (self.buffer.clone(), self.cursor);
+                for c in read_paste()?.chars() {
+                    self.insert(c);
+                }
+                self.record(before, false);
+                self.render(prompt, highlight, true);
+                continue;
+            }
+            if self.menu_key(key) {
+                self.render(prompt, highlight, true);
+                continue;
+            }
+            let Some(action) = keymap.action(self.mode, key) else {
+                continue;
+            };
+            match action {
+                Action::Complete => {
+                    let before = (self.buffer.clone(), self.cursor);
+                    let line: String = self.buffer[..self.cursor].iter().collect();
+                    self.complete(complete(&line));
+                    self.record(before, false);
+                }
+                Action::AcceptLine => {
+                    self.cursor = self.buffer.len();
+                    self.expand_abbreviation(abbreviations);
+                    self.render(prompt, highlight, false);
+                    print!("\r\n");
+                    let line = self.line();
+                    self.history.add(&line);
+                    return Ok(Some(line));
+                }
+                Action::DeleteOrEof if self.buffer.is_empty() => {
+                    print!("\r\n");
+                    return Ok(None);
+                }
+                Action::Interrupt => {
+                    // give up on the line, like other shells
+                    print!("^C\r\n");
+                    return Ok(Some(String::new()));
+                }
+                action => {
+                    self.perform(action, key, abbreviations);
+                    self.filter_menu();
+                }
+            }
+            self.render(prompt, highlight, true);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_decode() {
+        assert_eq!(escape_sequence(b"D"), Key::Left);
+        assert_eq!(escape_sequence(b"3~"), Key::Delete);
+        assert_eq!(escape_sequence(b"1;5C"), Key::Alt('f'));
+        assert_eq!(escape_sequence(b"1;2C"), Key::Unknown);
+        assert_eq!(escape_sequence(b"200~"), Key::Paste);
+        assert_eq!(clean_paste("cd x\r\n\tls\x07\n\n"), "cd x\n ls");
+        assert_eq!(control(b'\r'), Key::Enter);
+        assert_eq!(control(0x7f), Key::Backspace);
+        assert_eq!(control(0x03), Key::Ctrl('c'));
+        assert_eq!(control(0x1f), Key::Ctrl('_'));
+        assert_eq!(utf8_len("é".as_bytes()[0]), 2);
+        assert_eq!(utf8_len(b'a'), 1);
+
+        let mut editor = Editor::new();
+        for c in "lss".chars() {
+            editor.insert(c);
+        }
+        editor.cursor = 1;
+        editor.backspace();
+        editor.delete();
+        assert_eq!(editor.line(), "s");
+
+        editor.history.add("ssh dungeon");
+        assert_eq!(editor.suggestion(), None);
+        editor.cursor = 1;
+        assert_eq!(editor.suggestion().as_deref(), Some("sh dungeon"));
+        assert!(editor.accept_suggestion());
+        assert_eq!(editor.line(), "ssh dungeon");
+
+        let mut abbreviations = Abbreviations::default();
+        let args = ["l", "ls", "-l"].map(String::from);
+        abbreviations.command(&args).unwrap();
+        editor.clear();
+        for (typed, expands) in [("l", true), (" l", false), ("; l", true)] {
+            for c in typed.chars() {
+                editor.insert(c);
+            }
+            assert_eq!(editor.expand_abbreviation(&abbreviations), expands);
+        }
+        assert_eq!(editor.line(), "ls -l l; ls -l");
+    }
+
+    #[test]
+    fn actions_move_between_words_and_modes() {
+        let mut editor = Editor::new();
+        let abbreviations = Abbreviations::default();
+        let keymap = Keymap::default();
+        editor.mode = Mode::ViInsert;
+        for c in "cd  dungeon".chars() {
+            editor.perform(Action::SelfInsert, Key::Char(c), &abbreviations);
+        }
+        let press = |editor: &mut Editor, key| {
+            let action = keymap.action(editor.mode, key).unwrap();
+            editor.perform(action, key, &abbreviations);
+        };
+        press(&mut editor, Key::Escape);
+        assert_eq!((editor.mode, editor.cursor), (Mode::ViNormal, 10));
+        press(&mut editor, Key::Char('b'));
+        assert_eq!(editor.cursor, 4);
+        press(&mut editor, Key::Char('b'));
+        assert_eq!(editor.cursor, 0);
+        press(&mut editor, Key::Char('w'));
+        assert_eq!(editor.cursor, 4);
+        press(&mut editor, Key::Char('x'));
+        press(&mut editor, Key::Char('I'));
+        press(&mut editor, Key::Char('x'));
+        assert_eq!(editor.mode, Mode::ViInsert);
+        assert_eq!(editor.line(), "xcd  ungeon");
+
+        press(&mut editor, Key::Ctrl('e'));
+        press(&mut editor, Key::Ctrl('w'));
+        assert_eq!(editor.line(), "xcd  ");
+        press(&mut editor, Key::Ctrl('a'));
+        press(&mut editor, Key::Ctrl('y'));
+        assert_eq!(editor.line(), "ungeonxcd  ");
+        press(&mut editor, Key::Ctrl('k'));
+        press(&mut editor, Key::Ctrl('u'));
+        assert_eq!(editor.line(), "");
+        assert_eq!(editor.kills, ["ungeon", "xcd  ", "ungeon"]);
+    }
+
+    #[test]
+    fn words_are_moved_over_and_cut() {
+        let mut editor = Editor::new();
+        let abbreviations = Abbreviations::default();
+        let keymap = Keymap::default();
+        for c in "cat dungeon/map  loot".chars() {
+            editor.perform(Action::SelfInsert, Key::Char(c), &abbreviations);
+        }
+        let press = |editor: &mut Editor, key| {
+            let action = keymap.action(editor.mode, key).unwrap();
+            editor.perform(action, key, &abbreviations);
+        };
+        press(&mut editor, Key::Alt('b'));
+        press(&mut editor, Key::Alt('b'));
+        assert_eq!(editor.cursor, 4);
+        press(&mut editor, Key::Alt('d'));
+        assert_eq!(editor.line(), "cat   loot");
+        press(&mut editor, Key::Alt('d'));
+        assert_eq!(editor.line(), "cat ");
+        press(&mut editor, Key::Ctrl('a'));
+        press(&mut editor, Key::Alt('f'));
+        assert_eq!(editor.cursor, 4);
+        for c in "-n ".chars() {
+            press(&mut editor, Key::Char(c));
+        }
+        assert_eq!(editor.line(), "cat -n ");
+        assert_eq!(editor.kills, ["dungeon/map", "  loot"]);
+    }
+
+    #[test]
+    fn changes_undo_and_redo() {
+        let mut editor = Editor::new();
+        let abbreviations = Abbreviations::default();
+        let keymap = Keymap::default();
+        let press = |editor: &mut Editor, key| {
+            let action = keymap.action(Mode::Emacs, key).unwrap();
+            editor.perform(action, key, &abbreviations);
+        };
+        editor.history.add("ls -l");
+        editor.history.add("cd dungeon");
+        for c in "go west".chars() {
+            press(&mut editor, Key::Char(c));
+        }
+        press(&mut editor, Key::Backspace);
+        press(&mut editor, Key::Up);
+        press(&mut editor, Key::Up);
+        press(&mut editor, Key::Up);
+        assert_eq!(editor.line(), "ls -l");
+        press(&mut editor, Key::Down);
+        press(&mut editor, Key::Down);
+        assert_eq!(editor.line(), "go wes");
+
+        let mut undone = Vec::new();
+        for _ in 0..6 {
+            press(&mut editor, Key::Ctrl('_'));
+            undone.push(editor.line());
+        }
+        assert_eq!(
+            undone,
+            [
+                "cd dungeon",
+                "ls -l",
+                "cd dungeon",
+                "go wes",
+                "go west",
+                "go "
+            ]
+        );
+        press(&mut editor, Key::Ctrl('_'));
+        press(&mut editor, Key::Ctrl('_'));
+        assert_eq!(editor.line(), "");
+        press(&mut editor, Key::Alt('/'));
+        press(&mut editor, Key::Alt('/'));
+        assert_eq!(editor.line(), "go ");
+        press(&mut editor, Key::Char('x'));
+        press(&mut editor, Key::Alt('/'));
+        assert_eq!(editor.line(), "go x");
+    }
+
+    #[test]
+    fn menu_filters_and_picks() {
+        let mut editor = Editor::new();
+        let abbreviations = Abbreviations::default();
+        let candidates = ["realpath", "read", "rest"].map(|text| Candidate {
+            text: text.to_string(),
+            description: String::new(),
+        });
+        let completions = |candidates: &[Candidate]| Completions {
+            start: 3,
+            candidates: candidates.to_vec(),
+        };
+        for c in "ls r".chars() {
+            editor.insert(c);
+        }
+        editor.complete(completions(&candidates[..1]));
+        assert_eq!(editor.line(), "ls realpath ");
+        assert!(editor.menu.is_none());
+
+        editor.clear();
+        for c in "ls r".chars() {
+            editor.insert(c);
+        }
+        editor.complete(completions(&candidates));
+        assert_eq!(editor.line(), "ls re");
+        assert_eq!(editor.menu_lines(80).len(), 3);
+
+        editor.perform(Action::SelfInsert, Key::Char('a'), &abbreviations);
+        editor.filter_menu();
+        assert_eq!(editor.menu.as_ref().unwrap().shown, [0, 1]);
+        assert!(editor.menu_key(Key::BackTab));
+        assert!(editor.menu_key(Key::BackTab));
+        assert!(editor.menu_lines(80)[0].contains("\x1b[7m"));
+        assert!(editor.menu_key(Key::Enter));
+        assert_eq!(editor.line(), "ls realpath");
+        assert!(editor.menu.is_none());
+
+        editor.complete(completions(&candidates));
+        assert!(!editor.menu_key(Key::Char('x')));
+        assert!(!editor.menu_key(Key::Left));
+        assert!(editor.menu.is_none());
+    }
+}
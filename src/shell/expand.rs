@@ -0,0 +1,278 @@
+//! Expanding `$NAME`, `${NAME}`, `$(command)` and `<(command)`.
+//!
+//! This happens after a command line is split into [`Word`]s, which notes
+//! down where the variables are, and before working out what command it is,
+//! so `cd $HOME` goes where it should and `$EDITOR notes` runs the editor.
+//! Variables the shell sets come first, then the environment. Ones that
+//! aren't set expand to nothing. `$?` is the exit status of the last command.
+//!
+//! `$(command)` expands to what `command` prints, without the newlines at the
+//! end, so `cd $(cat last_dir.txt)` goes back to where `last_dir.txt` says.
+//! `<(command)` expands to a file that what it prints can be read from, so
+//! `diff <(ls crypt) <(ls tomb)` compares them.
+//!
+//! Unless the word is quoted or an assignment, what its expansions give is
+//! split into words at the characters in `IFS`, the way POSIX shells do it.
+//! When `IFS` isn't set it's only split at newlines, so a file name with a
+//! space in it stays one word, unless the `posixsplit` option is on.
+
+use std::ops::Range;
+
+use super::{tokenize::Word, variables::is_valid_name};
+
+/// A command to run while expanding.
+#[derive(Debug)]
+pub enum Substitution<'c> {
+    /// From `$(command)`, which expands to what it prints.
+    Output(&'c str),
+    /// From `<(command)`, which expands to a file to read that from.
+    File(&'c str),
+}
+
+/// Replaces each `$NAME` and `${NAME}` in `word` with its value, from
+/// `lookup`, and each `$(command)` and `<(command)` with what `run` gives
+/// for it. Returns where what they expanded to is in the new text.
+fn expand(
+    word: &mut Word,
+    lookup: &impl Fn(&str) -> Option<String>,
+    run: &impl Fn(Substitution) -> String,
+) -> Result<Vec<Range<usize>>, String> {
+    if word.expansions.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut text = String::new();
+    let mut expanded = Vec::new();
+    let mut last = 0;
+    for range in &word.expansions {
+        text.push_str(&word.text[last..range.start]);
+        let start = text.len();
+        let expansion = &word.text[range.clone()];
+        if let Some(command) = expansion
+            .strip_prefix("$(")
+            .and_then(|command| command.strip_suffix(')'))
+        {
+            text.push_str(run(Substitution::Output(command)).trim_end_matches('\n'));
+        } else if let Some(command) = expansion
+            .strip_prefix("<(")
+            .and_then(|command| command.strip_suffix(')'))
+        {
+            text.push_str(&run(Substitution::File(command)));
+        } else {
+            let name = expansion[1..]
+                .strip_prefix('{')
+                .and_then(|name| name.strip_suffix('}'))
+                .unwrap_or(&expansion[1..]);
+            if !is_valid_name(name) && name != "?" {
+                return Err(format!("rlsh: {expansion}: bad substitution"));
+            }
+            text.push_str(&lookup(name).unwrap_or_default());
+        }
+        expanded.push(start..text.len());
+        last = range.end;
+    }
+    text.push_str(&word.text[last..]);
+    word.text = text.into();
+    Ok(expanded)
+}
+
+/// Splits `text` into fields at the characters of `ifs`, but only where
+/// they're in the `expanded` parts of it.
+///
+/// Like in POSIX shells, a run of whitespace from `ifs` separates fields,
+/// and doesn't make empty ones at the start or end. Anything else in `ifs`
+/// separates fields on its own, so `a::b` with `IFS=:` is three of them.
+fn split<'t>(text: &'t str, expanded: &[Range<usize>], ifs: &str) -> Vec<&'t str> {
+    let separator =
+        |i: usize, c: char| ifs.contains(c) && expanded.iter().any(|range| range.contains(&i));
+    let mut fields = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if !separator(i, c) {
+            continue;
+        }
+        // whitespace either side of one other separator is all one
+        let mut other = !c.is_whitespace();
+        while let Some(&(i, c)) = chars.peek()
+            && separator(i, c)
+            && (c.is_whitespace() || !other)
+        {
+            other |= !c.is_whitespace();
+            chars.next();
+        }
+        if i > start || other {
+            fields.push(&text[start..i]);
+        }
+        start = chars.peek().map_or(text.len(), |&(i, _)| i);
+    }
+    if start < text.len() {
+        fields.push(&text[start..]);
+    }
+    fields
+}
+
+/// Expands the variables and substitutions in every word of `words`,
+/// splitting what they expand to at the characters in `ifs`. Unquoted words
+/// that expand to nothing are dropped, like in sh.
+pub fn words<'a>(
+    words: Vec<Word<'a>>,
+    lookup: impl Fn(&str) -> Option<String>,
+    run: impl Fn(Substitution) -> String,
+    ifs: &str,
+) -> Result<Vec<Word<'a>>, String> {
+    let mut expanded = Vec::new();
+    for mut word in words {
+        // `files=$(ls)` is one word
+        let assignment = word.expansions.first().is_some_and(|first| {
+            word.text[..first.start]
+                .split_once('=')
+                .is_some_and(|(name, _)| is_valid_name(name))
+        });
+        let ranges = expand(&mut word, &lookup, &run)?;
+        if word.text.is_empty() && !word.quoted && !word.expansions.is_empty() {
+            continue;
+        }
+        if !ranges.is_empty() && !word.quoted && !assignment {
+            // each field keeps where the expansions are in it, so `>&$fd`
+            // is still a redirection. One with none was split off by one, and
+            // gets an empty one so it isn't an operator either.
+            let fields = split(&word.text, &ranges, ifs);
+            let start = word.text.as_ptr() as usize;
+            expanded.extend(fields.into_iter().map(|text| {
+                let offset = text.as_ptr() as usize - start;
+                let mut expansions: Vec<Range<usize>> = ranges
+                    .iter()
+                    .map(|range| range.start.max(offset)..range.end.min(offset + text.len()))
+                    .filter(|range| !range.is_empty())
+                    .map(|range| range.start - offset..range.end - offset)
+                    .collect();
+                if expansions.is_empty() {
+                    expansions.push(0..0);
+                }
+                Word {
+                    text: text.to_string().into(),
+                    quoted: false,
+                    expansions,
+                }
+            }));
+            continue;
+        }
+        expanded.push(word);
+    }
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::tokenize;
+
+    fn expanded(input: &str) -> Result<Vec<String>, String> {
+        expanded_with(input, "\n")
+    }
+
+    fn expanded_with(input: &str, ifs: &str) -> Result<Vec<String>, String> {
+        let lookup = |name: &str| match name {
+            "HOME" => Some(String::from("/home/hero")),
+            "hp" => Some(String::from("5")),
+            "?" => Some(String::from("127")),
+            _ => None,
+        };
+        let run = |substitution: Substitution<'_>| match substitution {
+            Substitution::Output("pwd") => String::from("/dungeon\n\n"),
+            Substitution::Output("ls") => String::from("goblin  torch\nrat\n"),
+            Substitution::File(command) => format!("/dev/fd/{}", command.len()),
+            Substitution::Output(_) => String::new(),
+        };
+        let words = words(tokenize::words(input)?, lookup, run, ifs)?;
+        Ok(words
+            .into_iter()
+            .map(|word| word.text.into_owned())
+            .collect())
+    }
+
+    #[test]
+    fn variables_expand_unless_single_quoted() {
+        assert_eq!(
+            expanded("cd $HOME/dungeon").unwrap(),
+            ["cd", "/home/hero/dungeon"]
+        );
+        assert_eq!(
+            expanded(r#"echo ${hp}hp "$hp"hp '$hp' \$hp $ $1"#).unwrap(),
+            ["echo", "5hp", "5hp", "$hp", "$hp", "$", "$1"]
+        );
+        assert_eq!(
+            expanded(r#"echo $UNSET "$UNSET" x$UNSET"#).unwrap(),
+            ["echo", "", "x"]
+        );
+        assert_eq!(
+            expanded("echo ${not-a-name}"),
+            Err(String::from("rlsh: ${not-a-name}: bad substitution"))
+        );
+        assert_eq!(
+            expanded("echo ${HOME"),
+            Err(String::from("rlsh: ${HOME: bad substitution"))
+        );
+
+        assert_eq!(
+            expanded("echo $? ${?} '$?' $?x").unwrap(),
+            ["echo", "127", "127", "$?", "127x"]
+        );
+
+        let words = tokenize::words("$hp >").unwrap();
+        assert!(!words[0].is("$hp") && words[1].is(">"));
+    }
+
+    #[test]
+    fn substitutions_are_what_commands_print() {
+        assert_eq!(
+            expanded("cd $(pwd)/crypt").unwrap(),
+            ["cd", "/dungeon/crypt"]
+        );
+        assert_eq!(
+            expanded(r#"rm $(ls) "$(ls)" $(true)"#).unwrap(),
+            ["rm", "goblin  torch", "rat", "goblin  torch\nrat"]
+        );
+        assert_eq!(
+            expanded_with("rm $(ls)", " \t\n").unwrap(),
+            ["rm", "goblin", "torch", "rat"]
+        );
+        assert_eq!(
+            expanded("diff <(ls a) <(ls crypt) '<(ls)'").unwrap(),
+            ["diff", "/dev/fd/4", "/dev/fd/8", "<(ls)"]
+        );
+        assert_eq!(
+            expanded("files=$(ls)").unwrap(),
+            ["files=goblin  torch\nrat"]
+        );
+    }
+
+    #[test]
+    fn expansions_are_split_at_ifs() {
+        let posix = " \t\n";
+        assert_eq!(
+            split(" goblin \t torch\n", &[0..16], posix),
+            ["goblin", "torch"]
+        );
+        assert_eq!(split("a::b:", &[0..5], ":"), ["a", "", "b"]);
+        assert_eq!(split(":a", &[0..2], ":"), ["", "a"]);
+        assert_eq!(split("a : b", &[0..5], " :"), ["a", "b"]);
+        assert_eq!(split("a b", &[0..3], ""), ["a b"]);
+        // only what was expanded is split
+        assert_eq!(split("x:y:z", &[2..5], ":"), ["x:y", "z"]);
+        assert_eq!(
+            expanded_with("echo $HOME", "/").unwrap(),
+            ["echo", "", "home", "hero"]
+        );
+        // and the fields know which parts of them were expanded
+        let lookup = |_: &str| Some(String::from("3 x"));
+        let words = words(
+            tokenize::words(">&$fd").unwrap(),
+            lookup,
+            |_| String::new(),
+            " ",
+        );
+        let expansions: Vec<_> = words.unwrap().into_iter().map(|w| w.expansions).collect();
+        assert_eq!(expansions, [vec![2..3], vec![0..1]]);
+    }
+}
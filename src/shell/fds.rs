@@ -0,0 +1,170 @@
+//! Redirections of any file descriptor, like `2>&1`, `>&3`, `3<&-` and
+//! `3>log`.
+//!
+//! Each command keeps a table of them in the order they were written, which
+//! is applied in the child just before it runs the program, after its stdin,
+//! stdout and stderr have been set up by `<`, `>` and the pipeline.
+//! So `2>&1` sends errors wherever the output goes, pipe or file, even if
+//! it's written before the `>`, and `echo hi >&$oracle_IN` talks to a
+//! coprocess.
+//!
+//! - `n>&m` and `n<&m` make `n` a copy of `m`. Without an `n`, it's stdout
+//!   for `>&` and stdin for `<&`.
+//! - `n>&-` and `n<&-` close `n`.
+//! - `n>`, `n>>` and `n<` followed by a file open it as `n`, for writing,
+//!   adding to or reading, so `2> errors.txt` keeps the errors. Plain `>`
+//!   and `<` are redirected on their own.
+
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    os::fd::{AsRawFd, OwnedFd, RawFd},
+};
+
+/// How `n>`, `n>>` and `n<` open their file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Read,
+    Write,
+    Append,
+}
+
+/// What a redirection does to its file descriptor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Redirect {
+    /// Makes it a copy of another one.
+    Dup(RawFd),
+    Close,
+    /// Opens the file at the path.
+    Open(String, Mode),
+}
+
+/// The file descriptor redirections of a command, in order.
+#[derive(Clone, Debug, Default)]
+pub struct FdTable {
+    redirects: Vec<(RawFd, Redirect)>,
+}
+
+/// If `word` is a redirection, which file descriptor it's for and what it
+/// does to it. One that opens a file has an empty path, for the word after
+/// it.
+pub fn parse(word: &str) -> Option<(RawFd, Redirect)> {
+    let digits = word.len() - word.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let (number, op) = word.split_at(digits);
+    let fd = |default| match number {
+        "" => Some(default),
+        number => number.parse().ok(),
+    };
+    let target = |rest: &str| match rest {
+        "-" => Some(Redirect::Close),
+        rest if !rest.is_empty() && rest.bytes().all(|b| b.is_ascii_digit()) => {
+            rest.parse().ok().map(Redirect::Dup)
+        }
+        _ => None,
+    };
+    if let Some(rest) = op.strip_prefix(">&") {
+        return Some((fd(1)?, target(rest)?));
+    }
+    if let Some(rest) = op.strip_prefix("<&") {
+        return Some((fd(0)?, target(rest)?));
+    }
+    // plain `>`, `>>` and `<` are redirected on their own
+    let mode = match op {
+        ">" => Mode::Write,
+        ">>" => Mode::Append,
+        "<" => Mode::Read,
+        _ => return None,
+    };
+    match number {
+        "" => None,
+        _ => Some((fd(0)?, Redirect::Open(String::new(), mode))),
+    }
+}
+
+impl FdTable {
+    pub fn push(&mut self, fd: RawFd, redirect: Redirect) {
+        self.redirects.push((fd, redirect));
+    }
+
+    /// Opens the table's files and has it applied to `command`'s process
+    /// before it runs.
+    pub fn apply(self, command: &mut tokio::process::Command) -> io::Result<()> {
+        if self.redirects.is_empty() {
+            return Ok(());
+        }
+        // the files are opened here, since the child can't allocate, and
+        // are kept open by the closure until the command has started
+        let mut files: Vec<OwnedFd> = Vec::new();
+        let mut steps: Vec<(RawFd, Option<RawFd>)> = Vec::new();
+        for (fd, redirect) in self.redirects {
+            let from = match redirect {
+                Redirect::Dup(from) => Some(from),
+                Redirect::Close => None,
+                Redirect::Open(path, mode) => {
+                    let file: File = match mode {
+                        Mode::Read => File::open(path)?,
+                        Mode::Write => File::create(path)?,
+                        Mode::Append => OpenOptions::new().append(true).create(true).open(path)?,
+                    };
+                    files.push(file.into());
+                    files.last().map(AsRawFd::as_raw_fd)
+                }
+            };
+            steps.push((fd, from));
+        }
+        // SAFETY: dup2, fcntl and close are async-signal-safe, and nothing
+        // here allocates.
+        unsafe {
+            command.pre_exec(move || {
+                let _ = &files;
+                for &(fd, from) in &steps {
+                    let result = match from {
+                        // dup2 leaves it alone, close-on-exec and all
+                        Some(from) if from == fd => libc::fcntl(fd, libc::F_SETFD, 0),
+                        Some(from) => libc::dup2(from, fd),
+                        None => match libc::close(fd) {
+                            // closing one that isn't open is fine
+                            -1 if io::Error::last_os_error().raw_os_error()
+                                == Some(libc::EBADF) =>
+                            {
+                                0
+                            }
+                            result => result,
+                        },
+                    };
+                    if result == -1 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redirections_are_told_apart_from_files() {
+        assert_eq!(parse("2>&1"), Some((2, Redirect::Dup(1))));
+        assert_eq!(parse(">&3"), Some((1, Redirect::Dup(3))));
+        assert_eq!(parse("<&5"), Some((0, Redirect::Dup(5))));
+        assert_eq!(parse("3<&-"), Some((3, Redirect::Close)));
+        assert_eq!(parse(">&-"), Some((1, Redirect::Close)));
+        assert_eq!(
+            parse("3>>"),
+            Some((3, Redirect::Open(String::new(), Mode::Append)))
+        );
+        assert_eq!(
+            parse("1>"),
+            Some((1, Redirect::Open(String::new(), Mode::Write)))
+        );
+        // the ones that aren't, or are handled elsewhere
+        for word in [">", ">>", "<", ">&", "2>&x", "a>&1", "3>&-1", "3>x"] {
+            assert_eq!(parse(word), None, "{word}");
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! Here-documents, like `cat << EOF`.
+//!
+//! The lines after a command line that starts a here-document, up to one
+//! that's only the delimiter, are fed to the command's stdin. They're read
+//! before the command line runs, by whatever read the line, so they come
+//! from the terminal, a paste or a script alike. Unless the delimiter is
+//! quoted, `$NAME`s and `$(command)`s in them are expanded like they would
+//! be in double quotes.
+
+use super::{list, tokenize};
+
+/// The body of a here-document.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Heredoc {
+    pub body: String,
+    /// Whether to expand variables and substitutions in it, which is when
+    /// its delimiter wasn't quoted.
+    pub expand: bool,
+}
+
+/// The delimiters of the here-documents `line` starts, in order, and
+/// whether each should be expanded.
+pub fn delimiters(line: &str) -> Vec<(String, bool)> {
+    // a line that doesn't parse won't run, so it doesn't need any
+    let commands = list::split(line).unwrap_or_default();
+    let mut delimiters = Vec::new();
+    for (_, command) in commands {
+        let words = tokenize::words(command).unwrap_or_default();
+        delimiters.extend(
+            words
+                .windows(2)
+                .filter(|pair| pair[0].is("<<"))
+                .map(|pair| (pair[1].text.to_string(), !pair[1].quoted)),
+        );
+    }
+    delimiters
+}
+
+/// Reads the bodies of the here-documents `line` starts, a line at a time
+/// from `next_line`. If it runs out first, what there was is used, and the
+/// delimiter that was wanted is returned with them, to warn about.
+pub fn read(
+    line: &str,
+    mut next_line: impl FnMut() -> Option<String>,
+) -> (Vec<Heredoc>, Option<String>) {
+    let mut heredocs = Vec::new();
+    for (delimiter, expand) in delimiters(line) {
+        let mut body = String::new();
+        loop {
+            match next_line() {
+                Some(line) if line == delimiter => break,
+                Some(line) => {
+                    body.push_str(&line);
+                    body.push('\n');
+                }
+                None => {
+                    heredocs.push(Heredoc { body, expand });
+                    return (heredocs, Some(delimiter));
+                }
+            }
+        }
+        heredocs.push(Heredoc { body, expand });
+    }
+    (heredocs, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bodies_run_up_to_their_delimiters() {
+        assert_eq!(
+            delimiters("cat << EOF | grep << 'END' x; echo '<<' a && cat << A;"),
+            [
+                (String::from("EOF"), true),
+                (String::from("END"), false),
+                (String::from("A"), true)
+            ]
+        );
+
+        let mut lines = ["goblin", "$hp", "EOF", "rat", "END", "after"].into_iter();
+        let (heredocs, missing) = read("cat << EOF; cat << 'END'", || {
+            lines.next().map(String::from)
+        });
+        assert_eq!(
+            heredocs,
+            [
+                Heredoc {
+                    body: String::from("goblin\n$hp\n"),
+                    expand: true
+                },
+                Heredoc {
+                    body: String::from("rat\n"),
+                    expand: false
+                },
+            ]
+        );
+        assert_eq!(missing, None);
+        assert_eq!(lines.next(), Some("after"));
+
+        let mut lines = ["only"].into_iter();
+        let (heredocs, missing) = read("cat << EOF", || lines.next().map(String::from));
+        assert_eq!(heredocs[0].body, "only\n");
+        assert_eq!(missing.as_deref(), Some("EOF"));
+    }
+}
@@ -0,0 +1,342 @@
+//! A hex dumper, for the `hexdump` and `xxd` builtins.
+//!
+//! Entity files are a [`FILE_SIGNATURE`] followed by a MessagePack encoded
+//! [`Entity`](crate::game::Entity), so when something goes wrong with (de)serializing one, staring
+//! at its bytes is often the quickest way to find out why. To help with that,
+//! dumps of entity files are followed by notes on where the signature, the
+//! entity and each of its components are. Big entities are
+//! [compressed](crate::game::compress), and there's no telling where anything
+//! is in those, so they only get a note on how big they really are.
+
+use std::{
+    fmt::Write,
+    fs,
+    io::{self, Read},
+};
+
+use crate::game::{Component, FILE_SIGNATURE, compress};
+
+/// How many bytes go on each line of the dump.
+const BYTES_PER_LINE: usize = 16;
+
+/// A note about the bytes from `start` up to `end`.
+#[derive(Debug, PartialEq, Eq)]
+struct Note {
+    start: usize,
+    end: usize,
+    text: String,
+}
+
+/// The parts of a MessagePack value's header: how many bytes the header
+/// takes, how many bytes of data follow it, and how many values follow it.
+struct Header {
+    len: usize,
+    data: usize,
+    values: usize,
+}
+
+/// Reads a big endian number `n` bytes long from the start of `bytes`.
+fn read_be(bytes: &[u8], n: usize) -> Option<usize> {
+    let bytes = bytes.get(..n)?;
+    Some(bytes.iter().fold(0, |acc, &b| acc << 8 | b as usize))
+}
+
+/// Reads the header of the MessagePack value at the start of `bytes`.
+fn header(bytes: &[u8]) -> Option<Header> {
+    let marker = *bytes.first()?;
+    let rest = &bytes[1..];
+    let header = |len, data, values| Some(Header { len, data, values });
+    match marker {
+        0x00..=0x7f | 0xe0..=0xff | 0xc0 | 0xc2 | 0xc3 => header(1, 0, 0),
+        0x80..=0x8f => header(1, 0, (marker as usize & 0x0f) * 2),
+        0x90..=0x9f => header(1, 0, marker as usize & 0x0f),
+        0xa0..=0xbf => header(1, marker as usize & 0x1f, 0),
+        0xc4 | 0xd9 => header(2, read_be(rest, 1)?, 0),
+        0xc5 | 0xda => header(3, read_be(rest, 2)?, 0),
+        0xc6 | 0xdb => header(5, read_be(rest, 4)?, 0),
+        0xc7 => header(3, read_be(rest, 1)?, 0),
+        0xc8 => header(4, read_be(rest, 2)?, 0),
+        0xc9 => header(6, read_be(rest, 4)?, 0),
+        0xca => header(1, 4, 0),
+        0xcb => header(1, 8, 0),
+        0xcc | 0xd0 => header(1, 1, 0),
+        0xcd | 0xd1 => header(1, 2, 0),
+        0xce | 0xd2 => header(1, 4, 0),
+        0xcf | 0xd3 => header(1, 8, 0),
+        0xd4..=0xd8 => header(2, 1 << (marker - 0xd4), 0),
+        0xdc => header(3, 0, read_be(rest, 2)?),
+        0xdd => header(5, 0, read_be(rest, 4)?),
+        0xde => header(3, 0, read_be(rest, 2)? * 2),
+        0xdf => header(5, 0, read_be(rest, 4)? * 2),
+        0xc1 => None,
+    }
+}
+
+/// How many bytes the MessagePack value at the start of `bytes` takes up, if
+/// it's all there.
+///
+/// Values are walked one after another, counting how many more are still to
+/// come, rather than by recursing into arrays and maps, so a corrupt file that
+/// nests them thousands deep can't overflow the stack.
+fn value_len(bytes: &[u8]) -> Option<usize> {
+    let mut len = 0;
+    let mut pending = 1usize;
+    while pending > 0 {
+        let header = header(bytes.get(len..)?)?;
+        len = len.checked_add(header.len)?.checked_add(header.data)?;
+        pending = pending - 1 + header.values;
+    }
+    (len <= bytes.len()).then_some(len)
+}
+
+/// Describes the array at the start of `bytes`, if it is one.
+fn array(bytes: &[u8]) -> Option<Header> {
+    let header = header(bytes)?;
+    let is_array = matches!(bytes[0], 0x90..=0x9f | 0xdc | 0xdd);
+    is_array.then_some(header)
+}
+
+/// Works out where the parts of an entity file are. Files that aren't entities
+/// don't get any notes.
+fn annotate(bytes: &[u8]) -> Vec<Note> {
+    let Some(body) = bytes.strip_prefix(FILE_SIGNATURE) else {
+        return Vec::new();
+    };
+    let mut notes = vec![Note {
+        start: 0,
+        end: FILE_SIGNATURE.len(),
+        text: String::from("file signature"),
+    }];
+    let mut offset = FILE_SIGNATURE.len();
+
+    // there's nothing to pick out in deflated bytes
+    if compress::is_packed(body) {
+        notes.push(Note {
+            start: offset,
+            end: offset + 1,
+            text: String::from("compressed"),
+        });
+        let text = match compress::unpack(body) {
+            Ok(entity) => format!("entity, deflated from {} bytes", entity.len()),
+            Err(error) => error,
+        };
+        notes.push(Note {
+            start: offset + 1,
+            end: bytes.len(),
+            text,
+        });
+        return notes;
+    }
+
+    // the entity is a struct, which is written as an array of its fields
+    let Some(entity) = array(body) else {
+        notes.push(Note {
+            start: offset,
+            end: offset + 1,
+            text: String::from("expected the entity, but this isn't an array"),
+        });
+        return notes;
+    };
+    notes.push(Note {
+        start: offset,
+        end: offset + entity.len,
+        text: format!("entity, {} field(s)", entity.values),
+    });
+    offset += entity.len;
+
+    let Some(components) = array(&bytes[offset..]) else {
+        notes.push(Note {
+            start: offset,
+            end: (offset + 1).min(bytes.len()),
+            text: String::from("expected the components, but this isn't an array"),
+        });
+        return notes;
+    };
+    notes.push(Note {
+        start: offset,
+        end: offset + components.len,
+        text: format!("components, {} of them", components.values),
+    });
+    offset += components.len;
+
+    for i in 0..components.values {
+        let rest = &bytes[offset..];
+        let Some(len) = value_len(rest) else {
+            notes.push(Note {
+                start: offset,
+                end: bytes.len(),
+                text: format!("component {i} is cut off or corrupt"),
+            });
+            return notes;
+        };
+        let text = match rmp_serde::from_slice::<Component>(&rest[..len]) {
+            Ok(c) => format!("component {i}: {c}"),
+            Err(error) => format!("component {i} doesn't decode: {error}"),
+        };
+        notes.push(Note {
+            start: offset,
+            end: offset + len,
+            text,
+        });
+        offset += len;
+    }
+
+    if offset < bytes.len() {
+        notes.push(Note {
+            start: offset,
+            end: bytes.len(),
+            text: String::from("trailing bytes after the entity"),
+        });
+    }
+    notes
+}
+
+/// Dumps `bytes` like `xxd`: an offset, the bytes in hex in pairs, and then
+/// the bytes as text with anything unprintable shown as `.`.
+fn dump(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for (line, chunk) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+        // writing to a String can't fail
+        let _ = write!(out, "{:08x}:", line * BYTES_PER_LINE);
+        for i in 0..BYTES_PER_LINE {
+            if i % 2 == 0 {
+                out.push(' ');
+            }
+            match chunk.get(i) {
+                Some(b) => {
+                    let _ = write!(out, "{b:02x}");
+                }
+                None => out.push_str("  "),
+            }
+        }
+        out.push_str("  ");
+        out.extend(chunk.iter().map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        }));
+        out.push('\n');
+    }
+    out
+}
+
+/// Runs the `hexdump` and `xxd` builtins: `hexdump [-n length] [file]`,
+/// returning what should be printed. Reads stdin if there's no file.
+pub fn command(args: &[String]) -> Result<String, String> {
+    let mut length = None;
+    let mut file = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-n" => {
+                let n = args
+                    .next()
+                    .ok_or_else(|| String::from("hexdump: -n: option requires an argument"))?;
+                let n = n
+                    .parse::<usize>()
+                    .map_err(|_| format!("hexdump: invalid length '{n}'"))?;
+                length = Some(n);
+            }
+            path if file.is_none() => file = Some(path),
+            extra => return Err(format!("hexdump: extra operand '{extra}'")),
+        }
+    }
+
+    let mut bytes = match file {
+        Some(path) => fs::read(path).map_err(|error| format!("hexdump: {path}: {error}"))?,
+        None => {
+            let mut bytes = Vec::new();
+            io::stdin()
+                .read_to_end(&mut bytes)
+                .map_err(|error| format!("hexdump: {error}"))?;
+            bytes
+        }
+    };
+    let mut notes = annotate(&bytes);
+    if let Some(length) = length {
+        bytes.truncate(length);
+        notes.retain(|note| note.start < length);
+    }
+
+    let mut out = dump(&bytes);
+    if !notes.is_empty() {
+        out.push('\n');
+    }
+    for note in notes {
+        let _ = writeln!(out, "{:08x}-{:08x}  {}", note.start, note.end, note.text);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Entity;
+
+    #[test]
+    fn dumps_like_xxd() {
+        let dump = dump(b"\x00rlsh0.1 hello, goblin");
+        assert_eq!(
+            dump,
+            "00000000: 0072 6c73 6830 2e31 2068 656c 6c6f 2c20  .rlsh0.1 hello, \n\
+             00000010: 676f 626c 696e                           goblin\n"
+        );
+    }
+
+    #[test]
+    fn entities_are_annotated() {
+        let e = Entity {
+            components: vec![Component::Enemy, Component::TakesDamage(5)],
+        };
+        let mut bytes = FILE_SIGNATURE.to_vec();
+        bytes.extend(Vec::from(e));
+
+        let notes = annotate(&bytes);
+        let texts: Vec<&str> = notes.iter().map(|note| note.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            [
+                "file signature",
+                "entity, 1 field(s)",
+                "components, 2 of them",
+                "component 0: enemy",
+                "component 1: hp: 5",
+            ]
+        );
+        assert_eq!(notes.last().unwrap().end, bytes.len());
+
+        bytes.pop();
+        let notes = annotate(&bytes);
+        assert_eq!(
+            notes.last().unwrap().text,
+            "component 1 is cut off or corrupt"
+        );
+        assert!(annotate(b"just a file").is_empty());
+
+        // arrays in arrays in arrays, far deeper than the stack would go
+        let mut nested = FILE_SIGNATURE.to_vec();
+        nested.extend([0x91; 200_000]);
+        assert_eq!(
+            annotate(&nested).last().unwrap().text,
+            "component 0 is cut off or corrupt"
+        );
+
+        let hoard = Entity {
+            components: vec![Component::HasInventory(vec![String::from("gem"); 100])],
+        };
+        let mut bytes = FILE_SIGNATURE.to_vec();
+        bytes.extend(compress::pack(Vec::from(hoard)));
+        let texts: Vec<String> = annotate(&bytes).into_iter().map(|note| note.text).collect();
+        assert_eq!(
+            texts,
+            [
+                "file signature",
+                "compressed",
+                "entity, deflated from 419 bytes"
+            ]
+        );
+    }
+}
@@ -0,0 +1,155 @@
+//! Colouring the command line as it's typed.
+//!
+//! Each command's name is coloured by what it'll run: a builtin or program,
+//! a game verb, or nothing at all. Quoted strings are coloured too. This runs
+//! on every keystroke, so looking a name up on `$PATH` is only done once per
+//! name for each line.
+
+use std::{collections::HashMap, env, os::unix::fs::PermissionsExt, path::Path};
+
+use crate::style::{Palette, Role};
+
+/// Whether `name` is an executable file, either as a path or on `$PATH`.
+fn is_program(name: &str) -> bool {
+    let executable = |path: &Path| {
+        path.metadata()
+            .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+    };
+    if name.contains('/') {
+        return executable(Path::new(name));
+    }
+    env::var_os("PATH")
+        .is_some_and(|paths| env::split_paths(&paths).any(|dir| executable(&dir.join(name))))
+}
+
+/// Whether `c` separates one command from the next.
+pub fn is_separator(c: char) -> bool {
+    matches!(c, ';' | '|' | '&' | '\n')
+}
+
+/// Colours command lines.
+pub struct Highlighter<'a> {
+    palette: Palette,
+    /// Whether each name looked up so far is something that can be run.
+    known: HashMap<String, bool>,
+    /// Whether a command is aimed at something in the game.
+    is_verb: Box<dyn Fn(&str) -> bool + 'a>,
+}
+
+impl<'a> Highlighter<'a> {
+    pub fn new(palette: Palette, is_verb: impl Fn(&str) -> bool + 'a) -> Highlighter<'a> {
+        Highlighter {
+            palette,
+            known: HashMap::new(),
+            is_verb: Box::new(is_verb),
+        }
+    }
+
+    fn is_command(&mut self, name: &str) -> bool {
+        if super::BUILTINS.iter().any(|(builtin, _)| *builtin == name) {
+            return true;
+        }
+        *self
+            .known
+            .entry(name.to_string())
+            .or_insert_with(|| is_program(name))
+    }
+
+    /// Returns `line` with colour escapes added.
+    pub fn highlight(&mut self, line: &str) -> String {
+        let mut out = String::new();
+        let mut command_position = true;
+        let mut chars = line.char_indices().peekable();
+        while let Some(&(start, c)) = chars.peek() {
+            if is_separator(c) {
+                out.push(c);
+                chars.next();
+                command_position = true;
+                continue;
+            }
+            if c.is_whitespace() {
+                out.push(c);
+                chars.next();
+                continue;
+            }
+
+            // a word runs to the next space or separator, except in quotes
+            let mut quote = None;
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                if quote.is_none() && (c.is_whitespace() || is_separator(c)) {
+                    break;
+                }
+                match quote {
+                    Some(q) if c == q => quote = None,
+                    Some(_) => (),
+                    None if c == '\'' || c == '"' => quote = Some(c),
+                    None => (),
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            let word = &line[start..end];
+
+            if word.starts_with(['\'', '"']) {
+                command_position = false;
+                out.push_str(&self.palette.paint(Role::Quoted, word));
+            } else if command_position && word.contains('=') && !word.starts_with('=') {
+                // `NAME=value` before a command
+                out.push_str(word);
+            } else if command_position {
+                command_position = false;
+                let command = line[start..].split(is_separator).next().unwrap();
+                let role = if (self.is_verb)(command.trim_end()) {
+                    Role::Verb
+                } else if self.is_command(word) {
+                    Role::Command
+                } else {
+                    Role::Unknown
+                };
+                out.push_str(&self.palette.paint(role, word));
+            } else {
+                out.push_str(word);
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_coloured_by_kind() {
+        let palette = Palette::Default;
+        let mut highlighter = Highlighter::new(palette, |command| command == "bash door");
+        let paint = |role, text| palette.paint(role, text);
+        assert_eq!(
+            highlighter.highlight("ls -l; nosuchcommand 'a b'|bash door"),
+            format!(
+                "{} -l; {} {}|{} door",
+                paint(Role::Command, "ls"),
+                paint(Role::Unknown, "nosuchcommand"),
+                paint(Role::Quoted, "'a b'"),
+                paint(Role::Verb, "bash"),
+            )
+        );
+        assert_eq!(
+            highlighter.highlight("A=1 cd \"unfinished"),
+            format!(
+                "A=1 {} {}",
+                paint(Role::Command, "cd"),
+                paint(Role::Quoted, "\"unfinished")
+            )
+        );
+        assert_eq!(
+            highlighter.highlight("cd\nnosuchcommand"),
+            format!(
+                "{}\n{}",
+                paint(Role::Command, "cd"),
+                paint(Role::Unknown, "nosuchcommand")
+            )
+        );
+    }
+}
@@ -0,0 +1,158 @@
+//! The command lines entered so far.
+//!
+//! The shell keeps them in `history` in its data directory, a line each, so
+//! they can be recalled in the next session too. Each line is added to the
+//! end of the file as it's entered, so shells running side by side don't
+//! lose each other's lines, and `history -c` empties it.
+
+use std::{
+    fmt::Write as _,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::game::player;
+
+/// Every command line entered, oldest first.
+#[derive(Debug, Default)]
+pub struct History {
+    entries: Vec<String>,
+    /// The file lines are kept in, if they're kept.
+    file: Option<PathBuf>,
+}
+
+/// Where the history is kept.
+pub fn path() -> PathBuf {
+    player::get_data_dir().join("history")
+}
+
+impl History {
+    /// The history kept in `file`, which lines entered from now on are
+    /// added to. A file that isn't there yet is an empty history.
+    pub fn load(file: PathBuf) -> History {
+        let entries = fs::read_to_string(&file)
+            .map(|text| text.lines().map(String::from).collect())
+            .unwrap_or_default();
+        History {
+            entries,
+            file: Some(file),
+        }
+    }
+
+    /// Remembers `line`, unless it's blank or the same as the line before.
+    /// A file that can't be written to is left alone rather than getting in
+    /// the way of the line.
+    pub fn add(&mut self, line: &str) {
+        let line = line.trim_end();
+        if line.trim().is_empty() || self.entries.last().is_some_and(|last| last == line) {
+            return;
+        }
+        self.entries.push(line.to_string());
+        if let Some(file) = &self.file {
+            let _ = Self::append(file, line);
+        }
+    }
+
+    fn append(file: &Path, line: &str) -> io::Result<()> {
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(file)?;
+        writeln!(file, "{line}")
+    }
+
+    /// Runs the `history` builtin, returning what should be printed.
+    ///
+    /// - `history` lists every line, numbered from 1.
+    /// - `history <n>` lists the last `n`.
+    /// - `history -c` forgets them all.
+    pub fn command(&mut self, args: &[String]) -> Result<String, String> {
+        let shown = match args {
+            [] => self.entries.len(),
+            [flag] if flag == "-c" => {
+                self.entries.clear();
+                if let Some(file) = &self.file {
+                    File::create(file).map_err(|error| format!("history: {error}"))?;
+                }
+                return Ok(String::new());
+            }
+            [count] => match count.parse::<usize>() {
+                Ok(count) => count.min(self.entries.len()),
+                Err(_) if count.starts_with('-') => {
+                    return Err(format!("history: {count}: unknown option"));
+                }
+                Err(_) => return Err(format!("history: {count}: numeric argument required")),
+            },
+            _ => return Err(String::from("history: too many arguments")),
+        };
+        let mut out = String::new();
+        let start = self.entries.len() - shown;
+        for (i, entry) in self.entries.iter().enumerate().skip(start) {
+            writeln!(out, "{:>5}  {entry}", i + 1).unwrap();
+        }
+        Ok(out)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The `index`th line entered, oldest first.
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(String::as_str)
+    }
+
+    /// The most recent line that starts with `prefix` and has more after it,
+    /// as a suggestion for how to finish `prefix`.
+    pub fn suggest(&self, prefix: &str) -> Option<&str> {
+        if prefix.is_empty() {
+            return None;
+        }
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| entry.len() > prefix.len() && entry.starts_with(prefix))
+            .map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggestions_are_the_latest_match() {
+        let mut history = History::default();
+        for line in ["ls -l", "cd dungeon", "ls -a", "ls -a", "  "] {
+            history.add(line);
+        }
+        assert_eq!(history.entries, ["ls -l", "cd dungeon", "ls -a"]);
+        assert_eq!(history.suggest("ls"), Some("ls -a"));
+        assert_eq!(history.suggest("ls -l"), None);
+        assert_eq!(history.suggest("cd"), Some("cd dungeon"));
+        assert_eq!(history.suggest(""), None);
+    }
+
+    #[test]
+    fn lines_are_kept_in_the_file() {
+        let file = std::env::temp_dir().join(format!("rlsh-history-{}", std::process::id()));
+        let _ = fs::remove_file(&file);
+        let mut history = History::load(file.clone());
+        history.add("ls");
+        history.add("cd dungeon");
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+
+        let mut again = History::load(file.clone());
+        assert_eq!(again.entries, ["ls", "cd dungeon"]);
+        assert_eq!(
+            again.command(&[]).unwrap(),
+            "    1  ls\n    2  cd dungeon\n"
+        );
+        assert_eq!(again.command(&args(&["1"])).unwrap(), "    2  cd dungeon\n");
+        assert!(again.command(&args(&["x"])).is_err());
+        again.command(&args(&["-c"])).unwrap();
+        assert_eq!(History::load(file.clone()).len(), 0);
+        let _ = fs::remove_file(&file);
+    }
+}
@@ -1,14 +1,19 @@
-use std::cmp;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::fmt::{self, Display};
-use std::fs::File;
 use std::io::{self, Write};
 use std::sync::{Arc, Mutex};
 
+use tokio::sync::watch;
+use tokio::task::AbortHandle;
+
+use crate::headless;
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum State {
     BG,
     FG,
+    // Stopped, like by Ctrl-Z
+    ST,
 }
 
 impl Display for State {
@@ -16,6 +21,7 @@ impl Display for State {
         match self {
             Self::BG => write!(f, "Background"),
             Self::FG => write!(f, "Foreground"),
+            Self::ST => write!(f, "Stopped"),
         }
     }
 }
@@ -24,10 +30,29 @@ pub struct Job {
     pid: u32,
     state: State,
     cmdline: String,
+    // Its exit status once it's finished, for anyone waiting on it
+    done: watch::Sender<Option<i32>>,
+    // Cancels it, if it's run by the shell itself rather than a process
+    abort: Option<AbortHandle>,
+}
+
+// Waits for a job to finish, see `JobList::completion`
+pub struct Completion(watch::Receiver<Option<i32>>);
+
+impl Completion {
+    // Waits for the job to finish, returning its exit status, or None if it
+    // was taken off the list without one
+    pub async fn wait(mut self) -> Option<i32> {
+        self.0
+            .wait_for(Option::is_some)
+            .await
+            .ok()
+            .and_then(|code| *code)
+    }
 }
 
 struct JobData {
-    jobs: HashMap<usize, Job>,
+    jobs: BTreeMap<usize, Job>,
     fg_job: Option<usize>,
     max_jid: Option<usize>,
 }
@@ -40,7 +65,7 @@ impl JobList {
     // Creates a new empty job list
     pub fn new() -> Self {
         JobList(Arc::new(Mutex::new(JobData {
-            jobs: HashMap::new(),
+            jobs: BTreeMap::new(),
             fg_job: None,
             max_jid: None,
         })))
@@ -74,6 +99,8 @@ impl JobList {
             pid,
             state,
             cmdline,
+            done: watch::Sender::new(None),
+            abort: None,
         };
 
         // throw error if insert triggers an override
@@ -95,10 +122,7 @@ impl JobList {
         // update max jid
         if let Some(id) = job_list.max_jid {
             if jid == id {
-                job_list.max_jid = match job_list.jobs.keys().reduce(cmp::max) {
-                    Some(x) => Some(*x),
-                    None => None,
-                };
+                job_list.max_jid = job_list.jobs.keys().next_back().copied();
             }
         }
 
@@ -113,6 +137,37 @@ impl JobList {
         remove_status.is_some()
     }
 
+    // Moves a job to the foreground or background
+    // Only one job can be in the foreground at a time
+    pub fn set_state(&self, jid: usize, state: State) -> Result<(), &'static str> {
+        let JobList(arc) = self;
+        let mut job_list = arc.lock().unwrap();
+
+        if !job_list.jobs.contains_key(&jid) {
+            return Err("No job with that jid");
+        }
+
+        // Update foreground
+        match state {
+            State::FG => match job_list.fg_job {
+                Some(id) if id != jid => {
+                    return Err("Can't foreground a job if a foreground job already exists");
+                }
+                _ => job_list.fg_job = Some(jid),
+            },
+            State::BG | State::ST => {
+                if job_list.fg_job == Some(jid) {
+                    job_list.fg_job = None
+                }
+            }
+        }
+
+        if let Some(job) = job_list.jobs.get_mut(&jid) {
+            job.state = state;
+        }
+        Ok(())
+    }
+
     // Returns the state of any one job
     pub fn get_state(&self, jid: usize) -> Option<State> {
         let JobList(arc) = self;
@@ -131,6 +186,32 @@ impl JobList {
         Some(job.pid)
     }
 
+    // Gets the pid of the foreground job, if there is one
+    pub fn fg_pid(&self) -> Option<u32> {
+        let JobList(arc) = self;
+        let job_list = arc.lock().unwrap();
+
+        let job = job_list.jobs.get(&job_list.fg_job?)?;
+        Some(job.pid)
+    }
+
+    // Gets the jid of the job added last, like `%%`
+    pub fn last_jid(&self) -> Option<usize> {
+        let JobList(arc) = self;
+        let job_list = arc.lock().unwrap();
+
+        job_list.max_jid
+    }
+
+    // Gets the pid of the job added last, like `$!`, if it's still running
+    pub fn last_pid(&self) -> Option<u32> {
+        let JobList(arc) = self;
+        let job_list = arc.lock().unwrap();
+
+        let job = job_list.jobs.get(&job_list.max_jid?)?;
+        Some(job.pid)
+    }
+
     // Gets the cmdline of a job
     pub fn get_cmdline(&self, jid: usize) -> Option<String> {
         let JobList(arc) = self;
@@ -140,32 +221,99 @@ impl JobList {
         Some(job.cmdline.clone())
     }
 
-    // prints out the job list to the file specified by outfile or stdout if it is None
-    // If the file exists it is truncated before writing
-    // If it does not exist it is created
-    pub fn list_jobs(&self, outfile: Option<String>) -> io::Result<()> {
-        match outfile {
-            None => {
-                let stdout = io::stdout().lock();
-                self.print_jobs(stdout)
-            }
-            Some(path) => {
-                let file = File::create(path)?;
-                self.print_jobs(file)
-            }
+    // Records that a job has finished with the exit status `code`, waking
+    // anyone waiting on it. It's still up to the caller to delete it
+    pub fn finish(&self, jid: usize, code: i32) {
+        let JobList(arc) = self;
+        let job_list = arc.lock().unwrap();
+
+        if let Some(job) = job_list.jobs.get(&jid) {
+            job.done.send_replace(Some(code));
+        }
+    }
+
+    // Lets a job the shell runs itself, like a background sleep, be
+    // cancelled with `abort`
+    pub fn set_abort(&self, jid: usize, abort: AbortHandle) {
+        let JobList(arc) = self;
+        let mut job_list = arc.lock().unwrap();
+
+        if let Some(job) = job_list.jobs.get_mut(&jid) {
+            job.abort = Some(abort);
         }
     }
 
-    // Prints all jobs in the job list in a random order to the specified writer
-    fn print_jobs<W: Write>(&self, mut writer: W) -> io::Result<()> {
+    // Whether a job is run by the shell itself, so it can be cancelled but
+    // not signalled
+    pub fn is_task(&self, jid: usize) -> bool {
+        let JobList(arc) = self;
+        let job_list = arc.lock().unwrap();
+
+        job_list
+            .jobs
+            .get(&jid)
+            .is_some_and(|job| job.abort.is_some())
+    }
+
+    // Cancels a job the shell runs itself, returning whether it was one. Its
+    // task still says when it's finished
+    pub fn abort(&self, jid: usize) -> bool {
+        let JobList(arc) = self;
+        let job_list = arc.lock().unwrap();
+
+        let Some(abort) = job_list.jobs.get(&jid).and_then(|job| job.abort.as_ref()) else {
+            return false;
+        };
+        abort.abort();
+        true
+    }
+
+    // Gets a handle to wait for a job to finish
+    pub fn completion(&self, jid: usize) -> Option<Completion> {
+        let JobList(arc) = self;
+        let job_list = arc.lock().unwrap();
+
+        let job = job_list.jobs.get(&jid)?;
+        Some(Completion(job.done.subscribe()))
+    }
+
+    // Gets the jid of the job led by `pid`
+    pub fn find_pid(&self, pid: u32) -> Option<usize> {
+        let JobList(arc) = self;
+        let job_list = arc.lock().unwrap();
+
+        job_list
+            .jobs
+            .iter()
+            .find(|(_, job)| job.pid == pid)
+            .map(|(jid, _)| *jid)
+    }
+
+    // Gets the jids of every job in a state, in order
+    pub fn jids(&self, state: State) -> Vec<usize> {
+        let JobList(arc) = self;
+        let job_list = arc.lock().unwrap();
+
+        job_list
+            .jobs
+            .iter()
+            .filter(|(_, job)| job.state == state)
+            .map(|(jid, _)| *jid)
+            .collect()
+    }
+
+    // Prints all jobs in the job list in order of jid to the specified writer, one per line
+    pub fn list_jobs<W: Write>(&self, mut writer: W) -> io::Result<()> {
         let JobList(arc) = self;
         let job_list = arc.lock().unwrap();
 
         for (jid, job) in job_list.jobs.iter() {
-            write!(
+            writeln!(
                 writer,
-                "[{jid}] ({}) {} {}",
-                job.pid, job.state, job.cmdline
+                "{} {} {}",
+                describe(*jid, job.pid),
+                job.state,
+                job.cmdline
             )?;
         }
 
@@ -173,9 +321,21 @@ impl JobList {
     }
 }
 
+// Names a job the way job messages do, like `[0] (1234)`
+// Headless, the pid is left out, since it's different every run
+pub fn describe(jid: usize, pid: u32) -> String {
+    if headless::enabled() {
+        format!("[{jid}]")
+    } else {
+        format!("[{jid}] ({pid})")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+    use std::collections::BTreeMap;
 
     #[test]
     fn adding_jobs() {
@@ -193,6 +353,28 @@ mod tests {
         assert_eq!(Ok(2), result);
     }
 
+    #[tokio::test]
+    async fn waiting_for_jobs() {
+        let list = JobList::new();
+        list.add(1, State::BG, "one".to_string()).unwrap();
+        list.add(2, State::ST, "two".to_string()).unwrap();
+        assert_eq!(list.jids(State::BG), [0]);
+        assert_eq!(list.find_pid(2), Some(1));
+        assert_eq!(list.find_pid(3), None);
+
+        let one = list.completion(0).unwrap();
+        let waiting = tokio::spawn(one.wait());
+        list.finish(0, 3);
+        list.delete(0);
+        assert_eq!(waiting.await.unwrap(), Some(3));
+        assert!(list.completion(0).is_none());
+
+        // taken off without finishing
+        let two = list.completion(1).unwrap();
+        list.delete(1);
+        assert_eq!(two.wait().await, None);
+    }
+
     #[test]
     fn get_jobs() {
         let list = JobList::new();
@@ -209,6 +391,9 @@ mod tests {
         assert_eq!(Some(State::BG), list.get_state(2));
         assert_eq!(Some("three".to_string()), list.get_cmdline(2));
         assert_eq!(None, list.get_pid(3));
+        assert_eq!(Some(1), list.fg_pid());
+        list.set_state(0, State::BG).unwrap();
+        assert_eq!(None, list.fg_pid());
     }
 
     #[test]
@@ -227,4 +412,124 @@ mod tests {
         assert_eq!(None, list.get_pid(2));
         assert_eq!(Ok(1), list.add(5, State::BG, "four".to_string()));
     }
+
+    #[test]
+    fn printing_jobs() {
+        let list = JobList::new();
+        for pid in 0..12 {
+            list.add(pid, State::BG, format!("job {pid}")).unwrap();
+        }
+        list.set_state(10, State::FG).unwrap();
+        let mut out = Vec::new();
+        list.list_jobs(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(12, lines.len());
+        assert_eq!("[0] (0) Background job 0", lines[0]);
+        assert_eq!("[2] (2) Background job 2", lines[2]);
+        assert_eq!("[10] (10) Foreground job 10", lines[10]);
+        list.set_state(10, State::ST).unwrap();
+        let mut out = Vec::new();
+        list.list_jobs(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(Some("[10] (10) Stopped job 10"), out.lines().nth(10));
+        assert!(out.ends_with('\n'));
+    }
+
+    #[test]
+    fn moving_jobs() {
+        let list = JobList::new();
+        list.add(1, State::FG, "one".to_string()).unwrap();
+        list.add(2, State::BG, "two".to_string()).unwrap();
+        assert_eq!(
+            Err("Can't foreground a job if a foreground job already exists"),
+            list.set_state(1, State::FG)
+        );
+        assert_eq!(Ok(()), list.set_state(0, State::FG));
+        assert_eq!(Ok(()), list.set_state(0, State::BG));
+        assert_eq!(Ok(()), list.set_state(1, State::FG));
+        assert_eq!(Some(State::FG), list.get_state(1));
+        assert_eq!(Ok(()), list.set_state(1, State::ST));
+        assert_eq!(None, list.fg_pid());
+        assert_eq!(Ok(()), list.set_state(0, State::FG));
+        assert_eq!(Err("No job with that jid"), list.set_state(2, State::BG));
+    }
+
+    #[derive(Debug)]
+    enum Op {
+        Add(State),
+        Delete(usize),
+        SetState(usize, State),
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        let state = prop_oneof![Just(State::FG), Just(State::BG)];
+        // jids past the end are rarely used, but they should be handled
+        let jid = 0..8usize;
+        prop_oneof![
+            state.clone().prop_map(Op::Add),
+            jid.clone().prop_map(Op::Delete),
+            (jid, state).prop_map(|(jid, state)| Op::SetState(jid, state)),
+        ]
+    }
+
+    // Checks the list against a model of what it should hold
+    fn check(list: &JobList, model: &BTreeMap<usize, State>) {
+        let JobList(arc) = list;
+        let job_list = arc.lock().unwrap();
+
+        let states: BTreeMap<usize, State> = job_list
+            .jobs
+            .iter()
+            .map(|(jid, job)| (*jid, job.state))
+            .collect();
+        assert_eq!(model, &states);
+        assert_eq!(model.keys().max().copied(), job_list.max_jid);
+        let fg: Vec<usize> = model
+            .iter()
+            .filter(|(_, state)| **state == State::FG)
+            .map(|(jid, _)| *jid)
+            .collect();
+        assert!(fg.len() <= 1, "more than one foreground job: {fg:?}");
+        assert_eq!(fg.first().copied(), job_list.fg_job);
+    }
+
+    proptest! {
+        #[test]
+        fn random_operations_keep_the_list_consistent(ops in prop::collection::vec(op(), 0..50)) {
+            let list = JobList::new();
+            let mut model = BTreeMap::new();
+            for (pid, op) in (0..).zip(ops) {
+                let has_fg = model.values().any(|state| *state == State::FG);
+                match op {
+                    Op::Add(state) => {
+                        let result = list.add(pid, state, format!("job {pid}"));
+                        if state == State::FG && has_fg {
+                            prop_assert!(result.is_err(), "{:?}", op);
+                        } else {
+                            let jid = model.keys().max().map_or(0, |max| max + 1);
+                            prop_assert_eq!(Ok(jid), result, "{:?}", op);
+                            model.insert(jid, state);
+                        }
+                    }
+                    Op::Delete(jid) => {
+                        let deleted = list.delete(jid);
+                        prop_assert_eq!(model.remove(&jid).is_some(), deleted, "{:?}", op);
+                    }
+                    Op::SetState(jid, state) => {
+                        let result = list.set_state(jid, state);
+                        let allowed = match model.get(&jid) {
+                            None => false,
+                            Some(current) => state == State::BG || *current == State::FG || !has_fg,
+                        };
+                        prop_assert_eq!(allowed, result.is_ok(), "{:?}", op);
+                        if allowed {
+                            model.insert(jid, state);
+                        }
+                    }
+                }
+                check(&list, &model);
+            }
+        }
+    }
 }
@@ -0,0 +1,430 @@
+//! Which keys do what in the line editor.
+//!
+//! The `keymap` setting (see [`crate::config`]) picks emacs style keys, the
+//! default, or vi style keys. In vi style each line starts in insert mode,
+//! escape switches to normal mode for moving around, and the prompt shows
+//! which mode the editor is in. On top of that, the `bind` builtin can bind
+//! any key to any action in any mode.
+
+use std::fmt::Write;
+
+use super::editor::Key;
+use crate::config::Config;
+
+/// A set of key bindings the editor can be in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mode {
+    #[default]
+    Emacs,
+    ViInsert,
+    ViNormal,
+}
+
+impl Mode {
+    const ALL: [Mode; 3] = [Mode::Emacs, Mode::ViInsert, Mode::ViNormal];
+
+    fn name(self) -> &'static str {
+        match self {
+            Mode::Emacs => "emacs",
+            Mode::ViInsert => "vi-insert",
+            Mode::ViNormal => "vi-normal",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Mode> {
+        Mode::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+
+    /// What goes in front of the prompt to show the mode.
+    pub fn indicator(self) -> &'static str {
+        match self {
+            Mode::Emacs => "",
+            Mode::ViInsert => "[I] ",
+            Mode::ViNormal => "[N] ",
+        }
+    }
+
+    /// The mode each line starts in, from the `keymap` setting.
+    pub fn initial() -> Mode {
+        match Config::load().get("keymap") {
+            "vi" => Mode::ViInsert,
+            _ => Mode::Emacs,
+        }
+    }
+}
+
+/// Something the editor can do when a key is pressed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Type the key's character.
+    SelfInsert,
+    AcceptLine,
+    /// Give up on the line.
+    Interrupt,
+    /// Delete the character under the cursor, or end input on an empty line.
+    DeleteOrEof,
+    DeleteChar,
+    BackwardDeleteChar,
+    /// Move right, or take the suggestion at the end of the line.
+    ForwardChar,
+    BackwardChar,
+    ForwardWord,
+    BackwardWord,
+    BeginningOfLine,
+    /// Move to the end, or take the suggestion if already there.
+    EndOfLine,
+    AcceptSuggestion,
+    /// Finish the word before the cursor, or show the ways it could go.
+    Complete,
+    /// Cut from the cursor to the end of the line.
+    KillLine,
+    /// Cut from the start of the line to the cursor.
+    BackwardKillLine,
+    /// Cut the word before the cursor.
+    BackwardKillWord,
+    /// Cut from the cursor to the end of the word.
+    KillWord,
+    /// Paste the last thing cut, or what's on the clipboard.
+    Yank,
+    PreviousHistory,
+    NextHistory,
+    Undo,
+    Redo,
+    ViCommandMode,
+    /// Insert before the cursor.
+    ViInsert,
+    /// Insert after the cursor.
+    ViAppend,
+    ViInsertAtStart,
+    ViAppendAtEnd,
+}
+
+impl Action {
+    const ALL: [Action; 28] = [
+        Action::SelfInsert,
+        Action::AcceptLine,
+        Action::Interrupt,
+        Action::DeleteOrEof,
+        Action::DeleteChar,
+        Action::BackwardDeleteChar,
+        Action::ForwardChar,
+        Action::BackwardChar,
+        Action::ForwardWord,
+        Action::BackwardWord,
+        Action::BeginningOfLine,
+        Action::EndOfLine,
+        Action::AcceptSuggestion,
+        Action::Complete,
+        Action::KillLine,
+        Action::BackwardKillLine,
+        Action::BackwardKillWord,
+        Action::KillWord,
+        Action::Yank,
+        Action::PreviousHistory,
+        Action::NextHistory,
+        Action::Undo,
+        Action::Redo,
+        Action::ViCommandMode,
+        Action::ViInsert,
+        Action::ViAppend,
+        Action::ViInsertAtStart,
+        Action::ViAppendAtEnd,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Action::SelfInsert => "self-insert",
+            Action::AcceptLine => "accept-line",
+            Action::Interrupt => "interrupt",
+            Action::DeleteOrEof => "delete-or-eof",
+            Action::DeleteChar => "delete-char",
+            Action::BackwardDeleteChar => "backward-delete-char",
+            Action::ForwardChar => "forward-char",
+            Action::BackwardChar => "backward-char",
+            Action::ForwardWord => "forward-word",
+            Action::BackwardWord => "backward-word",
+            Action::BeginningOfLine => "beginning-of-line",
+            Action::EndOfLine => "end-of-line",
+            Action::AcceptSuggestion => "accept-suggestion",
+            Action::Complete => "complete",
+            Action::KillLine => "kill-line",
+            Action::BackwardKillLine => "backward-kill-line",
+            Action::BackwardKillWord => "backward-kill-word",
+            Action::KillWord => "kill-word",
+            Action::Yank => "yank",
+            Action::PreviousHistory => "previous-history",
+            Action::NextHistory => "next-history",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::ViCommandMode => "vi-command-mode",
+            Action::ViInsert => "vi-insert",
+            Action::ViAppend => "vi-append",
+            Action::ViInsertAtStart => "vi-insert-at-start",
+            Action::ViAppendAtEnd => "vi-append-at-end",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+/// Keys with names, besides Ctrl and a letter.
+const NAMED_KEYS: &[(Key, &str)] = &[
+    (Key::Enter, "enter"),
+    (Key::Backspace, "backspace"),
+    (Key::Delete, "delete"),
+    (Key::Left, "left"),
+    (Key::Right, "right"),
+    (Key::Up, "up"),
+    (Key::Down, "down"),
+    (Key::Home, "home"),
+    (Key::End, "end"),
+    (Key::Escape, "escape"),
+    (Key::Ctrl('i'), "tab"),
+    (Key::BackTab, "shift-tab"),
+    (Key::Char(' '), "space"),
+];
+
+/// The name `bind` uses for `key`.
+fn key_name(key: Key) -> String {
+    if let Some((_, name)) = NAMED_KEYS.iter().find(|(k, _)| *k == key) {
+        return name.to_string();
+    }
+    match key {
+        Key::Ctrl(c) => format!("ctrl-{c}"),
+        Key::Alt(c) => format!("alt-{c}"),
+        Key::Char(c) => c.to_string(),
+        _ => String::from("unknown"),
+    }
+}
+
+/// The key called `name`, like `ctrl-a`, `left` or `x`.
+fn parse_key(name: &str) -> Option<Key> {
+    if let Some((key, _)) = NAMED_KEYS.iter().find(|(_, n)| *n == name) {
+        return Some(*key);
+    }
+    if let Some(letter) = name.strip_prefix("ctrl-") {
+        let mut chars = letter.chars();
+        return match (chars.next(), chars.next()) {
+            (Some(c @ ('a'..='z' | '_')), None) => Some(Key::Ctrl(c)),
+            _ => None,
+        };
+    }
+    if let Some(key) = name.strip_prefix("alt-") {
+        let mut chars = key.chars();
+        return match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(Key::Alt(c)),
+            _ => None,
+        };
+    }
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(Key::Char(c)),
+        _ => None,
+    }
+}
+
+/// What `key` does in `mode` unless it's been bound to something else.
+fn default_binding(mode: Mode, key: Key) -> Option<Action> {
+    match (mode, key) {
+        (_, Key::Enter) => Some(Action::AcceptLine),
+        (_, Key::Ctrl('c')) => Some(Action::Interrupt),
+        (_, Key::Ctrl('d')) => Some(Action::DeleteOrEof),
+        (_, Key::Delete) => Some(Action::DeleteChar),
+        (_, Key::Left) => Some(Action::BackwardChar),
+        (_, Key::Right) => Some(Action::ForwardChar),
+        (_, Key::Home) => Some(Action::BeginningOfLine),
+        (_, Key::End) => Some(Action::EndOfLine),
+        (_, Key::Up) => Some(Action::PreviousHistory),
+        (_, Key::Down) => Some(Action::NextHistory),
+
+        (Mode::ViNormal, Key::Char(c)) => match c {
+            'h' => Some(Action::BackwardChar),
+            'l' | ' ' => Some(Action::ForwardChar),
+            'w' => Some(Action::ForwardWord),
+            'b' => Some(Action::BackwardWord),
+            '0' | '^' => Some(Action::BeginningOfLine),
+            '$' => Some(Action::EndOfLine),
+            'x' => Some(Action::DeleteChar),
+            'X' => Some(Action::BackwardDeleteChar),
+            'D' => Some(Action::KillLine),
+            'p' => Some(Action::Yank),
+            'k' => Some(Action::PreviousHistory),
+            'j' => Some(Action::NextHistory),
+            'u' => Some(Action::Undo),
+            'i' => Some(Action::ViInsert),
+            'a' => Some(Action::ViAppend),
+            'I' => Some(Action::ViInsertAtStart),
+            'A' => Some(Action::ViAppendAtEnd),
+            _ => None,
+        },
+        (Mode::ViNormal, Key::Backspace) => Some(Action::BackwardChar),
+        (Mode::ViNormal, Key::Ctrl('r')) => Some(Action::Redo),
+        (Mode::ViNormal, _) => None,
+
+        (Mode::ViInsert, Key::Escape) => Some(Action::ViCommandMode),
+        (_, Key::Char(_)) => Some(Action::SelfInsert),
+        (_, Key::Backspace | Key::Ctrl('h')) => Some(Action::BackwardDeleteChar),
+        (_, Key::Ctrl('i')) => Some(Action::Complete),
+        (_, Key::Ctrl('a')) => Some(Action::BeginningOfLine),
+        (_, Key::Ctrl('e')) => Some(Action::EndOfLine),
+        (_, Key::Ctrl('b')) => Some(Action::BackwardChar),
+        (_, Key::Ctrl('f')) => Some(Action::ForwardChar),
+        (_, Key::Ctrl('k')) => Some(Action::KillLine),
+        (_, Key::Ctrl('u')) => Some(Action::BackwardKillLine),
+        (_, Key::Ctrl('w')) => Some(Action::BackwardKillWord),
+        (_, Key::Alt('d')) => Some(Action::KillWord),
+        (_, Key::Alt('f')) => Some(Action::ForwardWord),
+        (_, Key::Alt('b')) => Some(Action::BackwardWord),
+        (_, Key::Ctrl('y')) => Some(Action::Yank),
+        (_, Key::Ctrl('p')) => Some(Action::PreviousHistory),
+        (_, Key::Ctrl('n')) => Some(Action::NextHistory),
+        (_, Key::Ctrl('_')) => Some(Action::Undo),
+        (_, Key::Alt('/')) => Some(Action::Redo),
+        _ => None,
+    }
+}
+
+/// The key bindings, including any changed with `bind`.
+#[derive(Clone, Debug, Default)]
+pub struct Keymap {
+    /// Bindings from `bind`, which win over the defaults.
+    custom: Vec<(Mode, Key, Action)>,
+}
+
+impl Keymap {
+    /// What pressing `key` in `mode` should do.
+    pub fn action(&self, mode: Mode, key: Key) -> Option<Action> {
+        self.custom
+            .iter()
+            .find(|(m, k, _)| *m == mode && *k == key)
+            .map(|(_, _, action)| *action)
+            .or_else(|| default_binding(mode, key))
+    }
+
+    fn bind(&mut self, mode: Mode, key: Key, action: Action) {
+        self.custom.retain(|(m, k, _)| !(*m == mode && *k == key));
+        self.custom.push((mode, key, action));
+    }
+
+    /// Every key that does something in `mode`, for listing.
+    fn bindings(&self, mode: Mode) -> Vec<(Key, Action)> {
+        let keys = NAMED_KEYS
+            .iter()
+            .map(|(key, _)| *key)
+            .chain(('a'..='z').chain(['_']).map(Key::Ctrl))
+            .chain(['/'].map(Key::Alt))
+            .chain(('!'..='~').map(Key::Char))
+            .chain(self.custom.iter().map(|(_, key, _)| *key));
+        let mut bindings: Vec<(Key, Action)> = Vec::new();
+        for key in keys {
+            if bindings.iter().any(|(k, _)| *k == key) {
+                continue;
+            }
+            // typing is the obvious thing for a character to do
+            match self.action(mode, key) {
+                Some(Action::SelfInsert) if matches!(key, Key::Char(_)) => (),
+                Some(action) => bindings.push((key, action)),
+                None => (),
+            }
+        }
+        bindings
+    }
+
+    /// Runs the `bind` builtin, returning what should be printed.
+    ///
+    /// - `bind` lists what each key does in each mode.
+    /// - `bind -l` lists the actions keys can be bound to.
+    /// - `bind [-m <mode>] <key>` prints what a key does.
+    /// - `bind [-m <mode>] <key> <action>` binds a key.
+    ///
+    /// Without `-m`, the mode is the one lines start in.
+    pub fn command(&mut self, args: &[String]) -> Result<String, String> {
+        let (mode, args) = match args {
+            [flag, mode, args @ ..] if flag == "-m" => (
+                Mode::from_name(mode).ok_or_else(|| format!("bind: {mode}: no such mode"))?,
+                args,
+            ),
+            _ => (Mode::initial(), args),
+        };
+        let key = |name: &str| parse_key(name).ok_or_else(|| format!("bind: {name}: no such key"));
+
+        match args {
+            [] => {
+                let mut out = String::new();
+                for mode in Mode::ALL {
+                    for (key, action) in self.bindings(mode) {
+                        let (mode, key, action) = (mode.name(), key_name(key), action.name());
+                        writeln!(out, "bind -m {mode} {key} {action}").unwrap();
+                    }
+                }
+                Ok(out)
+            }
+            [flag] if flag == "-l" => Ok(Action::ALL
+                .iter()
+                .map(|action| format!("{}\n", action.name()))
+                .collect()),
+            [name] => match self.action(mode, key(name)?) {
+                Some(action) => Ok(format!("{}\n", action.name())),
+                None => Ok(String::from("nothing\n")),
+            },
+            [name, action] => {
+                let key = key(name)?;
+                let action = Action::from_name(action)
+                    .ok_or_else(|| format!("bind: {action}: no such action, see `bind -l`"))?;
+                self.bind(mode, key, action);
+                Ok(String::new())
+            }
+            _ => Err(String::from("bind: too many arguments")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bind(keymap: &mut Keymap, args: &str) -> Result<String, String> {
+        let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+        keymap.command(&args)
+    }
+
+    #[test]
+    fn keys_can_be_rebound() {
+        let mut keymap = Keymap::default();
+        assert_eq!(
+            keymap.action(Mode::Emacs, Key::Char('x')),
+            Some(Action::SelfInsert)
+        );
+        assert_eq!(
+            keymap.action(Mode::ViNormal, Key::Char('x')),
+            Some(Action::DeleteChar)
+        );
+        assert_eq!(keymap.action(Mode::ViNormal, Key::Char('z')), None);
+
+        bind(&mut keymap, "-m emacs ctrl-o end-of-line").unwrap();
+        bind(&mut keymap, "-m vi-normal space accept-suggestion").unwrap();
+        assert_eq!(
+            keymap.action(Mode::Emacs, Key::Ctrl('o')),
+            Some(Action::EndOfLine)
+        );
+        assert_eq!(
+            bind(&mut keymap, "-m vi-normal space").unwrap(),
+            "accept-suggestion\n"
+        );
+        assert!(bind(&mut keymap, "-m emacs ctrl-o fly").is_err());
+        assert!(bind(&mut keymap, "-m emacs ctrl-oo end-of-line").is_err());
+        assert!(bind(&mut keymap, "-m viper x delete-char").is_err());
+
+        let listing = bind(&mut keymap, "").unwrap();
+        assert!(listing.contains("bind -m emacs ctrl-a beginning-of-line\n"));
+        assert!(listing.contains("bind -m emacs ctrl-o end-of-line\n"));
+        assert!(listing.contains("bind -m vi-insert escape vi-command-mode\n"));
+        assert!(!listing.contains("self-insert"));
+        for (key, name) in NAMED_KEYS {
+            assert_eq!(parse_key(name), Some(*key));
+            assert_eq!(key_name(*key), *name);
+        }
+    }
+}
@@ -0,0 +1,275 @@
+//! The `kill` builtin, which sends signals to jobs and processes.
+//!
+//! - `kill <target>...` sends `SIGTERM`.
+//! - `kill -<signal> <target>...` and `kill -s <signal> <target>...` send
+//!   another signal, given by number or name, with or without the `SIG`, so
+//!   `-9`, `-KILL` and `-SIGKILL` are all the same.
+//! - `kill -0 <target>...` sends nothing, but fails if a target isn't there.
+//! - `kill -l` lists the signals, and `kill -l <number>` names one.
+//!
+//! A target is a process id, or a job: `%n` for job `n`, as `jobs` numbers
+//! them, and `%%` or `%+` for the newest one. A job gets the signal in its
+//! whole pipeline. Stopping and continuing a job moves it between the stopped
+//! and background jobs, and a stopped job that's told to stop for good is
+//! continued so it can. It leaves the job list once it's finished, like any
+//! other job.
+//!
+//! A background `sleep` is run by the shell itself, not a process. Any
+//! signal that would end it cancels it instead, and it finishes with the
+//! status of a `SIGTERM`, 143. It can't be stopped.
+
+use std::fmt::Write;
+
+use super::job_list::{JobList, State};
+use super::terminal;
+
+/// The signals `kill` knows by name.
+const SIGNALS: &[(&str, libc::c_int)] = &[
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("TTIN", libc::SIGTTIN),
+    ("TTOU", libc::SIGTTOU),
+    ("WINCH", libc::SIGWINCH),
+];
+
+/// The signal called `name`, which is a number or a name, with or without
+/// the `SIG`, in any case.
+pub fn signal(name: &str) -> Option<libc::c_int> {
+    if let Ok(number) = name.parse() {
+        // 0 only checks the target's there
+        let known = number == 0 || SIGNALS.iter().any(|&(_, signal)| signal == number);
+        return known.then_some(number);
+    }
+    let name = name.to_ascii_uppercase();
+    let name = name.strip_prefix("SIG").unwrap_or(&name);
+    SIGNALS
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|&(_, signal)| signal)
+}
+
+/// The name of `signal`, without the `SIG`.
+fn name(signal: libc::c_int) -> Option<&'static str> {
+    SIGNALS
+        .iter()
+        .find(|&&(_, known)| known == signal)
+        .map(|&(name, _)| name)
+}
+
+/// Sends `signal` to the job `jid`, keeping its state up to date.
+fn signal_job(job_list: &JobList, jid: usize, signal: libc::c_int) -> Result<(), String> {
+    let spec = format!("%{jid}");
+    let Some(pid) = job_list.get_pid(jid) else {
+        return Err(format!("kill: {spec}: no such job"));
+    };
+    // a background sleep is run by the shell itself, so it's cancelled
+    // rather than signalled
+    if job_list.is_task(jid) {
+        return match signal {
+            0 | libc::SIGCONT => Ok(()),
+            libc::SIGSTOP | libc::SIGTSTP | libc::SIGTTIN | libc::SIGTTOU => Err(format!(
+                "kill: {spec}: it's run by the shell, which can't stop it"
+            )),
+            _ => {
+                job_list.abort(jid);
+                Ok(())
+            }
+        };
+    }
+    if !terminal::signal_job(pid, signal) {
+        return Err(format!("kill: {spec}: {}", std::io::Error::last_os_error()));
+    }
+    let state = job_list.get_state(jid);
+    match signal {
+        libc::SIGSTOP | libc::SIGTSTP | libc::SIGTTIN | libc::SIGTTOU => {
+            let _ = job_list.set_state(jid, State::ST);
+        }
+        libc::SIGCONT if state == Some(State::ST) => {
+            let _ = job_list.set_state(jid, State::BG);
+        }
+        // a stopped job won't do anything about it until it's carried on
+        _ if state == Some(State::ST) => {
+            terminal::signal_job(pid, libc::SIGCONT);
+            let _ = job_list.set_state(jid, State::BG);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// The job named by `spec`, a job without its `%`: `n` for job `n`, and `%`,
+/// `+` or nothing for the newest one.
+pub fn jid(job_list: &JobList, spec: &str) -> Option<usize> {
+    match spec {
+        "%" | "+" | "" => job_list.last_jid(),
+        number => number.parse().ok(),
+    }
+}
+
+/// Sends `signal` to `target`, see the module docs.
+fn send(job_list: &JobList, target: &str, signal: libc::c_int) -> Result<(), String> {
+    let Some(spec) = target.strip_prefix('%') else {
+        let Ok(pid) = target.parse::<libc::pid_t>() else {
+            return Err(format!(
+                "kill: {target}: expected a process id or a job like %1"
+            ));
+        };
+        // SAFETY: kill only sends a signal.
+        return match unsafe { libc::kill(pid, signal) } {
+            0 => Ok(()),
+            _ => Err(format!(
+                "kill: {target}: {}",
+                std::io::Error::last_os_error()
+            )),
+        };
+    };
+    match jid(job_list, spec) {
+        Some(jid) => signal_job(job_list, jid, signal),
+        None => Err(format!("kill: {target}: no such job")),
+    }
+}
+
+/// Lists the signals, or names the ones in `numbers`.
+fn list(numbers: &[String]) -> Result<String, String> {
+    let mut out = String::new();
+    if numbers.is_empty() {
+        for &(name, signal) in SIGNALS {
+            writeln!(out, "{signal:>2}) SIG{name}").unwrap();
+        }
+        return Ok(out);
+    }
+    for number in numbers {
+        // exit statuses from signals are 128 more than the signal
+        let signal = number.parse::<libc::c_int>().map(|n| match n {
+            129.. => n - 128,
+            n => n,
+        });
+        match signal.ok().and_then(name) {
+            Some(name) => writeln!(out, "{name}").unwrap(),
+            None => return Err(format!("{out}kill: {number}: no such signal")),
+        }
+    }
+    Ok(out)
+}
+
+/// Runs the `kill` builtin, returning what should be printed.
+pub fn command(args: &[String], job_list: &JobList) -> Result<String, String> {
+    let usage = || String::from("kill: usage: kill [-s signal | -signal] target... or kill -l");
+    let (signal, targets) = match args {
+        [] => return Err(usage()),
+        [flag, numbers @ ..] if flag == "-l" => return list(numbers),
+        [flag, name, targets @ ..] if flag == "-s" => (name.as_str(), targets),
+        [flag, targets @ ..] if flag == "--" => ("TERM", targets),
+        [flag, targets @ ..] if flag.starts_with('-') && flag.len() > 1 => (&flag[1..], targets),
+        targets => ("TERM", targets),
+    };
+    let Some(signal) = self::signal(signal) else {
+        return Err(format!("kill: {signal}: no such signal"));
+    };
+    if targets.is_empty() {
+        return Err(usage());
+    }
+    let errors: Vec<String> = targets
+        .iter()
+        .filter_map(|target| send(job_list, target, signal).err())
+        .collect();
+    match errors.is_empty() {
+        true => Ok(String::new()),
+        false => Err(errors.join("\n")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+    use std::time::Duration;
+
+    fn run(args: &[&str], job_list: &JobList) -> Result<String, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        command(&args, job_list)
+    }
+
+    #[test]
+    fn signals_are_named() {
+        for name in ["9", "KILL", "kill", "SIGKILL"] {
+            assert_eq!(signal(name), Some(libc::SIGKILL), "{name}");
+        }
+        assert_eq!(signal("0"), Some(0));
+        assert_eq!(signal("NOPE"), None);
+        assert_eq!(signal("999"), None);
+        let jobs = JobList::new();
+        assert_eq!(
+            run(&["-l", "15", "137"], &jobs),
+            Ok(String::from("TERM\nKILL\n"))
+        );
+        assert!(run(&["-l"], &jobs).unwrap().contains(" 9) SIGKILL\n"));
+        assert!(run(&["-FOO", "1"], &jobs).is_err());
+        assert!(run(&["-9"], &jobs).is_err());
+    }
+
+    #[test]
+    fn jobs_are_killed() {
+        let jobs = JobList::new();
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        let jid = jobs
+            .add(child.id(), State::BG, String::from("sleep 10"))
+            .unwrap();
+
+        run(&["-STOP", "%%"], &jobs).unwrap();
+        assert_eq!(jobs.get_state(jid), Some(State::ST));
+        // a stopped job is carried on so it can die
+        run(&["-s", "KILL", &format!("%{jid}")], &jobs).unwrap();
+        assert_eq!(jobs.get_state(jid), Some(State::BG));
+        assert_eq!(child.wait().unwrap().signal(), Some(libc::SIGKILL));
+
+        assert_eq!(
+            run(&["%7", "x"], &jobs),
+            Err(String::from(
+                "kill: %7: no such job\nkill: x: expected a process id or a job like %1"
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn background_sleeps_are_cancelled() {
+        let jobs = JobList::new();
+        let sleep = super::super::Executable::sleep(
+            Duration::from_secs(100),
+            State::BG,
+            String::from("sleep 100 &"),
+            jobs.clone(),
+        );
+        assert!(sleep.await);
+        let jid = jobs.last_jid().unwrap();
+        let done = jobs.completion(jid).unwrap();
+
+        run(&["-0", "%%"], &jobs).unwrap();
+        assert!(run(&["-STOP", "%%"], &jobs).is_err());
+        assert_eq!(jobs.get_state(jid), Some(State::BG));
+        run(&[&format!("%{jid}")], &jobs).unwrap();
+        assert_eq!(done.wait().await, Some(143));
+        // it's taken off the list just after it says it's finished
+        for _ in 0..100 {
+            if jobs.get_pid(jid).is_none() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("the cancelled sleep is still a job");
+    }
+}
@@ -0,0 +1,222 @@
+//! Resource limits for the programs the shell runs, set with the `limit`
+//! builtin.
+//!
+//! `limit cpu 10` caps every program run after it at 10 seconds of CPU time,
+//! so a runaway background job can't hog the machine. `limit cpu=10 memory=1G
+//! cmd` only limits `cmd`. Limits are set with `setrlimit` in the child just
+//! before it execs, so they never apply to the shell itself.
+
+use std::{fmt::Write, io};
+
+/// Something a program's use of can be limited.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resource {
+    /// CPU time, in seconds.
+    Cpu,
+    /// Address space, in bytes.
+    Memory,
+    /// The largest file that can be written, in bytes.
+    FileSize,
+}
+
+impl Resource {
+    const ALL: [Resource; 3] = [Resource::Cpu, Resource::Memory, Resource::FileSize];
+
+    fn name(self) -> &'static str {
+        match self {
+            Resource::Cpu => "cpu",
+            Resource::Memory => "memory",
+            Resource::FileSize => "filesize",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Resource> {
+        Resource::ALL.into_iter().find(|r| r.name() == name)
+    }
+
+    fn rlimit(self) -> libc::__rlimit_resource_t {
+        match self {
+            Resource::Cpu => libc::RLIMIT_CPU,
+            Resource::Memory => libc::RLIMIT_AS,
+            Resource::FileSize => libc::RLIMIT_FSIZE,
+        }
+    }
+
+    /// Reads a limit like `30` seconds or `512M` bytes. `unlimited` is `None`.
+    fn parse(self, text: &str) -> Result<Option<u64>, String> {
+        if text == "unlimited" {
+            return Ok(None);
+        }
+        let (number, scale) = match (self, text.char_indices().last()) {
+            (Resource::Cpu, _) => (text, 1),
+            (_, Some((i, c))) if c.is_ascii_alphabetic() => {
+                let scale = match c.to_ascii_uppercase() {
+                    'K' => 1 << 10,
+                    'M' => 1 << 20,
+                    'G' => 1 << 30,
+                    _ => return Err(format!("limit: {text}: unknown size")),
+                };
+                (&text[..i], scale)
+            }
+            _ => (text, 1),
+        };
+        number
+            .parse::<u64>()
+            .ok()
+            .and_then(|n| n.checked_mul(scale))
+            .map(Some)
+            .ok_or_else(|| format!("limit: {text}: invalid {}", self.name()))
+    }
+
+    /// Shows `value` the way it would be typed.
+    fn show(self, value: Option<u64>) -> String {
+        let Some(value) = value else {
+            return String::from("unlimited");
+        };
+        if self == Resource::Cpu {
+            return value.to_string();
+        }
+        for (suffix, scale) in [('G', 1 << 30), ('M', 1 << 20), ('K', 1 << 10)] {
+            if value >= scale && value.is_multiple_of(scale) {
+                return format!("{}{suffix}", value / scale);
+            }
+        }
+        value.to_string()
+    }
+}
+
+/// The limits a program is run with.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    values: Vec<(Resource, u64)>,
+}
+
+impl Limits {
+    pub fn get(&self, resource: Resource) -> Option<u64> {
+        self.values
+            .iter()
+            .find(|(r, _)| *r == resource)
+            .map(|(_, value)| *value)
+    }
+
+    fn set(&mut self, resource: Resource, value: Option<u64>) {
+        self.values.retain(|(r, _)| *r != resource);
+        if let Some(value) = value {
+            self.values.push((resource, value));
+        }
+    }
+
+    /// Reads `resource=value` words from the front of `args`, like `limit
+    /// cpu=10 cmd`, on top of these limits. Returns the limits and the rest of
+    /// `args`.
+    pub fn with<'a>(&self, args: &'a [String]) -> Result<(Limits, &'a [String]), String> {
+        let mut limits = self.clone();
+        let mut rest = args;
+        while let Some((word, after)) = rest.split_first() {
+            let Some((name, value)) = word.split_once('=') else {
+                break;
+            };
+            let resource = Resource::from_name(name)
+                .ok_or_else(|| format!("limit: {name}: no such resource"))?;
+            limits.set(resource, resource.parse(value)?);
+            rest = after;
+        }
+        Ok((limits, rest))
+    }
+
+    /// Runs the `limit` builtin, returning what should be printed.
+    ///
+    /// - `limit` lists the limits.
+    /// - `limit <resource> <value>` sets one, or lifts it with `unlimited`.
+    pub fn command(&mut self, args: &[String]) -> Result<String, String> {
+        match args {
+            [] => {
+                let mut out = String::new();
+                for resource in Resource::ALL {
+                    let value = resource.show(self.get(resource));
+                    writeln!(out, "{:<9}{value}", resource.name()).unwrap();
+                }
+                Ok(out)
+            }
+            [name, value] => {
+                let resource = Resource::from_name(name)
+                    .ok_or_else(|| format!("limit: {name}: no such resource"))?;
+                self.set(resource, resource.parse(value)?);
+                Ok(String::new())
+            }
+            [name] => Err(format!("limit: {name}: missing value")),
+            _ => Err(String::from("limit: too many arguments")),
+        }
+    }
+
+    /// Sets the limits on `command`'s process before it runs.
+    pub fn apply(&self, command: &mut tokio::process::Command) {
+        if self.values.is_empty() {
+            return;
+        }
+        let values = self.values.clone();
+        // SAFETY: getrlimit and setrlimit are async-signal-safe, and nothing
+        // here allocates.
+        unsafe {
+            command.pre_exec(move || {
+                for (resource, value) in &values {
+                    let mut limit: libc::rlimit = std::mem::zeroed();
+                    if libc::getrlimit(resource.rlimit(), &mut limit) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    // only the soft limit, which can't go over the hard one
+                    limit.rlim_cur = (*value as libc::rlim_t).min(limit.rlim_max);
+                    if libc::setrlimit(resource.rlimit(), &limit) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(text: &str) -> Vec<String> {
+        text.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn limits_are_set_and_listed() {
+        let mut limits = Limits::default();
+        limits.command(&words("cpu 10")).unwrap();
+        limits.command(&words("memory 512M")).unwrap();
+        limits.command(&words("filesize 1536")).unwrap();
+        assert_eq!(limits.get(Resource::Memory), Some(512 << 20));
+        assert_eq!(
+            limits.command(&[]).unwrap(),
+            "cpu      10\nmemory   512M\nfilesize 1536\n"
+        );
+        limits.command(&words("cpu unlimited")).unwrap();
+        assert_eq!(limits.get(Resource::Cpu), None);
+        assert!(limits.command(&words("disk 1G")).is_err());
+        assert!(limits.command(&words("memory 1T")).is_err());
+        assert!(limits.command(&words("cpu 1G")).is_err());
+
+        let args = words("cpu=5 filesize=1K dd if=/dev/zero");
+        let (job, rest) = limits.with(&args).unwrap();
+        assert_eq!(rest, &args[2..]);
+        assert_eq!(job.get(Resource::Cpu), Some(5));
+        assert_eq!(job.get(Resource::FileSize), Some(1024));
+        assert_eq!(job.get(Resource::Memory), Some(512 << 20));
+    }
+
+    #[tokio::test]
+    async fn limits_reach_the_program() {
+        let mut limits = Limits::default();
+        limits.command(&words("cpu 7")).unwrap();
+        let mut command = tokio::process::Command::new("sh");
+        command.args(["-c", "ulimit -t"]);
+        limits.apply(&mut command);
+        let output = command.output().await.unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "7");
+    }
+}
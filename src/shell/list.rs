@@ -0,0 +1,168 @@
+//! Lists of commands, joined with `;`, `&&` and `||`.
+//!
+//! `make && ./run || echo failed; jobs` runs `make`, then `./run` only if
+//! `make` succeeded, then `echo failed` only if one of them failed, and then
+//! `jobs` whatever happened. Each command is parsed just before it runs, so
+//! it sees what the ones before it did.
+//!
+//! The connectors in a `{ ...; }` group, like the one of
+//! `coproc log { tee log; }`, are part of its command instead.
+
+use super::tokenize::{self, SyntaxError};
+
+/// What joins a command in a list to the one before it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Connector {
+    /// `;`, or the start of the line: the command always runs.
+    Always,
+    /// `&&`: the command runs if the one before it succeeded.
+    And,
+    /// `||`: the command runs if the one before it failed.
+    Or,
+}
+
+impl Connector {
+    /// Whether a command joined with this runs, given the exit status of the
+    /// command before it.
+    pub fn runs(self, status: i32) -> bool {
+        match self {
+            Connector::Always => true,
+            Connector::And => status == 0,
+            Connector::Or => status != 0,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Connector::Always => ";",
+            Connector::And => "&&",
+            Connector::Or => "||",
+        }
+    }
+}
+
+/// Splits `input` into its commands at each `;`, `&&` and `||`, apart from
+/// quoted ones and the `|` in `>|`, along with what joins each one to the one
+/// before it.
+///
+/// Blank commands are left out, but `&&` and `||` need a command on both
+/// sides of them.
+pub fn split(input: &str) -> Result<Vec<(Connector, &str)>, SyntaxError> {
+    let mut commands = Vec::new();
+    let (mut start, mut connector) = (0, Connector::Always);
+    // where `connector` is, for when it's out of place
+    let mut connector_at = 0;
+    let masked = tokenize::mask(input);
+    let bytes = masked.as_bytes();
+    // whether the byte at `i` is a word of its own, like the `{` of a group
+    let alone = |i: usize| {
+        let edge = |byte: Option<&u8>| byte.is_none_or(|b| b.is_ascii_whitespace() || *b == b';');
+        edge(i.checked_sub(1).and_then(|i| bytes.get(i))) && edge(bytes.get(i + 1))
+    };
+    let mut groups = 0;
+    let mut i = 0;
+    while i <= bytes.len() {
+        // like in bash, `{` only starts a group where a command would start
+        let starts_command = || {
+            let before: Vec<&str> = masked[start..i].split_whitespace().collect();
+            groups > 0 || matches!(before[..], [] | ["coproc"] | ["coproc", _])
+        };
+        match bytes.get(i) {
+            Some(b'{') if alone(i) && starts_command() => groups += 1,
+            Some(b'}') if groups > 0 && alone(i) => groups -= 1,
+            _ => {}
+        }
+        if groups > 0 && i < bytes.len() {
+            i += 1;
+            continue;
+        }
+        let (next, width) = match &bytes[i..] {
+            [] => (Connector::Always, 0),
+            [b';', ..] => (Connector::Always, 1),
+            [b'&', b'&', ..] => (Connector::And, 2),
+            [b'|', b'|', ..] if !masked[..i].ends_with('>') => (Connector::Or, 2),
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        let command = &input[start..i];
+        if command.trim().is_empty() {
+            // the connector either side of nothing is out of place, the
+            // second one if both are
+            let misplaced = [(next, i), (connector, connector_at)]
+                .into_iter()
+                .find(|(c, _)| *c != Connector::Always);
+            if let Some((misplaced, at)) = misplaced {
+                return Err(SyntaxError::new(
+                    format!("rlsh: syntax error near `{}'", misplaced.symbol()),
+                    at,
+                ));
+            }
+        } else {
+            commands.push((connector, command));
+        }
+        if width == 0 {
+            break;
+        }
+        (connector, connector_at) = (next, i);
+        i += width;
+        start = i;
+    }
+    Ok(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_split_at_their_connectors() {
+        use Connector::*;
+        assert_eq!(
+            split("make && ./run || echo failed; jobs"),
+            Ok(vec![
+                (Always, "make "),
+                (And, " ./run "),
+                (Or, " echo failed"),
+                (Always, " jobs"),
+            ])
+        );
+        assert_eq!(
+            split("ls >| out;; cat out | wc -l;"),
+            Ok(vec![(Always, "ls >| out"), (Always, " cat out | wc -l")])
+        );
+        assert_eq!(
+            split("echo 'a && b'; echo \\;"),
+            Ok(vec![(Always, "echo 'a && b'"), (Always, " echo \\;")])
+        );
+        assert_eq!(
+            split("coproc log { tee log; }; echo { a,b }; x"),
+            Ok(vec![
+                (Always, "coproc log { tee log; }"),
+                (Always, " echo { a,b }"),
+                (Always, " x")
+            ])
+        );
+        assert_eq!(split(" "), Ok(vec![]));
+        assert_eq!(
+            split("&& ls"),
+            Err(SyntaxError::new("rlsh: syntax error near `&&'", 0))
+        );
+        assert_eq!(
+            split("ls ||"),
+            Err(SyntaxError::new("rlsh: syntax error near `||'", 3))
+        );
+        assert_eq!(
+            split("ls; || ls"),
+            Err(SyntaxError::new("rlsh: syntax error near `||'", 4))
+        );
+        assert_eq!(
+            split("ls && && ls"),
+            Err(SyntaxError::new("rlsh: syntax error near `&&'", 6))
+        );
+
+        assert!(And.runs(0) && !And.runs(1));
+        assert!(Or.runs(1) && !Or.runs(0));
+    }
+}
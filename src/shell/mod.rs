@@ -1,19 +1,137 @@
+mod abbr;
+mod alias;
+mod audit;
+mod braces;
+mod builtins;
+mod capture;
+mod clipboard;
+mod complete;
+mod coproc;
+pub(crate) mod date;
+mod editor;
+mod expand;
+mod fds;
+mod heredoc;
+mod hexdump;
+mod highlight;
+mod history;
 mod job_list;
+mod keymap;
+mod kill;
+mod limits;
+mod list;
+mod options;
+mod record;
+mod resume;
+mod specs;
+mod spellcheck;
+mod terminal;
+mod tokenize;
+mod variables;
+mod wait;
 
+use abbr::Abbreviations;
+use alias::Aliases;
+use builtins::EnvOverrides;
+use editor::Editor;
+use expand::Substitution;
+use fds::{FdTable, Redirect};
+use heredoc::Heredoc;
+use highlight::Highlighter;
+use history::History;
 use job_list::{JobList, State};
+use keymap::Keymap;
+use limits::Limits;
+use list::Connector;
+use options::{Options, ShellOption};
+use specs::Specs;
+use tokenize::SyntaxError;
+use variables::Variables;
 
 use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
     env,
-    fs::{self, DirEntry, File},
-    io::{self, Error, Write},
-    path::PathBuf,
-    process::Stdio,
-    time::SystemTime,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, Error, IsTerminal, Write},
+    os::{
+        fd::{AsRawFd, OwnedFd},
+        unix::process::ExitStatusExt,
+    },
+    path::{Path, PathBuf},
+    process::{ExitStatus, Stdio},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-use tokio::{process::Command, task};
+use tokio::{
+    process::{Child, Command},
+    runtime::Handle,
+    signal::unix::{SignalKind, signal},
+    task,
+};
+
+use crate::{
+    config, crash,
+    game::{self, locale::t},
+    headless,
+    style::{self, Palette, Role},
+    theme,
+};
 
-use crate::game;
+/// The names of the builtin commands, which [`App::parse`] handles itself
+/// rather than looking for a program, and what they do.
+const BUILTINS: &[(&str, &str)] = &[
+    ("abbr", "add or list abbreviations"),
+    ("alias", "add or list aliases"),
+    ("attack", "punch a monster"),
+    ("audit", "look back at the commands run"),
+    ("basename", "strip the directory from a path"),
+    ("bg", "carry on a stopped job in the background"),
+    ("bind", "change what keys do"),
+    ("buy", "buy from a merchant"),
+    ("cd", "change directory"),
+    ("config", "change settings"),
+    ("coproc", "run a command to talk to in the background"),
+    ("date", "print the date"),
+    ("dirname", "strip the last part of a path"),
+    ("env", "run with a changed environment"),
+    ("exit", "leave the shell"),
+    ("export", "pass a variable on to programs"),
+    ("fg", "bring a job to the foreground"),
+    ("game", "poke at the game world"),
+    ("hexdump", "show a file's bytes"),
+    ("history", "list the commands entered"),
+    ("jobs", "list background jobs"),
+    ("kill", "send a signal to a job or process"),
+    ("leaderboard", "show the high scores"),
+    ("limit", "cap what programs can use"),
+    ("ls", "list files"),
+    ("mkfifo", "make a named pipe"),
+    ("pet", "look after your pet"),
+    ("play", "play back a recording"),
+    ("printf", "print formatted text"),
+    ("read", "read a line into a variable"),
+    ("realpath", "resolve a path"),
+    ("record", "record a session to play back"),
+    ("reputation", "see what factions think of you"),
+    ("roll", "roll dice"),
+    ("rsh", "run a command on another machine"),
+    ("search", "look for hidden things"),
+    ("seq", "print a sequence of numbers"),
+    ("set", "set shell options"),
+    ("shuf", "shuffle lines"),
+    ("sleep", "wait for a while"),
+    ("stat", "describe a file"),
+    ("test", "check a condition"),
+    ("theme", "change how things look"),
+    ("unalias", "remove aliases"),
+    ("unset", "remove a variable"),
+    ("wait", "wait for background jobs to finish"),
+    ("xxd", "show a file's bytes"),
+    ("[", "check a condition"),
+];
 
 /// Any string can be parsed into one of these variants.
 ///
@@ -25,11 +143,129 @@ enum Executable {
     /// cd can be called with no args or one arg pointing to the directory to change to.
     Cd(Option<String>),
     Exit,
-    Jobs(Option<String>),
+    Jobs(Option<Outfile>),
+    /// pet takes a subcommand and its arguments, see [`game::pet::command`].
+    Pet(Vec<String>),
+    Search,
+    Buy(String),
+    Leaderboard(Vec<String>),
+    /// reputation reports what each faction thinks of the player, see
+    /// [`game::reputation::report`].
+    Reputation(Vec<String>),
+    Roll(Vec<String>),
+    /// abbr adds and lists abbreviations, see [`Abbreviations::command`].
+    Abbr(Vec<String>),
+    /// history lists and clears the lines entered, see [`History::command`].
+    History(Vec<String>),
+    /// kill sends signals to jobs and processes, see [`kill::command`].
+    Kill(Vec<String>),
+    /// fg brings a job to the foreground, see [`resume::fg`].
+    Fg(Vec<String>),
+    /// bg carries on a stopped job in the background, see [`resume::bg`].
+    Bg(Vec<String>),
+    /// wait waits for background jobs to finish, see [`wait::command`].
+    Wait(Vec<String>),
+    /// alias adds and lists aliases, see [`Aliases::alias`].
+    Alias(Vec<String>),
+    /// mkfifo makes named pipes, see [`builtins::mkfifo`].
+    Mkfifo(Vec<String>),
+    /// unalias removes aliases, see [`Aliases::unalias`].
+    Unalias(Vec<String>),
+    /// bind changes what keys do in the line editor, see [`Keymap::command`].
+    Bind(Vec<String>),
+    /// limit lists and sets resource limits for programs, see
+    /// [`Limits::command`].
+    Limit(Vec<String>),
+    /// set lists variables, or turns shell options on and off, see
+    /// [`Options::command`].
+    Set(Vec<String>),
+    /// read reads a line into variables, see [`builtins::read`].
+    Read(Vec<String>),
+    /// test and [ check a condition, see [`builtins::test`].
+    Test(Vec<String>),
+    /// printf prints its arguments formatted, see [`builtins::printf`].
+    Printf(Vec<String>, Option<Outfile>),
+    /// seq prints a sequence of numbers, see [`builtins::seq`].
+    Seq(Vec<String>, Option<Outfile>),
+    /// shuf shuffles lines, see [`builtins::shuf`].
+    Shuf(Vec<String>, Option<Outfile>),
+    /// basename prints the last part of paths, see [`builtins::basename`].
+    Basename(Vec<String>, Option<Outfile>),
+    /// dirname prints paths without their last part, see
+    /// [`builtins::dirname`].
+    Dirname(Vec<String>, Option<Outfile>),
+    /// realpath prints absolute paths, see [`builtins::realpath`].
+    Realpath(Vec<String>, Option<Outfile>),
+    /// stat shows a file's metadata, and an entity's components, see
+    /// [`builtins::stat`].
+    Stat(Vec<String>, Option<Outfile>),
+    /// hexdump and xxd dump a file's bytes, see [`hexdump::command`].
+    Hexdump(Vec<String>, Option<Outfile>),
+    /// config shows and changes settings, see [`config::command`].
+    Config(Vec<String>, Option<Outfile>),
+    /// Sets shell variables, from a line of `NAME=value` words.
+    Assign(Vec<(String, String)>),
+    /// env lists the environment, see [`EnvOverrides::listing`]. With a
+    /// command, it's run as a [`Executable::NonBuiltin`] instead.
+    Env(EnvOverrides, Option<Outfile>),
+    /// export sets variables in every command's environment, see
+    /// [`builtins::export`].
+    Export(Vec<String>, Option<Outfile>),
+    /// unset removes variables, see [`builtins::unset`].
+    Unset(Vec<String>),
+    /// coproc starts a program to talk to in the background, see [`coproc`].
+    Coproc(String, NonBuiltInData),
+    /// date prints the date and time, see [`date::command`].
+    Date(Vec<String>, Option<Outfile>),
+    /// audit looks back through the audit log, see [`audit::command`].
+    Audit(Vec<String>, Option<Outfile>),
+    /// theme lists and switches themes, see [`theme::command`].
+    Theme(Vec<String>, Option<Outfile>),
+    /// record records a new shell session, see [`record::record`].
+    Record(Vec<String>),
+    /// play plays back a recording, see [`record::play`].
+    Play(Vec<String>),
+    /// sleep waits for a while, see [`builtins::sleep_duration`]. Like other
+    /// commands, it can be run in the background.
+    Sleep(Vec<String>, State, String),
+    /// A verb from the game parser and its captured arguments.
+    Game(game::class::Verb, Vec<String>),
+    /// game takes a subcommand and its arguments, see [`game::command`].
+    GameCommand(Vec<String>),
     Noop,
-    TempDebugSpawnEnemy(String),
+    /// A command that went wrong before it could run, like a redirect that
+    /// would overwrite a file with noclobber set. What went wrong has been
+    /// printed, and this is the exit status for `$?`.
+    Failed(i32),
+    /// A command that couldn't be parsed. It's reported by
+    /// [`App::run_list`], which knows the whole line to point into.
+    SyntaxError(SyntaxError),
     TempDebugAttackEnemy(String),
     NonBuiltin(NonBuiltInData),
+    /// Commands joined with `|`, all in the same state and with the same
+    /// cmdline.
+    Pipeline(Vec<NonBuiltInData>),
+}
+
+/// Where a command's output goes, from `>`, `>|` or `>>`.
+#[derive(Clone, Debug)]
+struct Outfile {
+    path: String,
+    /// Whether to add to the end of the file, for `>>`, rather than replace
+    /// what's in it.
+    append: bool,
+}
+
+impl Outfile {
+    /// Opens the file for writing, creating it if it isn't there.
+    fn open(&self) -> io::Result<File> {
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(self.append)
+            .truncate(!self.append)
+            .open(&self.path)
+    }
 }
 
 struct NonBuiltInData {
@@ -44,9 +280,19 @@ struct NonBuiltInData {
     /// An option that either contains a string to the file to replace stdin
     /// or none if stdin should be inherrited
     infile: Option<String>,
-    /// An option that either contains a string to the file to replace stdout
-    /// or none if stdout should be inherrited
-    outfile: Option<String>,
+    /// What to feed to stdin, from a here-document or a here-string
+    heredoc: Option<String>,
+    /// An option that either contains the file to replace stdout or none if
+    /// stdout should be inherrited
+    outfile: Option<Outfile>,
+    /// Redirections of any other file descriptor, like `2>&1`, applied on
+    /// top of the ones above
+    fds: FdTable,
+    /// Changes to the environment the command is run with, from `env` or from
+    /// `NAME=value` words in front of the command.
+    env: EnvOverrides,
+    /// Resource limits the command is run with, from `limit`.
+    limits: Limits,
 }
 
 /// We attempt to mimic the GNU coreutils args as much as possible. This helps
@@ -68,47 +314,307 @@ struct LsData {
     /// `-t`.
     /// Whether to sort by time.
     sort_time: bool,
-    /// An option that either contains a string to the file to replace stdout
-    /// or none if stdout should be inherrited
-    outfile: Option<String>,
+    /// `--entities`, `--enemies`, `--items`, `--no-game`.
+    /// Only list files that match one of these, if any are given.
+    filters: Vec<game::Filter>,
+    /// An option that either contains the file to replace stdout or none if
+    /// stdout should be inherrited
+    outfile: Option<Outfile>,
+}
+
+/// A file `ls` lists.
+struct Listed {
+    path: PathBuf,
+    /// What kind of file it is, unless it's only in the game's files, like an
+    /// entity in a mounted dungeon.
+    file_type: Option<fs::FileType>,
+    modified: SystemTime,
 }
 
 impl Executable {
     /// Runs an executable
     ///
-    /// Takes in the global job list to add to or read from it if necessary
-    async fn eval(self, job_list: &JobList) -> bool {
-        match self {
-            Executable::TempDebugSpawnEnemy(s) => game::spawn(
-                game::Entity {
-                    components: Vec::from([
-                        game::Component::Enemy,
-                        game::Component::TakesDamage(5),
-                    ]),
-                },
-                s,
-            ),
+    /// Takes in the global job list to add to or read from it if necessary,
+    /// and the shell's context to consult or change.
+    async fn eval(self, job_list: &JobList, context: &mut Context, history: &mut History) -> bool {
+        // programs say how they exited, builtins only whether they succeeded
+        let mut status = None;
+        let succeeded = match self {
             Executable::TempDebugAttackEnemy(s) => {
                 if let Err(_) = game::attack(&s) {
                     println!("could not attack {s}??? weirdo...");
                 }
+                true
             }
-            Executable::Ls(args) => {
-                if let Err(error) = Self::ls(args) {
-                    println!("ls errored: {error}")
+            Executable::Ls(args) => match Self::ls(args) {
+                Ok(()) => true,
+                Err(error) => {
+                    println!("ls errored: {error}");
+                    false
                 }
-            }
+            },
             Executable::Cd(dest) => Self::cd(&dest),
-            Executable::Jobs(outfile) => match job_list.list_jobs(outfile) {
-                Ok(()) => (),
-                Err(err) => println!("Error printing jobs: {err}"),
+            Executable::Jobs(outfile) => {
+                match Self::output(&outfile).and_then(|out| job_list.list_jobs(out)) {
+                    Ok(()) => true,
+                    Err(err) => {
+                        println!("Error printing jobs: {err}");
+                        false
+                    }
+                }
+            }
+            Executable::Pet(args) => {
+                game::pet::command(&args);
+                true
+            }
+            Executable::Search => {
+                game::trap::search();
+                true
+            }
+            Executable::Buy(item) => {
+                game::events::buy(&item);
+                true
+            }
+            Executable::Leaderboard(args) => {
+                game::leaderboard::command(&args);
+                true
+            }
+            Executable::Reputation(args) => {
+                game::reputation::report(&args);
+                true
+            }
+            Executable::Roll(args) => {
+                game::dice::command(&args);
+                true
+            }
+            Executable::Set(args) if args.is_empty() => {
+                for (name, value) in context.variables.iter() {
+                    println!("{name}={value}");
+                }
+                true
+            }
+            Executable::Abbr(args) => match context.abbreviations.command(&args) {
+                Ok(output) => {
+                    print!("{output}");
+                    true
+                }
+                Err(error) => {
+                    println!("{error}");
+                    false
+                }
+            },
+            Executable::Kill(args) => match kill::command(&args, job_list) {
+                Ok(output) => {
+                    print!("{output}");
+                    true
+                }
+                Err(error) => {
+                    println!("{error}");
+                    false
+                }
+            },
+            Executable::Fg(args) => match resume::fg(&args, job_list).await {
+                Ok(code) => *status.insert(code) == 0,
+                Err(error) => {
+                    println!("{error}");
+                    false
+                }
+            },
+            Executable::Bg(args) => match resume::bg(&args, job_list) {
+                Ok(output) => {
+                    print!("{output}");
+                    true
+                }
+                Err(error) => {
+                    println!("{error}");
+                    false
+                }
+            },
+            Executable::Wait(args) => {
+                let (code, errors) = wait::command(&args, job_list).await;
+                for error in errors {
+                    println!("{error}");
+                }
+                *status.insert(code) == 0
+            }
+            Executable::History(args) => match history.command(&args) {
+                Ok(output) => {
+                    print!("{output}");
+                    true
+                }
+                Err(error) => {
+                    println!("{error}");
+                    false
+                }
+            },
+            Executable::Alias(args) => match context.aliases.alias(&args) {
+                Ok(output) => {
+                    print!("{output}");
+                    true
+                }
+                Err(error) => {
+                    println!("{error}");
+                    false
+                }
             },
+            Executable::Unalias(args) => match context.aliases.unalias(&args) {
+                Ok(()) => true,
+                Err(error) => {
+                    println!("{error}");
+                    false
+                }
+            },
+            Executable::Limit(args) => match context.limits.command(&args) {
+                Ok(output) => {
+                    print!("{output}");
+                    true
+                }
+                Err(error) => {
+                    println!("{error}");
+                    false
+                }
+            },
+            Executable::Bind(args) => match context.keymap.command(&args) {
+                Ok(output) => {
+                    print!("{output}");
+                    true
+                }
+                Err(error) => {
+                    println!("{error}");
+                    false
+                }
+            },
+            Executable::Set(args) => match context.options.command(&args) {
+                Ok(output) => {
+                    print!("{output}");
+                    true
+                }
+                Err(error) => {
+                    println!("{error}");
+                    false
+                }
+            },
+            Executable::Read(args) => match builtins::read(&args, &mut context.variables) {
+                Ok(read) => read,
+                Err(error) => {
+                    println!("{error}");
+                    false
+                }
+            },
+            Executable::Test(args) => match builtins::test(&args) {
+                Ok(result) => result,
+                Err(error) => {
+                    println!("{error}");
+                    false
+                }
+            },
+            Executable::Printf(args, outfile) => Self::write(builtins::printf(&args), &outfile),
+            Executable::Seq(args, outfile) => Self::write(builtins::seq(&args), &outfile),
+            Executable::Shuf(args, outfile) => Self::write(builtins::shuf(&args), &outfile),
+            Executable::Basename(args, outfile) => Self::write(builtins::basename(&args), &outfile),
+            Executable::Dirname(args, outfile) => Self::write(builtins::dirname(&args), &outfile),
+            Executable::Realpath(args, outfile) => Self::write(builtins::realpath(&args), &outfile),
+            Executable::Config(args, outfile) => Self::write(config::command(&args), &outfile),
+            Executable::Stat(args, outfile) => Self::write(builtins::stat(&args), &outfile),
+            Executable::Hexdump(args, outfile) => Self::write(hexdump::command(&args), &outfile),
+            Executable::Assign(assignments) => {
+                for (name, value) in assignments {
+                    // assignments are only parsed from valid names
+                    context.variables.set(&name, value).unwrap();
+                }
+                true
+            }
+            Executable::Env(env, outfile) => {
+                Self::write(Ok(context.environment.then(env).listing()), &outfile)
+            }
+            Executable::Export(args, outfile) => Self::write(
+                builtins::export(&args, &mut context.variables, &mut context.environment),
+                &outfile,
+            ),
+            Executable::Coproc(name, data) => Self::coproc(name, data, job_list, context).await,
+            Executable::Mkfifo(args) => match builtins::mkfifo(&args) {
+                Ok(()) => true,
+                Err(error) => {
+                    println!("{error}");
+                    false
+                }
+            },
+            Executable::Unset(args) => {
+                match builtins::unset(&args, &mut context.variables, &mut context.environment) {
+                    Ok(()) => true,
+                    Err(error) => {
+                        println!("{error}");
+                        false
+                    }
+                }
+            }
+            Executable::Date(args, outfile) => Self::write(date::command(&args), &outfile),
+            Executable::Audit(args, outfile) => Self::write(audit::command(&args), &outfile),
+            Executable::Theme(args, outfile) => Self::write(theme::command(&args), &outfile),
+            Executable::Record(args) => Self::write(record::record(&args), &None),
+            Executable::Play(args) => record::play(&args).await,
+            Executable::Sleep(args, state, cmdline) => match builtins::sleep_duration(&args) {
+                Ok(duration) => Self::sleep(duration, state, cmdline, job_list.clone()).await,
+                Err(error) => {
+                    println!("{error}");
+                    false
+                }
+            },
+            Executable::Game(verb, args) => {
+                game::class::act(verb, &args);
+                true
+            }
+            Executable::GameCommand(args) => {
+                game::command(&args);
+                true
+            }
             Executable::Exit => return false,
-            Executable::Noop => {}
-            Executable::NonBuiltin(data) => Self::run_command(data, job_list.clone()).await,
+            Executable::Noop => true,
+            Executable::Failed(code) => *status.insert(code) == 0,
+            Executable::SyntaxError(error) => {
+                println!("{}", error.message);
+                false
+            }
+            Executable::NonBuiltin(data) => {
+                let code = Self::run_command(vec![data], job_list.clone()).await;
+                *status.insert(code) == 0
+            }
+            Executable::Pipeline(stages) => {
+                let code = Self::run_command(stages, job_list.clone()).await;
+                *status.insert(code) == 0
+            }
         };
 
-        return true;
+        context.status = status.unwrap_or(if succeeded { 0 } else { 1 });
+        // errexit stops the shell at the first sign of trouble
+        succeeded || !context.options.is_set(ShellOption::Errexit)
+    }
+
+    /// Opens where a builtin should write to: the file it was redirected to, or
+    /// stdout if it wasn't.
+    fn output(outfile: &Option<Outfile>) -> io::Result<Box<dyn Write>> {
+        Ok(match outfile {
+            Some(outfile) => Box::new(outfile.open()?),
+            None => Box::new(io::stdout().lock()),
+        })
+    }
+
+    /// Writes the output of a builtin where it should go, or prints the error
+    /// it ran into. Returns whether the builtin succeeded.
+    fn write(output: Result<String, String>, outfile: &Option<Outfile>) -> bool {
+        let written = output.and_then(|output| {
+            let mut out = Self::output(outfile).map_err(|error| error.to_string())?;
+            out.write_all(output.as_bytes())
+                .and_then(|()| out.flush())
+                .map_err(|error| error.to_string())
+        });
+        match written {
+            Ok(()) => true,
+            Err(error) => {
+                println!("{error}");
+                false
+            }
+        }
     }
 
     /// Runs the ls command
@@ -116,10 +622,9 @@ impl Executable {
     /// This lists all the files in the specified directories or the current directory if none is specified
     /// Can be used with -a to print hidden files or -l for longer descriptions
     fn ls(mut data: LsData) -> Result<(), Error> {
-        let mut outfile: Box<dyn Write> = match &data.outfile {
-            Some(path) => Box::new(File::create(path)?),
-            None => Box::new(io::stdout().lock()),
-        };
+        let mut outfile = Self::output(&data.outfile)?;
+        let accessible = game::accessibility::enabled();
+        let palette = Palette::current();
 
         let path = env::current_dir()?;
 
@@ -138,17 +643,41 @@ impl Executable {
                 writeln!(outfile, "{}:", dir.file_name().unwrap().display())?;
             }
 
-            let entries = fs::read_dir(dir)?;
-            let mut files: Vec<DirEntry> = Vec::new();
-            for e in entries {
-                files.push(e?);
+            game::on_list(&dir);
+            if game::class::is_dark(&dir) {
+                writeln!(
+                    outfile,
+                    "it's pitch black in here. you can't make anything out."
+                )?;
+                continue;
+            }
+
+            let entities: HashMap<PathBuf, game::Entity> =
+                game::entities_in(&dir).into_iter().collect();
+            let mut files: Vec<Listed> = Vec::new();
+            for e in fs::read_dir(&dir)? {
+                let e = e?;
+                files.push(Listed {
+                    path: e.path(),
+                    file_type: Some(e.file_type()?),
+                    modified: e.metadata()?.modified()?,
+                });
+            }
+            // entities in a mounted dungeon are only in the game's files
+            for path in entities.keys() {
+                if !files.iter().any(|file| &file.path == path) {
+                    files.push(Listed {
+                        path: path.clone(),
+                        file_type: None,
+                        modified: SystemTime::UNIX_EPOCH,
+                    });
+                }
             }
-            files.sort_by_key(|entry| {
-                let is_dir = !entry.file_type().unwrap().is_dir();
-                let fname = entry.file_name();
+            files.sort_by_key(|file| {
+                let is_dir = !file.file_type.is_some_and(|t| t.is_dir());
+                let fname = file.path.file_name().unwrap().to_os_string();
                 if data.sort_time {
-                    let x = entry.metadata().unwrap().modified().unwrap();
-                    return (x, is_dir, fname);
+                    return (file.modified, is_dir, fname);
                 }
                 (SystemTime::UNIX_EPOCH, is_dir, fname)
             });
@@ -156,7 +685,7 @@ impl Executable {
             // Need Box hack because `iter` and `rev` have differently typed outputs.
             // An `either` crate exists for this use case, but we can cut down on
             // crate usage.
-            let file_order: Box<dyn Iterator<Item = &DirEntry>> = if data.reverse {
+            let file_order: Box<dyn Iterator<Item = &Listed>> = if data.reverse {
                 Box::new(files.iter().rev())
             } else {
                 Box::new(files.iter())
@@ -165,7 +694,7 @@ impl Executable {
                 // ignore dotfiles. NOTE: let chains would help this look nicer, but are nightly.
                 if !data.all {
                     if let Some('.') = file
-                        .path()
+                        .path
                         .file_name()
                         .unwrap()
                         .to_str()
@@ -177,11 +706,49 @@ impl Executable {
                     }
                 }
 
-                let (prefix, suffix) = if let None = data.outfile {
-                    if file.file_type().unwrap().is_dir() {
-                        ("\x1b[1;34m".to_string(), "\x1b[0m") // 1: bold text; 34: blue foreground; 0: reset
-                    } else if let Ok(_) = game::get_entity(file.path()) {
-                        ("\x1b[31m".to_string() + game::PERSON_ICON + " ", "\x1b[0m") // 31: red foreground; 0: reset
+                let entity = entities.get(&file.path);
+                if !data.filters.is_empty()
+                    && !data.filters.iter().any(|filter| filter.keeps(entity))
+                {
+                    continue;
+                }
+
+                // what's caught for `$(ls)` is only names, like what's written to a file
+                let decorated = data.outfile.is_none() && !capture::active();
+                let file_type = file.file_type;
+                // the long format starts with what kind of file it is
+                if data.long {
+                    write!(outfile, "{} ", file_type.map_or('-', Self::type_char))?;
+                }
+                let (prefix, suffix) = if decorated && accessible {
+                    let name = file.path.file_name().unwrap().display().to_string();
+                    write!(outfile, "{}", Self::plain_label(&name, file_type, entity))?;
+                    if data.long {
+                        writeln!(outfile)?;
+                    } else {
+                        write!(outfile, "  ")?;
+                    }
+                    continue;
+                } else if decorated {
+                    if let Some(role) = file_type.and_then(Self::type_role) {
+                        (palette.start(role), style::reset())
+                    } else if let Some(e) = entity {
+                        match e.trap() {
+                            None if e.is_mimic() => ("".to_string(), ""), // mimics are just files, honest
+                            None if e.is_door() && e.is_locked() => {
+                                (theme::icon("locked_door") + " ", "")
+                            }
+                            None if e.is_door() => (theme::icon("unlocked_door") + " ", ""),
+                            None => (
+                                palette.start(Role::Enemy) + &theme::icon("person") + " ",
+                                style::reset(),
+                            ),
+                            Some(_) if e.is_revealed() => (
+                                palette.start(Role::Trap) + &theme::icon("trap") + " ",
+                                style::reset(),
+                            ),
+                            Some(_) => ("".to_string(), ""), // hidden traps look like any other file
+                        }
                     } else {
                         ("".to_string(), "")
                     }
@@ -193,7 +760,7 @@ impl Executable {
                     outfile,
                     "{}{}{}",
                     prefix,
-                    file.path().file_name().unwrap().display(),
+                    file.path.file_name().unwrap().display(),
                     suffix
                 )?;
 
@@ -217,122 +784,528 @@ impl Executable {
         Ok(())
     }
 
+    /// The character the long format of `ls` marks a kind of file with,
+    /// like `d` for a directory or `p` for a named pipe, as in `ls -l`.
+    fn type_char(file_type: fs::FileType) -> char {
+        use std::os::unix::fs::FileTypeExt;
+        match file_type {
+            t if t.is_dir() => 'd',
+            t if t.is_symlink() => 'l',
+            t if t.is_fifo() => 'p',
+            t if t.is_socket() => 's',
+            t if t.is_block_device() => 'b',
+            t if t.is_char_device() => 'c',
+            _ => '-',
+        }
+    }
+
+    /// What colour `ls` shows a kind of file in, if it isn't a regular one.
+    fn type_role(file_type: fs::FileType) -> Option<Role> {
+        match Self::type_char(file_type) {
+            'd' => Some(Role::Directory),
+            'p' => Some(Role::Fifo),
+            's' => Some(Role::Socket),
+            'b' | 'c' => Some(Role::Device),
+            _ => None,
+        }
+    }
+
+    /// Describes a file in words rather than colours and icons, for
+    /// accessibility mode. Disguised entities are still just files.
+    fn plain_label(
+        name: &str,
+        file_type: Option<fs::FileType>,
+        entity: Option<&game::Entity>,
+    ) -> String {
+        let Some(e) = entity else {
+            return match file_type.and_then(Self::type_role) {
+                Some(Role::Directory) => t!("ls.directory", name = name),
+                Some(Role::Fifo) => t!("ls.fifo", name = name),
+                Some(Role::Socket) => t!("ls.socket", name = name),
+                Some(Role::Device) => t!("ls.device", name = name),
+                _ => name.to_string(),
+            };
+        };
+        if e.is_disguised() {
+            name.to_string()
+        } else if e.is_door() && e.is_locked() {
+            t!("ls.locked_door", name = name)
+        } else if e.is_door() {
+            t!("ls.door", name = name)
+        } else if e.trap().is_some() {
+            t!("ls.trap", name = name)
+        } else if e
+            .components
+            .iter()
+            .any(|c| matches!(c, game::Component::Item(_)))
+        {
+            t!("ls.item", name = name)
+        } else if e.schedule().is_some() {
+            t!("ls.person", name = name)
+        } else if e.chest().is_some() {
+            t!("ls.chest", name = name)
+        } else {
+            t!("ls.enemy", name = name)
+        }
+    }
+
     /// Runs the cd command
     ///
     /// This lets you change directories to the specified directory or home if none is specified
-    fn cd(dest: &Option<String>) {
+    ///
+    /// Returns whether the directory was changed.
+    fn cd(dest: &Option<String>) -> bool {
         // TODO: this computes homedir every call. we only need to when dest = None
         // I'd like to avoid creating a whole string because it's unneccessary, but
         // it's hard to get a string slice without such ownership without the borrow
         // checker complaining.
         let homedir = dirs::home_dir().unwrap();
         let dest = dest.as_deref().unwrap_or(homedir.to_str().unwrap());
-        env::set_current_dir(dest).unwrap_or_else(|error| println!("cd errored: {error}"));
+        if !game::may_enter(Path::new(dest)) {
+            return false;
+        }
+        let from = env::current_dir().unwrap_or_default();
+        match env::set_current_dir(dest) {
+            Ok(()) => {
+                game::on_enter(&from, &env::current_dir().unwrap_or_default());
+                true
+            }
+            Err(error) => {
+                println!("cd errored: {error}");
+                false
+            }
+        }
+    }
+
+    /// Runs the sleep builtin without blocking the runtime, so background jobs
+    /// keep going. A foreground sleep can be cut short with Ctrl-C, and a
+    /// background one by `kill`, which finishes it with the status of a
+    /// `SIGTERM`.
+    ///
+    /// Returns whether the sleep ran its course.
+    async fn sleep(duration: Duration, state: State, cmdline: String, job_list: JobList) -> bool {
+        // builtins run in the shell's own process
+        let pid = std::process::id();
+        let jid = match job_list.add(pid, state, cmdline) {
+            Ok(jid) => jid,
+            Err(error) => {
+                eprintln!("{error}");
+                return false;
+            }
+        };
+
+        if let State::BG = state {
+            let cmdline = job_list.get_cmdline(jid).unwrap_or_default();
+            // announced before the prompt comes back, not whenever the task gets going
+            println!("{} {cmdline}", job_list::describe(jid, pid));
+            let sleeping = task::spawn(tokio::time::sleep(duration));
+            job_list.set_abort(jid, sleeping.abort_handle());
+            task::spawn(async move {
+                let code = match sleeping.await {
+                    Ok(()) => 0,
+                    Err(_) => 128 + libc::SIGTERM,
+                };
+                Self::finished(jid, pid, code, &job_list);
+            });
+            return true;
+        }
+
+        let finished = tokio::select! {
+            () = tokio::time::sleep(duration) => true,
+            _ = tokio::signal::ctrl_c() => {
+                println!();
+                false
+            }
+        };
+        if !job_list.delete(jid) {
+            eprintln!("Failed to remove job");
+        }
+        finished
     }
 
-    /// Runs a non built in command
+    /// Runs a pipeline of non built in commands, each one's output going to
+    /// the next one's input. A single command is a pipeline of one.
     ///
-    /// This function either waits for the command to finish if it is a
+    /// This function either waits for the pipeline to finish if it is a
     /// foreground job or creates a new async thread to wait for the job
     /// to finish if it is a background job.
     ///
-    /// The job list is updated while the job is running.
-    async fn run_command(data: NonBuiltInData, job_list: JobList) {
-        // Calculate the infile
-        let infile: Stdio = match data.infile {
-            Some(path) => match File::create(path) {
-                Ok(file) => file.into(),
-                Err(err) => {
-                    println!("Error opening file: {err}");
-                    return;
+    /// The job list is updated while the job is running. The whole pipeline
+    /// is one job, in one process group, named after its first command.
+    ///
+    /// Returns the exit status of the last command. Background jobs are
+    /// assumed to succeed.
+    async fn run_command(pipeline: Vec<NonBuiltInData>, job_list: JobList) -> i32 {
+        let Some(first) = pipeline.first() else {
+            return 0;
+        };
+        let (state, cmdline) = (first.state, first.cmdline.clone());
+        if pipeline
+            .iter()
+            .any(|data| game::bestiary::ambush(&data.command, &data.args))
+        {
+            return 0;
+        }
+
+        // hauling files around is hard work with a full pack
+        if pipeline
+            .iter()
+            .any(|data| matches!(data.command.as_str(), "mv" | "cp"))
+            && game::inventory::encumbered()
+        {
+            println!("you strain under your pack as you haul the files around...");
+            // the pause is only for effect, so don't make screen reader users sit through it
+            if !game::accessibility::enabled() {
+                tokio::time::sleep(std::time::Duration::from_millis(800)).await;
+            }
+        }
+
+        let mut children = Vec::new();
+        let mut piped = None;
+        let last = pipeline.len() - 1;
+        for (i, data) in pipeline.into_iter().enumerate() {
+            match Self::spawn(data, state, piped.take(), i == last, &children).await {
+                Ok((child, output)) => {
+                    piped = output;
+                    children.push(child);
                 }
-            },
-            None => {
-                if let State::FG = data.state {
-                    Stdio::inherit()
+                Err(status) => {
+                    for child in &mut children {
+                        let _ = child.kill().await;
+                    }
+                    Self::wait_all(children).await;
+                    return status;
+                }
+            }
+        }
+        // the pipeline's process group is named after its first command
+        let pid = children[0].id().unwrap_or(0);
+
+        match job_list.add(pid, state, cmdline) {
+            Ok(jid) => {
+                if let State::FG = state {
+                    terminal::give_to(pid);
+                    let mut waiting = task::spawn(Self::wait_all(children));
+                    let status = tokio::select! {
+                        status = &mut waiting => status.ok(),
+                        () = terminal::stopped(pid) => None,
+                    };
+                    terminal::take_back();
+                    let Some(status) = status else {
+                        return Self::stop(jid, pid, waiting, job_list);
+                    };
+                    // the terminal echoed ^C but no newline, so the prompt
+                    // would come back on the end of it
+                    if status.signal() == Some(libc::SIGINT) {
+                        println!();
+                    }
+                    if !job_list.delete(jid) {
+                        eprintln!("Failed to remove job");
+                    }
+                    Self::code(status)
                 } else {
-                    Stdio::null()
+                    let cmdline = job_list.get_cmdline(jid).unwrap_or(String::new());
+                    println!("{} {cmdline}", job_list::describe(jid, pid));
+                    task::spawn(async move {
+                        let status = Self::wait_all(children).await;
+                        Self::finished(jid, pid, Self::code(status), &job_list);
+                    });
+                    0
                 }
             }
+            Err(error) => {
+                eprintln!("{error}");
+                for child in &mut children {
+                    child.kill().await.expect("Error killing child");
+                }
+                Self::wait_all(children).await;
+                1
+            }
+        }
+    }
+
+    /// Takes the job `jid` off the list now it's finished with the exit
+    /// status `code`, saying so unless `fg` brought it back to the foreground
+    /// to wait for it. Anyone [waiting](job_list::Completion) on it is told
+    /// last, so nothing's printed after they've carried on.
+    fn finished(jid: usize, pid: u32, code: i32, job_list: &JobList) {
+        if job_list.get_state(jid) != Some(State::FG) {
+            println!("\nJob {} terminated", job_list::describe(jid, pid));
+        }
+        job_list.finish(jid, code);
+        if !job_list.delete(jid) {
+            eprintln!("Failed to remove job");
+        }
+    }
+
+    /// Moves the foreground job `jid` to the stopped jobs once it's been
+    /// stopped, like by Ctrl-Z, and lets the prompt come back. The job's
+    /// still `waiting` to finish, which it can once it's carried on.
+    ///
+    /// Returns the exit status for `$?`, which is as if it was killed by
+    /// `SIGTSTP`, like in other shells.
+    fn stop(jid: usize, pid: u32, waiting: task::JoinHandle<ExitStatus>, job_list: JobList) -> i32 {
+        if let Err(error) = job_list.set_state(jid, State::ST) {
+            eprintln!("{error}");
+        }
+        let cmdline = job_list.get_cmdline(jid).unwrap_or_default();
+        // on a fresh line, after the ^Z the terminal echoed
+        println!("\n{} {} {cmdline}", job_list::describe(jid, pid), State::ST);
+        task::spawn(async move {
+            let code = waiting.await.map_or(1, Self::code);
+            Self::finished(jid, pid, code, &job_list);
+        });
+        128 + libc::SIGTSTP
+    }
+
+    /// Starts `data` in the background as the coprocess `name`, talking to
+    /// the shell through a pipe each way, see [`coproc`].
+    async fn coproc(
+        name: String,
+        mut data: NonBuiltInData,
+        job_list: &JobList,
+        context: &mut Context,
+    ) -> bool {
+        let pipes = capture::pipe().and_then(|input| Ok((input, capture::pipe()?)));
+        let ((its_input, to_it), (from_it, its_output)) = match pipes {
+            Ok(pipes) => pipes,
+            Err(error) => {
+                println!("coproc: {error}");
+                return false;
+            }
+        };
+        // it opens its ends of the pipes like files
+        data.infile = Some(format!("/dev/fd/{}", its_input.as_raw_fd()));
+        data.outfile = Some(Outfile {
+            path: format!("/dev/fd/{}", its_output.as_raw_fd()),
+            append: false,
+        });
+        if Self::run_command(vec![data], job_list.clone()).await != 0 {
+            return false;
+        }
+        let pid = job_list.last_pid().unwrap_or(0);
+        let variables = [
+            (format!("{name}_IN"), to_it.as_raw_fd()),
+            (format!("{name}_OUT"), from_it.as_raw_fd()),
+            (format!("{name}_PID"), pid as i32),
+        ];
+        for (variable, value) in variables {
+            // the name was checked when it was parsed
+            let _ = context.variables.set(&variable, value.to_string());
+        }
+        // a coprocess with the same name is left to finish once its input
+        // is closed
+        context.coprocs.insert(name, Arc::new([to_it, from_it]));
+        true
+    }
+
+    /// The exit status `$?` gives for `status`: its exit code, or 128 plus
+    /// the signal that killed it, like in sh.
+    fn code(status: ExitStatus) -> i32 {
+        status
+            .code()
+            .unwrap_or_else(|| 128 + status.signal().unwrap_or(0))
+    }
+
+    /// Starts one command of a pipeline. Its input comes from `piped`, the
+    /// output of the command before it, unless it's redirected or first. Its
+    /// output is piped on to the next command unless it's redirected or
+    /// `last`, and the end of the pipe it goes into is returned with it. It
+    /// joins the process group of the `earlier` commands.
+    ///
+    /// Errors have already been printed, and the exit status for `$?` is
+    /// returned instead: 127 if there's no such command, like in sh.
+    async fn spawn(
+        data: NonBuiltInData,
+        state: State,
+        piped: Option<OwnedFd>,
+        last: bool,
+        earlier: &[Child],
+    ) -> Result<(Child, Option<OwnedFd>), i32> {
+        let failed = |what: &str, error: io::Error| {
+            println!("{what}: {error}");
+            1
+        };
+        let inherit = || match state {
+            State::FG => Stdio::inherit(),
+            State::BG | State::ST => Stdio::null(),
         };
 
-        // Calculate the outfile
-        let outfile: Stdio = match data.outfile {
-            Some(path) => match File::create(path) {
+        // Calculate the infile
+        let infile: Stdio = match (data.infile, data.heredoc, piped) {
+            (Some(path), _, _) => match File::open(path) {
                 Ok(file) => file.into(),
-                Err(err) => {
-                    println!("Error opening file: {err}");
-                    return;
-                }
+                Err(err) => return Err(failed("Error opening file", err)),
             },
-            None => {
-                if let State::FG = data.state {
-                    Stdio::inherit()
-                } else {
-                    Stdio::null()
-                }
+            (None, Some(text), _) => Self::feed(text)
+                .map_err(|err| failed("Error making a pipe", err))?
+                .into(),
+            (None, None, Some(piped)) => piped.into(),
+            (None, None, None) => inherit(),
+        };
+
+        // Calculate the outfile. The pipe to the next command is made here
+        // rather than by Command, so `2>&1` can send stderr down it too.
+        let mut output = None;
+        let outfile: Option<OwnedFd> = match data.outfile {
+            Some(outfile) => match outfile.open() {
+                Ok(file) => Some(file.into()),
+                Err(err) => return Err(failed("Error opening file", err)),
+            },
+            None if !last => {
+                let (read, write) =
+                    capture::pipe().map_err(|err| failed("Error making a pipe", err))?;
+                output = Some(read);
+                Some(write)
             }
+            None => None,
         };
 
-        match Command::new(&data.command)
+        let outfile: Stdio = outfile.map_or_else(inherit, Stdio::from);
+
+        let group = earlier.first().and_then(Child::id).unwrap_or(0);
+        let mut command = Command::new(&data.command);
+        data.env.apply(&mut command);
+        data.limits.apply(&mut command);
+        terminal::prepare(&mut command, state == State::FG, group);
+        // the child applies these once its stdio is set up, so `2>&1` finds
+        // stdout where it's going
+        data.fds
+            .apply(&mut command)
+            .map_err(|err| failed("Error opening file", err))?;
+        command
             .args(data.args)
             .stdin(infile)
             .stdout(outfile)
             .spawn()
-        {
-            Err(error) => println!("{} errored: {error}", data.command),
-            Ok(mut child) => {
-                let pid = child.id().unwrap_or(0);
-                match job_list.add(pid, data.state, data.cmdline) {
-                    Ok(jid) => {
-                        if let State::FG = data.state {
-                            child.wait().await.expect("Error waiting for child");
-                            if !job_list.delete(jid) {
-                                eprintln!("Failed to remove job");
-                            }
-                        } else {
-                            let cmdline = job_list.get_cmdline(jid).unwrap_or(String::new());
-                            task::spawn(async move {
-                                print!("[{jid}] ({pid}) {}", cmdline);
-
-                                child.wait().await.expect("Error waiting for child");
-
-                                if !job_list.delete(jid) {
-                                    eprintln!("Failed to remove job");
-                                }
-                                println!("\nJob [{jid}] ({pid}) terminated");
-                            });
-                        }
-                    }
-                    Err(error) => {
-                        eprintln!("{error}");
-                        child.kill().await.expect("Error killing child");
-                        child.wait().await.expect("Error waiting for child");
-                    }
-                }
-            }
-        };
+            .map(|child| (child, output))
+            .map_err(|error| {
+                let not_found = error.kind() == io::ErrorKind::NotFound;
+                println!("{} errored: {error}", data.command);
+                if not_found { 127 } else { 126 }
+            })
+    }
+
+    /// Returns the read end of a pipe that `text` is written into, for a
+    /// here-document. It's written from a thread of its own, so a long one
+    /// doesn't fill the pipe and wait on a command that hasn't started.
+    fn feed(text: String) -> io::Result<OwnedFd> {
+        let (read, write) = capture::pipe()?;
+        std::thread::spawn(move || {
+            // the command doesn't have to read all of it
+            let _ = File::from(write).write_all(text.as_bytes());
+        });
+        Ok(read)
+    }
+
+    /// Waits for every command in a pipeline, returning how the last one
+    /// exited.
+    async fn wait_all(children: Vec<Child>) -> ExitStatus {
+        let mut status = ExitStatus::default();
+        for mut child in children {
+            status = child.wait().await.expect("Error waiting for child");
+        }
+        status
+    }
+}
+
+/// Splits `line` into its commands and their words and expands them, like
+/// the shell does before running it, returning how many words there were.
+/// Only public for the benchmarks.
+#[doc(hidden)]
+pub fn words_in(line: &str) -> Result<usize, String> {
+    let mut count = 0;
+    for (_, command) in list::split(line)? {
+        for stage in App::split_pipeline(command) {
+            let words = tokenize::words(stage)?;
+            count +=
+                expand::words(words, |name| env::var(name).ok(), |_| String::new(), "\n")?.len();
+        }
     }
+    Ok(count)
+}
+
+/// Everything the shell keeps track of from one command to the next.
+#[derive(Clone, Default)]
+struct Context {
+    /// Shell options, set with the `set` builtin.
+    options: Options,
+    variables: Variables,
+    /// Abbreviations, added with the `abbr` builtin.
+    abbreviations: Abbreviations,
+    /// Aliases, added with the `alias` builtin.
+    aliases: Aliases,
+    /// Key bindings for the line editor, changed with the `bind` builtin.
+    keymap: Keymap,
+    /// Resource limits for programs, set with the `limit` builtin.
+    limits: Limits,
+    /// Variables passed on to programs with `export`, and ones kept from them
+    /// with `unset`.
+    environment: EnvOverrides,
+    /// The exit status of the last command, 0 if it succeeded.
+    status: i32,
+    /// The shell's ends of the pipes to each coprocess, by name.
+    coprocs: HashMap<String, Arc<[OwnedFd; 2]>>,
 }
 
-pub struct App;
+pub struct App {
+    /// Parses game verbs, which depend on the player's class.
+    verbs: game::parser::Parser<game::class::Verb>,
+    context: Context,
+    editor: Editor,
+    /// Completion specs for external commands, read as they're needed.
+    specs: Specs,
+    /// Whether this is a subshell running a `$(command)`, which can't stop
+    /// to ask anything since what it prints is being caught.
+    subshell: bool,
+    /// The script being run and the line it's up to, if the shell is running
+    /// one rather than reading commands from the user.
+    script: Option<(String, usize)>,
+    /// The bodies of the here-documents of the line being run, read along
+    /// with it, for its commands to take in order. Parsing only borrows the
+    /// shell, so they're taken out of a RefCell.
+    heredocs: RefCell<VecDeque<Heredoc>>,
+    /// The pipes behind the `<(command)`s of the command being run, kept
+    /// open until it's done.
+    substituted: RefCell<Vec<OwnedFd>>,
+    /// What was in the current directory at the last prompt, for
+    /// `set -o watch`.
+    watch: game::watch::Watch,
+}
 
 impl App {
+    /// Creates the shell, setting up a new character first if there isn't one.
     pub fn new() -> Self {
-        App
+        game::check_setup();
+        App {
+            verbs: game::verbs(),
+            context: Context::default(),
+            editor: Editor::new(),
+            specs: Specs::default(),
+            subshell: false,
+            script: None,
+            heredocs: RefCell::default(),
+            substituted: RefCell::default(),
+            watch: Default::default(),
+        }
     }
 
-    /// Prints the prompt for the shell.
+    /// Returns the prompt for the shell.
     ///
-    /// That is, the thing that looks like `user@device ~/... $`.
-    fn print_prompt() {
-        print!(
-            "{}@{} {} $ ",
-            whoami::username(),
-            whoami::devicename(),
-            env::current_dir().unwrap_or(PathBuf::from("?")).display()
-        );
-        io::stdout().flush().unwrap();
+    /// That is, the thing that looks like `user@device ~/... $`, or however
+    /// the theme lays it out. Headless, it's always `$ `.
+    fn prompt() -> String {
+        if headless::enabled() {
+            return terminal::TRANSIENT_PROMPT.to_string();
+        }
+        theme::prompt(
+            &whoami::username(),
+            &whoami::devicename(),
+            &env::current_dir()
+                .unwrap_or(PathBuf::from("?"))
+                .display()
+                .to_string(),
+        )
     }
 
     /// Runs the application
@@ -340,17 +1313,37 @@ impl App {
     /// Parses the each line entered and then runs the parsed executable
     /// until the exit command is parsed.
     #[tokio::main]
-    pub async fn run(self) {
+    pub async fn run(mut self) {
         let mut input_buffer = String::new();
         let job_list = JobList::new();
+        terminal::save_shell_modes();
+        terminal::init_job_control();
+        Self::forward_interrupts(job_list.clone());
+        crash::install(terminal::restore_shell_modes);
+        *self.editor.history() = History::load(history::path());
         loop {
-            Self::print_prompt();
+            game::tick();
+            self.watch();
+            let prompt = Self::prompt();
 
-            match io::stdin().read_line(&mut input_buffer) {
+            match self.read_line(&prompt, &mut input_buffer) {
                 Ok(0) => return, // exit on EOF (CTRL-D)
                 Ok(_) => {
-                    for command in input_buffer.split(';').map(Self::parse) {
-                        if !command.eval(&job_list).await {
+                    if self.context.options.is_set(ShellOption::Transientprompt) {
+                        let entered = input_buffer.trim_end_matches('\n');
+                        terminal::rewrite_last_line(
+                            &(prompt + entered),
+                            &(terminal::TRANSIENT_PROMPT.to_string() + entered),
+                        );
+                    }
+                    // pasted lines arrive together, so run them one by one
+                    let mut lines: VecDeque<String> =
+                        input_buffer.lines().map(String::from).collect();
+                    while let Some(line) = lines.pop_front() {
+                        self.read_heredocs(&line, |app| {
+                            lines.pop_front().or_else(|| app.read_more())
+                        });
+                        if !self.run_list(&line, &job_list).await {
                             return;
                         }
                     }
@@ -362,60 +1355,596 @@ impl App {
         }
     }
 
+    /// Keeps Ctrl-C from killing the shell, passing it on to the foreground
+    /// job instead. With job control the terminal sends it to the job, but
+    /// the shell gets it too while a builtin has the terminal, or when it
+    /// shares a process group with what it runs.
+    fn forward_interrupts(job_list: JobList) {
+        let Ok(mut interrupts) = signal(SignalKind::interrupt()) else {
+            return;
+        };
+        task::spawn(async move {
+            while interrupts.recv().await.is_some() {
+                match job_list.fg_pid() {
+                    // builtins like sleep run in the shell's own process,
+                    // and look out for Ctrl-C themselves
+                    Some(pid) if pid == std::process::id() => {}
+                    Some(pid) => {
+                        terminal::signal_job(pid, libc::SIGINT);
+                    }
+                    None => println!(),
+                }
+            }
+        });
+    }
+
+    /// Announces what's come and gone from the current directory since the
+    /// last prompt, if `set -o watch` is on.
+    fn watch(&mut self) {
+        if !self.context.options.is_set(ShellOption::Watch) {
+            // start afresh when it's turned back on
+            self.watch = Default::default();
+            return;
+        }
+        let Ok(dir) = env::current_dir() else {
+            return;
+        };
+        for message in self.watch.check(&dir) {
+            println!("{message}");
+        }
+    }
+
+    /// Runs the script at `path` a line at a time, reading each line only
+    /// once the one before it has run, so even huge scripts, like generated
+    /// dungeons, don't have to fit in memory. Returns the exit status of the
+    /// last command.
+    #[tokio::main]
+    pub async fn run_script(mut self, path: &str) -> i32 {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(error) => {
+                println!("rlsh: {path}: {error}");
+                return 127;
+            }
+        };
+        let mut reader = io::BufReader::new(file);
+        let (mut line, mut more) = (String::new(), String::new());
+        let job_list = JobList::new();
+        crash::install(terminal::restore_shell_modes);
+        self.script = Some((path.to_string(), 0));
+        loop {
+            line.clear();
+            if let Some((_, number)) = &mut self.script {
+                *number += 1;
+            }
+            match reader.read_line(&mut line) {
+                Ok(0) => break,
+                // lines that are only a comment, like the #! line, are skipped
+                Ok(_) if line.trim_start().starts_with('#') => {}
+                Ok(_) => {
+                    let line = line.trim_end_matches('\n');
+                    self.read_heredocs(line, |app| {
+                        more.clear();
+                        if let Some((_, number)) = &mut app.script {
+                            *number += 1;
+                        }
+                        match reader.read_line(&mut more) {
+                            Ok(0) | Err(_) => None,
+                            Ok(_) => Some(more.trim_end_matches('\n').to_string()),
+                        }
+                    });
+                    if !self.run_list(line, &job_list).await {
+                        break;
+                    }
+                }
+                Err(error) => {
+                    self.error(&format!("rlsh: {error}"));
+                    return 1;
+                }
+            }
+        }
+        self.context.status
+    }
+
+    /// Reads the bodies of the here-documents `line` starts, from
+    /// `next_line`, for its commands to take when they run.
+    fn read_heredocs(
+        &mut self,
+        line: &str,
+        mut next_line: impl FnMut(&mut Self) -> Option<String>,
+    ) {
+        let (heredocs, missing) = heredoc::read(line, || next_line(self));
+        if let Some(delimiter) = missing {
+            self.error(&format!(
+                "rlsh: warning: here-document delimited by end of input (wanted `{delimiter}')"
+            ));
+        }
+        *self.heredocs.get_mut() = heredocs.into();
+    }
+
+    /// Reads another line for a command that needs more of them, like one
+    /// with a here-document, showing a `> ` prompt.
+    fn read_more(&mut self) -> Option<String> {
+        let mut buffer = String::new();
+        match self.read_line("> ", &mut buffer) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(buffer.trim_end_matches('\n').to_string()),
+        }
+    }
+
+    /// Prints one of the shell's own errors, like a syntax error. In a
+    /// script, it says where rather than starting with `rlsh:`, like
+    /// `dungeon.rlsh: line 12: syntax error near `&&'`.
+    fn error(&self, error: &str) {
+        match &self.script {
+            Some((path, line)) => {
+                let error = error.strip_prefix("rlsh: ").unwrap_or(error);
+                println!("{path}: line {line}: {error}");
+            }
+            None => println!("{error}"),
+        }
+    }
+
+    /// Prints a syntax error in `line`, with the line and a caret under
+    /// where it went wrong.
+    fn syntax_error(&self, error: &SyntaxError, line: &str) {
+        self.error(&error.message);
+        println!("{}", error.point(line));
+    }
+
+    /// Runs the commands in `line`, joined with `;`, `&&` and `||`, see
+    /// [`list::split`]. Returns whether the shell should keep going.
+    async fn run_list(&mut self, line: &str, job_list: &JobList) -> bool {
+        let commands = match list::split(line) {
+            Ok(commands) => commands,
+            Err(error) => {
+                self.syntax_error(&error, line);
+                self.context.status = 2;
+                return true;
+            }
+        };
+        let mut commands = commands.into_iter().peekable();
+        while let Some((connector, typed)) = commands.next() {
+            if !connector.runs(self.context.status) {
+                // its here-documents go unread
+                let heredocs = self.heredocs.get_mut();
+                let skipped = heredoc::delimiters(typed).len().min(heredocs.len());
+                heredocs.drain(..skipped);
+                continue;
+            }
+            let Some(s) = self.spellcheck(typed) else {
+                // the fixed command is being edited instead
+                return true;
+            };
+            crash::note_command(s.trim());
+            // aliases are expanded before anything else is made of the line
+            let s = self.context.aliases.expand(&s).into_owned();
+            let command = self.parse(&s);
+            if let Executable::SyntaxError(mut error) = command {
+                // point into the line as it was typed, unless it was fixed
+                if s == typed {
+                    error.at += typed.as_ptr() as usize - line.as_ptr() as usize;
+                    self.syntax_error(&error, line);
+                } else {
+                    self.syntax_error(&error, &s);
+                }
+                // like in sh, the rest of the line doesn't run
+                self.context.status = 2;
+                return true;
+            }
+            let exits = matches!(command, Executable::Exit);
+            let argv: Vec<String> = tokenize::words(&s)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|word| word.text.into_owned())
+                .collect();
+            let cwd = env::current_dir().unwrap_or_default();
+            let started = (SystemTime::now(), Instant::now());
+            let keep_going = command
+                .eval(job_list, &mut self.context, self.editor.history())
+                .await;
+            // what's reading them has them open now, or is done with them
+            self.substituted.get_mut().clear();
+            if !argv.is_empty() {
+                audit::record(&audit::Entry {
+                    timestamp: started
+                        .0
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |d| d.as_secs() as i64),
+                    duration: started.1.elapsed(),
+                    status: self.context.status,
+                    cwd: cwd.display().to_string(),
+                    argv,
+                });
+            }
+            // like in bash, errexit leaves alone failures that are checked
+            // with && or ||
+            let checked = commands
+                .peek()
+                .is_some_and(|(next, _)| *next != Connector::Always);
+            if !keep_going && (exits || !checked) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Runs `command` for `$(command)`, returning what it printed.
+    ///
+    /// It runs in a subshell, with a copy of the shell's variables and
+    /// settings and jobs of its own, so `$(cd crypt)` doesn't move the shell.
+    /// Parsing can't wait, so the runtime is blocked on it.
+    fn substitute(&self, command: &str) -> String {
+        let mut subshell = App {
+            verbs: game::verbs(),
+            context: self.context.clone(),
+            editor: Editor::new(),
+            specs: Specs::default(),
+            subshell: true,
+            script: self.script.clone(),
+            heredocs: RefCell::default(),
+            substituted: RefCell::default(),
+            watch: Default::default(),
+        };
+        let cwd = env::current_dir();
+        let job_list = JobList::new();
+        let output = task::block_in_place(|| {
+            Handle::current().block_on(capture::stdout(subshell.run_list(command, &job_list)))
+        });
+        // the subshell's cd only went so far
+        if let Ok(cwd) = cwd {
+            let _ = env::set_current_dir(cwd);
+        }
+        match output {
+            Ok((_, output)) => output,
+            Err(error) => {
+                self.error(&format!("rlsh: $({command}): {error}"));
+                String::new()
+            }
+        }
+    }
+
+    /// Runs `command` for `<(command)`, returning a file what it printed can
+    /// be read from. Like in bash, it's `/dev/fd/N`, the read end of a pipe
+    /// that commands started before this one finishes are given.
+    fn substitute_file(&self, command: &str) -> String {
+        let read = match Executable::feed(self.substitute(command)) {
+            Ok(read) => read,
+            Err(error) => {
+                self.error(&format!("rlsh: <({command}): {error}"));
+                return String::new();
+            }
+        };
+        // SAFETY: clearing close-on-exec only changes which programs get it
+        unsafe { libc::fcntl(read.as_raw_fd(), libc::F_SETFD, 0) };
+        let path = format!("/dev/fd/{}", read.as_raw_fd());
+        self.substituted.borrow_mut().push(read);
+        path
+    }
+
+    /// Shows `prompt` and reads a line of input onto the end of `buffer`,
+    /// returning how many bytes were read, so 0 at the end of input.
+    ///
+    /// From a terminal the line is edited and highlighted as it's typed.
+    fn read_line(&mut self, prompt: &str, buffer: &mut String) -> io::Result<usize> {
+        if !io::stdin().is_terminal() {
+            print!("{prompt}");
+            io::stdout().flush().unwrap();
+            let read = io::stdin().read_line(buffer)?;
+            // headless stands in for someone typing, so it keeps a history
+            if headless::enabled() {
+                for line in buffer.lines() {
+                    self.editor.history().add(line);
+                }
+            }
+            return Ok(read);
+        }
+
+        let verbs = &self.verbs;
+        let options = &self.context.options;
+        let specs = &mut self.specs;
+        let usable: Vec<(String, &str)> = match options.is_set(ShellOption::Gameverbs) {
+            true => verbs
+                .first_words()
+                .into_iter()
+                .map(|(word, verb)| (word, verb.description()))
+                .collect(),
+            false => Vec::new(),
+        };
+        let mut highlighter = Highlighter::new(Palette::current(), |command| {
+            options.is_set(ShellOption::Gameverbs)
+                && verbs
+                    .get(command)
+                    .is_some_and(|(verb, args)| game::class::applies(&verb, &args))
+        });
+        match self.editor.read_line(
+            prompt,
+            &mut |line| highlighter.highlight(line),
+            &mut |line| complete::complete(line, &usable, specs),
+            &self.context.abbreviations,
+            &self.context.keymap,
+        )? {
+            Some(line) => {
+                buffer.push_str(&line);
+                buffer.push('\n');
+                Ok(line.len() + 1)
+            }
+            None => Ok(0),
+        }
+    }
+
+    /// Whether `name` is a command the shell can run.
+    fn resolves(&self, name: &str) -> bool {
+        BUILTINS.iter().any(|(builtin, _)| *builtin == name)
+            || self.context.aliases.get(name).is_some()
+            || name.contains('/')
+            || (self.context.options.is_set(ShellOption::Gameverbs)
+                && self
+                    .verbs
+                    .first_words()
+                    .iter()
+                    .any(|(word, _)| word == name))
+            || spellcheck::on_path(name)
+    }
+
+    /// Offers to fix the command name in `line` if there's no such command.
+    /// Returns the line to run, or `None` if the fix is being edited first.
+    fn spellcheck(&mut self, line: &str) -> Option<String> {
+        let word = line
+            .split_whitespace()
+            .find(|word| builtins::assignment(word).is_none());
+        let interactive = !self.subshell && self.script.is_none();
+        let Some(word) =
+            word.filter(|word| interactive && io::stdin().is_terminal() && !self.resolves(word))
+        else {
+            return Some(line.to_string());
+        };
+
+        let builtins = BUILTINS.iter().map(|(name, _)| name.to_string());
+        let verbs = match self.context.options.is_set(ShellOption::Gameverbs) {
+            true => self
+                .verbs
+                .first_words()
+                .into_iter()
+                .map(|(word, _)| word)
+                .collect(),
+            false => Vec::new(),
+        };
+        let known = builtins.chain(verbs).chain(complete::programs());
+        let Some(fix) = spellcheck::correction(word, known) else {
+            return Some(line.to_string());
+        };
+        let at = word.as_ptr() as usize - line.as_ptr() as usize;
+        let fixed = format!("{}{fix}{}", &line[..at], &line[at + word.len()..]);
+        match editor::ask(&format!("rlsh: {word}: did you mean {fix}? [y/n/e] ")) {
+            Ok(Some('y')) => Some(fixed),
+            Ok(Some('e')) => {
+                self.editor.prefill(fixed.trim());
+                None
+            }
+            _ => Some(line.to_string()),
+        }
+    }
+
+    /// Prints a command the way xtrace shows it, prefixed by `ps4`, which is
+    /// `$PS4` or `+ ` if it isn't set.
+    fn trace(ps4: Option<String>, words: &[&str]) {
+        let ps4 = ps4.unwrap_or(String::from("+ "));
+        eprintln!("{ps4}{}", words.join(" "));
+    }
+
     /// Parses a command line input into a `Command`.
     ///
-    /// First checks for fg/bg job state signalled by ending the command with an &
-    /// Then checks for stdin and stdout overrides signaled with < and >
+    /// Builtins added here need adding to [`BUILTINS`] too.
+    ///
+    /// First splits pipelines into their commands at each |, see
+    /// [`App::parse_pipeline`].
+    /// Then checks for fg/bg job state signalled by ending the command with an &
+    /// Then checks for stdin and stdout overrides signaled with < and > (or >| to
+    /// overwrite a file even with noclobber set, or >> to append to it)
     /// Lastly parses the type of command and creates the appropriate executable.
-    fn parse(input: &str) -> Executable {
-        let cmdline = input.to_string();
+    fn parse(&self, input: &str) -> Executable {
+        // only jobs are named after their command line, so only they copy it
+        let cmdline = || input.to_string();
 
-        let mut input: Vec<&str> = input.split_whitespace().collect();
-        if let Some(&"spawn") = input.get(0) {
-            return Executable::TempDebugSpawnEnemy(String::from(
-                input.get(1..).unwrap_or(&["goblin"]).join(" "),
-            ));
+        let stages = Self::split_pipeline(input);
+        if stages.len() > 1 {
+            return self.parse_pipeline(&stages, input);
         }
 
-        if let Some(&"attack") = input.get(0) {
-            return Executable::TempDebugAttackEnemy(String::from(
-                input.get(1..).unwrap_or(&["goblin"]).join(" "),
-            ));
+        let lookup = |name: &str| {
+            if name == "?" {
+                return Some(self.context.status.to_string());
+            }
+            let value = self.context.variables.get(name).map(String::from);
+            // an unset variable stays unset, even if rlsh was started with it
+            let removed = self.context.environment.removes(name);
+            value.or_else(|| env::var(name).ok().filter(|_| !removed))
+        };
+        let run = |substitution: Substitution<'_>| match substitution {
+            Substitution::Output(command) => self.substitute(command),
+            Substitution::File(command) => self.substitute_file(command),
+        };
+        let ifs = lookup("IFS").unwrap_or_else(|| {
+            let posix = self.context.options.is_set(ShellOption::Posixsplit);
+            String::from(if posix { " \t\n" } else { "\n" })
+        });
+        let braced = braces::expand(input);
+        let words = tokenize::words(input).and_then(|words| match &braced {
+            // braces are expanded before anything else, like in bash, but
+            // syntax errors are found in the line as it was typed
+            Cow::Owned(braced) => tokenize::words(braced),
+            Cow::Borrowed(_) => Ok(words),
+        });
+        let words = match words {
+            Ok(words) => words,
+            Err(error) => return Executable::SyntaxError(error),
+        };
+        let mut words = match expand::words(words, lookup, run, &ifs) {
+            Ok(words) => words,
+            Err(error) => {
+                self.error(&error);
+                return Executable::Failed(1);
+            }
+        };
+        if self.context.options.is_set(ShellOption::Xtrace) && !words.is_empty() {
+            let words: Vec<_> = words.iter().map(|w| &*w.text).collect();
+            Self::trace(lookup("PS4"), &words);
+        }
+
+        if words.first().is_some_and(|word| word.is("attack")) {
+            let target: Vec<&str> = words[1..].iter().map(|w| &*w.text).collect();
+            return Executable::TempDebugAttackEnemy(target.join(" "));
         }
 
-        // first check if this is a foreground or background job
-        let last_word = input.last();
-        let state = match last_word {
-            Some(&"&") => {
-                input.pop();
+        // first check if this is a foreground or background job. Operators
+        // only count when they aren't quoted.
+        let state = match words.last() {
+            Some(word) if word.is("&") => {
+                words.pop();
                 State::BG
             }
             _ => State::FG,
         };
 
-        // Check for specified stdout and stdin
-        let (infile, mut input) = match input.iter().position(|x| x == &"<") {
-            Some(i) => {
-                let mut new_input = input.split_off(i);
-                new_input.remove(0);
-                (input.last().map(|v| v.to_string()), new_input)
+        // here-documents were read with the line, in the order they're in it,
+        // and a here-string is the word after `<<<`, as a line
+        // a redirect with nothing after it is pointed out like any other
+        // syntax error
+        let missing_target = |operator: &tokenize::Word| {
+            let at = operator.offset_in(input).unwrap_or(input.len());
+            Executable::SyntaxError(SyntaxError::new(
+                "rlsh: syntax error near unexpected newline",
+                at,
+            ))
+        };
+        let mut heredoc = None;
+        while let Some(i) = words.iter().position(|w| w.is("<<") || w.is("<<<")) {
+            let redirect = words.remove(i);
+            if i == words.len() {
+                return missing_target(&redirect);
+            }
+            let word = words.remove(i);
+            if redirect.is("<<<") {
+                heredoc = Some(format!("{}\n", word.text));
+                continue;
+            }
+            let Some(Heredoc { body, expand }) = self.heredocs.borrow_mut().pop_front() else {
+                continue;
+            };
+            if !expand {
+                heredoc = Some(body);
+                continue;
+            }
+            // it's expanded like one big double quoted word
+            let quoted = format!("\"{}\"", body.replace('"', "\\\""));
+            let expanded = tokenize::words(&quoted)
+                .map_err(String::from)
+                .and_then(|words| expand::words(words, lookup, run, ""));
+            match expanded {
+                Ok(words) => heredoc = words.into_iter().next().map(|w| w.text.into_owned()),
+                Err(error) => {
+                    self.error(&error);
+                    return Executable::Failed(1);
+                }
+            }
+        }
+
+        // stdin comes from the word after `<`, and the last `<` wins
+        let mut infile = None;
+        while let Some(i) = words.iter().position(|w| w.is("<")) {
+            let redirect = words.remove(i);
+            if i == words.len() {
+                return missing_target(&redirect);
+            }
+            infile = Some(words.remove(i).text.to_string());
+        }
+
+        let noclobber = |path: &str| {
+            let clobbers =
+                self.context.options.is_set(ShellOption::Noclobber) && Path::new(path).is_file();
+            if clobbers {
+                self.error(&format!("rlsh: {path}: cannot overwrite existing file"));
             }
-            None => (None, input),
+            clobbers
+        };
+
+        // file descriptors can be redirected anywhere in the command, and
+        // the redirects are applied in order, so the last one wins. The
+        // number and operator can't come from a variable, but `>&$fd` can.
+        let mut fds = FdTable::default();
+        let redirect = |w: &tokenize::Word| {
+            let plain = !w.quoted && w.expansions.first().is_none_or(|r| r.start > 0);
+            plain.then(|| fds::parse(&w.text)).flatten()
         };
+        while let Some(i) = words.iter().position(|w| redirect(w).is_some()) {
+            let operator = words.remove(i);
+            let (fd, mut target) = redirect(&operator).unwrap();
+            if let Redirect::Open(path, mode) = &mut target {
+                if i == words.len() {
+                    return missing_target(&operator);
+                }
+                *path = words.remove(i).text.to_string();
+                if *mode == fds::Mode::Write && noclobber(path) {
+                    return Executable::Failed(1);
+                }
+            }
+            fds.push(fd, target);
+        }
 
-        let outfile = match input.iter().position(|x| x == &">") {
+        // `>|` overwrites the file even with noclobber set, and `>>` adds to
+        // the end of it
+        let redirect = words
+            .iter()
+            .position(|w| w.is(">") || w.is(">|") || w.is(">>"));
+        let outfile = match redirect {
             Some(i) => {
-                let outvec = input.split_off(i);
-                outvec.get(1).map(|v| v.to_string())
+                let outvec = words.split_off(i);
+                let Some(path) = outvec.get(1) else {
+                    return missing_target(&outvec[0]);
+                };
+                let path = path.text.to_string();
+                if outvec[0].is(">") && noclobber(&path) {
+                    return Executable::Failed(1);
+                }
+                Some(Outfile {
+                    path,
+                    append: outvec[0].is(">>"),
+                })
             }
             None => None,
         };
 
+        let mut input: Vec<&str> = words.iter().map(|w| &*w.text).collect();
         // if empty then return no op
         if input.len() == 0 {
             return Executable::Noop;
         }
 
+        // `NAME=value` words in front of a command only go to the command's
+        // environment. On their own, they set shell variables.
+        let assignments = input
+            .iter()
+            .take_while(|word| builtins::assignment(word).is_some())
+            .count();
+        let prefix: Vec<(String, String)> = input
+            .drain(..assignments)
+            .filter_map(builtins::assignment)
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        if input.is_empty() {
+            return Executable::Assign(prefix);
+        }
+
+        // game verbs take precedence over programs of the same name, but only
+        // when they are aimed at something in the game
+        if self.context.options.is_set(ShellOption::Gameverbs)
+            && let Some((verb, args)) = self.verbs.get(&input.join(" "))
+            && game::class::applies(&verb, &args)
+        {
+            return Executable::Game(verb, args);
+        }
+
         // extract command
 
         match input.remove(0) {
@@ -423,32 +1952,265 @@ impl App {
             "cd" => {
                 if input.len() > 1 {
                     println!("cd: too many arguments");
-                    Executable::Noop
+                    Executable::Failed(1)
                 } else {
                     Executable::Cd(input.get(0).map(|v| v.to_string()))
                 }
             }
             "jobs" => Executable::Jobs(outfile),
+            "pet" => Executable::Pet(input.iter().map(|v| v.to_string()).collect()),
+            "search" => Executable::Search,
+            "buy" => Executable::Buy(input.join(" ")),
+            "roll" => Executable::Roll(input.iter().map(|v| v.to_string()).collect()),
+            "abbr" => Executable::Abbr(input.iter().map(|v| v.to_string()).collect()),
+            "history" => Executable::History(input.iter().map(|v| v.to_string()).collect()),
+            "kill" => Executable::Kill(input.iter().map(|v| v.to_string()).collect()),
+            "fg" => Executable::Fg(input.iter().map(|v| v.to_string()).collect()),
+            "bg" => Executable::Bg(input.iter().map(|v| v.to_string()).collect()),
+            "wait" => Executable::Wait(input.iter().map(|v| v.to_string()).collect()),
+            "alias" => Executable::Alias(input.iter().map(|v| v.to_string()).collect()),
+            "unalias" => Executable::Unalias(input.iter().map(|v| v.to_string()).collect()),
+            "bind" => Executable::Bind(input.iter().map(|v| v.to_string()).collect()),
+            "limit" => {
+                let args: Vec<String> = input.iter().map(|v| v.to_string()).collect();
+                if !args.first().is_some_and(|arg| arg.contains('=')) {
+                    return Executable::Limit(args);
+                }
+                // `limit cpu=10 cmd` runs cmd with its own limits
+                match self.context.limits.with(&args) {
+                    Ok((_, [])) => {
+                        println!("limit: missing command");
+                        Executable::Failed(1)
+                    }
+                    Ok((limits, [command, rest @ ..])) => Executable::NonBuiltin(NonBuiltInData {
+                        command: command.clone(),
+                        args: rest.to_vec(),
+                        state,
+                        cmdline: cmdline(),
+                        infile,
+                        heredoc,
+                        outfile,
+                        fds,
+                        env: self.environment(prefix),
+                        limits,
+                    }),
+                    Err(error) => {
+                        println!("{error}");
+                        Executable::Failed(1)
+                    }
+                }
+            }
+            "rsh" => {
+                let args: Vec<String> = input.iter().map(|v| v.to_string()).collect();
+                // a job in the background can't have the terminal
+                let terminal = io::stdin().is_terminal() && state == State::FG;
+                match builtins::rsh(&args, terminal) {
+                    Ok(args) => Executable::NonBuiltin(NonBuiltInData {
+                        command: String::from("ssh"),
+                        args,
+                        state,
+                        cmdline: cmdline(),
+                        infile,
+                        heredoc,
+                        outfile,
+                        fds,
+                        env: self.environment(prefix),
+                        limits: self.context.limits.clone(),
+                    }),
+                    Err(error) => {
+                        println!("{error}");
+                        Executable::Failed(1)
+                    }
+                }
+            }
+            "set" => Executable::Set(input.iter().map(|v| v.to_string()).collect()),
+            "read" => Executable::Read(input.iter().map(|v| v.to_string()).collect()),
+            "sleep" => Executable::Sleep(
+                input.iter().map(|v| v.to_string()).collect(),
+                state,
+                cmdline(),
+            ),
+            "printf" => Executable::Printf(input.iter().map(|v| v.to_string()).collect(), outfile),
+            "seq" => Executable::Seq(input.iter().map(|v| v.to_string()).collect(), outfile),
+            "shuf" => Executable::Shuf(input.iter().map(|v| v.to_string()).collect(), outfile),
+            "basename" => {
+                Executable::Basename(input.iter().map(|v| v.to_string()).collect(), outfile)
+            }
+            "dirname" => {
+                Executable::Dirname(input.iter().map(|v| v.to_string()).collect(), outfile)
+            }
+            "realpath" => {
+                Executable::Realpath(input.iter().map(|v| v.to_string()).collect(), outfile)
+            }
+            "config" => Executable::Config(input.iter().map(|v| v.to_string()).collect(), outfile),
+            "stat" => Executable::Stat(input.iter().map(|v| v.to_string()).collect(), outfile),
+            "hexdump" | "xxd" => {
+                Executable::Hexdump(input.iter().map(|v| v.to_string()).collect(), outfile)
+            }
+            "date" => Executable::Date(input.iter().map(|v| v.to_string()).collect(), outfile),
+            "audit" => Executable::Audit(input.iter().map(|v| v.to_string()).collect(), outfile),
+            "theme" => Executable::Theme(input.iter().map(|v| v.to_string()).collect(), outfile),
+            "record" => Executable::Record(input.iter().map(|v| v.to_string()).collect()),
+            "play" => Executable::Play(input.iter().map(|v| v.to_string()).collect()),
+            "test" => Executable::Test(input.iter().map(|v| v.to_string()).collect()),
+            "[" => match input.pop() {
+                Some("]") => Executable::Test(input.iter().map(|v| v.to_string()).collect()),
+                _ => {
+                    println!("[: missing `]'");
+                    Executable::Failed(2)
+                }
+            },
+            "game" => Executable::GameCommand(input.iter().map(|v| v.to_string()).collect()),
+            "leaderboard" => Executable::Leaderboard(input.iter().map(|v| v.to_string()).collect()),
+            "reputation" => Executable::Reputation(input.iter().map(|v| v.to_string()).collect()),
             "exit" => Executable::Exit,
+            "export" => Executable::Export(input.iter().map(|v| v.to_string()).collect(), outfile),
+            "mkfifo" => Executable::Mkfifo(input.iter().map(|v| v.to_string()).collect()),
+            "unset" => Executable::Unset(input.iter().map(|v| v.to_string()).collect()),
+            "coproc" => match coproc::parse(&input) {
+                Ok((name, command)) => Executable::Coproc(
+                    name.to_string(),
+                    NonBuiltInData {
+                        command: command[0].to_string(),
+                        args: command[1..].iter().map(|v| v.to_string()).collect(),
+                        state: State::BG,
+                        cmdline: cmdline(),
+                        // it's talked to through pipes instead
+                        infile: None,
+                        heredoc: None,
+                        outfile: None,
+                        fds,
+                        env: self.environment(prefix),
+                        limits: self.context.limits.clone(),
+                    },
+                ),
+                Err(error) => {
+                    println!("{error}");
+                    Executable::Failed(1)
+                }
+            },
+            "env" => {
+                let args: Vec<String> = input.iter().map(|v| v.to_string()).collect();
+                match EnvOverrides::parse(&args) {
+                    Ok((env, [])) => Executable::Env(env, outfile),
+                    Ok((mut env, [command, args @ ..])) => {
+                        // `A=1 env B=2 cmd` runs cmd with both
+                        let mut overrides = prefix;
+                        overrides.append(&mut env.set);
+                        env.set = overrides;
+                        let env = self.context.environment.then(env);
+                        Executable::NonBuiltin(NonBuiltInData {
+                            command: command.clone(),
+                            args: args.to_vec(),
+                            state,
+                            cmdline: cmdline(),
+                            infile,
+                            heredoc,
+                            outfile,
+                            fds,
+                            env,
+                            limits: self.context.limits.clone(),
+                        })
+                    }
+                    Err(error) => {
+                        println!("{error}");
+                        Executable::Failed(1)
+                    }
+                }
+            }
             x => Executable::NonBuiltin(NonBuiltInData {
                 command: x.to_string(),
                 args: input.iter().map(|v| v.to_string()).collect(),
                 state,
-                cmdline,
+                cmdline: cmdline(),
                 infile,
+                heredoc,
                 outfile,
+                fds,
+                env: self.environment(prefix),
+                limits: self.context.limits.clone(),
             }),
         }
     }
 
+    /// The environment a command is run with: the shell's exports, then the
+    /// `NAME=value` words in front of the command.
+    fn environment(&self, prefix: Vec<(String, String)>) -> EnvOverrides {
+        self.context.environment.then(EnvOverrides {
+            set: prefix,
+            ..EnvOverrides::default()
+        })
+    }
+
+    /// Splits `input` at each `|`, apart from quoted ones and the ones in
+    /// `>|`.
+    fn split_pipeline(input: &str) -> Vec<&str> {
+        let masked = tokenize::mask(input);
+        let mut stages = Vec::new();
+        let mut start = 0;
+        for (i, c) in masked.char_indices() {
+            if c == '|' && !masked[..i].ends_with('>') {
+                stages.push(&input[start..i]);
+                start = i + 1;
+            }
+        }
+        stages.push(&input[start..]);
+        stages
+    }
+
+    /// Parses the commands of a pipeline like `cat foo | grep bar | wc -l`.
+    /// Only programs can be piped together, not builtins. Ending it with `&`
+    /// puts the whole pipeline in the background.
+    fn parse_pipeline(&self, stages: &[&str], cmdline: &str) -> Executable {
+        let mut pipeline = Vec::new();
+        for (i, stage) in stages.iter().enumerate() {
+            // stages are slices of the command line, so errors can say where
+            // in it they are
+            let start = stage.as_ptr() as usize - cmdline.as_ptr() as usize;
+            let Some(name) = stage.split_whitespace().next() else {
+                // the `|` after it, or before it if it's the last
+                let at = match i + 1 == stages.len() {
+                    true => start - 1,
+                    false => start + stage.len(),
+                };
+                return Executable::SyntaxError(SyntaxError::new(
+                    "rlsh: syntax error near `|'",
+                    at,
+                ));
+            };
+            match self.parse(stage) {
+                Executable::NonBuiltin(data) => pipeline.push(data),
+                Executable::SyntaxError(error) => {
+                    return Executable::SyntaxError(SyntaxError {
+                        at: start + error.at,
+                        ..error
+                    });
+                }
+                // there's nothing to run, and if that's because of an error
+                // it's been printed
+                Executable::Noop => return Executable::Noop,
+                Executable::Failed(code) => return Executable::Failed(code),
+                _ => {
+                    self.error(&format!("rlsh: {name}: builtins can't be piped"));
+                    return Executable::Failed(1);
+                }
+            }
+        }
+        let state = pipeline.last().map_or(State::FG, |data| data.state);
+        for data in &mut pipeline {
+            data.state = state;
+            data.cmdline = cmdline.to_string();
+        }
+        Executable::Pipeline(pipeline)
+    }
+
     /// Parses the arguments for ls
-    fn parse_ls(mut input: Vec<&str>, outfile: Option<String>) -> Executable {
+    fn parse_ls(mut input: Vec<&str>, outfile: Option<Outfile>) -> Executable {
         let mut arg_list: Vec<String> = Vec::new();
         input.retain(|word| {
-            // input was split by whitespace, guaranteeing that word is nonzero length
-            let starts_with_dash = word.chars().nth(0).unwrap() == '-';
-            if starts_with_dash && word.len() > 1 {
-                if word.chars().nth(1).unwrap() == '-' {
+            // quoted words can be empty, and `-` on its own is a file
+            if word.starts_with('-') && word.len() > 1 {
+                if word.starts_with("--") {
                     // move --long-args to arg_list
                     arg_list.push(word.to_string());
                 } else {
@@ -492,6 +2254,17 @@ impl App {
                 arg_list.retain(|word| !(*word == "-t"));
                 old_arg_list_len > arg_list.len()
             },
+            filters: {
+                let mut filters = Vec::new();
+                arg_list.retain(|word| match game::Filter::from_flag(word) {
+                    Some(filter) => {
+                        filters.push(filter);
+                        false
+                    }
+                    None => true,
+                });
+                filters
+            },
             files: input.iter().map(|v| v.to_string()).collect(),
             outfile,
         };
@@ -501,7 +2274,7 @@ impl App {
                 "ls: could not recognize these arguments: {}",
                 arg_list.join(" ")
             );
-            Executable::Noop
+            Executable::Failed(2)
         } else {
             Executable::Ls(data)
         }
@@ -0,0 +1,195 @@
+//! Shell options, turned on and off with the `set` builtin.
+//!
+//! Like in other shells, options can be set by name with `set -o name` and
+//! unset with `set +o name`. Most also have a single letter form, like
+//! `set -x`.
+
+use std::fmt::Write;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShellOption {
+    /// Exit the shell as soon as a command fails.
+    Errexit,
+    /// Let game verbs take over commands aimed at things in the game.
+    Gameverbs,
+    /// Refuse to overwrite existing files with `>`.
+    Noclobber,
+    /// Split expansions on spaces and tabs too when `IFS` isn't set, like
+    /// POSIX shells, rather than only on newlines.
+    Posixsplit,
+    /// Collapse each prompt to a plain `$` once its command has been entered.
+    Transientprompt,
+    /// Announce entities that have appeared in or gone from the current
+    /// directory since the last prompt.
+    Watch,
+    /// Print each command before running it.
+    Xtrace,
+}
+
+impl ShellOption {
+    pub const ALL: [ShellOption; 7] = [
+        ShellOption::Errexit,
+        ShellOption::Gameverbs,
+        ShellOption::Noclobber,
+        ShellOption::Posixsplit,
+        ShellOption::Transientprompt,
+        ShellOption::Watch,
+        ShellOption::Xtrace,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ShellOption::Errexit => "errexit",
+            ShellOption::Gameverbs => "gameverbs",
+            ShellOption::Noclobber => "noclobber",
+            ShellOption::Posixsplit => "posixsplit",
+            ShellOption::Transientprompt => "transientprompt",
+            ShellOption::Watch => "watch",
+            ShellOption::Xtrace => "xtrace",
+        }
+    }
+
+    /// The letter for `set -<letter>`, if the option has one.
+    fn flag(self) -> Option<char> {
+        match self {
+            ShellOption::Errexit => Some('e'),
+            ShellOption::Gameverbs => None,
+            ShellOption::Noclobber => Some('C'),
+            ShellOption::Posixsplit => None,
+            ShellOption::Transientprompt => None,
+            ShellOption::Watch => None,
+            ShellOption::Xtrace => Some('x'),
+        }
+    }
+
+    fn from_name(name: &str) -> Option<ShellOption> {
+        ShellOption::ALL.into_iter().find(|opt| opt.name() == name)
+    }
+
+    fn from_flag(flag: char) -> Option<ShellOption> {
+        ShellOption::ALL
+            .into_iter()
+            .find(|opt| opt.flag() == Some(flag))
+    }
+}
+
+/// Which shell options are turned on.
+#[derive(Clone, Debug)]
+pub struct Options {
+    enabled: Vec<ShellOption>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            enabled: vec![ShellOption::Gameverbs],
+        }
+    }
+}
+
+impl Options {
+    pub fn is_set(&self, opt: ShellOption) -> bool {
+        self.enabled.contains(&opt)
+    }
+
+    pub fn set(&mut self, opt: ShellOption, on: bool) {
+        self.enabled.retain(|&enabled| enabled != opt);
+        if on {
+            self.enabled.push(opt);
+        }
+    }
+
+    /// Lists every option and whether it's on, like `set -o` in bash.
+    fn list(&self) -> String {
+        let mut out = String::new();
+        for opt in ShellOption::ALL {
+            let state = if self.is_set(opt) { "on" } else { "off" };
+            writeln!(out, "{:<15}{state}", opt.name()).unwrap();
+        }
+        out
+    }
+
+    /// Runs the `set` builtin, returning what should be printed.
+    ///
+    /// - `set -o` lists the options. (`set` on its own lists variables, which
+    ///   the shell handles itself.)
+    /// - `set -o <name>` turns an option on, and `set +o <name>` turns it off.
+    /// - `set -<letters>` and `set +<letters>` do the same using the options'
+    ///   single letter forms.
+    pub fn command(&mut self, args: &[String]) -> Result<String, String> {
+        let mut output = String::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let on = match arg.chars().next() {
+                Some('-') => true,
+                Some('+') => false,
+                _ => return Err(format!("set: {arg}: expected an option")),
+            };
+
+            let flags = &arg[1..];
+            if flags == "o" {
+                match args.next() {
+                    Some(name) => {
+                        let opt = ShellOption::from_name(name)
+                            .ok_or_else(|| format!("set: {name}: no such option"))?;
+                        self.set(opt, on);
+                    }
+                    None => output.push_str(&self.list()),
+                }
+                continue;
+            }
+
+            if flags.is_empty() {
+                return Err(format!("set: {arg}: expected an option"));
+            }
+            for flag in flags.chars() {
+                let opt = ShellOption::from_flag(flag)
+                    .ok_or_else(|| format!("set: -{flag}: no such option"))?;
+                self.set(opt, on);
+            }
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(options: &mut Options, args: &str) -> Result<String, String> {
+        let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+        options.command(&args)
+    }
+
+    #[test]
+    fn set_by_name_and_flag() {
+        let mut options = Options::default();
+        assert!(options.is_set(ShellOption::Gameverbs));
+        assert!(!options.is_set(ShellOption::Xtrace));
+
+        set(&mut options, "-o xtrace +o gameverbs").unwrap();
+        assert!(options.is_set(ShellOption::Xtrace));
+        assert!(!options.is_set(ShellOption::Gameverbs));
+
+        set(&mut options, "-eC +x").unwrap();
+        assert!(options.is_set(ShellOption::Errexit));
+        assert!(options.is_set(ShellOption::Noclobber));
+        assert!(!options.is_set(ShellOption::Xtrace));
+    }
+
+    #[test]
+    fn set_lists_options() {
+        let mut options = Options::default();
+        let listing = set(&mut options, "-o").unwrap();
+        assert!(listing.contains("gameverbs      on"));
+        assert!(listing.contains("xtrace         off"));
+    }
+
+    #[test]
+    fn set_rejects_nonsense() {
+        let mut options = Options::default();
+        assert!(set(&mut options, "-o nonsense").is_err());
+        assert!(set(&mut options, "-q").is_err());
+        assert!(set(&mut options, "xtrace").is_err());
+    }
+}
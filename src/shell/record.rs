@@ -0,0 +1,322 @@
+//! Recording sessions with `record` and playing them back with `play`.
+//!
+//! `record <file>` starts a new shell on a pseudo-terminal of its own and sits
+//! between it and the real terminal, like `script` or asciinema, so what
+//! programs print is caught as well as what the shell does. Everything typed
+//! and printed is written to `<file>` with when it happened, until that shell
+//! exits.
+//!
+//! Recordings are asciicast v2 files, so they can be played with asciinema
+//! or shared on asciinema.org as well as with `play <file>`. The first line
+//! describes the terminal, and each line after it is an event like
+//! `[1.25, "o", "hello\r\n"]`: the time in seconds, `o` for output, `i` for
+//! input or `r` for a resize, and the data.
+
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, BufWriter, IsTerminal, Write},
+    os::{fd::FromRawFd, unix::process::CommandExt},
+    process::{Command, Stdio},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use super::terminal::{self, Modes};
+use crate::game::json::{self, Value};
+
+/// The longest pause `play` makes between events, so a recording where
+/// someone went to make tea doesn't take as long to watch.
+const MAX_PAUSE: Duration = Duration::from_secs(2);
+
+/// Something that happened during a recording.
+#[derive(Clone, Debug, PartialEq)]
+struct Event {
+    /// Seconds since the recording started.
+    time: f64,
+    /// `o` for output, `i` for input and `r` for a resize.
+    kind: char,
+    data: String,
+}
+
+impl Event {
+    fn to_line(&self) -> String {
+        let value = Value::Array(vec![
+            Value::Number(format!("{:.6}", self.time)),
+            Value::String(self.kind.to_string()),
+            Value::String(self.data.clone()),
+        ]);
+        value.compact().to_string()
+    }
+
+    fn from_line(line: &str) -> Option<Event> {
+        let Ok(Value::Array(fields)) = json::parse(line) else {
+            return None;
+        };
+        match fields.as_slice() {
+            [
+                Value::Number(time),
+                Value::String(kind),
+                Value::String(data),
+            ] => Some(Event {
+                time: time.parse().ok()?,
+                kind: kind.chars().next()?,
+                data: data.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// The first line of a recording, describing the terminal it was made on.
+fn header(columns: u16, rows: u16) -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let env = ["SHELL", "TERM"]
+        .iter()
+        .filter_map(|name| Some((name.to_string(), Value::String(env::var(name).ok()?))))
+        .collect();
+    let header = Value::Object(vec![
+        (String::from("version"), Value::Number(String::from("2"))),
+        (String::from("width"), Value::Number(columns.to_string())),
+        (String::from("height"), Value::Number(rows.to_string())),
+        (
+            String::from("timestamp"),
+            Value::Number(timestamp.to_string()),
+        ),
+        (String::from("env"), Value::Object(env)),
+    ]);
+    header.compact().to_string()
+}
+
+/// Turns bytes read from a terminal into text, keeping back the start of a
+/// character that was split between reads in `partial`.
+fn decode(partial: &mut Vec<u8>, bytes: &[u8]) -> String {
+    partial.extend_from_slice(bytes);
+    let valid = match std::str::from_utf8(partial) {
+        Ok(_) => partial.len(),
+        Err(error) if error.error_len().is_none() => error.valid_up_to(),
+        Err(_) => partial.len(),
+    };
+    let text = String::from_utf8_lossy(&partial[..valid]).to_string();
+    partial.drain(..valid);
+    text
+}
+
+/// The size of the terminal on stdin.
+fn window_size() -> libc::winsize {
+    // SAFETY: TIOCGWINSZ only writes to the winsize it's given.
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    unsafe { libc::ioctl(libc::STDIN_FILENO, libc::TIOCGWINSZ, &mut size) };
+    if size.ws_col == 0 || size.ws_row == 0 {
+        (size.ws_col, size.ws_row) = (80, 24);
+    }
+    size
+}
+
+/// Opens a pseudo-terminal of `size`, returning its master and slave sides.
+fn open_pty(size: &libc::winsize) -> io::Result<(i32, i32)> {
+    // SAFETY: these are the usual calls for opening a pseudo-terminal, and
+    // only hand back file descriptors.
+    unsafe {
+        let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC);
+        if master < 0 || libc::grantpt(master) != 0 || libc::unlockpt(master) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let slave = libc::open(
+            libc::ptsname(master),
+            libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC,
+        );
+        if slave < 0 {
+            let error = io::Error::last_os_error();
+            libc::close(master);
+            return Err(error);
+        }
+        libc::ioctl(master, libc::TIOCSWINSZ, size);
+        Ok((master, slave))
+    }
+}
+
+/// Passes bytes between the terminal and the shell on `master` until it
+/// exits, writing what happens to `out`.
+fn relay(master: i32, out: &mut impl Write) -> io::Result<()> {
+    let start = Instant::now();
+    let mut event = |kind, data: String| -> io::Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let time = start.elapsed().as_secs_f64();
+        writeln!(out, "{}", Event { time, kind, data }.to_line())
+    };
+    let (mut typed, mut printed) = (Vec::new(), Vec::new());
+    let mut buffer = [0u8; 4096];
+    loop {
+        if terminal::resized() {
+            let size = window_size();
+            unsafe { libc::ioctl(master, libc::TIOCSWINSZ, &size) };
+            event('r', format!("{}x{}", size.ws_col, size.ws_row))?;
+        }
+        let mut fds = [
+            libc::pollfd {
+                fd: libc::STDIN_FILENO,
+                events: libc::POLLIN,
+                revents: 0,
+            },
+            libc::pollfd {
+                fd: master,
+                events: libc::POLLIN,
+                revents: 0,
+            },
+        ];
+        if unsafe { libc::poll(fds.as_mut_ptr(), 2, -1) } < 0 {
+            // interrupted by a resize, most likely
+            continue;
+        }
+        if fds[0].revents & libc::POLLIN != 0 {
+            let read = unsafe { libc::read(libc::STDIN_FILENO, buffer.as_mut_ptr().cast(), 4096) };
+            if read > 0 {
+                let bytes = &buffer[..read as usize];
+                unsafe { libc::write(master, bytes.as_ptr().cast(), bytes.len()) };
+                event('i', decode(&mut typed, bytes))?;
+            }
+        }
+        if fds[1].revents != 0 {
+            let read = unsafe { libc::read(master, buffer.as_mut_ptr().cast(), 4096) };
+            // the shell's gone once its side of the terminal is closed
+            if read <= 0 {
+                return Ok(());
+            }
+            let bytes = &buffer[..read as usize];
+            let mut stdout = io::stdout();
+            stdout.write_all(bytes)?;
+            stdout.flush()?;
+            event('o', decode(&mut printed, bytes))?;
+        }
+    }
+}
+
+/// Runs the `record` builtin: records a new shell to `args[0]` until it
+/// exits. Returns what should be printed.
+pub fn record(args: &[String]) -> Result<String, String> {
+    let [path] = args else {
+        return Err(String::from("record: usage: record <file>"));
+    };
+    if !io::stdin().is_terminal() {
+        return Err(String::from("record: only works on a terminal"));
+    }
+    let file = File::create(path).map_err(|error| format!("record: {path}: {error}"))?;
+    let mut out = BufWriter::new(file);
+    let size = window_size();
+    let error = |error: io::Error| format!("record: {error}");
+    writeln!(out, "{}", header(size.ws_col, size.ws_row)).map_err(error)?;
+
+    let (master, slave) = open_pty(&size).map_err(error)?;
+    let stdio = || unsafe { Stdio::from_raw_fd(libc::dup(slave)) };
+    let mut command = Command::new(env::current_exe().map_err(error)?);
+    command.stdin(stdio()).stdout(stdio()).stderr(stdio());
+    // SAFETY: setsid and ioctl are async-signal-safe
+    unsafe {
+        command.pre_exec(|| {
+            // the new shell gets the pseudo-terminal as its own
+            libc::setsid();
+            libc::ioctl(libc::STDIN_FILENO, libc::TIOCSCTTY, 0);
+            Ok(())
+        });
+    }
+    let child = command.spawn();
+    // the shell's side of the terminal has to be closed here too, or reading
+    // the master never finds out the shell has gone
+    drop(command);
+    unsafe { libc::close(slave) };
+    let mut child = child.map_err(error)?;
+
+    // keys go straight through to the new shell, which has its own terminal
+    // settings to deal with them
+    let modes = Modes::save(libc::STDIN_FILENO);
+    if let Some(modes) = modes {
+        let mut raw = modes;
+        raw.make_raw();
+        raw.restore(libc::STDIN_FILENO);
+    }
+    let relayed = relay(master, &mut out).and_then(|()| out.flush());
+    if let Some(modes) = modes {
+        modes.restore(libc::STDIN_FILENO);
+    }
+    let _ = child.wait();
+    unsafe { libc::close(master) };
+    relayed.map_err(|error| format!("record: {path}: {error}"))?;
+    Ok(format!("recorded to {path}\n"))
+}
+
+/// Runs the `play` builtin: plays back the recording `args[0]`, as fast as it
+/// was made but with long pauses cut short. Ctrl-C stops it.
+///
+/// Returns whether it played to the end.
+pub async fn play(args: &[String]) -> bool {
+    let [path] = args else {
+        println!("play: usage: play <file>");
+        return false;
+    };
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(error) => {
+            println!("play: {path}: {error}");
+            return false;
+        }
+    };
+    let mut lines = text.lines();
+    if !lines
+        .next()
+        .is_some_and(|line| line.contains("\"version\": 2") || line.contains("\"version\":2"))
+    {
+        println!("play: {path}: not an asciicast v2 recording");
+        return false;
+    }
+
+    let mut previous = 0.0;
+    let mut stdout = io::stdout();
+    for event in lines.filter_map(Event::from_line) {
+        let pause = Duration::from_secs_f64((event.time - previous).max(0.0)).min(MAX_PAUSE);
+        previous = event.time;
+        tokio::select! {
+            () = tokio::time::sleep(pause) => (),
+            _ = tokio::signal::ctrl_c() => {
+                println!();
+                return false;
+            }
+        }
+        if event.kind == 'o' {
+            let _ = stdout.write_all(event.data.as_bytes());
+            let _ = stdout.flush();
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_round_trip() {
+        let event = Event {
+            time: 1.25,
+            kind: 'o',
+            data: String::from("\x1b[32mhi\x1b[0m \"there\"\r\n"),
+        };
+        let line = event.to_line();
+        assert_eq!(
+            line,
+            r#"[1.250000, "o", "\u001b[32mhi\u001b[0m \"there\"\r\n"]"#
+        );
+        assert_eq!(Event::from_line(&line), Some(event));
+        assert_eq!(Event::from_line("[1, \"o\"]"), None);
+        assert!(header(80, 24).starts_with(r#"{"version": 2, "width": 80, "height": 24, "#));
+
+        let mut partial = Vec::new();
+        let heart = "♥".as_bytes();
+        assert_eq!(decode(&mut partial, &[b'a', heart[0]]), "a");
+        assert_eq!(decode(&mut partial, &heart[1..]), "♥");
+        assert!(partial.is_empty());
+    }
+}
@@ -0,0 +1,144 @@
+//! The `fg` and `bg` builtins, which carry on stopped and background jobs.
+//!
+//! - `bg [job]` carries on a stopped job in the background.
+//! - `fg [job]` brings a stopped or background job to the foreground,
+//!   carrying it on if it's stopped, and waits for it to finish or be stopped
+//!   again.
+//!
+//! A job is `%n` for job `n`, as `jobs` numbers them, and `%%` or `%+`, or
+//! nothing at all, for the newest one.
+//!
+//! A background `sleep` is run by the shell itself, so it's never stopped and
+//! can't be carried on, but `fg` can still wait for it.
+
+use super::job_list::{self, Completion, JobList, State};
+use super::{kill, terminal};
+
+/// The job `args` name, and its pid, for the builtin `name`.
+fn find(name: &str, args: &[String], job_list: &JobList) -> Result<(usize, u32), String> {
+    let target = match args {
+        [] => "%%",
+        [target] => target.as_str(),
+        _ => return Err(format!("{name}: usage: {name} [%job]")),
+    };
+    let Some(spec) = target.strip_prefix('%') else {
+        return Err(format!("{name}: {target}: expected a job like %1"));
+    };
+    let jid = kill::jid(job_list, spec);
+    match jid.and_then(|jid| Some((jid, job_list.get_pid(jid)?))) {
+        Some(job) => Ok(job),
+        None => Err(format!("{name}: {target}: no such job")),
+    }
+}
+
+/// Runs the `bg` builtin, returning what should be printed.
+pub fn bg(args: &[String], job_list: &JobList) -> Result<String, String> {
+    let (jid, pid) = find("bg", args, job_list)?;
+    if job_list.get_state(jid) != Some(State::ST) {
+        return Err(format!("bg: %{jid}: already in the background"));
+    }
+    if !terminal::signal_job(pid, libc::SIGCONT) {
+        return Err(format!("bg: %{jid}: {}", std::io::Error::last_os_error()));
+    }
+    job_list.set_state(jid, State::BG).map_err(String::from)?;
+    let cmdline = job_list.get_cmdline(jid).unwrap_or_default();
+    Ok(format!("{} {cmdline}\n", job_list::describe(jid, pid)))
+}
+
+/// Runs the `fg` builtin, returning the job's exit status once it's
+/// finished, or saying so if it was stopped again.
+pub async fn fg(args: &[String], job_list: &JobList) -> Result<i32, String> {
+    let (jid, pid) = find("fg", args, job_list)?;
+    // it may have finished since it was found
+    let Some(completion) = job_list.completion(jid) else {
+        return Err(format!("fg: %{jid}: no such job"));
+    };
+    let stopped = job_list.get_state(jid) == Some(State::ST);
+    job_list.set_state(jid, State::FG).map_err(String::from)?;
+    println!("{}", job_list.get_cmdline(jid).unwrap_or_default());
+    if job_list.is_task(jid) {
+        return Ok(fg_task(jid, completion, job_list).await);
+    }
+    terminal::give_to(pid);
+    if stopped && !terminal::signal_job(pid, libc::SIGCONT) {
+        terminal::take_back();
+        let _ = job_list.set_state(jid, State::ST);
+        return Err(format!("fg: %{jid}: {}", std::io::Error::last_os_error()));
+    }
+    // the job's own task says when it's done
+    let code = tokio::select! {
+        code = completion.wait() => Some(code.unwrap_or(1)),
+        () = terminal::stopped(pid) => None,
+    };
+    terminal::take_back();
+    let Some(code) = code else {
+        let _ = job_list.set_state(jid, State::ST);
+        let cmdline = job_list.get_cmdline(jid).unwrap_or_default();
+        // on a fresh line, after the ^Z the terminal echoed
+        return Err(format!(
+            "\n{} {} {cmdline}",
+            job_list::describe(jid, pid),
+            State::ST
+        ));
+    };
+    Ok(code)
+}
+
+/// Waits in the foreground for the job `jid`, which the shell runs itself,
+/// like a background sleep. It has the terminal already, and never stops, but
+/// Ctrl-C cancels it.
+async fn fg_task(jid: usize, completion: Completion, job_list: &JobList) -> i32 {
+    let mut waiting = std::pin::pin!(completion.wait());
+    let code = tokio::select! {
+        code = &mut waiting => code,
+        _ = tokio::signal::ctrl_c() => {
+            println!();
+            job_list.abort(jid);
+            waiting.await
+        }
+    };
+    code.unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn jobs_are_carried_on() {
+        let jobs = JobList::new();
+        let mut child = tokio::process::Command::new("sleep")
+            .arg("0.2")
+            .spawn()
+            .unwrap();
+        let pid = child.id().unwrap();
+        let jid = jobs.add(pid, State::BG, String::from("sleep 0.2")).unwrap();
+        assert!(bg(&args(&[]), &jobs).is_err());
+
+        terminal::signal_job(pid, libc::SIGSTOP);
+        jobs.set_state(jid, State::ST).unwrap();
+        assert_eq!(
+            bg(&args(&["%%"]), &jobs),
+            Ok(format!("{} sleep 0.2\n", job_list::describe(jid, pid)))
+        );
+        assert_eq!(jobs.get_state(jid), Some(State::BG));
+
+        let done = jobs.clone();
+        tokio::spawn(async move {
+            let _ = child.wait().await;
+            done.finish(jid, 0);
+            done.delete(jid);
+        });
+        assert_eq!(fg(&args(&[&format!("%{jid}")]), &jobs).await, Ok(0));
+        assert_eq!(jobs.get_pid(jid), None);
+        assert_eq!(
+            fg(&args(&["%3"]), &jobs).await,
+            Err(String::from("fg: %3: no such job"))
+        );
+        assert!(bg(&args(&["3"]), &jobs).is_err());
+    }
+}
@@ -0,0 +1,337 @@
+//! Completion specs, which say what can be typed after external commands.
+//!
+//! A spec is a file named after the command, with one line for each thing
+//! that can be typed: the words after the command leading up to it, then a
+//! `#` and what it does. `git commit --amend # redo the last commit` means
+//! `--amend` can follow `git commit`. Placeholders in angle brackets stand for
+//! whatever's around at the time:
+//!
+//! - `<file>` and `<directory>`: paths
+//! - `<host>`: hosts from `~/.ssh/config` and `~/.ssh/known_hosts`
+//! - `<branch>`: branches in the current git repository
+//!
+//! Specs in the `completions` directory next to the save file win over the
+//! ones bundled for git, cargo and ssh. Each is only read the first time its
+//! command is completed.
+//!
+//! With `config helphints on`, completing a flag also runs the command with
+//! `--help` and picks the flags and what they do out of that, for commands
+//! without a spec or flags a spec doesn't describe. What it finds is kept for
+//! the rest of the session.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::Read,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::complete::{self, Candidate};
+use crate::{config::Config, game::player};
+
+/// How long a command gets to print its `--help` before it's given up on.
+const HELP_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Specs that come with the shell, by command.
+const BUNDLED: &[(&str, &str)] = &[
+    ("git", include_str!("completions/git")),
+    ("cargo", include_str!("completions/cargo")),
+    ("ssh", include_str!("completions/ssh")),
+];
+
+/// One line of a spec.
+#[derive(Debug, PartialEq, Eq)]
+struct Entry {
+    /// The words leading up to this one.
+    parent: Vec<String>,
+    word: String,
+    description: String,
+}
+
+/// What can be typed after a command.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Spec {
+    entries: Vec<Entry>,
+}
+
+/// Whether `word` is one of the `<...>` placeholders.
+fn is_placeholder(word: &str) -> bool {
+    word.starts_with('<') && word.ends_with('>')
+}
+
+/// Whether `typed` fits `word` from a spec.
+fn fits(word: &str, typed: &str) -> bool {
+    match is_placeholder(word) {
+        true => !typed.starts_with('-'),
+        false => word == typed,
+    }
+}
+
+/// Hosts named in the ssh config and known hosts.
+fn hosts() -> Vec<String> {
+    let Some(ssh) = dirs::home_dir().map(|home| home.join(".ssh")) else {
+        return Vec::new();
+    };
+    let mut hosts = Vec::new();
+    let config = fs::read_to_string(ssh.join("config")).unwrap_or_default();
+    for line in config.lines() {
+        let mut words = line.split_whitespace();
+        if words.next().is_some_and(|w| w.eq_ignore_ascii_case("host")) {
+            hosts.extend(words.filter(|w| !w.contains(['*', '?'])).map(String::from));
+        }
+    }
+    let known = fs::read_to_string(ssh.join("known_hosts")).unwrap_or_default();
+    for line in known.lines() {
+        let Some(names) = line.split_whitespace().next() else {
+            continue;
+        };
+        // hashed hosts can't be read back
+        if names.starts_with('|') || names.starts_with('#') {
+            continue;
+        }
+        for name in names.split(',') {
+            // [host]:port
+            let name = name.strip_prefix('[').map_or(name, |name| {
+                name.split_once(']').map_or(name, |(name, _)| name)
+            });
+            hosts.push(name.to_string());
+        }
+    }
+    hosts
+}
+
+/// Branches in the git repository around the current directory.
+fn branches() -> Vec<String> {
+    Command::new("git")
+        .args(["for-each-ref", "--format=%(refname:short)", "refs/heads"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// What `command --help` prints, if it finishes in time.
+fn help_output(command: &str) -> Option<String> {
+    let mut child = Command::new(command)
+        .arg("--help")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let mut stdout = child.stdout.take()?;
+    let reader = thread::spawn(move || {
+        let mut text = String::new();
+        stdout.read_to_string(&mut text).ok().map(|_| text)
+    });
+    let start = Instant::now();
+    while child.try_wait().ok()?.is_none() {
+        if start.elapsed() > HELP_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            return None;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    reader.join().ok()?
+}
+
+/// The flags described in `--help` output, from lines like
+/// `-a, --all   do not ignore entries starting with .`.
+fn parse_help(text: &str) -> Vec<Candidate> {
+    let mut flags = Vec::new();
+    for line in text.lines().map(str::trim_start) {
+        if !line.starts_with('-') {
+            continue;
+        }
+        // the flags and the description are split by a run of spaces
+        let (names, description) = match line.find("  ") {
+            Some(i) => (&line[..i], line[i..].trim()),
+            None => (line, ""),
+        };
+        for name in names.split([',', ' ']) {
+            let name = name.split(['=', '[', '<']).next().unwrap_or_default();
+            if name.len() > 1 && name.starts_with('-') && name != "--" {
+                flags.push(Candidate::new(name, description));
+            }
+        }
+    }
+    flags
+}
+
+impl Spec {
+    pub fn parse(text: &str) -> Spec {
+        let entries = text
+            .lines()
+            .filter_map(|line| {
+                let (words, description) = line.split_once('#').unwrap_or((line, ""));
+                let mut words: Vec<String> = words.split_whitespace().map(String::from).collect();
+                let word = words.pop()?;
+                Some(Entry {
+                    parent: words,
+                    word,
+                    description: description.trim().to_string(),
+                })
+            })
+            .collect();
+        Spec { entries }
+    }
+
+    /// How many of `parent` fit `typed`, the words already typed after the
+    /// command, or `None` if it doesn't fit. Options that aren't in `parent`
+    /// are passed over.
+    fn matches(parent: &[String], typed: &[&str]) -> Option<usize> {
+        let mut next = parent.iter().peekable();
+        for word in typed {
+            if next.peek().is_some_and(|p| fits(p, word)) {
+                next.next();
+            } else if !word.starts_with('-') {
+                return None;
+            }
+        }
+        next.peek().is_none().then_some(parent.len())
+    }
+
+    /// What `word` could be after `typed`. Options only come up once a `-`
+    /// has been typed.
+    pub fn complete(&self, typed: &[&str], word: &str) -> Vec<Candidate> {
+        // the lines that go deepest into what's been typed win
+        let fitting: Vec<(usize, &Entry)> = self
+            .entries
+            .iter()
+            .filter_map(|entry| Some((Spec::matches(&entry.parent, typed)?, entry)))
+            .collect();
+        let Some(deepest) = fitting.iter().map(|(depth, _)| *depth).max() else {
+            return Vec::new();
+        };
+
+        let mut candidates = Vec::new();
+        for (_, entry) in fitting.into_iter().filter(|(depth, _)| *depth == deepest) {
+            let found = match entry.word.as_str() {
+                "<file>" | "<directory>" => complete::files(word),
+                "<host>" => hosts()
+                    .into_iter()
+                    .map(|h| Candidate::new(h, "host"))
+                    .collect(),
+                "<branch>" => branches()
+                    .into_iter()
+                    .map(|b| Candidate::new(b, "branch"))
+                    .collect(),
+                option if option.starts_with('-') && !word.starts_with('-') => continue,
+                literal => vec![Candidate::new(literal, entry.description.as_str())],
+            };
+            candidates.extend(found.into_iter().filter(|c| c.text.starts_with(word)));
+        }
+        candidates
+    }
+}
+
+/// The specs read so far, by command.
+#[derive(Debug, Default)]
+pub struct Specs {
+    loaded: HashMap<String, Option<Spec>>,
+    /// Flags from each command's `--help`.
+    help: HashMap<String, Vec<Candidate>>,
+}
+
+impl Specs {
+    /// The spec for `command`, reading it if it hasn't been yet.
+    pub fn get(&mut self, command: &str) -> Option<&Spec> {
+        self.loaded
+            .entry(command.to_string())
+            .or_insert_with(|| {
+                let path = player::get_data_dir().join("completions").join(command);
+                let text = fs::read_to_string(path).ok().or_else(|| {
+                    BUNDLED
+                        .iter()
+                        .find(|(name, _)| *name == command)
+                        .map(|(_, text)| text.to_string())
+                })?;
+                Some(Spec::parse(&text))
+            })
+            .as_ref()
+    }
+
+    /// Fills in `candidates` for the flag `word` with what `command --help`
+    /// says, if help hints are on.
+    pub fn add_help(&mut self, command: &str, word: &str, candidates: &mut Vec<Candidate>) {
+        if !word.starts_with('-') || !Config::load().is_on("helphints") {
+            return;
+        }
+        let flags = self
+            .help
+            .entry(command.to_string())
+            .or_insert_with(|| help_output(command).map_or_else(Vec::new, |t| parse_help(&t)));
+        for flag in flags.iter().filter(|flag| flag.text.starts_with(word)) {
+            match candidates.iter_mut().find(|c| c.text == flag.text) {
+                Some(known) if known.description.is_empty() => {
+                    known.description = flag.description.clone();
+                }
+                Some(_) => (),
+                None => candidates.push(flag.clone()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(candidates: Vec<Candidate>) -> Vec<String> {
+        candidates.into_iter().map(|c| c.text).collect()
+    }
+
+    #[test]
+    fn specs_complete_by_what_was_typed() {
+        let spec = Spec::parse(
+            "# a comment\n\
+             commit   # record changes\n\
+             commit --amend  # redo the last commit\n\
+             checkout <branch>\n\
+             checkout\n\
+             --version\n",
+        );
+        assert_eq!(
+            spec.complete(&[], "c"),
+            [
+                Candidate::new("commit", "record changes"),
+                Candidate::new("checkout", "")
+            ]
+        );
+        assert!(spec.complete(&["commit"], "").is_empty());
+        assert_eq!(texts(spec.complete(&["commit"], "-")), ["--amend"]);
+        assert_eq!(texts(spec.complete(&["commit", "-v"], "--a")), ["--amend"]);
+        assert_eq!(texts(spec.complete(&[], "--")), ["--version"]);
+        assert!(spec.complete(&["checkout", "main"], "").is_empty());
+
+        let help = "Usage: ls [OPTION]... [FILE]...\n\
+                    \n  -a, --all                  do not ignore entries starting with .\n\
+                    \x20     --color[=WHEN]         color the output\n\
+                    \x20 -w, --width=COLS           set output width to COLS\n\
+                    \x20     --help     display this help and exit\n";
+        assert_eq!(
+            parse_help(help),
+            [
+                Candidate::new("-a", "do not ignore entries starting with ."),
+                Candidate::new("--all", "do not ignore entries starting with ."),
+                Candidate::new("--color", "color the output"),
+                Candidate::new("-w", "set output width to COLS"),
+                Candidate::new("--width", "set output width to COLS"),
+                Candidate::new("--help", "display this help and exit"),
+            ]
+        );
+
+        let mut specs = Specs::default();
+        assert!(specs.get("git").is_some());
+        assert!(specs.get("not-a-real-command").is_none());
+    }
+}
@@ -0,0 +1,75 @@
+//! Catching typos in command names before they're run.
+//!
+//! When the first word of a command isn't a builtin, a game verb or a program
+//! on `$PATH`, the shell offers the closest one that is instead: `y` runs the
+//! fixed command, `n` runs it as typed and `e` puts the fixed command back in
+//! the editor to look over first.
+
+use std::{env, path::Path};
+
+/// How many single-character edits turn `a` into `b`, counting swapping two
+/// neighbours as one.
+pub fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // rows[i][j] is the distance between the first i of a and first j of b
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    rows[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = best;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+/// The name in `known` closest to `word`, if one is close enough to be a
+/// typo. Earlier names win ties.
+pub fn correction(word: &str, known: impl IntoIterator<Item = String>) -> Option<String> {
+    // short words are only a letter or two away from too many others
+    let allowed = if word.chars().count() <= 4 { 1 } else { 2 };
+    known
+        .into_iter()
+        .map(|name| (distance(word, &name), name))
+        .filter(|(d, _)| (1..=allowed).contains(d))
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, name)| name)
+}
+
+/// Whether there's a program called `name` on `$PATH`.
+pub fn on_path(name: &str) -> bool {
+    env::var_os("PATH").is_some_and(|paths| {
+        env::split_paths(&paths).any(|dir| Path::new(&dir).join(name).is_file())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typos_are_corrected() {
+        assert_eq!(distance("gti", "git"), 1);
+        assert_eq!(distance("sl", "ls"), 1);
+        assert_eq!(distance("cargo", "crago"), 1);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("", "ls"), 2);
+
+        let known = || ["ls", "cd", "cargo", "git"].map(String::from);
+        assert_eq!(correction("gti", known()).as_deref(), Some("git"));
+        assert_eq!(correction("carg", known()).as_deref(), Some("cargo"));
+        assert_eq!(correction("cargoo", known()).as_deref(), Some("cargo"));
+        assert_eq!(correction("ls", known()), None);
+        assert_eq!(correction("xyz", known()), None);
+    }
+}
@@ -0,0 +1,378 @@
+//! Rewriting what's already on the terminal.
+//!
+//! Input is read a line at a time, so by the time the shell sees a command the
+//! terminal has already echoed it after the prompt. To change how that looks,
+//! like collapsing the prompt for the transient prompt option, the shell works
+//! out how many rows the prompt and command wrapped onto, moves the cursor back
+//! up over them, clears from there down and writes the new version. Nothing
+//! else on screen gets redrawn.
+//!
+//! It also looks after the terminal's settings for programs run in the
+//! foreground. The shell only changes them while a line is being edited, so
+//! programs like `sudo` get the terminal as it normally is and can turn off
+//! echo to ask for a password. If one exits without turning echo back on,
+//! say because it was killed mid-prompt, the shell puts back the settings it
+//! started with rather than carrying on with them broken.
+//!
+//! Each program also gets a process group of its own, and one in the
+//! foreground is handed the terminal while it runs, like in other shells. Then
+//! full-screen programs like vim and htop own the terminal outright: the
+//! kernel sends them the keyboard's signals and `SIGWINCH` when the window is
+//! resized, and the shell gets them back afterwards, or as soon as Ctrl-Z
+//! stops them.
+//!
+//! The shell watches for `SIGWINCH` itself too, so a line being edited can be
+//! redrawn to fit when the window changes size.
+
+use std::{
+    io::{self, IsTerminal, Write},
+    os::fd::RawFd,
+    sync::{
+        OnceLock,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+};
+
+use tokio::signal::unix::{SignalKind, signal};
+
+/// What the prompt collapses to with the transient prompt option on.
+pub const TRANSIENT_PROMPT: &str = "$ ";
+
+/// A terminal's settings, saved to put back later.
+#[derive(Clone, Copy)]
+pub struct Modes {
+    termios: libc::termios,
+}
+
+impl Modes {
+    /// The settings of the terminal `fd`, if it is one.
+    pub fn save(fd: RawFd) -> Option<Modes> {
+        // SAFETY: termios is plain data, and tcgetattr fills it in.
+        let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+        (unsafe { libc::tcgetattr(fd, &mut termios) } == 0).then_some(Modes { termios })
+    }
+
+    /// Changes the settings to pass every byte through untouched, with no
+    /// echo, line editing or signals from keys.
+    pub fn make_raw(&mut self) {
+        unsafe { libc::cfmakeraw(&mut self.termios) };
+    }
+
+    /// Puts the settings back on the terminal `fd`, once anything already
+    /// written to it has gone out.
+    pub fn restore(&self, fd: RawFd) {
+        unsafe { libc::tcsetattr(fd, libc::TCSADRAIN, &self.termios) };
+    }
+}
+
+/// The terminal's settings when the shell started.
+static SHELL_MODES: OnceLock<Option<Modes>> = OnceLock::new();
+
+/// Remembers the terminal's settings as the ones to go back to after
+/// foreground programs. Later calls do nothing.
+pub fn save_shell_modes() {
+    SHELL_MODES.get_or_init(|| Modes::save(libc::STDIN_FILENO));
+}
+
+/// Puts back the settings from [`save_shell_modes`], after a foreground
+/// program has had the terminal.
+pub fn restore_shell_modes() {
+    if let Some(Some(modes)) = SHELL_MODES.get() {
+        modes.restore(libc::STDIN_FILENO);
+    }
+}
+
+/// Whether the window's changed size since [`resized`] was last asked.
+static RESIZED: AtomicBool = AtomicBool::new(false);
+
+/// The thread that reads from the terminal, as a `pthread_t`.
+static READER: AtomicU64 = AtomicU64::new(0);
+
+extern "C" fn on_resize(_: libc::c_int) {
+    RESIZED.store(true, Ordering::Relaxed);
+    // the signal can land on any thread, so pass it on to interrupt the read
+    let reader = READER.load(Ordering::Relaxed) as libc::pthread_t;
+    if unsafe { libc::pthread_self() } != reader {
+        unsafe { libc::pthread_kill(reader, libc::SIGWINCH) };
+    }
+}
+
+/// Starts watching for the window changing size. Reads from the terminal on
+/// this thread are interrupted when it does, so they can stop and redraw.
+pub fn watch_resizes() {
+    READER.store(unsafe { libc::pthread_self() } as u64, Ordering::Relaxed);
+    // SAFETY: sigaction is plain data, and the handler only touches atomics
+    // and sends a signal, which are async-signal-safe.
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_resize as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        // no SA_RESTART, so a read that's waiting gets EINTR
+        action.sa_flags = 0;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(libc::SIGWINCH, &action, std::ptr::null_mut());
+    }
+}
+
+/// Whether the window's changed size since this was last called.
+pub fn resized() -> bool {
+    RESIZED.swap(false, Ordering::Relaxed)
+}
+
+/// Whether the shell hands the terminal to the programs it runs.
+static JOB_CONTROL: AtomicBool = AtomicBool::new(false);
+
+/// The signals a shell doing job control ignores, which programs it runs need
+/// back.
+const JOB_SIGNALS: [libc::c_int; 3] = [libc::SIGTSTP, libc::SIGTTIN, libc::SIGTTOU];
+
+/// Sets up handing the terminal to programs, if the shell is on one. The
+/// shell gets a process group of its own and takes the terminal for it.
+pub fn init_job_control() {
+    if !io::stdin().is_terminal() {
+        return;
+    }
+    // SAFETY: these only change this process's signal dispositions and
+    // process group, and the terminal's foreground group.
+    unsafe {
+        // SIGTTOU would stop the shell for taking the terminal back
+        for signal in JOB_SIGNALS {
+            libc::signal(signal, libc::SIG_IGN);
+        }
+        // fails if the shell already leads a session, which is fine
+        libc::setpgid(0, 0);
+        libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp());
+    }
+    JOB_CONTROL.store(true, Ordering::Relaxed);
+    watch_resizes();
+}
+
+/// Gets `command` ready to run with job control: in the process `group` of
+/// the rest of its pipeline, or one of its own if that's 0, with the terminal
+/// if it's to run in the `foreground`, and with the signals the shell ignores
+/// back to normal.
+pub fn prepare(command: &mut tokio::process::Command, foreground: bool, group: u32) {
+    if !JOB_CONTROL.load(Ordering::Relaxed) {
+        return;
+    }
+    // SAFETY: setpgid, tcsetpgrp and signal are async-signal-safe.
+    unsafe {
+        command.pre_exec(move || {
+            libc::setpgid(0, group as libc::pid_t);
+            // the shell does this too, but the program mustn't start before
+            // it's happened
+            if foreground {
+                libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp());
+            }
+            for signal in JOB_SIGNALS {
+                libc::signal(signal, libc::SIG_DFL);
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Hands the terminal to the program `pid`, which was started with
+/// [`prepare`], and the rest of its pipeline.
+pub fn give_to(pid: u32) {
+    if !JOB_CONTROL.load(Ordering::Relaxed) {
+        return;
+    }
+    let pid = pid as libc::pid_t;
+    // SAFETY: these only change process groups and the terminal's foreground
+    // group.
+    unsafe {
+        // the child does this too, but it may not have got there yet
+        libc::setpgid(pid, pid);
+        libc::tcsetpgrp(libc::STDIN_FILENO, pid);
+    }
+}
+
+/// Sends `signal` to the job led by `pid`: its whole process group when the
+/// shell does job control, and otherwise just it. Returns whether it was
+/// sent.
+pub fn signal_job(pid: u32, signal: libc::c_int) -> bool {
+    let pid = pid as libc::pid_t;
+    let target = match JOB_CONTROL.load(Ordering::Relaxed) {
+        true => -pid,
+        false => pid,
+    };
+    // SAFETY: kill only sends a signal.
+    unsafe { libc::kill(target, signal) == 0 }
+}
+
+/// Waits until the job led by `pid` has been stopped, like by Ctrl-Z. Never
+/// returns if it isn't, so it's to be raced against the job finishing.
+pub async fn stopped(pid: u32) {
+    let Ok(mut children) = signal(SignalKind::child()) else {
+        return std::future::pending().await;
+    };
+    // checked before each wait, so a stop before the first one isn't missed
+    while !is_stopped(pid) {
+        children.recv().await;
+    }
+}
+
+/// Whether the job led by `pid`, or any of the rest of its pipeline, is
+/// stopped. The stop is left to be seen again, and nothing is reaped.
+fn is_stopped(pid: u32) -> bool {
+    let (idtype, id) = match JOB_CONTROL.load(Ordering::Relaxed) {
+        true => (libc::P_PGID, pid),
+        false => (libc::P_PID, pid),
+    };
+    // SAFETY: siginfo_t is plain data, and waitid only writes to it.
+    unsafe {
+        let mut info: libc::siginfo_t = std::mem::zeroed();
+        let flags = libc::WSTOPPED | libc::WNOHANG | libc::WNOWAIT;
+        libc::waitid(idtype, id, &mut info, flags) == 0 && info.si_pid() != 0
+    }
+}
+
+/// Takes the terminal back for the shell after a program's had it, putting
+/// its settings back too.
+pub fn take_back() {
+    if JOB_CONTROL.load(Ordering::Relaxed) {
+        unsafe { libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp()) };
+    }
+    restore_shell_modes();
+}
+
+/// How many columns the terminal is, if stdout is one.
+pub fn columns() -> Option<usize> {
+    // SAFETY: TIOCGWINSZ only writes to the winsize it's given.
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+    (ok && size.ws_col > 0).then_some(size.ws_col.into())
+}
+
+/// Where the cursor ends up, as a row and column, after writing `text` on a
+/// fresh line of a terminal `columns` wide.
+///
+/// Text that exactly fills a row leaves the cursor on that row in column
+/// `columns`, waiting to wrap, which is what terminals do. Colour escapes
+/// take up no room.
+pub fn position(text: &str, columns: usize) -> (usize, usize) {
+    let columns = columns.max(1);
+    let (mut row, mut column) = (0, 0);
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // up to and including the letter that ends it
+            chars.by_ref().skip(1).find(|c| c.is_ascii_alphabetic());
+            continue;
+        }
+        if c == '\n' {
+            (row, column) = (row + 1, 0);
+            continue;
+        }
+        if column == columns {
+            (row, column) = (row + 1, 0);
+        }
+        column += 1;
+    }
+    (row, column)
+}
+
+/// How many rows `text` takes up when written on a fresh line of a terminal
+/// `columns` wide.
+fn rows(text: &str, columns: usize) -> usize {
+    position(text, columns).0 + 1
+}
+
+/// Replaces the `line` just entered, which the cursor is now below, with
+/// `replacement`. Does nothing unless the shell is talking to a terminal.
+pub fn rewrite_last_line(line: &str, replacement: &str) {
+    if !io::stdin().is_terminal() {
+        return;
+    }
+    let Some(columns) = columns() else {
+        return;
+    };
+    // up: CSI n A; back to the start of the line: CR; clear to the end of the
+    // screen: CSI J
+    let mut stdout = io::stdout();
+    let _ = write!(
+        stdout,
+        "\x1b[{}A\r\x1b[J{replacement}\n",
+        rows(line, columns)
+    );
+    let _ = stdout.flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_wrap_onto_rows() {
+        assert_eq!(rows("", 80), 1);
+        assert_eq!(rows("user@host ~ $ ls", 80), 1);
+        assert_eq!(rows(&"x".repeat(80), 80), 1);
+        assert_eq!(rows(&"x".repeat(81), 80), 2);
+        assert_eq!(rows("ünïcödé", 3), 3);
+        assert_eq!(rows("cd a\nls", 80), 2);
+        assert_eq!(position("abc", 3), (0, 3));
+        assert_eq!(position("abcd\n", 3), (2, 0));
+        assert_eq!(position("\x1b[1;34mab\x1b[0mc", 3), (0, 3));
+    }
+
+    #[test]
+    fn jobs_are_signalled() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        assert!(signal_job(child.id(), libc::SIGINT));
+        let status = child.wait().unwrap();
+        assert_eq!(
+            std::os::unix::process::ExitStatusExt::signal(&status),
+            Some(libc::SIGINT)
+        );
+    }
+
+    #[tokio::test]
+    async fn stops_are_noticed() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        assert!(!is_stopped(child.id()));
+        signal_job(child.id(), libc::SIGSTOP);
+        tokio::time::timeout(std::time::Duration::from_secs(5), stopped(child.id()))
+            .await
+            .unwrap();
+        // and it's still there to be waited for
+        child.kill().unwrap();
+        assert!(child.wait().is_ok());
+    }
+
+    #[test]
+    fn modes_are_put_back() {
+        // a pseudo-terminal to stand in for the real one
+        let (master, slave) = unsafe {
+            let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            assert!(master >= 0);
+            assert_eq!(libc::grantpt(master), 0);
+            assert_eq!(libc::unlockpt(master), 0);
+            let name = libc::ptsname(master);
+            (master, libc::open(name, libc::O_RDWR | libc::O_NOCTTY))
+        };
+        assert!(slave >= 0);
+        let echoes = || Modes::save(slave).unwrap().termios.c_lflag & libc::ECHO != 0;
+
+        let modes = Modes::save(slave).unwrap();
+        assert!(echoes());
+        // what a password prompt does, and doesn't always undo
+        let mut quiet = modes;
+        quiet.termios.c_lflag &= !libc::ECHO;
+        quiet.restore(slave);
+        assert!(!echoes());
+        modes.restore(slave);
+        assert!(echoes());
+        assert!(Modes::save(-1).is_none());
+
+        unsafe {
+            libc::close(slave);
+            libc::close(master);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! Shell variables, set by builtins like `read`.
+
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, Default)]
+pub struct Variables {
+    values: HashMap<String, String>,
+}
+
+/// Whether `name` can be used as a variable name: letters, digits and
+/// underscores, not starting with a digit.
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl Variables {
+    /// Every variable and its value, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        let mut values: Vec<(&str, &str)> = self
+            .values
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        values.sort();
+        values.into_iter()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    /// Removes the variable `name`, if it's set.
+    pub fn unset(&mut self, name: &str) {
+        self.values.remove(name);
+    }
+
+    /// Sets the variable `name` to `value`, failing if `name` isn't a valid
+    /// variable name.
+    pub fn set(&mut self, name: &str, value: impl Into<String>) -> Result<(), String> {
+        if !is_valid_name(name) {
+            return Err(format!("{name}: not a valid identifier"));
+        }
+        self.values.insert(name.to_string(), value.into());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_valid_names_are_set() {
+        let mut variables = Variables::default();
+        assert!(variables.set("name", "goblin").is_ok());
+        assert!(variables.set("_hp2", "5").is_ok());
+        assert!(variables.set("2hp", "5").is_err());
+        assert!(variables.set("hit-points", "5").is_err());
+        assert!(variables.set("", "5").is_err());
+
+        let set: Vec<(&str, &str)> = variables.iter().collect();
+        assert_eq!(set, vec![("_hp2", "5"), ("name", "goblin")]);
+    }
+}
@@ -0,0 +1,113 @@
+//! The `wait` builtin, which waits for background jobs to finish.
+//!
+//! - `wait` waits for every background job. Stopped jobs are left alone,
+//!   since they won't finish until they're carried on.
+//! - `wait <target>...` waits for each of the targets in turn, and gives the
+//!   exit status of the last one.
+//!
+//! A target is a process id, or a job: `%n` for job `n`, as `jobs` numbers
+//! them, and `%%` or `%+` for the newest one. A target that isn't one of the
+//! shell's jobs has an exit status of 127, like in other shells. Ctrl-C stops
+//! waiting, without doing anything to the jobs.
+
+use super::job_list::{JobList, State};
+use super::kill;
+
+/// The exit status for a target that isn't one of the shell's jobs.
+const NOT_A_JOB: i32 = 127;
+
+/// The job `target` names, see the module docs.
+fn find(job_list: &JobList, target: &str) -> Result<usize, String> {
+    let jid = match target.strip_prefix('%') {
+        Some(spec) => kill::jid(job_list, spec),
+        None => match target.parse() {
+            Ok(pid) => job_list.find_pid(pid),
+            Err(_) => {
+                return Err(format!(
+                    "wait: {target}: expected a process id or a job like %1"
+                ));
+            }
+        },
+    };
+    jid.filter(|&jid| job_list.get_pid(jid).is_some())
+        .ok_or_else(|| format!("wait: {target}: not a job of this shell"))
+}
+
+/// Waits for each of `jids` in turn, returning the exit status of the last
+/// one.
+async fn wait_for(jids: Vec<usize>, job_list: &JobList) -> i32 {
+    // all taken at once, so jobs that finish while the ones before them are
+    // waited for aren't forgotten
+    let completions: Vec<_> = jids
+        .into_iter()
+        .filter_map(|jid| job_list.completion(jid))
+        .collect();
+    let mut code = 0;
+    for completion in completions {
+        code = completion.wait().await.unwrap_or(NOT_A_JOB);
+    }
+    code
+}
+
+/// Runs the `wait` builtin, returning the exit status for `$?` and whatever
+/// went wrong.
+pub async fn command(args: &[String], job_list: &JobList) -> (i32, Vec<String>) {
+    let mut errors = Vec::new();
+    let jids = match args {
+        [] => job_list.jids(State::BG),
+        targets => targets
+            .iter()
+            .filter_map(|target| find(job_list, target).map_err(|e| errors.push(e)).ok())
+            .collect(),
+    };
+    let code = tokio::select! {
+        code = wait_for(jids, job_list) => code,
+        _ = tokio::signal::ctrl_c() => {
+            // past the ^C the terminal echoed
+            println!();
+            return (128 + libc::SIGINT, errors);
+        }
+    };
+    match errors.is_empty() {
+        true => (code, errors),
+        false => (NOT_A_JOB, errors),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn jobs_are_waited_for() {
+        let jobs = JobList::new();
+        jobs.add(10, State::BG, String::from("one")).unwrap();
+        jobs.add(20, State::BG, String::from("two")).unwrap();
+        jobs.add(30, State::ST, String::from("three")).unwrap();
+        let done = jobs.clone();
+        tokio::spawn(async move {
+            for (jid, code) in [(1, 4), (0, 0)] {
+                tokio::task::yield_now().await;
+                done.finish(jid, code);
+                done.delete(jid);
+            }
+        });
+        assert_eq!(command(&args(&["%0", "20"]), &jobs).await, (4, vec![]));
+        // the stopped job isn't waited for
+        assert_eq!(command(&args(&[]), &jobs).await, (0, vec![]));
+
+        let (code, errors) = command(&args(&["%7", "x"]), &jobs).await;
+        assert_eq!(code, NOT_A_JOB);
+        assert_eq!(
+            errors,
+            [
+                "wait: %7: not a job of this shell",
+                "wait: x: expected a process id or a job like %1"
+            ]
+        );
+    }
+}
@@ -0,0 +1,272 @@
+//! Colours for everything the shell and game draw in the terminal.
+//!
+//! Nothing else should write colour escapes itself. Instead it asks for a
+//! [`Role`], and the `palette` setting (see [`crate::config`]) decides what
+//! that looks like. Besides the usual colours there are palettes for the
+//! common kinds of colour blindness, which avoid the pairs of colours each one
+//! makes hard to tell apart (red and green for deuteranopia and protanopia,
+//! blue and yellow for tritanopia).
+//!
+//! In headless mode nothing is coloured at all.
+
+use std::fmt;
+
+use crate::{config::Config, headless, theme};
+
+/// Resets all colours and text styles.
+const RESET: &str = "\x1b[0m";
+
+/// How many cells wide an HP bar is.
+const HP_BAR_WIDTH: i32 = 10;
+
+/// What some coloured text is for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    Directory,
+    Enemy,
+    Trap,
+    /// A command line's builtins and programs.
+    Command,
+    /// A command line's commands that don't exist.
+    Unknown,
+    /// Quoted strings on the command line.
+    Quoted,
+    /// Game verbs on the command line.
+    Verb,
+    /// The filled part of an HP bar with more than half HP left.
+    HpHigh,
+    /// The filled part of an HP bar with between a quarter and half left.
+    HpMid,
+    /// The filled part of an HP bar with a quarter or less left.
+    HpLow,
+    /// Named pipes in `ls`.
+    Fifo,
+    Socket,
+    /// Block and character devices in `ls`.
+    Device,
+}
+
+impl Role {
+    const ALL: [Role; 13] = [
+        Role::Directory,
+        Role::Enemy,
+        Role::Trap,
+        Role::Command,
+        Role::Unknown,
+        Role::Quoted,
+        Role::Verb,
+        Role::HpHigh,
+        Role::HpMid,
+        Role::HpLow,
+        Role::Fifo,
+        Role::Socket,
+        Role::Device,
+    ];
+
+    /// What themes call the role.
+    pub fn key(self) -> &'static str {
+        match self {
+            Role::Directory => "directory",
+            Role::Enemy => "enemy",
+            Role::Trap => "trap",
+            Role::Command => "command",
+            Role::Unknown => "unknown",
+            Role::Quoted => "quoted",
+            Role::Verb => "verb",
+            Role::HpHigh => "hp_high",
+            Role::HpMid => "hp_mid",
+            Role::HpLow => "hp_low",
+            Role::Fifo => "fifo",
+            Role::Socket => "socket",
+            Role::Device => "device",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Role> {
+        Role::ALL.into_iter().find(|role| role.key() == key)
+    }
+}
+
+/// A set of colours for each [`Role`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Palette {
+    #[default]
+    Default,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+}
+
+impl Palette {
+    /// The names the `palette` setting takes.
+    pub const NAMES: &[&str] = &["default", "deuteranopia", "protanopia", "tritanopia"];
+
+    pub fn from_name(name: &str) -> Option<Palette> {
+        match name {
+            "default" => Some(Palette::Default),
+            "deuteranopia" => Some(Palette::Deuteranopia),
+            "protanopia" => Some(Palette::Protanopia),
+            "tritanopia" => Some(Palette::Tritanopia),
+            _ => None,
+        }
+    }
+
+    /// The palette picked with the `palette` setting. Palettes that don't
+    /// exist fall back on the default.
+    pub fn current() -> Palette {
+        Palette::from_name(Config::load().get("palette")).unwrap_or_default()
+    }
+
+    /// The SGR parameters for `role`, the bit between `\x1b[` and `m`.
+    fn sgr(self, role: Role) -> &'static str {
+        use Role::*;
+        match (self, role) {
+            // 1: bold text; 34: blue; 31: red; 33: yellow; 32: green; 35: purple
+            (Palette::Default, Directory) => "1;34",
+            (Palette::Default, Enemy) => "31",
+            (Palette::Default, Trap) => "33",
+            (Palette::Default, HpHigh) => "32",
+            (Palette::Default, HpMid) => "33",
+            (Palette::Default, HpLow) => "31",
+            (Palette::Default, Command) => "32",
+            (Palette::Default, Unknown) => "31",
+            (Palette::Default, Quoted) => "33",
+            (Palette::Default, Verb) => "35",
+            (Palette::Default, Fifo) => "33",
+            (Palette::Default, Socket) => "1;35",
+            (Palette::Default, Device) => "1;33",
+            // blues against oranges, from the 256 colour cube
+            (Palette::Deuteranopia, Directory) => "1;38;5;32",
+            (Palette::Deuteranopia, Enemy) => "38;5;166",
+            (Palette::Deuteranopia, Trap) => "38;5;220",
+            (Palette::Deuteranopia, HpHigh) => "38;5;39",
+            (Palette::Deuteranopia, HpMid) => "38;5;220",
+            (Palette::Deuteranopia, HpLow) => "38;5;166",
+            (Palette::Deuteranopia, Command) => "38;5;39",
+            (Palette::Deuteranopia, Unknown) => "38;5;166",
+            (Palette::Deuteranopia, Quoted) => "38;5;220",
+            (Palette::Deuteranopia, Verb) => "38;5;177",
+            (Palette::Deuteranopia, Fifo) => "38;5;220",
+            (Palette::Deuteranopia, Socket) => "1;38;5;177",
+            (Palette::Deuteranopia, Device) => "1;38;5;220",
+            // reds look dark without red cones, so use brighter oranges
+            (Palette::Protanopia, Directory) => "1;38;5;33",
+            (Palette::Protanopia, Enemy) => "1;38;5;214",
+            (Palette::Protanopia, Trap) => "38;5;227",
+            (Palette::Protanopia, HpHigh) => "38;5;33",
+            (Palette::Protanopia, HpMid) => "38;5;227",
+            (Palette::Protanopia, HpLow) => "1;38;5;214",
+            (Palette::Protanopia, Command) => "38;5;33",
+            (Palette::Protanopia, Unknown) => "1;38;5;214",
+            (Palette::Protanopia, Quoted) => "38;5;227",
+            (Palette::Protanopia, Verb) => "38;5;183",
+            (Palette::Protanopia, Fifo) => "38;5;227",
+            (Palette::Protanopia, Socket) => "1;38;5;183",
+            (Palette::Protanopia, Device) => "1;38;5;227",
+            // reds against teals, with pink where a third colour is needed
+            (Palette::Tritanopia, Directory) => "1;38;5;37",
+            (Palette::Tritanopia, Enemy) => "38;5;160",
+            (Palette::Tritanopia, Trap) => "38;5;213",
+            (Palette::Tritanopia, HpHigh) => "38;5;37",
+            (Palette::Tritanopia, HpMid) => "38;5;213",
+            (Palette::Tritanopia, HpLow) => "38;5;160",
+            (Palette::Tritanopia, Command) => "38;5;37",
+            (Palette::Tritanopia, Unknown) => "38;5;160",
+            (Palette::Tritanopia, Quoted) => "38;5;213",
+            (Palette::Tritanopia, Verb) => "38;5;250",
+            (Palette::Tritanopia, Fifo) => "38;5;213",
+            (Palette::Tritanopia, Socket) => "1;38;5;250",
+            (Palette::Tritanopia, Device) => "1;38;5;213",
+        }
+    }
+
+    /// The escape that starts text coloured for `role`. End it with
+    /// [`reset`]. The default palette takes the theme's colours over its own.
+    pub fn start(self, role: Role) -> String {
+        if headless::enabled() {
+            return String::new();
+        }
+        let themed = match self {
+            Palette::Default => theme::color(role),
+            _ => None,
+        };
+        format!("\x1b[{}m", themed.as_deref().unwrap_or(self.sgr(role)))
+    }
+
+    /// `text`, coloured for `role`.
+    pub fn paint(self, role: Role, text: impl fmt::Display) -> String {
+        format!("{}{text}{}", self.start(role), reset())
+    }
+
+    /// A bar showing how much of `max` `hp` is, like `[######----]`, drawn
+    /// with the theme's characters.
+    pub fn hp_bar(self, hp: i32, max: i32) -> String {
+        let (full, empty) = theme::bar();
+        let max = max.max(1);
+        let hp = hp.clamp(0, max);
+        // round up, so anything still standing has at least one cell
+        let filled = (hp * HP_BAR_WIDTH + max - 1) / max;
+        let role = if hp * 2 > max {
+            Role::HpHigh
+        } else if hp * 4 > max {
+            Role::HpMid
+        } else {
+            Role::HpLow
+        };
+        format!(
+            "[{}{}]",
+            self.paint(role, full.repeat(filled as usize)),
+            empty.repeat((HP_BAR_WIDTH - filled) as usize)
+        )
+    }
+}
+
+/// `text`, faded, for things that are only hints. This looks the same in
+/// every palette.
+pub fn dim(text: &str) -> String {
+    if text.is_empty() || headless::enabled() {
+        return text.to_string();
+    }
+    format!("\x1b[2m{text}{RESET}")
+}
+
+/// `text` in reverse video, for what's picked in a menu. This looks the same
+/// in every palette.
+pub fn selected(text: &str) -> String {
+    if headless::enabled() {
+        return text.to_string();
+    }
+    format!("\x1b[7m{text}{RESET}")
+}
+
+/// The escape that ends coloured text.
+pub fn reset() -> &'static str {
+    match headless::enabled() {
+        true => "",
+        false => RESET,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hp_bars_fill_and_change_colour() {
+        let palette = Palette::Default;
+        assert_eq!(palette.hp_bar(20, 20), "[\x1b[32m##########\x1b[0m]");
+        assert_eq!(palette.hp_bar(9, 20), "[\x1b[33m#####\x1b[0m-----]");
+        assert_eq!(palette.hp_bar(1, 20), "[\x1b[31m#\x1b[0m---------]");
+        assert_eq!(palette.hp_bar(-3, 20), "[\x1b[31m\x1b[0m----------]");
+        assert_eq!(
+            Palette::Deuteranopia.hp_bar(1, 20),
+            "[\x1b[38;5;166m#\x1b[0m---------]"
+        );
+        for name in Palette::NAMES {
+            assert!(Palette::from_name(name).is_some());
+        }
+        for role in Role::ALL {
+            assert_eq!(Role::from_key(role.key()), Some(role));
+        }
+    }
+}
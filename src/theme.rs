@@ -0,0 +1,249 @@
+//! Themes, which change how the prompt, `ls` and battles look.
+//!
+//! A theme is a file of `key = value` lines, like a language file. Lines
+//! starting with `#` are comments, and keys a theme leaves out keep their
+//! default, so a theme only needs to say what it changes:
+//!
+//! - `color.<role>`: the SGR parameters for a [`Role`], like `1;34` for bold
+//!   blue. These only apply with the default palette, since the colour blind
+//!   palettes are there to be told apart.
+//! - `icon.person`, `icon.trap`, `icon.locked_door`, `icon.unlocked_door`:
+//!   what `ls` puts in front of entities.
+//! - `prompt`: the prompt, made of segments: `{user}`, `{host}`, `{cwd}`, the
+//!   last part of it as `{dir}`, the time on the game's clock as `{clock}`, a
+//!   role like `{directory}` to start colouring and `{reset}` to stop.
+//! - `battle.bar_full` and `battle.bar_empty`: what HP bars are drawn with.
+//!
+//! Themes are read from `themes/<name>` in the data directory, then from the
+//! ones bundled with the shell. `theme use <name>` switches to one straight
+//! away.
+
+use std::{collections::HashMap, fmt::Write, fs, path::PathBuf, sync::Mutex};
+
+use crate::{
+    config::Config,
+    game::{self, player},
+    style::{Palette, Role},
+};
+
+/// What a theme that leaves everything out looks like.
+const DEFAULTS: &[(&str, &str)] = &[
+    ("prompt", "{user}@{host} {cwd} $ "),
+    ("icon.person", game::PERSON_ICON),
+    ("icon.trap", game::TRAP_ICON),
+    ("icon.locked_door", game::LOCKED_DOOR_ICON),
+    ("icon.unlocked_door", game::UNLOCKED_DOOR_ICON),
+    ("battle.bar_full", "#"),
+    ("battle.bar_empty", "-"),
+];
+
+/// Themes that come with the shell, by name.
+const BUNDLED: &[(&str, &str)] = &[
+    ("default", ""),
+    ("ascii", include_str!("themes/ascii")),
+    ("dungeon", include_str!("themes/dungeon")),
+    ("ocean", include_str!("themes/ocean")),
+];
+
+/// The theme in use, read the first time it's needed.
+static THEME: Mutex<Option<Theme>> = Mutex::new(None);
+
+#[derive(Debug, Default)]
+pub struct Theme {
+    values: HashMap<String, String>,
+}
+
+fn get_theme_path(name: &str) -> PathBuf {
+    let mut path = player::get_data_dir();
+    path.push("themes");
+    path.push(name);
+    path
+}
+
+/// The text of the theme called `name`, if there is one.
+fn read(name: &str) -> Option<String> {
+    fs::read_to_string(get_theme_path(name)).ok().or_else(|| {
+        BUNDLED
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, text)| text.to_string())
+    })
+}
+
+/// Whether there's a theme called `name`.
+pub fn exists(name: &str) -> bool {
+    !name.contains('/') && read(name).is_some()
+}
+
+/// Every theme there is, bundled ones first.
+fn names() -> Vec<String> {
+    let mut names: Vec<String> = BUNDLED.iter().map(|(n, _)| n.to_string()).collect();
+    let mut own: Vec<String> = fs::read_dir(player::get_data_dir().join("themes"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| !names.contains(name))
+        .collect();
+    own.sort();
+    names.append(&mut own);
+    names
+}
+
+impl Theme {
+    pub fn parse(text: &str) -> Theme {
+        let values = text
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .filter_map(|line| line.split_once('='))
+            // values are only trimmed on the left, so prompts can end in a space
+            .map(|(key, value)| (key.trim().to_string(), value.trim_start().to_string()))
+            .collect();
+        Theme { values }
+    }
+
+    fn load(name: &str) -> Theme {
+        read(name)
+            .map(|text| Theme::parse(&text))
+            .unwrap_or_default()
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str).or_else(|| {
+            DEFAULTS
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, value)| *value)
+        })
+    }
+}
+
+/// Fills in the segments of a prompt `template`.
+fn fill(template: &str, user: &str, host: &str, cwd: &str, palette: Palette) -> String {
+    let dir = cwd.rsplit('/').find(|part| !part.is_empty()).unwrap_or(cwd);
+    let mut prompt = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        prompt.push_str(&rest[..open]);
+        let Some(close) = rest[open..].find('}') else {
+            break;
+        };
+        let segment = &rest[open + 1..open + close];
+        match segment {
+            "user" => prompt.push_str(user),
+            "host" => prompt.push_str(host),
+            "cwd" => prompt.push_str(cwd),
+            "dir" => prompt.push_str(dir),
+            "clock" => prompt.push_str(&game::clock::Time::now().to_string()),
+            "reset" => prompt.push_str(crate::style::reset()),
+            other => match Role::from_key(other) {
+                Some(role) => prompt.push_str(&palette.start(role)),
+                None => prompt.push_str(&rest[open..=open + close]),
+            },
+        }
+        rest = &rest[open + close + 1..];
+    }
+    prompt.push_str(rest);
+    prompt
+}
+
+/// Forgets the theme in use, so it's read afresh. Called when the theme
+/// setting changes.
+pub fn reload() {
+    *THEME.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Looks up `key` in the theme in use, loading it if needed.
+fn lookup(key: &str) -> Option<String> {
+    let mut theme = THEME.lock().unwrap_or_else(|e| e.into_inner());
+    let theme = theme.get_or_insert_with(|| Theme::load(Config::load().get("theme")));
+    theme.get(key).map(String::from)
+}
+
+/// The theme's SGR parameters for `role`, if it has any.
+pub fn color(role: Role) -> Option<String> {
+    lookup(&format!("color.{}", role.key()))
+}
+
+/// The theme's icon called `name`, like `trap`.
+pub fn icon(name: &str) -> String {
+    lookup(&format!("icon.{name}")).unwrap_or_default()
+}
+
+/// The theme's HP bar characters, full then empty.
+pub fn bar() -> (String, String) {
+    let get = |key| lookup(key).unwrap_or_default();
+    (get("battle.bar_full"), get("battle.bar_empty"))
+}
+
+/// The prompt, as the theme lays it out.
+pub fn prompt(user: &str, host: &str, cwd: &str) -> String {
+    let template = lookup("prompt").unwrap_or_default();
+    fill(&template, user, host, cwd, Palette::current())
+}
+
+/// Runs the `theme` builtin, returning what should be printed.
+///
+/// - `theme` lists the themes, marking the one in use.
+/// - `theme use <name>` switches to a theme.
+pub fn command(args: &[String]) -> Result<String, String> {
+    let mut config = Config::load();
+    match args {
+        [] => {
+            let current = config.get("theme");
+            let mut out = String::new();
+            for name in names() {
+                let mark = if name == current { '*' } else { ' ' };
+                writeln!(out, "{mark} {name}").unwrap();
+            }
+            Ok(out)
+        }
+        [subcommand, name] if subcommand == "use" => {
+            if !exists(name) {
+                return Err(format!("theme: {name}: no such theme"));
+            }
+            config.set("theme", name)?;
+            config
+                .save()
+                .map_err(|error| format!("theme: could not save: {error}"))?;
+            reload();
+            Ok(String::new())
+        }
+        [subcommand, ..] if subcommand == "use" => {
+            Err(String::from("theme: usage: theme use <name>"))
+        }
+        [subcommand, ..] => Err(format!("theme: {subcommand}: unknown command")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn themes_fill_in_the_defaults() {
+        let theme = Theme::parse("# a comment\nprompt = {dir}{reset} > \nicon.trap = ^\n");
+        assert_eq!(theme.get("icon.trap"), Some("^"));
+        assert_eq!(theme.get("icon.person"), Some(game::PERSON_ICON));
+        assert_eq!(theme.get("color.enemy"), None);
+        let prompt = |theme: &Theme, cwd| {
+            let template = theme.get("prompt").unwrap();
+            fill(template, "me", "box", cwd, Palette::Deuteranopia)
+        };
+        assert_eq!(prompt(&theme, "/home/me/dungeon"), "dungeon\x1b[0m > ");
+        assert_eq!(prompt(&Theme::default(), "/"), "me@box / $ ");
+        let coloured = Theme::parse("prompt = {directory}{cwd}{nonsense}");
+        assert_eq!(prompt(&coloured, "/tmp"), "\x1b[1;38;5;32m/tmp{nonsense}");
+
+        for (name, text) in BUNDLED {
+            let theme = Theme::parse(text);
+            for key in theme.values.keys() {
+                let known = DEFAULTS.iter().any(|(k, _)| k == key)
+                    || key
+                        .strip_prefix("color.")
+                        .is_some_and(|role| Role::from_key(role).is_some());
+                assert!(known, "{name}: {key}");
+            }
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! Finding out about new versions of rlsh, and about this one.
+//!
+//! `rlsh update` asks the release endpoint (GitHub's, unless `RLSH_UPDATE_URL`
+//! says otherwise) for the latest release and says whether it's newer. Built
+//! with the `self-update` feature, it also downloads the release built for
+//! this machine and puts it in place of the running binary. Fetching is left
+//! to `curl`, so the shell doesn't need an HTTP client of its own.
+//!
+//! `rlsh --version --verbose` describes the build and where rlsh keeps its
+//! files, for bug reports.
+
+use std::{cmp::Ordering, env, fmt::Write, process::Command};
+
+use serde::Deserialize;
+
+use crate::game::{json, player};
+
+/// Where the latest release is described.
+const RELEASES_URL: &str = "https://api.github.com/repos/wade-cheng/rlsh/releases/latest";
+
+/// This version of rlsh.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The features rlsh was built with.
+const FEATURES: &[(&str, bool)] = &[("self-update", cfg!(feature = "self-update"))];
+
+/// A file attached to a release.
+#[derive(Debug, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// A release, as the endpoint describes it.
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+impl Release {
+    fn parse(text: &str) -> Result<Release, String> {
+        json::from_value(json::parse(text)?).map_err(|error| error.to_string())
+    }
+
+    /// The version released, without the `v` tags usually start with.
+    fn version(&self) -> &str {
+        self.tag_name.strip_prefix('v').unwrap_or(&self.tag_name)
+    }
+}
+
+/// Compares versions like `0.10.2` part by part, as numbers.
+fn compare(a: &str, b: &str) -> Ordering {
+    let parts = |version: &str| -> Vec<u64> {
+        version
+            .split(['.', '-', '+'])
+            .map_while(|part| part.parse().ok())
+            .collect()
+    };
+    parts(a).cmp(&parts(b))
+}
+
+/// What the release for this machine is called, like `rlsh-x86_64-linux`.
+fn asset_name() -> String {
+    format!("rlsh-{}-{}", env::consts::ARCH, env::consts::OS)
+}
+
+/// Fetches `url` with curl.
+fn fetch(url: &str) -> Result<Vec<u8>, String> {
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location", url])
+        .output()
+        .map_err(|error| format!("could not run curl: {error}"))?;
+    match output.status.success() {
+        true => Ok(output.stdout),
+        false => Err(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+    }
+}
+
+/// Puts the release for this machine in place of the running binary.
+#[cfg(feature = "self-update")]
+fn install(release: &Release) -> Result<String, String> {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    let name = asset_name();
+    let asset = release
+        .assets
+        .iter()
+        .find(|asset| asset.name == name)
+        .ok_or_else(|| format!("there's no {name} in this release"))?;
+    let binary = fetch(&asset.browser_download_url)?;
+    let exe = env::current_exe().map_err(|error| error.to_string())?;
+    // written next to the old binary, so the rename can't cross filesystems
+    let new = exe.with_extension("new");
+    fs::write(&new, binary).map_err(|error| format!("{}: {error}", new.display()))?;
+    fs::set_permissions(&new, fs::Permissions::from_mode(0o755))
+        .and_then(|_| fs::rename(&new, &exe))
+        .map_err(|error| {
+            let _ = fs::remove_file(&new);
+            format!("{}: {error}", exe.display())
+        })?;
+    Ok(format!("updated to {}\n", release.version()))
+}
+
+#[cfg(not(feature = "self-update"))]
+fn install(release: &Release) -> Result<String, String> {
+    Ok(format!(
+        "get it from {} or build with --features self-update to update in place\n",
+        release
+            .assets
+            .iter()
+            .find(|asset| asset.name == asset_name())
+            .map_or(RELEASES_URL, |asset| asset.browser_download_url.as_str())
+    ))
+}
+
+/// Runs `rlsh update`, returning what should be printed.
+pub fn update() -> Result<String, String> {
+    let url = env::var("RLSH_UPDATE_URL").unwrap_or_else(|_| RELEASES_URL.to_string());
+    let text = fetch(&url).map_err(|error| format!("update: {error}"))?;
+    let release = Release::parse(&String::from_utf8_lossy(&text))
+        .map_err(|error| format!("update: {url}: {error}"))?;
+    match compare(release.version(), VERSION) {
+        Ordering::Greater => {
+            let mut out = format!("rlsh {} is out (you have {VERSION})\n", release.version());
+            out += &install(&release).map_err(|error| format!("update: {error}"))?;
+            Ok(out)
+        }
+        _ => Ok(format!("rlsh {VERSION} is the latest\n")),
+    }
+}
+
+/// What `rlsh --version`, or with `verbose`, `rlsh --version --verbose`
+/// prints.
+pub fn version(verbose: bool) -> String {
+    let mut out = format!("rlsh {VERSION}\n");
+    if !verbose {
+        return out;
+    }
+    let profile = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    };
+    let features: Vec<String> = FEATURES
+        .iter()
+        .map(|(name, on)| format!("{}{name}", if *on { '+' } else { '-' }))
+        .collect();
+    let data = player::get_data_dir();
+    writeln!(out, "target:   {}-{}", env::consts::ARCH, env::consts::OS).unwrap();
+    writeln!(out, "profile:  {profile}").unwrap();
+    writeln!(out, "features: {}", features.join(" ")).unwrap();
+    writeln!(out, "data:     {}", data.display()).unwrap();
+    writeln!(out, "save:     {}", player::get_data_path().display()).unwrap();
+    writeln!(out, "config:   {}", data.join("config").display()).unwrap();
+    writeln!(out, "themes:   {}", data.join("themes").display()).unwrap();
+    writeln!(out, "audit:    {}", data.join("audit.log").display()).unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn releases_are_read_and_compared() {
+        let release = Release::parse(
+            r#"{
+                "url": "https://example.com",
+                "tag_name": "v0.10.0",
+                "draft": false,
+                "assets": [
+                    {"name": "rlsh-x86_64-linux", "size": 1234,
+                     "browser_download_url": "https://example.com/rlsh"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(release.version(), "0.10.0");
+        assert_eq!(
+            release.assets[0].browser_download_url,
+            "https://example.com/rlsh"
+        );
+        assert!(Release::parse("{}").is_err());
+
+        assert_eq!(compare("0.10.0", "0.9.3"), Ordering::Greater);
+        assert_eq!(compare("0.1.0", "0.1.0"), Ordering::Equal);
+        assert_eq!(compare("0.1.0-rc1", "0.1.1"), Ordering::Less);
+
+        assert_eq!(version(false), format!("rlsh {VERSION}\n"));
+        assert!(version(true).contains("\nfeatures: "));
+    }
+}
@@ -0,0 +1,119 @@
+//! Golden-file tests: transcripts of `rlsh --headless` sessions, checked
+//! against what they printed before.
+//!
+//! Each `tests/golden/<name>.in` is typed into a fresh shell and what it
+//! prints is compared with `<name>.out`. Run with `UPDATE_GOLDEN=1` to write
+//! the `.out` files afresh after changing what rlsh prints.
+
+use std::{
+    env, fs,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+/// Types `input` into a headless shell in an empty directory, returning what
+/// it printed.
+fn transcript(name: &str, input: &str) -> String {
+    let dir = env::temp_dir().join(format!("rlsh-golden-{name}-{}", std::process::id()));
+    let (home, world) = (dir.join("home"), dir.join("world"));
+    fs::create_dir_all(&home).unwrap();
+    fs::create_dir_all(&world).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rlsh"))
+        .arg("--headless")
+        .current_dir(&world)
+        .env("HOME", &home)
+        .env_remove("XDG_DATA_HOME")
+        .env_remove("RLSH_SEED")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    fs::remove_dir_all(dir).unwrap();
+    String::from_utf8_lossy(&output.stdout).to_string() + &String::from_utf8_lossy(&output.stderr)
+}
+
+#[test]
+fn transcripts_match_the_golden_files() {
+    let golden = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let mut inputs: Vec<_> = fs::read_dir(&golden)
+        .unwrap()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|e| e == "in"))
+        .collect();
+    inputs.sort();
+    assert!(!inputs.is_empty());
+
+    for input in inputs {
+        let name = input.file_stem().unwrap().to_string_lossy().to_string();
+        let got = transcript(&name, &fs::read_to_string(&input).unwrap());
+        let expected = input.with_extension("out");
+        if env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::write(&expected, &got).unwrap();
+            continue;
+        }
+        let want = fs::read_to_string(&expected).unwrap_or_default();
+        assert_eq!(got, want, "{name}: the transcript changed");
+        // and again, to be sure nothing in it depends on chance
+        assert_eq!(transcript(&name, &fs::read_to_string(&input).unwrap()), got);
+    }
+}
+
+#[test]
+fn scripts_run_a_line_at_a_time_and_say_where_errors_are() {
+    let dir = env::temp_dir().join(format!("rlsh-script-{}", std::process::id()));
+    fs::create_dir_all(dir.join("home")).unwrap();
+    // like a generated dungeon, thousands of lines long
+    let mut script = String::from("#!/usr/bin/env rlsh\n");
+    for room in 1..5000 {
+        script.push_str(&format!("room{room}=built\n"));
+    }
+    script.push_str("echo \"oops\n# still going\necho $room4999\n");
+    fs::write(dir.join("dungeon.rlsh"), script).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rlsh"))
+        .args(["--headless", "dungeon.rlsh"])
+        .current_dir(&dir)
+        .env("HOME", dir.join("home"))
+        .env_remove("XDG_DATA_HOME")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    fs::remove_dir_all(dir).unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.ends_with(
+            "dungeon.rlsh: line 5001: unexpected end of line looking for the matching `\"'\necho \"oops\n     ^\nbuilt\n"
+        ),
+        "{stdout}"
+    );
+    assert!(output.status.success());
+}
+
+#[test]
+fn scripts_dont_ask_who_the_player_is() {
+    let dir = env::temp_dir().join(format!("rlsh-class-{}", std::process::id()));
+    fs::create_dir_all(dir.join("home")).unwrap();
+    fs::write(dir.join("hello.rlsh"), "echo hello\n").unwrap();
+
+    // not headless, and with nothing on stdin to answer with
+    let output = Command::new(env!("CARGO_BIN_EXE_rlsh"))
+        .arg("hello.rlsh")
+        .current_dir(&dir)
+        .env("HOME", dir.join("home"))
+        .env_remove("XDG_DATA_HOME")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    fs::remove_dir_all(dir).unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hello\n");
+}
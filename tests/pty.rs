@@ -0,0 +1,212 @@
+//! Runs the shell on a pseudo-terminal, the way a person would, to check how
+//! it shares the terminal with the programs it runs.
+
+use std::{
+    fs,
+    os::{fd::FromRawFd, unix::process::CommandExt},
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+/// Held by each test, so the shells don't end up with each other's terminals.
+static ONE_AT_A_TIME: Mutex<()> = Mutex::new(());
+
+/// The shell, running on the slave side of a pseudo-terminal.
+struct Pty {
+    _turn: MutexGuard<'static, ()>,
+    master: i32,
+    child: Child,
+    /// Everything the shell has written so far.
+    output: String,
+}
+
+impl Pty {
+    fn spawn(home: &PathBuf) -> Pty {
+        let turn = ONE_AT_A_TIME.lock().unwrap_or_else(|e| e.into_inner());
+        let (master, slave) = unsafe {
+            let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC);
+            assert!(master >= 0);
+            assert_eq!(libc::grantpt(master), 0);
+            assert_eq!(libc::unlockpt(master), 0);
+            let slave = libc::open(
+                libc::ptsname(master),
+                libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC,
+            );
+            assert!(slave >= 0);
+            (master, slave)
+        };
+        let stdio = || unsafe { Stdio::from_raw_fd(libc::dup(slave)) };
+        let mut command = Command::new(env!("CARGO_BIN_EXE_rlsh"));
+        command
+            .env("HOME", home)
+            .env_remove("XDG_DATA_HOME")
+            .stdin(stdio())
+            .stdout(stdio())
+            .stderr(stdio());
+        // SAFETY: setsid and ioctl are async-signal-safe
+        unsafe {
+            command.pre_exec(|| {
+                // make the pseudo-terminal the shell's controlling terminal
+                libc::setsid();
+                libc::ioctl(libc::STDIN_FILENO, libc::TIOCSCTTY, 0);
+                Ok(())
+            });
+        }
+        let child = command.spawn().unwrap();
+        unsafe { libc::close(slave) };
+        Pty {
+            _turn: turn,
+            master,
+            child,
+            output: String::new(),
+        }
+    }
+
+    fn write(&self, text: &str) {
+        let written = unsafe { libc::write(self.master, text.as_ptr().cast(), text.len()) };
+        assert_eq!(written, text.len() as isize);
+    }
+
+    /// Reads until the output since `from` contains `text`, returning that
+    /// output.
+    fn wait_for(&mut self, from: usize, text: &str) -> String {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !self.output[from..].contains(text) {
+            let left = deadline.saturating_duration_since(Instant::now());
+            assert!(!left.is_zero(), "never saw {text:?} in {:?}", self.output);
+            let mut poll = libc::pollfd {
+                fd: self.master,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            if unsafe { libc::poll(&mut poll, 1, left.as_millis() as i32) } <= 0 {
+                continue;
+            }
+            let mut buffer = [0u8; 4096];
+            let read = unsafe { libc::read(self.master, buffer.as_mut_ptr().cast(), buffer.len()) };
+            assert!(read > 0, "the shell went away: {:?}", self.output);
+            self.output
+                .push_str(&String::from_utf8_lossy(&buffer[..read as usize]));
+        }
+        self.output[from..].to_string()
+    }
+
+    /// Types `line` at the prompt.
+    fn enter(&mut self, line: &str) {
+        let from = self.output.len();
+        self.write(line);
+        self.write("\r");
+        self.wait_for(from, "\n");
+    }
+
+    /// Types `line` at the prompt and waits for the next prompt, returning
+    /// what was written in between.
+    fn run(&mut self, line: &str) -> String {
+        self.enter(line);
+        let from = self.output.len();
+        self.wait_for(from, " $ ")
+    }
+
+    /// Resizes the terminal, like dragging the window's corner.
+    fn resize(&self, rows: u16, columns: u16) {
+        let size = libc::winsize {
+            ws_row: rows,
+            ws_col: columns,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        assert_eq!(
+            unsafe { libc::ioctl(self.master, libc::TIOCSWINSZ, &size) },
+            0
+        );
+    }
+
+    /// Starts the shell and picks a class, leaving it at the first prompt.
+    fn start(home: &PathBuf) -> Pty {
+        let mut pty = Pty::spawn(home);
+        pty.wait_for(0, "pick a class");
+        let from = pty.output.len();
+        pty.write("1\r");
+        pty.wait_for(from, " $ ");
+        pty
+    }
+}
+
+impl Drop for Pty {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        unsafe { libc::close(self.master) };
+    }
+}
+
+#[test]
+fn terminal_is_put_back_after_programs() {
+    let dir = std::env::temp_dir().join(format!("rlsh-pty-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    // like sudo killed while it's asking for a password, echo is left off
+    fs::write(dir.join("prompt.sh"), "stty -echo\nprintf 'password: '\n").unwrap();
+    fs::write(
+        dir.join("check.sh"),
+        "if stty | grep -q -- -echo; then echo echo-off; else echo echo-on; fi\n",
+    )
+    .unwrap();
+
+    let mut pty = Pty::start(&dir);
+
+    let prompted = pty.run(&format!("sh {}/prompt.sh", dir.display()));
+    assert!(prompted.contains("password: "));
+    let checked = pty.run(&format!("sh {}/check.sh", dir.display()));
+    assert!(checked.contains("echo-on"), "{checked:?}");
+
+    pty.write("exit\r");
+    drop(pty);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn programs_get_the_terminal_and_window_changes() {
+    let dir = std::env::temp_dir().join(format!("rlsh-pty-winch-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("resize.sh"),
+        "trap 'stty size; exit' WINCH\necho ready\nwhile :; do sleep 0.05; done\n",
+    )
+    .unwrap();
+
+    let mut pty = Pty::start(&dir);
+    let from = pty.output.len();
+    pty.enter(&format!("sh {}/resize.sh", dir.display()));
+    pty.wait_for(from, "ready");
+    let from = pty.output.len();
+    pty.resize(30, 100);
+    let resized = pty.wait_for(from, " $ ");
+    assert!(resized.contains("30 100"), "{resized:?}");
+
+    pty.write("exit\r");
+    drop(pty);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn lines_are_redrawn_when_the_window_changes() {
+    let dir = std::env::temp_dir().join(format!("rlsh-pty-redraw-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let mut pty = Pty::start(&dir);
+    pty.write("echo half");
+    pty.wait_for(0, "half");
+    let from = pty.output.len();
+    pty.resize(24, 40);
+    // the whole line comes back after clearing the screen below the prompt
+    let redrawn = pty.wait_for(from, "half");
+    assert!(redrawn.contains("\x1b[J"), "{redrawn:?}");
+    let ran = pty.run(" done");
+    assert!(ran.contains("half done"), "{ran:?}");
+
+    pty.write("exit\r");
+    drop(pty);
+    fs::remove_dir_all(dir).unwrap();
+}